@@ -0,0 +1,402 @@
+# German is the reference catalog: every key must exist here, other
+# languages fall back to it.
+
+lang-code = de
+language-name = { $code ->
+    [de] Deutsch
+   *[en] English
+}
+app-name = Kawaii Mood Journal ✨
+footer-disclaimer = Kein Ersatz für medizinische Hilfe. Bei Notfällen wähle bitte 112. 💖
+
+common-save = Speichern
+common-never = noch nie
+common-back = Zurück zur Übersicht
+common-unknown = unbekannt
+
+## Landing & auth
+
+landing-title = Willkommen 💖
+landing-heading = Dein cozy Mood- und Trip-Journal 🌸
+landing-intro = Stimmung tracken, Trips reflektieren und Safety-Button bereit halten – alles kawaii.
+auth-register = Registrieren 💖
+auth-login = Einloggen ✨
+auth-password = Passwort
+login-heading = Hey, schön dich zu sehen 💕
+login-identifier = Nutzername oder E-Mail
+register-heading = Willkommen im Mood-Club 💗
+register-username = Nutzername
+register-email = E-Mail
+register-password-confirm = Passwort bestätigen
+register-submit = Los geht's 🌸
+
+## Dashboard
+
+dashboard-title = Dein Dashboard 🌸
+dashboard-greeting = Hey { $name }, schön, dass du da bist 💖
+analytics-heading = Deine Stimmung 📈
+analytics-range = { $days } Tage
+analytics-no-data = Noch keine Check-ins in diesem Zeitraum 🌱
+analytics-checkin-count = { $count } Check-ins
+analytics-average-mood = Ø Stimmung: { $value }
+analytics-average-high = Ø High-Level: { $value }
+analytics-unsafe-share = Bei { $percent } % deiner Check-ins hast du dich nicht sicher gefühlt.
+analytics-mood-chart = Stimmung pro Tag
+analytics-high-chart = High-Level pro Tag
+analytics-heatmap = Kalender
+analytics-by-weekday = Stimmung nach Wochentag
+analytics-by-time-of-day = Stimmung nach Tageszeit
+analytics-night = Nacht
+analytics-morning = Morgen
+analytics-afternoon = Nachmittag
+analytics-evening = Abend
+weekday-mon = Mo
+weekday-tue = Di
+weekday-wed = Mi
+weekday-thu = Do
+weekday-fri = Fr
+weekday-sat = Sa
+weekday-sun = So
+insights-title = Substanz-Insights 🔬
+insights-heading = Wie geht's dir nach dem Konsum? 🔬
+insights-disclaimer = Keine medizinische Beratung. Das sind einfache Vergleiche deiner eigenen Einträge – sie zeigen Zusammenhänge, keine Ursachen, und bei wenigen Check-ins sind sie kaum aussagekräftig.
+insights-window = Verglichen wird deine Stimmung { $from }–{ $to } Stunden nach der Einnahme mit deiner Baseline.
+insights-baseline = Baseline (keine Substanz in den 72 Stunden davor): Ø { $mood } aus { $count } Check-ins
+insights-empty = In deinen Check-ins sind noch keine Substanzen erfasst.
+insights-col-substance = Substanz
+insights-col-uses = Einnahmen
+insights-col-samples = Check-ins danach
+insights-col-mood = Ø Stimmung danach
+insights-col-delta = Unterschied zur Baseline
+insights-low-sample = wenig Daten
+analytics-insights-link = Substanz-Insights ansehen →
+trend-nudge-heading = Pass gut auf dich auf 💜
+trend-nudge-drop = Deine Stimmung liegt in den letzten Tagen bei Ø { $recent }, sonst eher bei Ø { $baseline }.
+trend-nudge-declines = Deine letzten { $count } Check-ins gingen jedes Mal ein Stück nach unten.
+trend-nudge-help = Vielleicht tut ein Gespräch mit jemandem gut, dem du vertraust.
+trend-nudge-help-link = Hilfe & Kontakte →
+quick-heading = Schnell-Check-in ⚡
+quick-saved = Mood { $mood } gespeichert 💖
+quick-full-checkin = Ausführliches Check-in →
+
+## Check-ins
+
+checkins-title = Deine Check-ins 📖
+checkins-heading = Verlauf deiner Gefühle 💞
+checkins-view = Ansehen →
+checkins-empty = Noch keine Check-ins 🌱
+checkin-new-title = Neues Check-in 🌸
+checkin-new-heading = Wie fühlst du dich? 🌈
+checkin-timestamp = Zeitpunkt
+checkin-timestamp-hint = Leer lassen für jetzt. Nachtragen geht, Zukunft nicht.
+checkin-mood = Mood (-5 .. +5)
+checkin-high-level = High-Level (0 .. 10)
+checkin-safety = Sicherheitsgefühl
+checkin-safety-yes = Ja, ich fühle mich sicher
+checkin-safety-no = Nein, ich fühle mich nicht sicher
+checkin-safety-answer = Magst du mehr dazu sagen?
+checkin-notes = Notizen
+checkin-substances = Substanzen
+checkin-substances-placeholder = MDMA, 80 mg, oral
+checkin-substances-hint = Eine pro Zeile: Substanz, Dosis, Einnahmeweg. Dosis und Einnahmeweg sind optional.
+checkin-save = Speichern 💖
+checkin-edit-title = Check-in bearbeiten ✏️
+checkin-edit-no-renotify = Beim Bearbeiten werden keine Kontakte erneut benachrichtigt.
+checkin-edit-save = Änderungen speichern 💖
+checkin-detail-title = Check-in Detail ✨
+checkin-summary = Mood { $mood } · High-Level { $high }
+checkin-no-notes = Keine Notizen hinterlegt 🌱
+checkin-edit = Bearbeiten ✏️
+checkin-delete = Löschen 🗑️
+
+history-heading = Verlauf 🕰️
+history-current = aktuell
+history-restore = Diese Version wiederherstellen
+history-restored = Version wiederhergestellt 💖
+history-empty = Noch keine gespeicherten Versionen 🌱
+
+trash-title = Papierkorb 🗑️
+trash-retention = Gelöschte Check-ins werden nach { $days } Tagen endgültig entfernt.
+trash-deleted-at = gelöscht { $deleted } · noch { $days } Tage
+trash-undelete = Wiederherstellen
+trash-purge = Endgültig löschen
+trash-purge-all = Papierkorb leeren
+trash-empty = Der Papierkorb ist leer 🌱
+
+## Trips
+
+trips-title = Trip-Journal ✨
+trips-heading = Trips und Erlebnisse ✨
+trips-field-title = Titel
+trips-title-placeholder = Festival, Chill-Abend, …
+trips-start = Trip starten 🚀
+trips-running = läuft gerade 🌈
+trips-end = Beenden
+trips-empty = Noch keine Trips 🌱
+
+## Panic
+
+panic-title = Panic & Hilfe 😰
+panic-heading = Hey, du bist hier sicher 💖
+panic-breathe = Atme tief ein (4 Sekunden), halte (4), aus (6). Ich bin bei dir.
+panic-trigger = Alarm auslösen 🚨
+panic-sent-title = Alarm ausgelöst 💖
+panic-sent-heading = Alarm ist raus 💖
+panic-sent-at = Ausgelöst am { $timestamp }
+panic-sent-nobody = Es konnte gerade niemand benachrichtigt werden. Bitte ruf jemanden an oder wähle 112.
+panic-sent-notified = Benachrichtigt:
+panic-sent-retrying = Bisher wurde noch niemand erreicht – wir versuchen es weiter. Bitte ruf in der Zwischenzeit jemanden an oder wähl die 112.
+
+## Settings
+
+settings-title = Settings ⚙️
+settings-heading = Deine Matrix- und Notify-Settings 💞
+settings-display-name = Anzeigename
+settings-language = Sprache
+settings-language-auto = Automatisch (Browser)
+settings-timezone = Zeitzone
+settings-timezone-hint = Für alle Zeitangaben und Benachrichtigungen, z. B. Europe/Berlin.
+settings-homeserver = Matrix-Homeserver
+settings-matrix-id = Deine Matrix-ID
+settings-token-stored = gespeichert – leer lassen zum Behalten
+settings-token-missing = noch keins hinterlegt
+settings-token-hint = Wird verschlüsselt getrennt vom Journal gespeichert und landet nie im Git-Verlauf.
+settings-token-clear = Gespeichertes Token löschen
+settings-matrix-allow-unencrypted = Auch an unverschlüsselte Direktchats senden
+settings-matrix-encryption-hint = Alarme gehen sonst nur Ende-zu-Ende-verschlüsselt raus; fehlt ein verschlüsselter Chat, wird einer angelegt. Nutze ein eigenes Login-Token, nicht das deiner Element-Sitzung.
+settings-primary-contact = Hauptkontakt
+settings-emergency-contacts = Notfallkontakte (einer pro Zeile)
+settings-contacts-hint = Eine Matrix-ID wie @alex:matrix.org oder Kanal und Adresse: email:alex@example.com, push:https://ntfy.sh/dein-topic (für Gotify: https://gotify.example.org/message?token=…) oder webhook:https://example.org/hook. Mit Ruhezeiten wie „22:00-07:00“ hinter einem Kontakt wartet alles außer Alarmen bis zu deren Ende.
+settings-auto-notify = Kontakte bei niedriger Stimmung automatisch benachrichtigen
+settings-threshold = Schwelle (Mood ≤)
+settings-low-mood-cooldown = Pause nach einer Benachrichtigung bei niedriger Stimmung (Stunden)
+settings-low-mood-cooldown-hint = Weitere niedrige Check-ins in dieser Zeit benachrichtigen niemanden erneut. 0 benachrichtigt jedes Mal. Der Alarm-Button benachrichtigt immer sofort.
+settings-trend-sensitivity = Frühwarnung bei sinkender Stimmung
+settings-trend-level = { $level ->
+    [off] Aus
+    [low] Niedrig – nur deutliche Einbrüche
+    [high] Hoch – schon kleine Abwärtstrends
+   *[medium] Mittel
+}
+settings-trend-hint = Vergleicht deine letzten 3 Tage mit deinem Durchschnitt der 4 Wochen davor.
+settings-trend-notify = Bei einem Abwärtstrend auch meinen Hauptkontakt benachrichtigen
+settings-summary-heading = Rückblicke per Matrix
+settings-summary-weekly = Wöchentlicher Rückblick (montags für die Vorwoche)
+settings-summary-monthly = Monatlicher Rückblick (am 1. für den Vormonat)
+settings-therapist-contact = Therapeut*in (Kontakt wie oben)
+settings-summary-to-therapist = Rückblicke auch an meine*n Therapeut*in schicken
+settings-summary-hint = Der Rückblick geht an deine eigene Matrix-ID und enthält Durchschnitt, Tiefpunkt, Anzahl der Check-ins, Trips und Substanzen.
+summary-period-name = { $period ->
+    [monthly] Monatsrückblick
+   *[weekly] Wochenrückblick
+}
+notify-title-low-mood = { $name } könnte gerade etwas Unterstützung brauchen
+notify-title-panic = { $name } braucht jetzt Hilfe
+notify-title-trend = Die Stimmung von { $name } sinkt seit ein paar Tagen
+notify-delivered-count = { $total ->
+    [1] An { $sent } von 1 Kontakt zugestellt
+   *[other] An { $sent } von { $total } Kontakten zugestellt
+}
+notify-next-attempt = nächster Versuch { $time }
+notify-deferred-until = Ruhezeit bis { $time }
+notify-kind-low-mood = niedrige Stimmung
+notify-kind-trend = Abwärtstrend
+notify-kind-panic = Alarm
+api-tokens-heading = API-Tokens 🔑
+api-tokens-hint = Für Kurzbefehle und Automationen mit der JSON-API unter /api/v1. Tokens können nur, was ihre Berechtigungen erlauben, und funktionieren nicht für diese Seiten.
+api-tokens-created = Dein neues Token – kopiere es jetzt, es wird nur dieses eine Mal angezeigt:
+api-tokens-empty = Noch keine Tokens.
+api-tokens-created-at = erstellt { $time }
+api-tokens-last-used = zuletzt benutzt { $time }
+api-tokens-never-used = noch nie benutzt
+api-tokens-revoke = Widerrufen
+api-tokens-name = Name (z. B. „Handy-Kurzbefehl“)
+api-tokens-scopes = Berechtigungen
+api-tokens-create = Token erstellen
+api-scope-checkins-read = Check-ins lesen
+api-scope-checkins-write = Check-ins anlegen, bearbeiten und löschen
+api-scope-trips-read = Trips lesen
+api-scope-trips-write = Trips starten und beenden
+api-scope-panic-trigger = Den Panik-Button auslösen
+api-scope-settings-read = Einstellungen lesen
+api-scope-settings-write = Einstellungen ändern
+webhooks-heading = Webhooks 🪝
+webhooks-hint = Für Unterstützer*innen ohne Matrix: Diese URLs bekommen bei den gewählten Ereignissen einen signierten JSON-POST. Notizen und Antworten zur Sicherheit werden nie mitgeschickt.
+webhooks-created = Das Signatur-Geheimnis für diesen Webhook – kopiere es jetzt, es wird nur dieses eine Mal angezeigt:
+webhooks-empty = Noch keine Webhooks.
+webhooks-created-at = hinzugefügt { $time }
+webhooks-delete = Löschen
+webhooks-url = URL
+webhooks-events = Ereignisse
+webhooks-create = Webhook hinzufügen
+webhooks-log-heading = Letzte Zustellungen
+webhooks-log-empty = Noch nichts zugestellt.
+webhooks-log-time = Zeit
+webhooks-log-event = Ereignis
+webhooks-log-status = Status
+webhooks-log-attempts = Versuche
+webhooks-log-response = Antwort
+webhooks-next-attempt = nächster Versuch { $time }
+webhook-event-checkin-created = Jeder neue Check-in
+webhook-event-low-mood = Check-ins an oder unter der Schwelle für schlechte Stimmung
+webhook-event-panic-triggered = Panik-Button
+webhook-status-pending = ausstehend
+webhook-status-delivered = zugestellt
+webhook-status-failed = fehlgeschlagen
+reminders-heading = Erinnerungen ⏰
+reminders-hint = Wir erinnern dich an deinen Check-in, z. B. täglich um 20:00 oder alle paar Stunden während eines Trips. Hast du gerade erst eingecheckt, lassen wir die Erinnerung aus.
+reminders-empty = Noch keine Erinnerungen.
+reminders-daily-at = Täglich um { $time }
+reminders-during-trip-every = Während eines Trips alle { $hours ->
+    [one] Stunde
+   *[other] { $hours } Stunden
+}
+reminders-next = nächste { $time }
+reminders-waiting-for-trip = wartet auf den nächsten Trip
+reminders-last = zuletzt { $time }
+reminders-delete = Löschen
+reminders-when = Wann?
+reminders-kind-daily = Täglich um
+reminders-kind-trip = Während eines Trips alle
+reminders-hours = Stunden
+reminders-contact = An
+reminders-create = Erinnerung hinzufügen
+reminder-status-sent = gesendet
+reminder-status-skipped = ausgelassen
+reminder-status-failed = fehlgeschlagen
+reminder-title = Zeit für einen Check-in 🌸
+reminder-body-daily = Wie geht's dir gerade? Nimm dir kurz Zeit für einen Check-in.
+reminder-body-trip = Dein Trip „{ $title }“ läuft seit { $hours ->
+    [one] einer Stunde
+   *[other] { $hours } Stunden
+}. Wie geht's dir? Ein kurzer Check-in hilft dir und deinen Kontakten.
+password-heading = Passwort 🔑
+password-hint = Dein Passwort entsperrt auch deine verschlüsselten Daten. Wenn du es änderst, werden deine anderen Geräte abgemeldet.
+password-current = Aktuelles Passwort
+password-new = Neues Passwort
+password-submit = Passwort ändern
+password-changed = Passwort geändert. Deine anderen Geräte wurden abgemeldet.
+matrix-bot-heading = Matrix-Bot 🤖
+matrix-bot-hint = Check-ins, Trips und Alarm direkt aus Element: Verknüpfe deinen Matrix-Account und schreib dann { $bot }. Mit !help siehst du alle Befehle.
+matrix-bot-code-created = Schick das innerhalb von 15 Minuten an { $bot }, z. B. als Direktnachricht:
+matrix-bot-linked-at = verknüpft { $time }
+matrix-bot-unlink = Verknüpfung lösen
+matrix-bot-not-linked = Noch kein Matrix-Account verknüpft.
+matrix-bot-create-code = Code zum Verknüpfen erstellen
+bot-help = Befehle:
+    !mood <-5 bis 5> [high <0 bis 10>] [Notiz] – Check-in eintragen, z. B. !mood -2 high 4 fühl mich komisch
+    !trip start <Titel> / !trip end – Trip starten oder beenden
+    !panic – Alarm an deine Kontakte
+    !unlink – Verknüpfung dieses Matrix-Accounts lösen
+bot-unknown-command = Diesen Befehl kenne ich nicht.
+bot-usage-mood = So geht's: !mood <-5 bis 5> [high <0 bis 10>] [Notiz], z. B. !mood -2 high 4 fühl mich komisch
+bot-usage-trip = So geht's: !trip start <Titel> oder !trip end
+bot-usage-link = So geht's: !link <Code> – den Code findest du in deinen Einstellungen.
+bot-not-linked = Dieser Matrix-Account ist noch nicht verknüpft. Erstell unter Einstellungen → Matrix-Bot einen Code und schick ihn hier mit !link <Code>.
+bot-link-invalid = Diesen Code kenne ich nicht oder er ist abgelaufen. Bitte erstell in deinen Einstellungen einen neuen.
+bot-linked = Verknüpft mit { $username } 💖 Mit !help siehst du, was ich kann.
+bot-unlinked = Verknüpfung gelöst. Von diesem Account nehme ich keine Befehle mehr an.
+bot-checkin-saved = Check-in gespeichert: Mood { $mood }, High { $high } 🌸
+bot-checkin-contacts-alerted = Deine Kontakte werden benachrichtigt.
+bot-panic-sent = Der Alarm ist raus 🚨
+bot-trip-started = Trip „{ $title }“ gestartet ✨ Pass auf dich auf.
+bot-trip-ended = Trip „{ $title }“ beendet. Willkommen zurück 💖
+bot-trip-none-active = Gerade läuft kein Trip.
+bot-locked = Dein Journal ist gerade gesperrt. Melde dich einmal in der Web-App an und versuch es dann nochmal.
+bot-error = Da ist etwas schiefgelaufen. Bitte versuch es gleich nochmal.
+
+## Admin
+
+admin-dashboard-title = Admin Dashboard 🛠️
+admin-dashboard-heading = Systemüberblick
+admin-dashboard-placeholder = Anzahl der Accounts, Check-ins und Panic-Events folgt.
+admin-settings-title = Admin · Einstellungen
+admin-settings-heading = Globale Templates anpassen
+admin-settings-placeholder = Form folgt später.
+admin-users-title = Admin · Accounts
+admin-users-heading = Alle Accounts
+admin-users-details = Details
+admin-user-title = Admin · Account-Details
+admin-user-role = Rolle
+admin-reset-heading = Passwort zurücksetzen 🔑
+admin-reset-info = Der Datenschlüssel wird über den Escrow-Schlüssel neu verpackt. Alle Sessions werden beendet.
+admin-reset-new-password = Neues Passwort
+admin-reset-submit = Zurücksetzen
+admin-reset-no-escrow = Kein Escrow-Schlüssel (DATA_ESCROW_KEY) konfiguriert – verschlüsselte Daten lassen sich ohne das alte Passwort nicht wiederherstellen.
+admin-system-title = Admin · System
+admin-system-heading = Git & Systemstatus
+admin-system-version = Version
+admin-system-uptime = Uptime
+admin-system-database = Datenbank
+admin-system-free-space = Freier Speicher (ai/)
+admin-system-free-of-total = { $free } von { $total }
+admin-system-tables = Tabellen
+admin-system-repo = Git-Repository
+admin-system-repo-error = Repository nicht lesbar: { $error }
+admin-system-branch = Branch
+admin-system-no-commits = noch keine Commits
+admin-system-repo-size = Repo-Größe
+admin-system-uncommitted = Nicht committete Änderungen unter ai/
+admin-system-all-committed = Alles committet ✨
+admin-system-recent-commits = Letzte Commits unter ai/
+admin-system-no-commits-yet = Noch keine Commits 🌱
+admin-backup-heading = Backup-Remote
+admin-backup-last-success = Letzter erfolgreicher Push
+admin-backup-last-attempt = Letzter Versuch
+admin-backup-failing-since = Fehlschläge seit
+admin-backup-last-error = Letzter Fehler
+admin-backup-not-configured = Kein Backup-Remote konfiguriert
+admin-jobs-heading = Hintergrundjobs
+admin-jobs-name = Job
+admin-jobs-schedule = Zeitplan
+admin-jobs-last-run = Letzter Lauf
+admin-jobs-next-run = Nächster Lauf
+admin-jobs-last-error = Letzter Fehler
+admin-jobs-running = läuft gerade
+admin-jobs-none = Noch keine Jobs registriert.
+admin-commit-message = Commit-Nachricht
+admin-commit-submit = Manuellen Commit auslösen 💾
+admin-commit-created = Commit erstellt 💾
+admin-commit-nothing = Keine Änderungen unter ai/ – nichts zu committen.
+
+## Errors
+
+error-username-email-required = Bitte Nutzername und E-Mail ausfüllen.
+error-invalid-email = Bitte eine gültige E-Mail-Adresse eingeben.
+error-identifier-required = Bitte Nutzername oder E-Mail eingeben.
+error-password-too-short = Passwort muss mindestens { $min } Zeichen lang sein.
+error-password-mismatch = Passwörter stimmen nicht überein.
+error-current-password-wrong = Das aktuelle Passwort stimmt nicht.
+error-username-taken = Nutzername oder E-Mail bereits vergeben.
+error-no-escrow = Kein Escrow-Schlüssel konfiguriert, Wiederherstellung nicht möglich.
+error-commit-message-required = Bitte eine Commit-Nachricht angeben.
+error-trip-title-required = Bitte gib deinem Trip einen Titel.
+error-trip-already-active = Es läuft schon ein Trip – beende ihn zuerst.
+error-unknown-timezone = Unbekannte Zeitzone.
+error-invalid-timestamp = Ungültiger Zeitpunkt.
+error-checkin-in-future = Check-ins können nicht in der Zukunft liegen.
+error-substance-required = Bitte gib bei jeder Substanz einen Namen an.
+error-invalid-credentials = Nutzername oder Passwort stimmt nicht.
+error-api-token-name-required = Bitte gib dem Token einen Namen.
+error-api-token-scope-required = Bitte wähle mindestens eine Berechtigung.
+error-webhook-url-invalid = Bitte gib eine http://- oder https://-URL ein.
+error-webhook-event-required = Bitte wähle mindestens ein Ereignis.
+error-reminder-time-invalid = Bitte gib eine Uhrzeit wie 20:00 an.
+error-reminder-interval-invalid = Bitte gib einen Abstand von 1 bis { $max } Stunden an.
+error-reminder-kind-invalid = Bitte wähle, wann wir dich erinnern sollen.
+error-contact-invalid = „{ $contact }“ ist kein Kontakt, den wir erreichen können. Nutze eine Matrix-ID oder email:, push: bzw. webhook: gefolgt von der Adresse.
+
+## Error pages
+
+error-page-400-title = Da stimmt was nicht 🙈
+error-page-400-text = Die Anfrage konnte nicht verarbeitet werden.
+error-page-403-title = Kein Zutritt 🔒
+error-page-403-text = Diese Seite ist nur für Admins.
+error-page-404-title = Nicht gefunden 🔍
+error-page-404-text = Diese Seite gibt es nicht (mehr).
+error-page-500-title = Huch, ein Fehler 💔
+error-page-500-text = Etwas ist schiefgelaufen. Versuch es gleich nochmal.
+error-page-501-title = Kommt bald 🌱
+error-page-501-text = Diese Funktion gibt es noch nicht.
+error-page-correlation = Fehler-ID: { $id } – nenne sie gern, wenn du uns Bescheid gibst.
+login-continue-notice = Bitte logge dich ein, um weiterzumachen.
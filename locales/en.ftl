@@ -0,0 +1,399 @@
+lang-code = en
+language-name = { $code ->
+    [de] Deutsch
+   *[en] English
+}
+app-name = Kawaii Mood Journal ✨
+footer-disclaimer = Not a substitute for medical help. In an emergency, please call 112. 💖
+
+common-save = Save
+common-never = never
+common-back = Back to the overview
+common-unknown = unknown
+
+## Landing & auth
+
+landing-title = Welcome 💖
+landing-heading = Your cozy mood and trip journal 🌸
+landing-intro = Track your mood, reflect on trips and keep a safety button at hand – all kawaii.
+auth-register = Sign up 💖
+auth-login = Log in ✨
+auth-password = Password
+login-heading = Hey, good to see you 💕
+login-identifier = Username or email
+register-heading = Welcome to the mood club 💗
+register-username = Username
+register-email = Email
+register-password-confirm = Confirm password
+register-submit = Let's go 🌸
+
+## Dashboard
+
+dashboard-title = Your dashboard 🌸
+dashboard-greeting = Hey { $name }, glad you're here 💖
+analytics-heading = Your mood 📈
+analytics-range = { $days } days
+analytics-no-data = No check-ins in this period yet 🌱
+analytics-checkin-count = { $count } check-ins
+analytics-average-mood = Avg. mood: { $value }
+analytics-average-high = Avg. high level: { $value }
+analytics-unsafe-share = You didn't feel safe in { $percent } % of your check-ins.
+analytics-mood-chart = Mood per day
+analytics-high-chart = High level per day
+analytics-heatmap = Calendar
+analytics-by-weekday = Mood by weekday
+analytics-by-time-of-day = Mood by time of day
+analytics-night = Night
+analytics-morning = Morning
+analytics-afternoon = Afternoon
+analytics-evening = Evening
+weekday-mon = Mon
+weekday-tue = Tue
+weekday-wed = Wed
+weekday-thu = Thu
+weekday-fri = Fri
+weekday-sat = Sat
+weekday-sun = Sun
+insights-title = Substance insights 🔬
+insights-heading = How are you after using? 🔬
+insights-disclaimer = Not medical advice. These are simple comparisons of your own entries – they show associations, not causes, and with few check-ins they mean very little.
+insights-window = Your mood { $from }–{ $to } hours after use is compared with your baseline.
+insights-baseline = Baseline (no substance in the 72 hours before): avg. { $mood } from { $count } check-ins
+insights-empty = Your check-ins don't contain any substances yet.
+insights-col-substance = Substance
+insights-col-uses = Uses
+insights-col-samples = Check-ins after
+insights-col-mood = Avg. mood after
+insights-col-delta = Difference to baseline
+insights-low-sample = little data
+analytics-insights-link = View substance insights →
+trend-nudge-heading = Take good care of yourself 💜
+trend-nudge-drop = Your mood has been around avg. { $recent } over the last days, usually it's around avg. { $baseline }.
+trend-nudge-declines = Your last { $count } check-ins went a bit lower each time.
+trend-nudge-help = Maybe talking to someone you trust would help.
+trend-nudge-help-link = Help & contacts →
+quick-heading = Quick check-in ⚡
+quick-saved = Mood { $mood } saved 💖
+quick-full-checkin = Full check-in →
+
+## Check-ins
+
+checkins-title = Your check-ins 📖
+checkins-heading = Your feelings over time 💞
+checkins-view = View →
+checkins-empty = No check-ins yet 🌱
+checkin-new-title = New check-in 🌸
+checkin-new-heading = How are you feeling? 🌈
+checkin-timestamp = Time
+checkin-timestamp-hint = Leave empty for now. Logging after the fact works, the future doesn't.
+checkin-mood = Mood (-5 .. +5)
+checkin-high-level = High level (0 .. 10)
+checkin-safety = Feeling safe?
+checkin-safety-yes = Yes, I feel safe
+checkin-safety-no = No, I don't feel safe
+checkin-safety-answer = Anything you want to add?
+checkin-notes = Notes
+checkin-substances = Substances
+checkin-substances-placeholder = MDMA, 80 mg, oral
+checkin-substances-hint = One per line: substance, dose, route. Dose and route are optional.
+checkin-save = Save 💖
+checkin-edit-title = Edit check-in ✏️
+checkin-edit-no-renotify = Editing never notifies your contacts again.
+checkin-edit-save = Save changes 💖
+checkin-detail-title = Check-in details ✨
+checkin-summary = Mood { $mood } · High { $high }
+checkin-no-notes = No notes 🌱
+checkin-edit = Edit ✏️
+checkin-delete = Delete 🗑️
+
+history-heading = History 🕰️
+history-current = current
+history-restore = Restore this version
+history-restored = Version restored 💖
+history-empty = No saved versions yet 🌱
+
+trash-title = Trash 🗑️
+trash-retention = Deleted check-ins are removed for good after { $days } days.
+trash-deleted-at = deleted { $deleted } · { $days } days left
+trash-undelete = Restore
+trash-purge = Delete forever
+trash-purge-all = Empty trash
+trash-empty = The trash is empty 🌱
+
+## Trips
+
+trips-title = Trip journal ✨
+trips-heading = Trips and experiences ✨
+trips-field-title = Title
+trips-title-placeholder = Festival, chill night, …
+trips-start = Start trip 🚀
+trips-running = in progress 🌈
+trips-end = End
+trips-empty = No trips yet 🌱
+
+## Panic
+
+panic-title = Panic & help 😰
+panic-heading = Hey, you're safe here 💖
+panic-breathe = Breathe in (4 seconds), hold (4), out (6). I'm with you.
+panic-trigger = Raise the alarm 🚨
+panic-sent-title = Alarm raised 💖
+panic-sent-heading = The alarm is out 💖
+panic-sent-at = Raised at { $timestamp }
+panic-sent-nobody = Nobody could be notified right now. Please call someone or dial 112.
+panic-sent-notified = Notified:
+panic-sent-retrying = Nobody has been reached yet – we keep trying. Please call someone or dial 112 in the meantime.
+
+## Settings
+
+settings-title = Settings ⚙️
+settings-heading = Your Matrix and notification settings 💞
+settings-display-name = Display name
+settings-language = Language
+settings-language-auto = Automatic (browser)
+settings-timezone = Timezone
+settings-timezone-hint = Used for all times and notifications, e.g. Europe/London.
+settings-homeserver = Matrix homeserver
+settings-matrix-id = Your Matrix ID
+settings-token-stored = stored – leave empty to keep it
+settings-token-missing = none stored yet
+settings-token-hint = Stored encrypted, separately from your journal, and never in the git history.
+settings-token-clear = Delete stored token
+settings-matrix-allow-unencrypted = Also send to unencrypted direct chats
+settings-matrix-encryption-hint = Otherwise alerts only go out end-to-end encrypted; if there's no encrypted chat yet, one is created. Use a dedicated login token, not the one of your Element session.
+settings-primary-contact = Primary contact
+settings-emergency-contacts = Emergency contacts (one per line)
+settings-contacts-hint = A Matrix ID like @alex:matrix.org, or a channel and address: email:alex@example.com, push:https://ntfy.sh/your-topic (for Gotify: https://gotify.example.org/message?token=…) or webhook:https://example.org/hook. Add quiet hours like "22:00-07:00" after a contact to hold back everything but panic alerts until they end.
+settings-auto-notify = Automatically notify contacts when my mood is low
+settings-threshold = Threshold (mood ≤)
+settings-low-mood-cooldown = Pause after a low-mood alert (hours)
+settings-low-mood-cooldown-hint = Further low check-ins in this time don't alert anyone again. 0 alerts every time. The panic button always alerts right away.
+settings-trend-sensitivity = Early warning for a dropping mood
+settings-trend-level = { $level ->
+    [off] Off
+    [low] Low – only clear drops
+    [high] High – even small downward trends
+   *[medium] Medium
+}
+settings-trend-hint = Compares your last 3 days with your average over the 4 weeks before.
+settings-trend-notify = Also notify my primary contact about a downward trend
+settings-summary-heading = Summaries via Matrix
+settings-summary-weekly = Weekly summary (on Mondays, for the week before)
+settings-summary-monthly = Monthly summary (on the 1st, for the month before)
+settings-therapist-contact = Therapist (contact as above)
+settings-summary-to-therapist = Also send summaries to my therapist
+settings-summary-hint = The summary goes to your own Matrix ID and covers your average, lowest point, number of check-ins, trips and substances.
+summary-period-name = { $period ->
+    [monthly] Monthly summary
+   *[weekly] Weekly summary
+}
+notify-title-low-mood = { $name } might need a little support
+notify-title-panic = { $name } needs help now
+notify-title-trend = { $name }'s mood has been dropping
+notify-delivered-count = { $total ->
+    [1] Delivered to { $sent } of 1 contact
+   *[other] Delivered to { $sent } of { $total } contacts
+}
+notify-next-attempt = next try { $time }
+notify-deferred-until = quiet hours until { $time }
+notify-kind-low-mood = low mood
+notify-kind-trend = downward trend
+notify-kind-panic = alarm
+api-tokens-heading = API tokens 🔑
+api-tokens-hint = For shortcuts and automations using the JSON API at /api/v1. Tokens can only do what their scopes allow and don't work for these pages.
+api-tokens-created = Your new token – copy it now, it is only shown this once:
+api-tokens-empty = No tokens yet.
+api-tokens-created-at = created { $time }
+api-tokens-last-used = last used { $time }
+api-tokens-never-used = never used
+api-tokens-revoke = Revoke
+api-tokens-name = Name (e.g. "Phone shortcut")
+api-tokens-scopes = Scopes
+api-tokens-create = Create token
+api-scope-checkins-read = Read check-ins
+api-scope-checkins-write = Create, edit and delete check-ins
+api-scope-trips-read = Read trips
+api-scope-trips-write = Start and end trips
+api-scope-panic-trigger = Trigger the panic button
+api-scope-settings-read = Read settings
+api-scope-settings-write = Change settings
+webhooks-heading = Webhooks 🪝
+webhooks-hint = For support circles that don't use Matrix: these URLs receive a signed JSON POST for the events you pick. Notes and safety answers are never sent.
+webhooks-created = The signing secret for this webhook – copy it now, it is only shown this once:
+webhooks-empty = No webhooks yet.
+webhooks-created-at = added { $time }
+webhooks-delete = Delete
+webhooks-url = URL
+webhooks-events = Events
+webhooks-create = Add webhook
+webhooks-log-heading = Recent deliveries
+webhooks-log-empty = Nothing delivered yet.
+webhooks-log-time = Time
+webhooks-log-event = Event
+webhooks-log-status = Status
+webhooks-log-attempts = Attempts
+webhooks-log-response = Response
+webhooks-next-attempt = next attempt { $time }
+webhook-event-checkin-created = Every new check-in
+webhook-event-low-mood = Check-ins at or below the low-mood threshold
+webhook-event-panic-triggered = Panic button
+webhook-status-pending = pending
+webhook-status-delivered = delivered
+webhook-status-failed = failed
+reminders-heading = Reminders ⏰
+reminders-hint = We remind you to check in, e.g. daily at 20:00 or every few hours during a trip. If you've just checked in, the reminder is left out.
+reminders-empty = No reminders yet.
+reminders-daily-at = Daily at { $time }
+reminders-during-trip-every = During a trip every { $hours ->
+    [one] hour
+   *[other] { $hours } hours
+}
+reminders-next = next { $time }
+reminders-waiting-for-trip = waiting for the next trip
+reminders-last = last { $time }
+reminders-delete = Delete
+reminders-when = When?
+reminders-kind-daily = Daily at
+reminders-kind-trip = During a trip every
+reminders-hours = hours
+reminders-contact = To
+reminders-create = Add reminder
+reminder-status-sent = sent
+reminder-status-skipped = skipped
+reminder-status-failed = failed
+reminder-title = Time for a check-in 🌸
+reminder-body-daily = How are you doing right now? Take a moment for a check-in.
+reminder-body-trip = Your trip “{ $title }” has been going for { $hours ->
+    [one] an hour
+   *[other] { $hours } hours
+}. How are you? A quick check-in helps you and your contacts.
+password-heading = Password 🔑
+password-hint = Your password also unlocks your encrypted data. Changing it logs out your other devices.
+password-current = Current password
+password-new = New password
+password-submit = Change password
+password-changed = Password changed. Your other devices have been logged out.
+matrix-bot-heading = Matrix bot 🤖
+matrix-bot-hint = Log check-ins, trips and the alarm straight from Element: link your Matrix account, then write to { $bot }. Send !help for all commands.
+matrix-bot-code-created = Send this to { $bot } within 15 minutes, e.g. in a direct message:
+matrix-bot-linked-at = linked { $time }
+matrix-bot-unlink = Unlink
+matrix-bot-not-linked = No Matrix account linked yet.
+matrix-bot-create-code = Create link code
+bot-help = Commands:
+    !mood <-5 to 5> [high <0 to 10>] [notes] – log a check-in, e.g. !mood -2 high 4 feeling off
+    !trip start <title> / !trip end – start or end a trip
+    !panic – raise the alarm to your contacts
+    !unlink – unlink this Matrix account
+bot-unknown-command = I don't know that command.
+bot-usage-mood = Usage: !mood <-5 to 5> [high <0 to 10>] [notes], e.g. !mood -2 high 4 feeling off
+bot-usage-trip = Usage: !trip start <title> or !trip end
+bot-usage-link = Usage: !link <code> – you'll find the code in your settings.
+bot-not-linked = This Matrix account isn't linked yet. Create a code under Settings → Matrix bot and send it here with !link <code>.
+bot-link-invalid = That code is unknown or expired. Please create a new one in your settings.
+bot-linked = Linked to { $username } 💖 Send !help to see what I can do.
+bot-unlinked = Unlinked. I won't accept commands from this account anymore.
+bot-checkin-saved = Check-in saved: mood { $mood }, high { $high } 🌸
+bot-checkin-contacts-alerted = Your contacts are being notified.
+bot-panic-sent = The alarm is out 🚨
+bot-trip-started = Trip "{ $title }" started ✨ Take care of yourself.
+bot-trip-ended = Trip "{ $title }" ended. Welcome back 💖
+bot-trip-none-active = There's no trip running right now.
+bot-locked = Your journal is locked right now. Log in to the web app once, then try again.
+bot-error = Something went wrong. Please try again in a moment.
+
+## Admin
+
+admin-dashboard-title = Admin dashboard 🛠️
+admin-dashboard-heading = System overview
+admin-dashboard-placeholder = Account, check-in and panic event counts are coming soon.
+admin-settings-title = Admin · Settings
+admin-settings-heading = Edit global templates
+admin-settings-placeholder = Form coming soon.
+admin-users-title = Admin · Users
+admin-users-heading = All accounts
+admin-users-details = Details
+admin-user-title = Admin · User details
+admin-user-role = Role
+admin-reset-heading = Reset password 🔑
+admin-reset-info = The data key is re-wrapped using the escrow key. All sessions are ended.
+admin-reset-new-password = New password
+admin-reset-submit = Reset
+admin-reset-no-escrow = No escrow key (DATA_ESCROW_KEY) configured – encrypted data cannot be recovered without the old password.
+admin-system-title = Admin · System
+admin-system-heading = Git & system status
+admin-system-version = Version
+admin-system-uptime = Uptime
+admin-system-database = Database
+admin-system-free-space = Free space (ai/)
+admin-system-free-of-total = { $free } of { $total }
+admin-system-tables = Tables
+admin-system-repo = Git repository
+admin-system-repo-error = Repository not readable: { $error }
+admin-system-branch = Branch
+admin-system-no-commits = no commits yet
+admin-system-repo-size = Repository size
+admin-system-uncommitted = Uncommitted changes under ai/
+admin-system-all-committed = Everything committed ✨
+admin-system-recent-commits = Recent commits under ai/
+admin-system-no-commits-yet = No commits yet 🌱
+admin-backup-heading = Backup remote
+admin-backup-last-success = Last successful push
+admin-backup-last-attempt = Last attempt
+admin-backup-failing-since = Failing since
+admin-backup-last-error = Last error
+admin-backup-not-configured = No backup remote configured
+admin-jobs-heading = Background jobs
+admin-jobs-name = Job
+admin-jobs-schedule = Schedule
+admin-jobs-last-run = Last run
+admin-jobs-next-run = Next run
+admin-jobs-last-error = Last error
+admin-jobs-running = running
+admin-jobs-none = No jobs registered yet.
+admin-commit-message = Commit message
+admin-commit-submit = Trigger manual commit 💾
+admin-commit-created = Commit created 💾
+admin-commit-nothing = No changes under ai/ – nothing to commit.
+
+## Errors
+
+error-username-email-required = Please enter a username and email.
+error-invalid-email = Please enter a valid email address.
+error-identifier-required = Please enter your username or email.
+error-password-too-short = The password must be at least { $min } characters long.
+error-password-mismatch = The passwords don't match.
+error-current-password-wrong = The current password is wrong.
+error-username-taken = Username or email is already taken.
+error-no-escrow = No escrow key configured, recovery is not possible.
+error-commit-message-required = Please enter a commit message.
+error-trip-title-required = Please give your trip a title.
+error-trip-already-active = A trip is already running – end it first.
+error-unknown-timezone = Unknown timezone.
+error-invalid-timestamp = Invalid time.
+error-checkin-in-future = Check-ins can't be in the future.
+error-substance-required = Every substance needs a name.
+error-invalid-credentials = Username or password is incorrect.
+error-api-token-name-required = Please give the token a name.
+error-api-token-scope-required = Please pick at least one scope.
+error-webhook-url-invalid = Please enter an http:// or https:// URL.
+error-webhook-event-required = Please pick at least one event.
+error-reminder-time-invalid = Please enter a time like 20:00.
+error-reminder-interval-invalid = Please enter an interval of 1 to { $max } hours.
+error-reminder-kind-invalid = Please pick when we should remind you.
+error-contact-invalid = "{ $contact }" isn't a contact we can reach. Use a Matrix ID or email:, push: or webhook: followed by the address.
+
+## Error pages
+
+error-page-400-title = Something's not right 🙈
+error-page-400-text = The request couldn't be processed.
+error-page-403-title = No access 🔒
+error-page-403-text = This page is for admins only.
+error-page-404-title = Not found 🔍
+error-page-404-text = This page doesn't exist (anymore).
+error-page-500-title = Oops, an error 💔
+error-page-500-text = Something went wrong. Please try again in a moment.
+error-page-501-title = Coming soon 🌱
+error-page-501-text = This feature doesn't exist yet.
+error-page-correlation = Error ID: { $id } – please mention it when you reach out.
+login-continue-notice = Please log in to continue.
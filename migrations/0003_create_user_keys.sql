@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS user_keys (
+    user_uuid        TEXT PRIMARY KEY REFERENCES users(uuid) ON DELETE CASCADE,
+    password_salt    BLOB NOT NULL,
+    password_wrapped BLOB NOT NULL,
+    escrow_wrapped   BLOB,
+    created_at       TEXT NOT NULL,
+    updated_at       TEXT NOT NULL
+);
@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS secrets (
+    id          TEXT PRIMARY KEY,
+    user_uuid   TEXT NOT NULL REFERENCES users(uuid) ON DELETE CASCADE,
+    kind        TEXT NOT NULL,
+    ciphertext  BLOB NOT NULL,
+    created_at  TEXT NOT NULL,
+    updated_at  TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_secrets_user_uuid ON secrets(user_uuid);
@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS summary_deliveries (
+    id            INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_uuid     TEXT NOT NULL REFERENCES users(uuid) ON DELETE CASCADE,
+    period        TEXT NOT NULL,
+    period_start  TEXT NOT NULL,
+    recipient     TEXT NOT NULL,
+    delivered_at  TEXT NOT NULL,
+    UNIQUE (user_uuid, period, period_start, recipient)
+);
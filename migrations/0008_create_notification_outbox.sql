@@ -0,0 +1,21 @@
+-- Every alert to a contact goes through here before it is sent. Rows stay
+-- after sending or giving up and back the delivery status shown to users.
+CREATE TABLE IF NOT EXISTS notification_outbox (
+    id               INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id          INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    kind             TEXT NOT NULL,
+    source_id        TEXT NOT NULL,
+    channel          TEXT NOT NULL,
+    payload          BLOB NOT NULL,
+    status           TEXT NOT NULL,
+    attempts         INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at  TEXT NOT NULL,
+    last_error       TEXT,
+    created_at       TEXT NOT NULL,
+    sent_at          TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_notification_outbox_due
+    ON notification_outbox(status, next_attempt_at);
+CREATE INDEX IF NOT EXISTS idx_notification_outbox_source
+    ON notification_outbox(user_id, source_id);
@@ -0,0 +1,11 @@
+-- Matrix accounts that may use the chat bot for a user. A row with a code
+-- but no Matrix ID is a pending link; the bot fills in the sender once the
+-- code is sent to it.
+CREATE TABLE IF NOT EXISTS matrix_links (
+    user_id          INTEGER PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    matrix_user_id   TEXT UNIQUE,
+    code_hash        TEXT UNIQUE,
+    code_expires_at  TEXT,
+    linked_at        TEXT,
+    created_at       TEXT NOT NULL
+);
@@ -0,0 +1,18 @@
+-- Check-in reminders. `next_run_at` is the next slot to send; it stays NULL
+-- for trip reminders while no trip is running.
+CREATE TABLE IF NOT EXISTS reminders (
+    id           INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id      INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    kind         TEXT NOT NULL,
+    time_of_day  TEXT,
+    every_hours  INTEGER,
+    contact      TEXT NOT NULL,
+    next_run_at  TEXT,
+    last_run_at  TEXT,
+    last_status  TEXT,
+    last_error   TEXT,
+    created_at   TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_reminders_user_id ON reminders(user_id);
+CREATE INDEX IF NOT EXISTS idx_reminders_due ON reminders(next_run_at);
@@ -0,0 +1,14 @@
+-- Background jobs. One row per registered job; `next_run_at` is advanced
+-- when a run is claimed, and `locked_until` keeps other instances from
+-- running the same job until the lease runs out.
+CREATE TABLE IF NOT EXISTS jobs (
+    name              TEXT PRIMARY KEY,
+    schedule          TEXT NOT NULL,
+    next_run_at       TEXT,
+    locked_by         TEXT,
+    locked_until      TEXT,
+    last_started_at   TEXT,
+    last_finished_at  TEXT,
+    last_duration_ms  INTEGER,
+    last_error        TEXT
+);
@@ -6,16 +6,27 @@ use argon2::{
     Argon2,
 };
 use async_trait::async_trait;
-use axum::{extract::FromRequestParts, http::request::Parts};
+use axum::{
+    extract::{FromRef, FromRequestParts},
+    http::{header, request::Parts, HeaderMap},
+};
 use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
-use chrono::Utc;
+use chrono::{Duration, Utc};
 use sqlx::{sqlite::SqliteQueryResult, Row};
 use uuid::Uuid;
 
-use crate::{error::AppError, models::user::UserRole, state::AppState};
+use crate::{
+    error::AppError,
+    i18n,
+    models::{api_token::ApiScope, user::UserRole},
+    services::tokens,
+    state::AppState,
+};
 
 pub const SESSION_COOKIE: &str = "kawaii_session";
 const MIN_PASSWORD_LENGTH: usize = 8;
+/// Sessions unused for this long end and are purged.
+pub const SESSION_IDLE_DAYS: i64 = 30;
 
 #[derive(Debug, Clone)]
 pub struct AuthenticatedUser {
@@ -23,6 +34,9 @@ pub struct AuthenticatedUser {
     pub uuid: String,
     pub username: String,
     pub role: UserRole,
+    /// Scopes of the API token the request came with; `None` for sessions,
+    /// which may do everything.
+    pub token_scopes: Option<Vec<ApiScope>>,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -32,18 +46,24 @@ pub struct CurrentUser(pub Option<AuthenticatedUser>);
 impl<S> FromRequestParts<S> for CurrentUser
 where
     S: Send + Sync,
+    AppState: FromRef<S>,
 {
     type Rejection = AppError;
 
-    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
         if let Some(user) = parts.extensions.get::<AuthenticatedUser>() {
             return Ok(Self(Some(user.clone())));
         }
 
-        let state = match parts.extensions.get::<AppState>() {
-            Some(state) => state.clone(),
-            None => return Ok(Self(None)),
-        };
+        let state = AppState::from_ref(state);
+
+        if let Some(secret) = bearer_token(&parts.headers) {
+            let user = load_user_from_token(&state, secret).await?;
+            if let Some(user) = &user {
+                parts.extensions.insert(user.clone());
+            }
+            return Ok(Self(user));
+        }
 
         let jar = CookieJar::from_headers(&parts.headers);
         let Some(session_cookie) = jar.get(SESSION_COOKIE) else {
@@ -61,8 +81,23 @@ where
 }
 
 impl CurrentUser {
+    /// The logged-in user of a browser session. API tokens only work for
+    /// the API, see [`CurrentUser::require_scope`].
     pub fn require_user(&self) -> Result<&AuthenticatedUser, AppError> {
-        self.0.as_ref().ok_or(AppError::Unauthorized)
+        let user = self.0.as_ref().ok_or(AppError::Unauthorized)?;
+        if user.token_scopes.is_some() {
+            return Err(AppError::Forbidden);
+        }
+        Ok(user)
+    }
+
+    /// The user of a session, or of an API token that grants `scope`.
+    pub fn require_scope(&self, scope: ApiScope) -> Result<&AuthenticatedUser, AppError> {
+        let user = self.0.as_ref().ok_or(AppError::Unauthorized)?;
+        match &user.token_scopes {
+            Some(scopes) if !scopes.contains(&scope) => Err(AppError::Forbidden),
+            _ => Ok(user),
+        }
     }
 
     pub fn require_admin(&self) -> Result<&AuthenticatedUser, AppError> {
@@ -93,15 +128,13 @@ pub async fn register_user(
     let email = email.trim();
 
     if username.is_empty() || email.is_empty() {
-        return Err(AppError::BadRequest(
-            "Bitte Nutzername und E-Mail ausfüllen.".into(),
-        ));
+        return Err(AppError::BadRequest(i18n::tr(
+            "error-username-email-required",
+        )));
     }
 
     if !email.contains('@') {
-        return Err(AppError::BadRequest(
-            "Bitte eine gültige E-Mail-Adresse eingeben.".into(),
-        ));
+        return Err(AppError::BadRequest(i18n::tr("error-invalid-email")));
     }
 
     validate_password(password)?;
@@ -134,11 +167,14 @@ pub async fn register_user(
 
     let id = insert_result.last_insert_rowid();
 
+    state.crypto.provision(&uuid, password).await?;
+
     Ok(AuthenticatedUser {
         id,
         uuid,
         username: username.to_string(),
         role: UserRole::User,
+        token_scopes: None,
     })
 }
 
@@ -149,9 +185,7 @@ pub async fn authenticate_user(
 ) -> Result<AuthenticatedUser, AppError> {
     let identifier = identifier.trim();
     if identifier.is_empty() {
-        return Err(AppError::BadRequest(
-            "Bitte Nutzername oder E-Mail eingeben.".into(),
-        ));
+        return Err(AppError::BadRequest(i18n::tr("error-identifier-required")));
     }
 
     let row = sqlx::query(
@@ -180,6 +214,8 @@ pub async fn authenticate_user(
     let username: String = row.try_get("username")?;
     let role = parse_role(row.try_get::<String, _>("role")?.as_str());
 
+    state.crypto.unlock(&uuid, password).await?;
+
     sqlx::query("UPDATE users SET last_login_at = ?1 WHERE id = ?2")
         .bind(Utc::now())
         .bind(id)
@@ -191,6 +227,7 @@ pub async fn authenticate_user(
         uuid,
         username,
         role,
+        token_scopes: None,
     })
 }
 
@@ -213,10 +250,148 @@ pub async fn create_session(state: &AppState, user_id: i64) -> Result<String, Ap
 }
 
 pub async fn destroy_session(state: &AppState, session_id: &str) -> Result<(), AppError> {
+    let user_uuid: Option<String> = sqlx::query_scalar(
+        r#"
+        SELECT users.uuid
+        FROM sessions
+        JOIN users ON users.id = sessions.user_id
+        WHERE sessions.id = ?1
+        "#,
+    )
+    .bind(session_id)
+    .fetch_optional(&state.db)
+    .await?;
+
     sqlx::query("DELETE FROM sessions WHERE id = ?1")
         .bind(session_id)
         .execute(&state.db)
         .await?;
+
+    // Forget the data key once the user has no session left.
+    if let Some(user_uuid) = user_uuid {
+        let remaining: i64 = sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*)
+            FROM sessions
+            JOIN users ON users.id = sessions.user_id
+            WHERE users.uuid = ?1
+            "#,
+        )
+        .bind(&user_uuid)
+        .fetch_one(&state.db)
+        .await?;
+        if remaining == 0 {
+            state.crypto.lock(&user_uuid);
+        }
+    }
+    Ok(())
+}
+
+/// Deletes sessions that expired or went unused for [`SESSION_IDLE_DAYS`]
+/// and, as on logout, locks the data key of users left without one.
+/// Returns how many sessions were deleted.
+pub async fn purge_stale_sessions(state: &AppState) -> Result<usize, AppError> {
+    let now = Utc::now();
+    let mut user_ids: Vec<i64> = sqlx::query_scalar(
+        r#"
+        DELETE FROM sessions
+        WHERE (expires_at IS NOT NULL AND expires_at <= ?1) OR last_seen_at <= ?2
+        RETURNING user_id
+        "#,
+    )
+    .bind(now)
+    .bind(now - Duration::days(SESSION_IDLE_DAYS))
+    .fetch_all(&state.db)
+    .await?;
+    let purged = user_ids.len();
+
+    user_ids.sort_unstable();
+    user_ids.dedup();
+    for user_id in user_ids {
+        let user_uuid: Option<String> = sqlx::query_scalar(
+            r#"
+            SELECT uuid
+            FROM users
+            WHERE id = ?1 AND NOT EXISTS (SELECT 1 FROM sessions WHERE user_id = users.id)
+            "#,
+        )
+        .bind(user_id)
+        .fetch_optional(&state.db)
+        .await?;
+        if let Some(user_uuid) = user_uuid {
+            state.crypto.lock(&user_uuid);
+        }
+    }
+    Ok(purged)
+}
+
+/// Sets a new password once the current one checks out and re-wraps the
+/// user's data key for it. The user's other sessions are ended.
+pub async fn change_password(
+    state: &AppState,
+    user: &AuthenticatedUser,
+    session_id: &str,
+    current_password: &str,
+    new_password: &str,
+) -> Result<(), AppError> {
+    let password_hash: String = sqlx::query_scalar("SELECT password_hash FROM users WHERE id = ?1")
+        .bind(user.id)
+        .fetch_one(&state.db)
+        .await?;
+    if !verify_password(&password_hash, current_password)? {
+        return Err(AppError::BadRequest(i18n::tr(
+            "error-current-password-wrong",
+        )));
+    }
+    validate_password(new_password)?;
+
+    state.crypto.rewrap(&user.uuid, new_password).await?;
+
+    sqlx::query("UPDATE users SET password_hash = ?1 WHERE id = ?2")
+        .bind(hash_password(new_password)?)
+        .bind(user.id)
+        .execute(&state.db)
+        .await?;
+    sqlx::query("DELETE FROM sessions WHERE user_id = ?1 AND id <> ?2")
+        .bind(user.id)
+        .bind(session_id)
+        .execute(&state.db)
+        .await?;
+    Ok(())
+}
+
+/// Admin recovery: sets a new password and re-wraps the user's data key via
+/// the escrow copy. All existing sessions of the user are ended.
+pub async fn reset_password_with_escrow(
+    state: &AppState,
+    user_id: i64,
+    new_password: &str,
+) -> Result<(), AppError> {
+    if !state.crypto.escrow_enabled() {
+        return Err(AppError::BadRequest(i18n::tr("error-no-escrow")));
+    }
+    validate_password(new_password)?;
+
+    let user_uuid: String = sqlx::query_scalar("SELECT uuid FROM users WHERE id = ?1")
+        .bind(user_id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    state
+        .crypto
+        .recover_with_escrow(&user_uuid, new_password)
+        .await?;
+
+    sqlx::query("UPDATE users SET password_hash = ?1 WHERE id = ?2")
+        .bind(hash_password(new_password)?)
+        .bind(user_id)
+        .execute(&state.db)
+        .await?;
+    sqlx::query("DELETE FROM sessions WHERE user_id = ?1")
+        .bind(user_id)
+        .execute(&state.db)
+        .await?;
     Ok(())
 }
 
@@ -238,15 +413,20 @@ async fn load_user_from_session(
     state: &AppState,
     session_id: &str,
 ) -> Result<Option<AuthenticatedUser>, AppError> {
+    let now = Utc::now();
     let row = sqlx::query(
         r#"
         SELECT users.id, users.uuid, users.username, users.role
         FROM sessions
         JOIN users ON users.id = sessions.user_id
         WHERE sessions.id = ?1
+          AND (sessions.expires_at IS NULL OR sessions.expires_at > ?2)
+          AND sessions.last_seen_at > ?3
         "#,
     )
     .bind(session_id)
+    .bind(now)
+    .bind(now - Duration::days(SESSION_IDLE_DAYS))
     .fetch_optional(&state.db)
     .await?;
 
@@ -254,17 +434,77 @@ async fn load_user_from_session(
         return Ok(None);
     };
 
+    // After a restart without escrow the data key is gone until the next
+    // login. Such a session could only fail with `Locked`, so it ends and
+    // the user is sent to log in again.
+    let user_uuid: String = row.try_get("uuid")?;
+    if let Err(AppError::Locked) = state.crypto.key_for(&user_uuid).await {
+        sqlx::query("DELETE FROM sessions WHERE id = ?1")
+            .bind(session_id)
+            .execute(&state.db)
+            .await?;
+        return Ok(None);
+    }
+
     sqlx::query("UPDATE sessions SET last_seen_at = ?1 WHERE id = ?2")
-        .bind(Utc::now())
+        .bind(now)
         .bind(session_id)
         .execute(&state.db)
         .await?;
 
+    Ok(Some(AuthenticatedUser {
+        id: row.try_get("id")?,
+        uuid: user_uuid,
+        username: row.try_get("username")?,
+        role: parse_role(row.try_get::<String, _>("role")?.as_str()),
+        token_scopes: None,
+    }))
+}
+
+/// The secret of an `Authorization: Bearer` header.
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    let value = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+    scheme
+        .eq_ignore_ascii_case("bearer")
+        .then(|| token.trim())
+        .filter(|token| !token.is_empty())
+}
+
+async fn load_user_from_token(
+    state: &AppState,
+    secret: &str,
+) -> Result<Option<AuthenticatedUser>, AppError> {
+    let Some(token) = tokens::verify(&state.db, secret).await? else {
+        return Ok(None);
+    };
+    Ok(load_user(state, token.user_id)
+        .await?
+        .map(|user| AuthenticatedUser {
+            token_scopes: Some(token.scopes),
+            ..user
+        }))
+}
+
+/// Loads a user by ID for callers that authenticate it themselves, such as
+/// API tokens or the Matrix bot. The user gets session rights.
+pub async fn load_user(
+    state: &AppState,
+    user_id: i64,
+) -> Result<Option<AuthenticatedUser>, AppError> {
+    let row = sqlx::query("SELECT id, uuid, username, role FROM users WHERE id = ?1")
+        .bind(user_id)
+        .fetch_optional(&state.db)
+        .await?;
+    let Some(row) = row else {
+        return Ok(None);
+    };
     Ok(Some(AuthenticatedUser {
         id: row.try_get("id")?,
         uuid: row.try_get("uuid")?,
         username: row.try_get("username")?,
         role: parse_role(row.try_get::<String, _>("role")?.as_str()),
+        token_scopes: None,
     }))
 }
 
@@ -296,8 +536,9 @@ fn parse_role(role: &str) -> UserRole {
 
 fn validate_password(password: &str) -> Result<(), AppError> {
     if password.len() < MIN_PASSWORD_LENGTH {
-        return Err(AppError::BadRequest(format!(
-            "Passwort muss mindestens {MIN_PASSWORD_LENGTH} Zeichen lang sein."
+        return Err(AppError::BadRequest(i18n::tr_args(
+            "error-password-too-short",
+            &[("min", MIN_PASSWORD_LENGTH.into())],
         )));
     }
     Ok(())
@@ -307,7 +548,7 @@ fn match_unique_error(err: sqlx::Error) -> AppError {
     if let sqlx::Error::Database(db_err) = &err {
         let code = db_err.code().map(|c| c.to_string());
         if matches_unique_constraint(code.as_deref(), db_err.message()) {
-            return AppError::BadRequest("Nutzername oder E-Mail bereits vergeben.".into());
+            return AppError::BadRequest(i18n::tr("error-username-taken"));
         }
     }
     err.into()
@@ -0,0 +1,250 @@
+//! Geometry for the inline SVG charts. Everything is laid out server-side so
+//! the pages work without JavaScript; templates only place the shapes.
+
+use chrono::{Datelike, NaiveDate};
+
+const PAD_LEFT: f64 = 28.0;
+const PAD_RIGHT: f64 = 8.0;
+const PAD_TOP: f64 = 8.0;
+const PAD_BOTTOM: f64 = 20.0;
+/// Roughly how many x-axis labels a line chart gets.
+const X_LABELS: usize = 6;
+
+pub struct AxisLabel {
+    pub pos: f64,
+    pub text: String,
+}
+
+pub struct Dot {
+    pub x: f64,
+    pub y: f64,
+    pub title: String,
+}
+
+pub struct LineChart {
+    pub width: u32,
+    pub height: u32,
+    /// `points` attributes for `<polyline>`; days without data split the line.
+    pub segments: Vec<String>,
+    pub dots: Vec<Dot>,
+    pub y_labels: Vec<AxisLabel>,
+    pub x_labels: Vec<AxisLabel>,
+    pub plot_left: f64,
+    pub plot_right: f64,
+}
+
+impl LineChart {
+    /// `points` are `(label, value)` pairs at equal spacing; values are
+    /// clamped to `min..=max`.
+    pub fn new(
+        points: &[(String, Option<f64>)],
+        min: f64,
+        max: f64,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let plot_right = f64::from(width) - PAD_RIGHT;
+        let plot_bottom = f64::from(height) - PAD_BOTTOM;
+        let step = if points.len() > 1 {
+            (plot_right - PAD_LEFT) / (points.len() - 1) as f64
+        } else {
+            0.0
+        };
+        let x_at = |index: usize| round(PAD_LEFT + step * index as f64);
+        let y_at = |value: f64| {
+            let ratio = (value.clamp(min, max) - min) / (max - min);
+            round(plot_bottom - ratio * (plot_bottom - PAD_TOP))
+        };
+
+        let mut segments = Vec::new();
+        let mut current: Vec<String> = Vec::new();
+        let mut dots = Vec::new();
+        for (index, (label, value)) in points.iter().enumerate() {
+            match value {
+                Some(value) => {
+                    let (x, y) = (x_at(index), y_at(*value));
+                    current.push(format!("{x},{y}"));
+                    dots.push(Dot {
+                        x,
+                        y,
+                        title: format!("{label}: {value:.1}"),
+                    });
+                }
+                None if !current.is_empty() => {
+                    segments.push(std::mem::take(&mut current).join(" "))
+                }
+                None => {}
+            }
+        }
+        if !current.is_empty() {
+            segments.push(current.join(" "));
+        }
+
+        let every = points.len().div_ceil(X_LABELS).max(1);
+        let x_labels = points
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| index % every == 0)
+            .map(|(index, (label, _))| AxisLabel {
+                pos: x_at(index),
+                text: label.clone(),
+            })
+            .collect();
+        let mid = (min + max) / 2.0;
+        let y_labels = [max, mid, min]
+            .into_iter()
+            .map(|value| AxisLabel {
+                pos: y_at(value),
+                text: format!("{value}"),
+            })
+            .collect();
+
+        Self {
+            width,
+            height,
+            segments,
+            dots,
+            y_labels,
+            x_labels,
+            plot_left: PAD_LEFT,
+            plot_right,
+        }
+    }
+}
+
+pub struct Bar {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    pub label_x: f64,
+    /// Message key for the bar's label.
+    pub label: &'static str,
+    pub value: String,
+    pub fill: &'static str,
+}
+
+pub struct BarChart {
+    pub width: u32,
+    pub height: u32,
+    pub zero_y: f64,
+    pub label_y: f64,
+    pub bars: Vec<Bar>,
+}
+
+impl BarChart {
+    /// Mood bars (`-5..=5`) growing up or down from the zero line. Missing
+    /// values get no bar but keep their slot and label.
+    pub fn mood(values: &[(&'static str, Option<f64>)], width: u32, height: u32) -> Self {
+        let plot_bottom = f64::from(height) - PAD_BOTTOM;
+        let zero_y = round((PAD_TOP + plot_bottom) / 2.0);
+        let half = zero_y - PAD_TOP;
+        let slot = (f64::from(width) - PAD_LEFT - PAD_RIGHT) / values.len().max(1) as f64;
+        let bar_width = round(slot * 0.6);
+        let bars = values
+            .iter()
+            .enumerate()
+            .map(|(index, (label, value))| {
+                let x = PAD_LEFT + slot * index as f64;
+                let extent = value
+                    .map(|v| v.clamp(-5.0, 5.0) / 5.0 * half)
+                    .unwrap_or(0.0);
+                Bar {
+                    x: round(x + (slot - bar_width) / 2.0),
+                    y: round(if extent > 0.0 {
+                        zero_y - extent
+                    } else {
+                        zero_y
+                    }),
+                    width: bar_width,
+                    height: round(extent.abs()),
+                    label_x: round(x + slot / 2.0),
+                    label,
+                    value: value
+                        .map(|v| format!("{v:.1}"))
+                        .unwrap_or_else(|| "–".into()),
+                    fill: mood_fill(*value),
+                }
+            })
+            .collect();
+        Self {
+            width,
+            height,
+            zero_y,
+            label_y: f64::from(height) - 6.0,
+            bars,
+        }
+    }
+}
+
+pub struct HeatCell {
+    pub x: f64,
+    pub y: f64,
+    pub fill: &'static str,
+    pub title: String,
+}
+
+pub struct Heatmap {
+    pub width: u32,
+    pub height: u32,
+    pub cell: f64,
+    pub cells: Vec<HeatCell>,
+}
+
+impl Heatmap {
+    const CELL: f64 = 14.0;
+    const GAP: f64 = 3.0;
+
+    /// Calendar grid with one column per week (Monday on top), coloured by
+    /// the day's average mood.
+    pub fn new(days: &[(NaiveDate, Option<f64>)]) -> Self {
+        let Some((first, _)) = days.first() else {
+            return Self {
+                width: 0,
+                height: 0,
+                cell: Self::CELL,
+                cells: Vec::new(),
+            };
+        };
+        let offset = i64::from(first.weekday().num_days_from_monday());
+        let pitch = Self::CELL + Self::GAP;
+        let cells: Vec<HeatCell> = days
+            .iter()
+            .map(|(date, mood)| {
+                let slot = (*date - *first).num_days() + offset;
+                HeatCell {
+                    x: (slot / 7) as f64 * pitch,
+                    y: (slot % 7) as f64 * pitch,
+                    fill: mood_fill(*mood),
+                    title: match mood {
+                        Some(mood) => format!("{}: {mood:.1}", date.format("%d.%m.%Y")),
+                        None => date.format("%d.%m.%Y").to_string(),
+                    },
+                }
+            })
+            .collect();
+        let columns = cells.iter().map(|cell| cell.x).fold(0.0, f64::max) / pitch + 1.0;
+        Self {
+            width: (columns * pitch) as u32,
+            height: (7.0 * pitch) as u32,
+            cell: Self::CELL,
+            cells,
+        }
+    }
+}
+
+/// Colour scale from low (blue) over neutral to high (pink) mood.
+pub fn mood_fill(mood: Option<f64>) -> &'static str {
+    match mood {
+        None => "#f3f4f6",
+        Some(mood) if mood <= -3.0 => "#6366f1",
+        Some(mood) if mood < -1.0 => "#a5b4fc",
+        Some(mood) if mood <= 1.0 => "#f5d0fe",
+        Some(mood) if mood < 3.0 => "#f9a8d4",
+        Some(_) => "#ec4899",
+    }
+}
+
+fn round(value: f64) -> f64 {
+    (value * 10.0).round() / 10.0
+}
@@ -1,6 +1,18 @@
-use std::{env, net::SocketAddr, path::PathBuf};
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::{ErrorKind, Write},
+    net::SocketAddr,
+    path::{self, Path, PathBuf},
+    time::Duration,
+};
 
-use crate::error::AppError;
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+
+use crate::{
+    error::AppError,
+    models::settings::{ChannelKind, Contact},
+};
 
 #[derive(Debug, Clone)]
 pub struct AppConfig {
@@ -9,6 +21,60 @@ pub struct AppConfig {
     pub ai_root: PathBuf,
     pub repo_root: PathBuf,
     pub cookie_secret: String,
+    pub data_escrow_key: Option<String>,
+    pub secrets_key: String,
+    pub git_commit_debounce: Duration,
+    pub backup: Option<BackupConfig>,
+    pub admin_alert_contact: Option<Contact>,
+    /// Outgoing mail server for the email channel; email contacts can't be
+    /// reached without it.
+    pub smtp: Option<SmtpConfig>,
+    /// Bot account that linked users can send check-ins and panics to.
+    pub matrix_bot: Option<MatrixBotConfig>,
+    /// Encrypted Matrix crypto stores, one per user device that sends alerts.
+    pub matrix_store: PathBuf,
+}
+
+/// Optional remote the `ai/` repository is pushed to.
+#[derive(Debug, Clone)]
+pub struct BackupConfig {
+    /// SSH (`git@host:repo.git`) or local (`file:///srv/backup.git`) URL.
+    pub remote_url: String,
+    pub ssh_key: Option<PathBuf>,
+    pub push_interval: Duration,
+    /// Alert once backups have been failing for longer than this.
+    pub alert_after: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    pub tls: SmtpTls,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Sender address, e.g. `Mood Tracker <mood@example.com>`.
+    pub from: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SmtpTls {
+    /// Plain connection upgraded with STARTTLS (usually port 587).
+    StartTls,
+    /// TLS from the start (usually port 465).
+    Tls,
+    /// No encryption; only for local relays.
+    None,
+}
+
+#[derive(Debug, Clone)]
+pub struct MatrixBotConfig {
+    pub homeserver_url: String,
+    /// Full Matrix ID of the bot, e.g. `@moodbot:example.org`.
+    pub user_id: String,
+    pub password: String,
+    /// Directory for the bot's sync state and session.
+    pub store_path: PathBuf,
 }
 
 impl AppConfig {
@@ -31,7 +97,56 @@ impl AppConfig {
             });
 
         let cookie_secret = env::var("COOKIE_SECRET")
-            .unwrap_or_else(|_| "change-me-super-secret-kawaii-cookie".to_string());
+            .ok()
+            .filter(|value| !value.trim().is_empty())
+            .unwrap_or_else(|| "change-me-super-secret-kawaii-cookie".to_string());
+
+        let data_escrow_key = env::var("DATA_ESCROW_KEY")
+            .ok()
+            .filter(|value| !value.trim().is_empty());
+
+        // Never derived from the cookie secret: changing that must not make
+        // the stored secrets undecryptable.
+        let secrets_key = match env::var("SECRETS_KEY") {
+            Ok(key) if !key.trim().is_empty() => key,
+            _ => {
+                let path = env::var("SECRETS_KEY_FILE")
+                    .map(PathBuf::from)
+                    .unwrap_or_else(|_| PathBuf::from("secrets.key"));
+                load_or_create_secrets_key(&path, &ai_root)?
+            }
+        };
+
+        let git_commit_debounce = env_secs("GIT_COMMIT_DEBOUNCE_SECS", 10)?;
+
+        let backup = match env::var("BACKUP_REMOTE_URL") {
+            Ok(remote_url) if !remote_url.trim().is_empty() => Some(BackupConfig {
+                remote_url,
+                ssh_key: env::var("BACKUP_SSH_KEY").ok().map(PathBuf::from),
+                push_interval: env_secs("BACKUP_PUSH_INTERVAL_SECS", 3600)?,
+                alert_after: env_secs("BACKUP_ALERT_AFTER_SECS", 24 * 3600)?,
+            }),
+            _ => None,
+        };
+
+        let admin_alert_contact = match env::var("ADMIN_ALERT_CONTACT") {
+            Ok(raw) if !raw.trim().is_empty() => Some(parse_admin_alert_contact(&raw)?),
+            _ => None,
+        };
+
+        let smtp = match env::var("SMTP_HOST") {
+            Ok(host) if !host.trim().is_empty() => Some(smtp_from_env(host)?),
+            _ => None,
+        };
+
+        let matrix_bot = match env::var("MATRIX_BOT_USER") {
+            Ok(user_id) if !user_id.trim().is_empty() => Some(matrix_bot_from_env(user_id)?),
+            _ => None,
+        };
+
+        let matrix_store = env::var("MATRIX_STORE")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| PathBuf::from("matrix-store"));
 
         Ok(Self {
             database_url,
@@ -39,6 +154,120 @@ impl AppConfig {
             ai_root,
             repo_root,
             cookie_secret,
+            data_escrow_key,
+            secrets_key,
+            git_commit_debounce,
+            backup,
+            admin_alert_contact,
+            smtp,
+            matrix_bot,
+            matrix_store,
         })
     }
 }
+
+/// Reads the secrets key from `path`, or generates a random one and writes
+/// it there (readable by the owner only) if the file doesn't exist yet. The
+/// file may not live under `ai_root`, which is committed and pushed.
+pub fn load_or_create_secrets_key(path: &Path, ai_root: &Path) -> Result<String, AppError> {
+    if path::absolute(path)?.starts_with(path::absolute(ai_root)?) {
+        return Err(AppError::Config(format!(
+            "SECRETS_KEY_FILE {} is inside the committed data directory",
+            path.display()
+        )));
+    }
+    match fs::read_to_string(path) {
+        Ok(key) if !key.trim().is_empty() => return Ok(key.trim().to_string()),
+        Ok(_) => {
+            return Err(AppError::Config(format!(
+                "secrets key file {} is empty",
+                path.display()
+            )))
+        }
+        Err(err) if err.kind() == ErrorKind::NotFound => {}
+        Err(err) => return Err(err.into()),
+    }
+
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    let key = hex::encode(bytes);
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)?.write_all(key.as_bytes())?;
+    Ok(key)
+}
+
+/// Parses `ADMIN_ALERT_CONTACT`. Matrix messages go out from a user's own
+/// account and the server has none, so Matrix contacts are refused.
+pub fn parse_admin_alert_contact(raw: &str) -> Result<Contact, AppError> {
+    let contact = Contact::parse(raw)
+        .ok_or_else(|| AppError::Config(format!("invalid ADMIN_ALERT_CONTACT: {raw}")))?;
+    if contact.channel == ChannelKind::Matrix {
+        return Err(AppError::Config(format!(
+            "ADMIN_ALERT_CONTACT can't be a Matrix ID ({raw}); use email, push or a webhook"
+        )));
+    }
+    Ok(contact)
+}
+
+fn smtp_from_env(host: String) -> Result<SmtpConfig, AppError> {
+    let tls = match env::var("SMTP_TLS").as_deref().unwrap_or("starttls") {
+        "starttls" => SmtpTls::StartTls,
+        "tls" => SmtpTls::Tls,
+        "none" => SmtpTls::None,
+        other => return Err(AppError::Config(format!("invalid SMTP_TLS: {other}"))),
+    };
+    let default_port = match tls {
+        SmtpTls::StartTls => 587,
+        SmtpTls::Tls => 465,
+        SmtpTls::None => 25,
+    };
+    let port = match env::var("SMTP_PORT") {
+        Ok(raw) => raw
+            .parse()
+            .map_err(|err| AppError::Config(format!("invalid SMTP_PORT: {err}")))?,
+        Err(_) => default_port,
+    };
+    let from = env::var("SMTP_FROM")
+        .map_err(|_| AppError::Config("SMTP_FROM is required with SMTP_HOST".into()))?;
+    Ok(SmtpConfig {
+        host,
+        port,
+        tls,
+        username: env::var("SMTP_USERNAME").ok(),
+        password: env::var("SMTP_PASSWORD").ok(),
+        from,
+    })
+}
+
+fn matrix_bot_from_env(user_id: String) -> Result<MatrixBotConfig, AppError> {
+    let homeserver_url = env::var("MATRIX_BOT_HOMESERVER").map_err(|_| {
+        AppError::Config("MATRIX_BOT_HOMESERVER is required with MATRIX_BOT_USER".into())
+    })?;
+    let password = env::var("MATRIX_BOT_PASSWORD").map_err(|_| {
+        AppError::Config("MATRIX_BOT_PASSWORD is required with MATRIX_BOT_USER".into())
+    })?;
+    Ok(MatrixBotConfig {
+        homeserver_url,
+        user_id,
+        password,
+        store_path: env::var("MATRIX_BOT_STORE")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| PathBuf::from("matrix-bot")),
+    })
+}
+
+fn env_secs(name: &str, default: u64) -> Result<Duration, AppError> {
+    let secs = match env::var(name) {
+        Ok(raw) => raw
+            .parse::<u64>()
+            .map_err(|err| AppError::Config(format!("invalid {name}: {err}")))?,
+        Err(_) => default,
+    };
+    Ok(Duration::from_secs(secs))
+}
@@ -1,10 +1,20 @@
 #![allow(dead_code)]
 
+use askama::Template;
+use askama_axum::IntoResponse as AskamaTemplateResponse;
 use axum::{
-    http::StatusCode,
-    response::{IntoResponse, Response},
+    extract::Request,
+    http::{Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Redirect, Response},
+    Json,
 };
+use serde::Serialize;
 use thiserror::Error;
+use tracing::error;
+use uuid::Uuid;
+
+use crate::i18n::filters;
 
 #[derive(Debug, Error)]
 pub enum AppError {
@@ -26,13 +36,15 @@ pub enum AppError {
     Unauthorized,
     #[error("forbidden")]
     Forbidden,
+    #[error("data locked, please log in again")]
+    Locked,
     #[error("not implemented")]
     NotImplemented,
 }
 
-impl IntoResponse for AppError {
-    fn into_response(self) -> Response {
-        let status = match self {
+impl AppError {
+    pub fn status(&self) -> StatusCode {
+        match self {
             AppError::Config(_)
             | AppError::Io(_)
             | AppError::Database(_)
@@ -40,11 +52,182 @@ impl IntoResponse for AppError {
             | AppError::Other(_) => StatusCode::INTERNAL_SERVER_ERROR,
             AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
             AppError::NotFound => StatusCode::NOT_FOUND,
-            AppError::Unauthorized => StatusCode::UNAUTHORIZED,
+            AppError::Unauthorized | AppError::Locked => StatusCode::UNAUTHORIZED,
             AppError::Forbidden => StatusCode::FORBIDDEN,
             AppError::NotImplemented => StatusCode::NOT_IMPLEMENTED,
+        }
+    }
+
+    /// Stable machine-readable name of the error kind, used by the API.
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::BadRequest(_) => "bad_request",
+            AppError::NotFound => "not_found",
+            AppError::Unauthorized => "unauthorized",
+            AppError::Forbidden => "forbidden",
+            AppError::Locked => "locked",
+            AppError::NotImplemented => "not_implemented",
+            AppError::Config(_)
+            | AppError::Io(_)
+            | AppError::Database(_)
+            | AppError::Git(_)
+            | AppError::Other(_) => "internal",
+        }
+    }
+
+    /// Shows a validation error inline on the form that caused it, rendered
+    /// by `render` with the submitted input. Any other error is passed on.
+    pub fn rerender_form<T: Template>(
+        self,
+        render: impl FnOnce(String) -> T,
+    ) -> Result<Response, AppError> {
+        match self {
+            AppError::BadRequest(message) => Ok((
+                StatusCode::BAD_REQUEST,
+                askama_axum::into_response(&render(message)),
+            )
+                .into_response()),
+            other => Err(other),
+        }
+    }
+}
+
+/// Attached to error responses so [`error_pages`] can render them with the
+/// request at hand. The body is a plain-text fallback without the detail.
+#[derive(Clone, Debug)]
+pub struct ErrorPage {
+    status: StatusCode,
+    message: Option<String>,
+    correlation_id: Option<String>,
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        let page = match self {
+            AppError::BadRequest(message) => ErrorPage {
+                status,
+                message: Some(message),
+                correlation_id: None,
+            },
+            err if status.is_server_error() && status != StatusCode::NOT_IMPLEMENTED => {
+                // The detail may contain paths or SQL; it only goes to the log.
+                let id = Uuid::new_v4().simple().to_string()[..12].to_string();
+                error!(correlation_id = %id, "request failed: {err:?}");
+                ErrorPage {
+                    status,
+                    message: None,
+                    correlation_id: Some(id),
+                }
+            }
+            _ => ErrorPage {
+                status,
+                message: None,
+                correlation_id: None,
+            },
         };
 
-        (status, self.to_string()).into_response()
+        let body = match (&page.message, &page.correlation_id) {
+            (Some(message), _) => message.clone(),
+            (None, Some(id)) => format!("internal error (ref {id})"),
+            (None, None) => status.canonical_reason().unwrap_or("error").to_string(),
+        };
+        let mut response = (status, body).into_response();
+        response.extensions_mut().insert(page);
+        response
     }
 }
+
+/// [`AppError`] answered with a JSON body instead of an error page, for the
+/// API under `/api`.
+#[derive(Debug)]
+pub struct ApiError(pub AppError);
+
+impl From<AppError> for ApiError {
+    fn from(err: AppError) -> Self {
+        Self(err)
+    }
+}
+
+#[derive(Serialize)]
+struct ApiErrorBody {
+    error: &'static str,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    correlation_id: Option<String>,
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let code = self.0.code();
+        let message = match &self.0 {
+            AppError::BadRequest(message) => Some(message.clone()),
+            _ if code == "internal" => None,
+            err => Some(err.to_string()),
+        };
+        // Logging and the correlation ID work as for the HTML pages.
+        let mut response = self.0.into_response();
+        let status = response.status();
+        let page = response.extensions_mut().remove::<ErrorPage>();
+        let correlation_id = page.and_then(|page| page.correlation_id);
+        let body = ApiErrorBody {
+            error: code,
+            message: message.unwrap_or_else(|| match &correlation_id {
+                Some(id) => format!("internal error (ref {id})"),
+                None => "internal error".to_string(),
+            }),
+            correlation_id,
+        };
+        (status, Json(body)).into_response()
+    }
+}
+
+#[derive(Template)]
+#[template(path = "error.html")]
+struct ErrorTemplate {
+    status: u16,
+    title_key: &'static str,
+    text_key: &'static str,
+    message: Option<String>,
+    correlation_id: Option<String>,
+}
+
+/// Turns [`AppError`] responses into themed error pages. Unauthenticated
+/// requests (and sessions whose data key was forgotten) are sent to the
+/// login, which returns to the requested page afterwards.
+pub async fn error_pages(request: Request, next: Next) -> Response {
+    let return_to = (request.method() == Method::GET)
+        .then(|| request.uri().path_and_query().map(|pq| pq.to_string()))
+        .flatten();
+    let mut response = next.run(request).await;
+    let Some(page) = response.extensions_mut().remove::<ErrorPage>() else {
+        return response;
+    };
+
+    if page.status == StatusCode::UNAUTHORIZED {
+        let location = match return_to {
+            Some(path) => format!(
+                "/login?next={}",
+                form_urlencoded::byte_serialize(path.as_bytes()).collect::<String>()
+            ),
+            None => "/login".to_string(),
+        };
+        return Redirect::to(&location).into_response();
+    }
+
+    let (title_key, text_key) = match page.status {
+        StatusCode::BAD_REQUEST => ("error-page-400-title", "error-page-400-text"),
+        StatusCode::FORBIDDEN => ("error-page-403-title", "error-page-403-text"),
+        StatusCode::NOT_FOUND => ("error-page-404-title", "error-page-404-text"),
+        StatusCode::NOT_IMPLEMENTED => ("error-page-501-title", "error-page-501-text"),
+        _ => ("error-page-500-title", "error-page-500-text"),
+    };
+    let template = ErrorTemplate {
+        status: page.status.as_u16(),
+        title_key,
+        text_key,
+        message: page.message,
+        correlation_id: page.correlation_id,
+    };
+    (page.status, AskamaTemplateResponse::into_response(template)).into_response()
+}
@@ -0,0 +1,236 @@
+//! Message catalogs (Fluent, `locales/<lang>.ftl`) and the language of the
+//! current request.
+//!
+//! The [`middleware`] picks the language once per request and stores it in a
+//! task-local, so templates (via the `t` filter), error messages and
+//! notification texts can look up messages without passing it around.
+//! Messages missing from a catalog fall back to German.
+
+use std::{fmt, sync::LazyLock};
+
+use axum::{extract::Request, http::header, middleware::Next, response::Response};
+use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
+use fluent_bundle::{concurrent::FluentBundle, FluentArgs, FluentResource, FluentValue};
+use tracing::warn;
+use unic_langid::LanguageIdentifier;
+
+pub const LANG_COOKIE: &str = "kawaii_lang";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Lang {
+    #[default]
+    De,
+    En,
+}
+
+impl Lang {
+    pub const ALL: [Lang; 2] = [Lang::De, Lang::En];
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Lang::De => "de",
+            Lang::En => "en",
+        }
+    }
+
+    /// Accepts `de`, `en`, and regional variants such as `en-GB`.
+    pub fn from_code(code: &str) -> Option<Self> {
+        let primary = code.trim().split(['-', '_']).next()?.to_ascii_lowercase();
+        match primary.as_str() {
+            "de" => Some(Lang::De),
+            "en" => Some(Lang::En),
+            _ => None,
+        }
+    }
+
+    /// Best supported language from an `Accept-Language` header.
+    pub fn from_accept_language(header: &str) -> Option<Self> {
+        let mut candidates: Vec<(f32, Lang)> = header
+            .split(',')
+            .filter_map(|entry| {
+                let mut parts = entry.split(';');
+                let lang = Lang::from_code(parts.next()?)?;
+                let quality = parts
+                    .find_map(|param| param.trim().strip_prefix("q="))
+                    .and_then(|q| q.parse().ok())
+                    .unwrap_or(1.0);
+                (quality > 0.0).then_some((quality, lang))
+            })
+            .collect();
+        // Stable sort keeps header order for equal weights.
+        candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+        candidates.first().map(|(_, lang)| *lang)
+    }
+
+    fn bundle(self) -> &'static FluentBundle<FluentResource> {
+        match self {
+            Lang::De => &DE,
+            Lang::En => &EN,
+        }
+    }
+}
+
+impl fmt::Display for Lang {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+static DE: LazyLock<FluentBundle<FluentResource>> =
+    LazyLock::new(|| load_bundle("de", include_str!("../locales/de.ftl")));
+static EN: LazyLock<FluentBundle<FluentResource>> =
+    LazyLock::new(|| load_bundle("en", include_str!("../locales/en.ftl")));
+
+fn load_bundle(code: &str, source: &str) -> FluentBundle<FluentResource> {
+    let resource = FluentResource::try_new(source.to_string())
+        .unwrap_or_else(|(_, errors)| panic!("invalid {code}.ftl: {errors:?}"));
+    let lang: LanguageIdentifier = code.parse().expect("valid language id");
+    let mut bundle = FluentBundle::new_concurrent(vec![lang]);
+    // Unicode isolation marks would end up verbatim in HTML and Matrix texts.
+    bundle.set_use_isolating(false);
+    bundle
+        .add_resource(resource)
+        .unwrap_or_else(|errors| panic!("duplicate keys in {code}.ftl: {errors:?}"));
+    bundle
+}
+
+tokio::task_local! {
+    static CURRENT: Lang;
+}
+
+/// Language of the request being handled; German outside of a request.
+pub fn current() -> Lang {
+    CURRENT.try_with(|lang| *lang).unwrap_or_default()
+}
+
+/// Runs `f` with `lang` as the current language, e.g. for background jobs
+/// that render texts for a specific user.
+pub async fn scope<F: std::future::Future>(lang: Lang, f: F) -> F::Output {
+    CURRENT.scope(lang, f).await
+}
+
+/// Looks up `key` in the current language.
+pub fn tr(key: &str) -> String {
+    tr_in(current(), key, &[])
+}
+
+/// Looks up `key` in the current language with `{ $name }` arguments.
+pub fn tr_args(key: &str, args: &[(&str, FluentValue<'_>)]) -> String {
+    tr_in(current(), key, args)
+}
+
+/// Whether `lang`'s own catalog defines `key`, without the German fallback.
+pub fn has_message(lang: Lang, key: &str) -> bool {
+    lang.bundle().has_message(key)
+}
+
+/// Looks up `key` in `lang`, falling back to German and finally to the key
+/// itself so a missing message is visible but never fatal.
+pub fn tr_in(lang: Lang, key: &str, args: &[(&str, FluentValue<'_>)]) -> String {
+    let mut fluent_args = FluentArgs::new();
+    for (name, value) in args {
+        fluent_args.set(*name, value.clone());
+    }
+    let args = (!args.is_empty()).then_some(&fluent_args);
+
+    for bundle in [lang.bundle(), Lang::De.bundle()] {
+        let Some(pattern) = bundle.get_message(key).and_then(|msg| msg.value()) else {
+            continue;
+        };
+        let mut errors = Vec::new();
+        let text = bundle.format_pattern(pattern, args, &mut errors);
+        if !errors.is_empty() {
+            warn!(%key, %lang, "message formatting errors: {errors:?}");
+        }
+        return text.into_owned();
+    }
+    warn!(%key, %lang, "missing message");
+    key.to_string()
+}
+
+/// Chooses the request language: the cookie mirrored from the user's
+/// settings first, then `Accept-Language`, then German.
+pub async fn middleware(request: Request, next: Next) -> Response {
+    let cookie_lang = CookieJar::from_headers(request.headers())
+        .get(LANG_COOKIE)
+        .and_then(|cookie| Lang::from_code(cookie.value()));
+    let lang = cookie_lang
+        .or_else(|| {
+            request
+                .headers()
+                .get(header::ACCEPT_LANGUAGE)
+                .and_then(|value| value.to_str().ok())
+                .and_then(Lang::from_accept_language)
+        })
+        .unwrap_or_default();
+    CURRENT.scope(lang, next.run(request)).await
+}
+
+/// Remembers the user's language choice; `None` clears it so
+/// `Accept-Language` applies again.
+pub fn apply_lang_cookie(jar: CookieJar, lang: Option<Lang>) -> CookieJar {
+    match lang {
+        Some(lang) => jar.add(
+            Cookie::build((LANG_COOKIE, lang.code()))
+                .path("/")
+                .same_site(SameSite::Lax)
+                .permanent()
+                .build(),
+        ),
+        None => jar.remove(Cookie::build((LANG_COOKIE, "")).path("/").build()),
+    }
+}
+
+/// Askama filters; bring into scope with `use crate::i18n::filters;` next
+/// to the template structs.
+pub mod filters {
+    use std::fmt::Display;
+
+    use fluent_bundle::FluentValue;
+
+    /// `{{ "message-key"|t }}`
+    pub fn t(key: impl Display) -> askama::Result<String> {
+        Ok(super::tr(&key.to_string()))
+    }
+
+    /// Counts go in as numbers, so plural variants like `[1]` match; other
+    /// values, formatted moods like `+2` included, stay as they are.
+    fn arg(value: &str) -> FluentValue<'_> {
+        if !value.is_empty() && value.bytes().all(|byte| byte.is_ascii_digit()) {
+            FluentValue::try_number(value)
+        } else {
+            FluentValue::from(value)
+        }
+    }
+
+    /// `{{ "message-key"|t1("name", value) }}`
+    pub fn t1(
+        key: impl Display,
+        name: impl AsRef<str>,
+        value: impl Display,
+    ) -> askama::Result<String> {
+        let value = value.to_string();
+        Ok(super::tr_args(
+            &key.to_string(),
+            &[(name.as_ref(), arg(&value))],
+        ))
+    }
+
+    /// `{{ "message-key"|t2("a", a, "b", b) }}`
+    pub fn t2(
+        key: impl Display,
+        name_a: impl AsRef<str>,
+        value_a: impl Display,
+        name_b: impl AsRef<str>,
+        value_b: impl Display,
+    ) -> askama::Result<String> {
+        let (value_a, value_b) = (value_a.to_string(), value_b.to_string());
+        Ok(super::tr_args(
+            &key.to_string(),
+            &[
+                (name_a.as_ref(), arg(&value_a)),
+                (name_b.as_ref(), arg(&value_b)),
+            ],
+        ))
+    }
+}
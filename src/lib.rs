@@ -1,8 +1,11 @@
 pub mod auth;
+pub mod charts;
 pub mod config;
 pub mod db;
 pub mod error;
+pub mod i18n;
 pub mod models;
 pub mod routes;
 pub mod services;
 pub mod state;
+pub mod timezone;
@@ -2,10 +2,17 @@ use mood::config::AppConfig;
 use mood::db::init_pool;
 use mood::error::AppError;
 use mood::routes::create_router;
-use mood::services::{git::GitService, storage::StorageService};
+use mood::services::{
+    crypto::CryptoService,
+    git::GitService,
+    jobs::{self, JobRunner},
+    matrix_bot,
+    secrets::scrub_committed_tokens,
+    storage::StorageService,
+};
 use mood::state::AppState;
-use tokio::net::TcpListener;
-use tracing::{error, info};
+use tokio::{net::TcpListener, sync::watch};
+use tracing::{error, info, warn};
 
 #[tokio::main]
 async fn main() -> Result<(), AppError> {
@@ -20,23 +27,107 @@ async fn main() -> Result<(), AppError> {
         return Err(AppError::Other(err.into()));
     }
 
-    let storage = StorageService::new(config.ai_root.clone());
+    let crypto = CryptoService::new(db.clone(), config.data_escrow_key.as_deref());
+    let storage = StorageService::new(config.ai_root.clone(), crypto.clone());
     storage.ensure_structure().await?;
 
-    let git = GitService::new(config.repo_root.clone());
+    let mut git = GitService::new(config.repo_root.clone());
     git.init_repo_if_needed()?;
+    // Stopped only after the jobs, so changes they record are committed too.
+    let (git_shutdown_tx, git_shutdown_rx) = watch::channel(false);
+    let auto_commit = git.start_auto_commit(config.git_commit_debounce, git_shutdown_rx);
 
-    let state = AppState::new(config.clone(), db.clone(), storage.clone(), git.clone());
+    let state = AppState::new(
+        config.clone(),
+        db.clone(),
+        crypto.clone(),
+        storage.clone(),
+        git.clone(),
+    );
+
+    if std::env::args().nth(1).as_deref() == Some("scrub-secrets") {
+        let report = scrub_committed_tokens(&state).await?;
+        info!(
+            commits = report.history.commits_rewritten,
+            blobs = report.history.blobs_rewritten,
+            "scrubbed matrix access tokens from git history"
+        );
+        for user_uuid in &report.locked_users {
+            warn!(%user_uuid, "data key locked, log in once (or set DATA_ESCROW_KEY) and rerun");
+        }
+        if report.history.commits_rewritten > 0 {
+            info!("run `git gc --prune=now` and force-push any remotes to drop the old objects");
+        }
+        return Ok(());
+    }
+
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+    let runner = JobRunner::new(state.clone(), jobs::builtin(&state));
+    runner.register().await?;
+    let jobs = runner.start(shutdown_rx.clone());
+    let bot = config
+        .matrix_bot
+        .clone()
+        .map(|bot| matrix_bot::start(state.clone(), bot, shutdown_rx));
 
     let app = create_router(state.clone());
 
     let listener = TcpListener::bind(config.listen_addr).await?;
     info!("listening on {}", listener.local_addr()?);
-    axum::serve(listener, app.into_make_service()).await?;
+    axum::serve(listener, app.into_make_service())
+        .with_graceful_shutdown(async move {
+            shutdown_signal().await;
+            shutdown_tx.send_replace(true);
+        })
+        .await?;
 
+    if let Err(err) = jobs.await {
+        error!("job runner failed: {err}");
+    }
+    if let Some(bot) = bot {
+        if let Err(err) = bot.await {
+            error!("matrix bot task failed: {err}");
+        }
+    }
+    git_shutdown_tx.send_replace(true);
+    if let Err(err) = auto_commit.await {
+        error!("auto-commit worker failed: {err}");
+    }
+    info!("stopped");
     Ok(())
 }
 
+/// Resolves on Ctrl-C or, on Unix, SIGTERM.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(err) = tokio::signal::ctrl_c().await {
+            error!("listening for Ctrl-C failed: {err}");
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(err) => {
+                error!("listening for SIGTERM failed: {err}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+    info!("shutting down, finishing running jobs");
+}
+
 fn init_logging() {
     use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
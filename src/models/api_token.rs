@@ -0,0 +1,76 @@
+#![allow(dead_code)]
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// What a personal API token may do. Sessions are not restricted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ApiScope {
+    #[serde(rename = "checkins:read")]
+    CheckinsRead,
+    #[serde(rename = "checkins:write")]
+    CheckinsWrite,
+    #[serde(rename = "trips:read")]
+    TripsRead,
+    #[serde(rename = "trips:write")]
+    TripsWrite,
+    #[serde(rename = "panic:trigger")]
+    PanicTrigger,
+    #[serde(rename = "settings:read")]
+    SettingsRead,
+    #[serde(rename = "settings:write")]
+    SettingsWrite,
+}
+
+impl ApiScope {
+    pub const ALL: [ApiScope; 7] = [
+        ApiScope::CheckinsRead,
+        ApiScope::CheckinsWrite,
+        ApiScope::TripsRead,
+        ApiScope::TripsWrite,
+        ApiScope::PanicTrigger,
+        ApiScope::SettingsRead,
+        ApiScope::SettingsWrite,
+    ];
+
+    pub fn code(self) -> &'static str {
+        match self {
+            ApiScope::CheckinsRead => "checkins:read",
+            ApiScope::CheckinsWrite => "checkins:write",
+            ApiScope::TripsRead => "trips:read",
+            ApiScope::TripsWrite => "trips:write",
+            ApiScope::PanicTrigger => "panic:trigger",
+            ApiScope::SettingsRead => "settings:read",
+            ApiScope::SettingsWrite => "settings:write",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|scope| scope.code() == code)
+    }
+
+    /// Message key describing the scope in the token form.
+    pub fn message_key(self) -> &'static str {
+        match self {
+            ApiScope::CheckinsRead => "api-scope-checkins-read",
+            ApiScope::CheckinsWrite => "api-scope-checkins-write",
+            ApiScope::TripsRead => "api-scope-trips-read",
+            ApiScope::TripsWrite => "api-scope-trips-write",
+            ApiScope::PanicTrigger => "api-scope-panic-trigger",
+            ApiScope::SettingsRead => "api-scope-settings-read",
+            ApiScope::SettingsWrite => "api-scope-settings-write",
+        }
+    }
+}
+
+/// A personal API token. Only a hash of the secret is stored; the token
+/// itself is shown once, when it is created.
+#[derive(Debug, Clone)]
+pub struct ApiToken {
+    pub id: i64,
+    pub user_id: i64,
+    pub name: String,
+    pub scopes: Vec<ApiScope>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
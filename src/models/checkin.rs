@@ -1,6 +1,6 @@
 #![allow(dead_code)]
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
@@ -16,8 +16,14 @@ pub struct Checkin {
     pub notes: Option<String>,
     pub drugs: Vec<DrugEntry>,
     pub auto_notifications: AutoNotifications,
+    /// Set when the check-in was moved to the trash.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime<Utc>>,
 }
 
+/// How long soft-deleted check-ins stay in the trash before being purged.
+pub const TRASH_RETENTION_DAYS: i64 = 30;
+
 impl Checkin {
     pub fn new(user_uuid: impl Into<String>) -> Self {
         Self {
@@ -31,20 +37,35 @@ impl Checkin {
             notes: None,
             drugs: Vec::new(),
             auto_notifications: AutoNotifications::default(),
+            deleted_at: None,
         }
     }
+
+    pub fn is_deleted(&self) -> bool {
+        self.deleted_at.is_some()
+    }
+
+    /// Whether a trashed check-in is past its retention period at `now`.
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.deleted_at
+            .is_some_and(|deleted_at| now - deleted_at >= Duration::days(TRASH_RETENTION_DAYS))
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct AutoNotifications {
     pub mood_threshold_triggered: bool,
     pub panic_triggered: bool,
+    /// A downward trend alert went out with this check-in.
+    #[serde(default)]
+    pub trend_triggered: bool,
     pub notified_contacts: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DrugEntry {
     pub substance: String,
+    #[serde(default)]
     pub dose: String,
     pub route: Option<String>,
     pub start_time: Option<DateTime<Utc>>,
@@ -60,3 +81,16 @@ pub struct PanicEvent {
     pub high_level_at_panic: Option<i32>,
     pub notified_contacts: Vec<String>,
 }
+
+impl PanicEvent {
+    pub fn new(user_uuid: impl Into<String>) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            user_uuid: user_uuid.into(),
+            timestamp: Utc::now(),
+            mood_at_panic: None,
+            high_level_at_panic: None,
+            notified_contacts: Vec::new(),
+        }
+    }
+}
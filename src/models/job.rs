@@ -0,0 +1,206 @@
+#![allow(dead_code)]
+
+use std::{fmt, time::Duration as StdDuration};
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Timelike, Utc};
+
+/// When a background job runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JobSchedule {
+    /// At a fixed interval, first right after startup.
+    Every(StdDuration),
+    /// At the times matching a cron expression, in UTC.
+    Cron(CronExpr),
+}
+
+impl JobSchedule {
+    /// Parses `@every 15s` (units `s`, `m`, `h`, `d`), `@hourly`, `@daily`
+    /// or a five-field cron expression.
+    pub fn parse(input: &str) -> Option<Self> {
+        let input = input.trim();
+        if let Some(interval) = input.strip_prefix("@every ") {
+            let interval = interval.trim();
+            let unit_at = interval.len().checked_sub(1)?;
+            let amount: u64 = interval.get(..unit_at)?.parse().ok()?;
+            let unit = match &interval[unit_at..] {
+                "s" => 1,
+                "m" => 60,
+                "h" => 60 * 60,
+                "d" => 24 * 60 * 60,
+                _ => return None,
+            };
+            return (amount > 0).then(|| JobSchedule::Every(StdDuration::from_secs(amount * unit)));
+        }
+        let expr = match input {
+            "@hourly" => "0 * * * *",
+            "@daily" => "0 0 * * *",
+            other => other,
+        };
+        CronExpr::parse(expr).map(JobSchedule::Cron)
+    }
+
+    /// Where a new job starts: interval jobs right away, cron jobs at
+    /// their first matching minute.
+    pub fn first_run(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            JobSchedule::Every(_) => Some(now),
+            JobSchedule::Cron(_) => self.next_after(now),
+        }
+    }
+
+    /// The first run strictly after `after`; `None` for a cron expression
+    /// that never matches, like the 31st of February.
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            JobSchedule::Every(interval) => Some(after + Duration::from_std(*interval).ok()?),
+            JobSchedule::Cron(expr) => expr.next_after(after),
+        }
+    }
+}
+
+impl fmt::Display for JobSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JobSchedule::Every(interval) => {
+                let secs = interval.as_secs();
+                let (amount, unit) = [(24 * 60 * 60, "d"), (60 * 60, "h"), (60, "m")]
+                    .into_iter()
+                    .find(|(unit_secs, _)| secs > 0 && secs % unit_secs == 0)
+                    .map_or((secs, "s"), |(unit_secs, unit)| (secs / unit_secs, unit));
+                write!(f, "@every {amount}{unit}")
+            }
+            JobSchedule::Cron(expr) => f.write_str(&expr.source),
+        }
+    }
+}
+
+/// A five-field cron expression (minute, hour, day of month, month, day of
+/// week) with `*`, lists, ranges and steps. As in cron, a job with both a
+/// day of month and a day of week runs on either.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronExpr {
+    source: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl CronExpr {
+    pub fn parse(input: &str) -> Option<Self> {
+        let fields: Vec<&str> = input.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return None;
+        };
+        // Sunday is both 0 and 7.
+        let mut weekday_bits = parse_field(weekdays, 0, 7)?;
+        if weekday_bits & (1 << 7) != 0 {
+            weekday_bits = (weekday_bits | 1) & !(1 << 7);
+        }
+        Some(Self {
+            source: fields.join(" "),
+            minutes: parse_field(minutes, 0, 59)?,
+            hours: parse_field(hours, 0, 23)?,
+            days: parse_field(days, 1, 31)?,
+            months: parse_field(months, 1, 12)?,
+            weekdays: weekday_bits,
+            any_day: days == "*",
+            any_weekday: weekdays == "*",
+        })
+    }
+
+    fn matches_day(&self, date: NaiveDate) -> bool {
+        let day = self.days & (1 << date.day()) != 0;
+        let weekday = self.weekdays & (1 << date.weekday().num_days_from_sunday()) != 0;
+        let day_matches = match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (false, true) => day,
+            (true, false) => weekday,
+            (false, false) => day || weekday,
+        };
+        day_matches && self.months & (1 << date.month()) != 0
+    }
+
+    /// The first matching minute after `after`. Looks up to five years
+    /// ahead, which covers every expression that can match at all.
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let start = after.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let mut date = start.date_naive();
+        for _ in 0..5 * 366 {
+            if self.matches_day(date) {
+                let from = if date == start.date_naive() {
+                    (start.hour(), start.minute())
+                } else {
+                    (0, 0)
+                };
+                for hour in from.0..24 {
+                    if self.hours & (1 << hour) == 0 {
+                        continue;
+                    }
+                    let first_minute = if hour == from.0 { from.1 } else { 0 };
+                    if let Some(minute) =
+                        (first_minute..60).find(|minute| self.minutes & (1 << minute) != 0)
+                    {
+                        return Some(date.and_hms_opt(hour, minute, 0)?.and_utc());
+                    }
+                }
+            }
+            date = date.succ_opt()?;
+        }
+        None
+    }
+}
+
+/// The bits `min..=max` a cron field selects, e.g. `*/15`, `1-5` or `0,30`.
+fn parse_field(field: &str, min: u32, max: u32) -> Option<u64> {
+    let mut bits = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().ok().filter(|step| *step > 0)?),
+            None => (part, 1),
+        };
+        let (from, to) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((from, to)) => (from.parse().ok()?, to.parse().ok()?),
+                // `5/10` means every tenth from 5 on.
+                None if part.contains('/') => (range.parse().ok()?, max),
+                None => {
+                    let value = range.parse().ok()?;
+                    (value, value)
+                }
+            },
+        };
+        if from < min || to > max || from > to {
+            return None;
+        }
+        for value in (from..=to).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Some(bits)
+}
+
+/// A job's row in `jobs`, as shown on the admin system page.
+#[derive(Debug, Clone)]
+pub struct JobRecord {
+    pub name: String,
+    pub schedule: String,
+    pub next_run_at: Option<DateTime<Utc>>,
+    pub locked_by: Option<String>,
+    pub locked_until: Option<DateTime<Utc>>,
+    pub last_started_at: Option<DateTime<Utc>>,
+    pub last_finished_at: Option<DateTime<Utc>>,
+    pub last_duration_ms: Option<i64>,
+    pub last_error: Option<String>,
+}
+
+impl JobRecord {
+    /// Whether some instance holds an unexpired lock on the job.
+    pub fn is_running(&self, now: DateTime<Utc>) -> bool {
+        self.locked_until.is_some_and(|until| until > now)
+    }
+}
@@ -1,5 +1,10 @@
+pub mod api_token;
 pub mod checkin;
+pub mod job;
+pub mod notification;
+pub mod reminder;
 pub mod session;
 pub mod settings;
 pub mod trip;
 pub mod user;
+pub mod webhook;
@@ -0,0 +1,64 @@
+#![allow(dead_code)]
+
+use chrono::{DateTime, Utc};
+
+use crate::models::{settings::Contact, webhook::DeliveryStatus};
+
+/// What an outbox entry alerts about. Its source is the check-in for
+/// low-mood and trend alerts and the panic event for panic alerts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotificationKind {
+    LowMood,
+    Trend,
+    Panic,
+}
+
+impl NotificationKind {
+    pub const ALL: [NotificationKind; 3] = [
+        NotificationKind::LowMood,
+        NotificationKind::Trend,
+        NotificationKind::Panic,
+    ];
+
+    pub fn code(self) -> &'static str {
+        match self {
+            NotificationKind::LowMood => "low_mood",
+            NotificationKind::Trend => "trend",
+            NotificationKind::Panic => "panic",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.code() == code)
+    }
+
+    pub fn message_key(self) -> &'static str {
+        match self {
+            NotificationKind::LowMood => "notify-kind-low-mood",
+            NotificationKind::Trend => "notify-kind-trend",
+            NotificationKind::Panic => "notify-kind-panic",
+        }
+    }
+}
+
+/// One alert to one contact, without the message.
+#[derive(Debug, Clone)]
+pub struct OutboxEntry {
+    pub id: i64,
+    pub kind: NotificationKind,
+    pub source_id: String,
+    pub contact: Contact,
+    pub status: DeliveryStatus,
+    pub attempts: i64,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub sent_at: Option<DateTime<Utc>>,
+}
+
+impl OutboxEntry {
+    /// When the next attempt is scheduled; `None` once the entry is done.
+    pub fn next_attempt(&self) -> Option<DateTime<Utc>> {
+        (self.status == DeliveryStatus::Pending).then_some(self.next_attempt_at)
+    }
+}
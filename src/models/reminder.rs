@@ -0,0 +1,114 @@
+#![allow(dead_code)]
+
+use chrono::{DateTime, Duration, NaiveTime, Utc};
+use chrono_tz::Tz;
+
+use crate::{models::settings::Contact, timezone};
+
+/// When a check-in reminder goes out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReminderSchedule {
+    /// Every day at this time in the user's timezone.
+    Daily(NaiveTime),
+    /// Every this many hours while a trip is running, counted from its start.
+    DuringTrip { every_hours: u32 },
+}
+
+impl ReminderSchedule {
+    pub fn code(self) -> &'static str {
+        match self {
+            ReminderSchedule::Daily(_) => "daily",
+            ReminderSchedule::DuringTrip { .. } => "trip",
+        }
+    }
+
+    /// A check-in this recent makes the reminder pointless: for trips, one
+    /// since the previous slot; for daily reminders, one in the last three
+    /// hours.
+    pub fn recent_checkin_window(self) -> Duration {
+        match self {
+            ReminderSchedule::Daily(_) => Duration::hours(3),
+            ReminderSchedule::DuringTrip { every_hours } => Duration::hours(every_hours.into()),
+        }
+    }
+
+    /// The first slot after `after`. Trip slots need the start of the
+    /// running trip; without one there is none.
+    pub fn next_after(
+        self,
+        after: DateTime<Utc>,
+        tz: Tz,
+        trip_started_at: Option<DateTime<Utc>>,
+    ) -> Option<DateTime<Utc>> {
+        match self {
+            ReminderSchedule::Daily(time) => {
+                let today = timezone::local_date(after, tz);
+                (0..=2)
+                    .filter_map(|days| {
+                        timezone::from_local((today + Duration::days(days)).and_time(time), tz)
+                    })
+                    .find(|slot| *slot > after)
+            }
+            ReminderSchedule::DuringTrip { every_hours } => {
+                let start = trip_started_at?;
+                let interval_secs = i64::from(every_hours.max(1)) * 60 * 60;
+                let slots_passed = (after - start).num_seconds().max(-1) / interval_secs;
+                Some(start + Duration::seconds(interval_secs * (slots_passed + 1)))
+            }
+        }
+    }
+}
+
+/// What happened the last time a reminder was due.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReminderStatus {
+    Sent,
+    /// Not sent: a recent check-in, the contact's quiet hours, or a slot
+    /// missed while the app was down.
+    Skipped,
+    Failed,
+}
+
+impl ReminderStatus {
+    pub fn code(self) -> &'static str {
+        match self {
+            ReminderStatus::Sent => "sent",
+            ReminderStatus::Skipped => "skipped",
+            ReminderStatus::Failed => "failed",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        [
+            ReminderStatus::Sent,
+            ReminderStatus::Skipped,
+            ReminderStatus::Failed,
+        ]
+        .into_iter()
+        .find(|status| status.code() == code)
+    }
+
+    pub fn message_key(self) -> &'static str {
+        match self {
+            ReminderStatus::Sent => "reminder-status-sent",
+            ReminderStatus::Skipped => "reminder-status-skipped",
+            ReminderStatus::Failed => "reminder-status-failed",
+        }
+    }
+}
+
+/// A reminder schedule and where it goes, usually the user's own Matrix ID
+/// or push topic.
+#[derive(Debug, Clone)]
+pub struct Reminder {
+    pub id: i64,
+    pub user_id: i64,
+    pub schedule: ReminderSchedule,
+    pub contact: Contact,
+    /// `None` while a trip reminder waits for a trip.
+    pub next_run_at: Option<DateTime<Utc>>,
+    pub last_run_at: Option<DateTime<Utc>>,
+    pub last_status: Option<ReminderStatus>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
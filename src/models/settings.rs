@@ -1,13 +1,39 @@
 #![allow(dead_code)]
 
+use std::{collections::BTreeMap, fmt};
+
+use chrono::{DateTime, Duration, NaiveTime, Utc};
+use chrono_tz::Tz;
 use serde::{Deserialize, Serialize};
 
+use crate::{i18n::Lang, timezone};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GlobalConfig {
     pub default_low_mood_threshold: i32,
     pub default_auto_notify_on_low_mood: bool,
-    pub low_mood_message_template: String,
-    pub panic_message_template: String,
+    pub low_mood_message_template: LocalizedText,
+    pub panic_message_template: LocalizedText,
+    /// Sent to the primary contact when a downward mood trend is detected.
+    #[serde(default = "default_trend_message_template")]
+    pub trend_message_template: LocalizedText,
+    /// Weekly and monthly summaries for the user (and their therapist).
+    #[serde(default = "default_summary_message_template")]
+    pub summary_message_template: LocalizedText,
+}
+
+fn default_summary_message_template() -> LocalizedText {
+    LocalizedText::from([
+        (Lang::De, "{period_name} von {display_name} 🌸 ({period}): {checkin_count} Check-ins, Ø Stimmung {average_mood}, Tiefpunkt {lowest_mood} am {lowest_at}. Trips: {trip_count}. Substanzen: {substances}."),
+        (Lang::En, "{period_name} for {display_name} 🌸 ({period}): {checkin_count} check-ins, avg. mood {average_mood}, lowest {lowest_mood} on {lowest_at}. Trips: {trip_count}. Substances: {substances}."),
+    ])
+}
+
+fn default_trend_message_template() -> LocalizedText {
+    LocalizedText::from([
+        (Lang::De, "Hey 💕, hier ist der Mood-Tracker von {username}. Die Stimmung sinkt seit ein paar Tagen (zuletzt Ø {recent}, sonst Ø {baseline}). Vielleicht magst du dich mal melden 🌸"),
+        (Lang::En, "Hey 💕, this is {username}'s mood tracker. Their mood has been dropping for a few days (lately avg. {recent}, usually avg. {baseline}). Maybe reach out to them 🌸"),
+    ])
 }
 
 impl Default for GlobalConfig {
@@ -15,8 +41,266 @@ impl Default for GlobalConfig {
         Self {
             default_low_mood_threshold: 1,
             default_auto_notify_on_low_mood: true,
-            low_mood_message_template: "Hey 💕, hier ist der Mood-Tracker von {username}. Stimmung: {mood}, Rausch: {high_level}/10 am {timestamp}. Nur ein kleiner Hinweis, dass ein kurzer Check-in gut tun könnte 🌸".into(),
-            panic_message_template: "ALARM 💖: {username} hat in der App 'Ich brauche Hilfe' gedrückt. Stimmung: {mood} / Rausch: {high_level}/10. Vielleicht magst du kurz nach ihnen schauen 💕".into(),
+            low_mood_message_template: LocalizedText::from([
+                (Lang::De, "Hey 💕, hier ist der Mood-Tracker von {username}. Stimmung: {mood}, Rausch: {high_level}/10 am {timestamp}. Nur ein kleiner Hinweis, dass ein kurzer Check-in gut tun könnte 🌸"),
+                (Lang::En, "Hey 💕, this is {username}'s mood tracker. Mood: {mood}, high: {high_level}/10 on {timestamp}. Just a gentle nudge that a quick check-in might help 🌸"),
+            ]),
+            panic_message_template: LocalizedText::from([
+                (Lang::De, "ALARM 💖: {username} hat in der App 'Ich brauche Hilfe' gedrückt. Stimmung: {mood} / Rausch: {high_level}/10. Vielleicht magst du kurz nach ihnen schauen 💕"),
+                (Lang::En, "ALERT 💖: {username} pressed 'I need help' in the app. Mood: {mood} / high: {high_level}/10. Maybe check in on them 💕"),
+            ]),
+            trend_message_template: default_trend_message_template(),
+            summary_message_template: default_summary_message_template(),
+        }
+    }
+}
+
+/// A text with one variant per language, stored as `{"de": …, "en": …}`.
+/// Plain strings from older configs are read as German.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "LocalizedTextRepr")]
+pub struct LocalizedText(BTreeMap<String, String>);
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LocalizedTextRepr {
+    Plain(String),
+    Localized(BTreeMap<String, String>),
+}
+
+impl From<LocalizedTextRepr> for LocalizedText {
+    fn from(repr: LocalizedTextRepr) -> Self {
+        match repr {
+            LocalizedTextRepr::Plain(text) => Self::from([(Lang::De, text.as_str())]),
+            LocalizedTextRepr::Localized(map) => Self(map),
+        }
+    }
+}
+
+impl<const N: usize> From<[(Lang, &str); N]> for LocalizedText {
+    fn from(variants: [(Lang, &str); N]) -> Self {
+        Self(
+            variants
+                .into_iter()
+                .map(|(lang, text)| (lang.code().to_string(), text.to_string()))
+                .collect(),
+        )
+    }
+}
+
+impl LocalizedText {
+    /// The variant for `lang`, falling back to German.
+    pub fn get(&self, lang: Lang) -> &str {
+        self.0
+            .get(lang.code())
+            .or_else(|| self.0.get(Lang::De.code()))
+            .or_else(|| self.0.values().next())
+            .map(String::as_str)
+            .unwrap_or_default()
+    }
+
+    pub fn set(&mut self, lang: Lang, text: impl Into<String>) {
+        self.0.insert(lang.code().to_string(), text.into());
+    }
+}
+
+/// The way a notification reaches a contact.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChannelKind {
+    Matrix,
+    Email,
+    /// ntfy or Gotify style HTTP push.
+    Push,
+    /// A JSON `POST` to any URL.
+    Webhook,
+}
+
+impl ChannelKind {
+    pub const ALL: [ChannelKind; 4] = [
+        ChannelKind::Matrix,
+        ChannelKind::Email,
+        ChannelKind::Push,
+        ChannelKind::Webhook,
+    ];
+
+    pub fn code(self) -> &'static str {
+        match self {
+            ChannelKind::Matrix => "matrix",
+            ChannelKind::Email => "email",
+            ChannelKind::Push => "push",
+            ChannelKind::Webhook => "webhook",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.code() == code)
+    }
+
+    fn accepts(self, address: &str) -> bool {
+        let is_url = |prefix: &str| {
+            address
+                .strip_prefix(prefix)
+                .is_some_and(|rest| !rest.is_empty())
+        };
+        match self {
+            ChannelKind::Matrix => address.starts_with('@') && address.contains(':'),
+            ChannelKind::Email => {
+                matches!(address.split_once('@'), Some((local, domain)) if !local.is_empty() && domain.contains('.'))
+            }
+            ChannelKind::Push | ChannelKind::Webhook => is_url("https://") || is_url("http://"),
+        }
+    }
+}
+
+/// A daily window, in the user's timezone, in which a contact only gets
+/// panic alerts. It may wrap past midnight, like `22:00-07:00`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuietHours {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl QuietHours {
+    /// Parses `HH:MM-HH:MM`. Empty windows are rejected.
+    pub fn parse(input: &str) -> Option<Self> {
+        let (start, end) = input.split_once('-')?;
+        let start = NaiveTime::parse_from_str(start.trim(), "%H:%M").ok()?;
+        let end = NaiveTime::parse_from_str(end.trim(), "%H:%M").ok()?;
+        (start != end).then_some(Self { start, end })
+    }
+
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start < self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+
+    /// When the window `now` falls into ends, or `None` outside of it.
+    pub fn deferral_end(&self, now: DateTime<Utc>, tz: Tz) -> Option<DateTime<Utc>> {
+        let local = now.with_timezone(&tz).naive_local();
+        if !self.contains(local.time()) {
+            return None;
+        }
+        let mut end = local.date().and_time(self.end);
+        if end <= local {
+            end += Duration::days(1);
+        }
+        timezone::from_local(end, tz)
+    }
+}
+
+impl fmt::Display for QuietHours {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}-{}",
+            self.start.format("%H:%M"),
+            self.end.format("%H:%M")
+        )
+    }
+}
+
+/// Someone to notify: a channel plus the address on it, and optionally
+/// quiet hours. Stored as `{"channel": "email", "address": "…"}`; plain
+/// strings from older configs are Matrix IDs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "ContactRepr")]
+pub struct Contact {
+    pub channel: ChannelKind,
+    pub address: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quiet_hours: Option<QuietHours>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ContactRepr {
+    Plain(String),
+    Typed {
+        channel: ChannelKind,
+        address: String,
+        #[serde(default)]
+        quiet_hours: Option<QuietHours>,
+    },
+}
+
+impl From<ContactRepr> for Contact {
+    fn from(repr: ContactRepr) -> Self {
+        match repr {
+            ContactRepr::Plain(address) => Self::matrix(address),
+            ContactRepr::Typed {
+                channel,
+                address,
+                quiet_hours,
+            } => Self {
+                channel,
+                address,
+                quiet_hours,
+            },
+        }
+    }
+}
+
+impl Contact {
+    pub fn matrix(address: impl Into<String>) -> Self {
+        Self {
+            channel: ChannelKind::Matrix,
+            address: address.into(),
+            quiet_hours: None,
+        }
+    }
+
+    /// Parses `channel:address`, e.g. `email:alex@example.com` or
+    /// `push:https://ntfy.sh/topic`. Without a channel prefix, Matrix IDs,
+    /// email addresses and URLs (as push) are recognised by their shape. A
+    /// trailing `22:00-07:00` sets quiet hours.
+    pub fn parse(input: &str) -> Option<Self> {
+        let input = input.trim();
+        let (input, quiet_hours) = match input.rsplit_once(char::is_whitespace) {
+            Some((target, quiet_hours)) => {
+                (target.trim_end(), Some(QuietHours::parse(quiet_hours)?))
+            }
+            None => (input, None),
+        };
+        let explicit = input.split_once(':').and_then(|(prefix, address)| {
+            ChannelKind::from_code(prefix).map(|channel| (channel, address.trim()))
+        });
+        let (channel, address) = match explicit {
+            Some(explicit) => explicit,
+            None if input.starts_with('@') => (ChannelKind::Matrix, input),
+            None if input.starts_with("http://") || input.starts_with("https://") => {
+                (ChannelKind::Push, input)
+            }
+            None => (ChannelKind::Email, input),
+        };
+        channel.accepts(address).then(|| Self {
+            channel,
+            address: address.to_string(),
+            quiet_hours,
+        })
+    }
+
+    /// Channel and address without the quiet hours, for delivery logs.
+    /// Matrix IDs stay bare, as in configs written before contacts had
+    /// channels.
+    pub fn target(&self) -> String {
+        match self.channel {
+            ChannelKind::Matrix => self.address.clone(),
+            channel => format!("{}:{}", channel.code(), self.address),
+        }
+    }
+}
+
+/// The form `parse` reads back.
+impl fmt::Display for Contact {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.target())?;
+        match self.quiet_hours {
+            Some(quiet_hours) => write!(f, " {quiet_hours}"),
+            None => Ok(()),
         }
     }
 }
@@ -27,11 +311,113 @@ pub struct UserConfig {
     pub display_name: String,
     pub homeserver_url: String,
     pub matrix_user_id: String,
-    pub matrix_access_token: String,
-    pub primary_contact: Option<String>,
-    pub emergency_contacts: Vec<String>,
+    /// ID of the access token in the secrets store, never the token itself.
+    #[serde(default)]
+    pub matrix_access_token_secret: Option<String>,
+    /// Plaintext token from configs written before the secrets store
+    /// existed. Read once for migration and never written back.
+    #[serde(default, rename = "matrix_access_token", skip_serializing)]
+    pub legacy_matrix_access_token: Option<String>,
+    /// Matrix alerts normally go to end-to-end encrypted rooms only; this
+    /// allows an existing unencrypted direct chat as well.
+    #[serde(default)]
+    pub matrix_allow_unencrypted: bool,
+    pub primary_contact: Option<Contact>,
+    pub emergency_contacts: Vec<Contact>,
     pub auto_notify_on_low_mood: bool,
     pub auto_notify_threshold: i32,
+    /// Low-mood alerts stay off for this long after one went out; 0 alerts
+    /// on every low check-in. Panic alerts ignore it.
+    #[serde(default = "default_low_mood_cooldown_hours")]
+    pub low_mood_cooldown_hours: u32,
+    /// IANA timezone used for everything shown to or sent about this user.
+    #[serde(default = "default_timezone")]
+    pub timezone: String,
+    /// UI and notification language code; `None` follows the browser.
+    #[serde(default)]
+    pub language: Option<String>,
+    /// How eagerly downward mood trends are flagged.
+    #[serde(default)]
+    pub trend_sensitivity: TrendSensitivity,
+    /// Also tell the primary contact about a detected trend.
+    #[serde(default)]
+    pub trend_notify_primary_contact: bool,
+    #[serde(default)]
+    pub summary_weekly: bool,
+    #[serde(default)]
+    pub summary_monthly: bool,
+    /// A therapist who may receive the summaries as well.
+    #[serde(default)]
+    pub therapist_contact: Option<Contact>,
+    #[serde(default)]
+    pub summary_to_therapist: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrendSensitivity {
+    Off,
+    Low,
+    #[default]
+    Medium,
+    High,
+}
+
+impl TrendSensitivity {
+    pub const ALL: [TrendSensitivity; 4] = [
+        TrendSensitivity::Off,
+        TrendSensitivity::Low,
+        TrendSensitivity::Medium,
+        TrendSensitivity::High,
+    ];
+
+    pub fn code(self) -> &'static str {
+        match self {
+            TrendSensitivity::Off => "off",
+            TrendSensitivity::Low => "low",
+            TrendSensitivity::Medium => "medium",
+            TrendSensitivity::High => "high",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|level| level.code() == code)
+    }
+}
+
+/// Upper bound for [`UserConfig::low_mood_cooldown_hours`].
+pub const MAX_LOW_MOOD_COOLDOWN_HOURS: u32 = 7 * 24;
+
+fn default_low_mood_cooldown_hours() -> u32 {
+    3
+}
+
+fn default_timezone() -> String {
+    timezone::DEFAULT_TIMEZONE.into()
+}
+
+impl UserConfig {
+    /// The configured timezone, falling back to UTC if it isn't a valid
+    /// IANA name.
+    pub fn tz(&self) -> Tz {
+        timezone::parse(&self.timezone).unwrap_or(Tz::UTC)
+    }
+
+    pub fn lang(&self) -> Option<Lang> {
+        self.language.as_deref().and_then(Lang::from_code)
+    }
+
+    /// Who low-mood and panic alerts go to: the primary contact, then the
+    /// emergency contacts, without duplicates.
+    pub fn alert_contacts(&self) -> Vec<Contact> {
+        let mut contacts: Vec<Contact> = Vec::new();
+        for contact in self.primary_contact.iter().chain(&self.emergency_contacts) {
+            if !contacts.contains(contact) {
+                contacts.push(contact.clone());
+            }
+        }
+        contacts
+    }
 }
 
 impl Default for UserConfig {
@@ -41,11 +427,22 @@ impl Default for UserConfig {
             display_name: "Cutie".into(),
             homeserver_url: "https://matrix.org".into(),
             matrix_user_id: "@cutie:matrix.org".into(),
-            matrix_access_token: "SECRET".into(),
+            matrix_access_token_secret: None,
+            legacy_matrix_access_token: None,
+            matrix_allow_unencrypted: false,
             primary_contact: None,
             emergency_contacts: Vec::new(),
             auto_notify_on_low_mood: true,
             auto_notify_threshold: 1,
+            low_mood_cooldown_hours: default_low_mood_cooldown_hours(),
+            timezone: default_timezone(),
+            language: None,
+            trend_sensitivity: TrendSensitivity::default(),
+            trend_notify_primary_contact: false,
+            summary_weekly: false,
+            summary_monthly: false,
+            therapist_contact: None,
+            summary_to_therapist: false,
         }
     }
 }
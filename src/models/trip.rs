@@ -2,6 +2,7 @@
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Trip {
@@ -12,3 +13,20 @@ pub struct Trip {
     pub ended_at: Option<DateTime<Utc>>,
     pub notes: Option<String>,
 }
+
+impl Trip {
+    pub fn new(user_uuid: impl Into<String>, title: impl Into<String>) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            user_uuid: user_uuid.into(),
+            title: title.into(),
+            started_at: Utc::now(),
+            ended_at: None,
+            notes: None,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.ended_at.is_none()
+    }
+}
@@ -0,0 +1,116 @@
+#![allow(dead_code)]
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Events a webhook can subscribe to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WebhookEvent {
+    #[serde(rename = "checkin.created")]
+    CheckinCreated,
+    #[serde(rename = "checkin.low_mood")]
+    LowMood,
+    #[serde(rename = "panic.triggered")]
+    PanicTriggered,
+}
+
+impl WebhookEvent {
+    pub const ALL: [WebhookEvent; 3] = [
+        WebhookEvent::CheckinCreated,
+        WebhookEvent::LowMood,
+        WebhookEvent::PanicTriggered,
+    ];
+
+    pub fn code(self) -> &'static str {
+        match self {
+            WebhookEvent::CheckinCreated => "checkin.created",
+            WebhookEvent::LowMood => "checkin.low_mood",
+            WebhookEvent::PanicTriggered => "panic.triggered",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|event| event.code() == code)
+    }
+
+    /// Message key describing the event in the webhook form.
+    pub fn message_key(self) -> &'static str {
+        match self {
+            WebhookEvent::CheckinCreated => "webhook-event-checkin-created",
+            WebhookEvent::LowMood => "webhook-event-low-mood",
+            WebhookEvent::PanicTriggered => "webhook-event-panic-triggered",
+        }
+    }
+}
+
+/// A URL that receives signed JSON payloads. The signing secret lives in the
+/// secrets store and is shown once, when the webhook is created.
+#[derive(Debug, Clone)]
+pub struct Webhook {
+    pub id: i64,
+    pub user_id: i64,
+    pub url: String,
+    pub events: Vec<WebhookEvent>,
+    pub secret_id: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeliveryStatus {
+    /// Waiting for its first attempt or a retry.
+    Pending,
+    Delivered,
+    /// Gave up after the maximum number of attempts.
+    Failed,
+}
+
+impl DeliveryStatus {
+    pub fn code(self) -> &'static str {
+        match self {
+            DeliveryStatus::Pending => "pending",
+            DeliveryStatus::Delivered => "delivered",
+            DeliveryStatus::Failed => "failed",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        [
+            DeliveryStatus::Pending,
+            DeliveryStatus::Delivered,
+            DeliveryStatus::Failed,
+        ]
+        .into_iter()
+        .find(|status| status.code() == code)
+    }
+
+    pub fn message_key(self) -> &'static str {
+        match self {
+            DeliveryStatus::Pending => "webhook-status-pending",
+            DeliveryStatus::Delivered => "webhook-status-delivered",
+            DeliveryStatus::Failed => "webhook-status-failed",
+        }
+    }
+}
+
+/// One entry of the delivery log, without the payload.
+#[derive(Debug, Clone)]
+pub struct WebhookDelivery {
+    pub id: i64,
+    pub webhook_id: i64,
+    pub url: String,
+    pub event: String,
+    pub status: DeliveryStatus,
+    pub attempts: i64,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_status_code: Option<i64>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+impl WebhookDelivery {
+    /// When the next attempt is scheduled; `None` once the delivery is done.
+    pub fn next_attempt(&self) -> Option<DateTime<Utc>> {
+        (self.status == DeliveryStatus::Pending).then_some(self.next_attempt_at)
+    }
+}
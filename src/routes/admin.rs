@@ -1,19 +1,36 @@
 use askama::Template;
 use askama_axum::IntoResponse as AskamaTemplateResponse;
 use axum::{
+    extract::{Path, Query, State},
     response::{IntoResponse, Redirect},
-    routing::get,
-    Router,
+    routing::{get, post},
+    Form, Router,
 };
+use serde::Deserialize;
+use sqlx::Row;
 
-use crate::{auth::CurrentUser, error::AppError, state::AppState};
+use crate::{
+    auth::{self, CurrentUser},
+    error::AppError,
+    i18n::{self, filters},
+    services::{
+        git::CommitInfo,
+        jobs,
+        system::{self, BUILD_COMMIT, VERSION},
+    },
+    state::AppState,
+};
+
+const RECENT_COMMITS: usize = 15;
 
 pub fn router() -> Router<AppState> {
     Router::new()
         .route("/", get(dashboard))
         .route("/users", get(users_list))
         .route("/users/:id", get(user_detail))
-        .route("/system", get(system_page).post(system_commit))
+        .route("/users/:id/reset-password", post(user_reset_password))
+        .route("/system", get(system_page))
+        .route("/system/commit", post(system_commit))
         .route("/settings", get(settings_form).post(settings_submit))
 }
 
@@ -43,16 +60,33 @@ struct AdminUserRow {
     uuid: String,
 }
 
-async fn users_list(current: CurrentUser) -> Result<impl IntoResponse, AppError> {
+impl TryFrom<sqlx::sqlite::SqliteRow> for AdminUserRow {
+    type Error = sqlx::Error;
+
+    fn try_from(row: sqlx::sqlite::SqliteRow) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: row.try_get("id")?,
+            username: row.try_get("username")?,
+            email: row.try_get("email")?,
+            role: row.try_get("role")?,
+            uuid: row.try_get("uuid")?,
+        })
+    }
+}
+
+async fn users_list(
+    State(state): State<AppState>,
+    current: CurrentUser,
+) -> Result<impl IntoResponse, AppError> {
     current.require_admin()?;
+    let users = sqlx::query("SELECT id, username, email, role, uuid FROM users ORDER BY id")
+        .fetch_all(&state.db)
+        .await?
+        .into_iter()
+        .map(AdminUserRow::try_from)
+        .collect::<Result<Vec<_>, _>>()?;
     Ok(AskamaTemplateResponse::into_response(AdminUsersTemplate {
-        users: vec![AdminUserRow {
-            id: 1,
-            username: "cutie".into(),
-            email: "cutie@example.com".into(),
-            role: "admin".into(),
-            uuid: "demo".into(),
-        }],
+        users,
     }))
 }
 
@@ -60,35 +94,223 @@ async fn users_list(current: CurrentUser) -> Result<impl IntoResponse, AppError>
 #[template(path = "admin/user_detail.html")]
 struct AdminUserDetailTemplate {
     user: AdminUserRow,
+    escrow_enabled: bool,
 }
 
-async fn user_detail(current: CurrentUser) -> Result<impl IntoResponse, AppError> {
+async fn user_detail(
+    State(state): State<AppState>,
+    current: CurrentUser,
+    Path(user_id): Path<i64>,
+) -> Result<impl IntoResponse, AppError> {
     current.require_admin()?;
+    let row = sqlx::query("SELECT id, username, email, role, uuid FROM users WHERE id = ?1")
+        .bind(user_id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(AppError::NotFound)?;
     Ok(AskamaTemplateResponse::into_response(
         AdminUserDetailTemplate {
-            user: AdminUserRow {
-                id: 1,
-                username: "cutie".into(),
-                email: "cutie@example.com".into(),
-                role: "admin".into(),
-                uuid: "demo".into(),
-            },
+            user: AdminUserRow::try_from(row)?,
+            escrow_enabled: state.crypto.escrow_enabled(),
         },
     ))
 }
 
+#[derive(Deserialize)]
+struct ResetPasswordForm {
+    new_password: String,
+}
+
+async fn user_reset_password(
+    State(state): State<AppState>,
+    current: CurrentUser,
+    Path(user_id): Path<i64>,
+    Form(form): Form<ResetPasswordForm>,
+) -> Result<Redirect, AppError> {
+    current.require_admin()?;
+    auth::reset_password_with_escrow(&state, user_id, &form.new_password).await?;
+    Ok(Redirect::to(&format!("/admin/users/{user_id}")))
+}
+
+struct CommitRow {
+    id: String,
+    summary: String,
+    time: String,
+}
+
+impl From<&CommitInfo> for CommitRow {
+    fn from(commit: &CommitInfo) -> Self {
+        Self {
+            id: commit.id.clone(),
+            summary: commit.summary.clone(),
+            time: commit.time.format("%d.%m.%Y %H:%M UTC").to_string(),
+        }
+    }
+}
+
 #[derive(Template)]
 #[template(path = "admin/system.html")]
-struct AdminSystemTemplate;
+struct AdminSystemTemplate {
+    version: String,
+    uptime: String,
+    repo_error: Option<String>,
+    branch: String,
+    head: Option<CommitRow>,
+    recent_commits: Vec<CommitRow>,
+    uncommitted: Vec<String>,
+    repo_size: String,
+    database_size: String,
+    table_counts: Vec<(&'static str, i64)>,
+    ai_free: String,
+    backup: Option<BackupRow>,
+    jobs: Vec<JobRow>,
+    flash: Option<&'static str>,
+}
 
-async fn system_page(current: CurrentUser) -> Result<impl IntoResponse, AppError> {
-    current.require_admin()?;
-    Ok(AskamaTemplateResponse::into_response(AdminSystemTemplate))
+struct JobRow {
+    name: String,
+    schedule: String,
+    last_run: Option<String>,
+    next_run: Option<String>,
+    last_error: Option<String>,
+    running: bool,
+}
+
+struct BackupRow {
+    remote: String,
+    last_success: Option<String>,
+    last_attempt: Option<String>,
+    last_error: Option<String>,
+    failing_since: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SystemQuery {
+    committed: Option<bool>,
 }
 
-async fn system_commit(current: CurrentUser) -> Result<Redirect, AppError> {
+async fn system_page(
+    State(state): State<AppState>,
+    current: CurrentUser,
+    Query(query): Query<SystemQuery>,
+) -> Result<impl IntoResponse, AppError> {
     current.require_admin()?;
-    Err(AppError::NotImplemented)
+    let status = system::collect_status(&state, RECENT_COMMITS).await?;
+
+    let version = match BUILD_COMMIT {
+        Some(commit) => format!("{VERSION} ({commit})"),
+        None => VERSION.to_string(),
+    };
+    let ai_free = match (status.ai_free_bytes, status.ai_total_bytes) {
+        (Some(free), Some(total)) => i18n::tr_args(
+            "admin-system-free-of-total",
+            &[
+                ("free", format_bytes(free).into()),
+                ("total", format_bytes(total).into()),
+            ],
+        ),
+        (Some(free), None) => format_bytes(free),
+        _ => i18n::tr("common-unknown"),
+    };
+    let flash = query.committed.map(|committed| {
+        if committed {
+            "admin-commit-created"
+        } else {
+            "admin-commit-nothing"
+        }
+    });
+
+    let (repo_error, branch, head, recent_commits, uncommitted, repo_size) = match &status.repo {
+        Ok(repo) => (
+            None,
+            repo.branch.clone().unwrap_or_else(|| "–".into()),
+            repo.head.as_ref().map(CommitRow::from),
+            repo.recent_ai_commits.iter().map(CommitRow::from).collect(),
+            repo.uncommitted.clone(),
+            format_bytes(repo.repo_size_bytes),
+        ),
+        Err(err) => (
+            Some(err.clone()),
+            "–".into(),
+            None,
+            Vec::new(),
+            Vec::new(),
+            "–".into(),
+        ),
+    };
+
+    let fmt = |ts: Option<chrono::DateTime<chrono::Utc>>| {
+        ts.map(|ts| ts.format("%d.%m.%Y %H:%M UTC").to_string())
+    };
+    let backup = state.backup.config().map(|config| {
+        let status = state.backup.status();
+        BackupRow {
+            remote: redact_url(&config.remote_url),
+            last_success: fmt(status.last_success_at),
+            last_attempt: fmt(status.last_attempt_at),
+            last_error: status.last_error,
+            failing_since: fmt(status.failing_since),
+        }
+    });
+
+    let now = chrono::Utc::now();
+    let jobs = jobs::list(&state.db)
+        .await?
+        .into_iter()
+        .map(|job| JobRow {
+            running: job.is_running(now),
+            last_run: fmt(job.last_started_at),
+            next_run: fmt(job.next_run_at),
+            name: job.name,
+            schedule: job.schedule,
+            last_error: job.last_error,
+        })
+        .collect();
+
+    Ok(AskamaTemplateResponse::into_response(AdminSystemTemplate {
+        version,
+        uptime: format_duration(status.uptime),
+        repo_error,
+        branch,
+        head,
+        recent_commits,
+        uncommitted,
+        repo_size,
+        database_size: format_bytes(status.database_size_bytes),
+        table_counts: status.table_counts,
+        ai_free,
+        backup,
+        jobs,
+        flash,
+    }))
+}
+
+#[derive(Deserialize)]
+struct CommitForm {
+    message: String,
+}
+
+async fn system_commit(
+    State(state): State<AppState>,
+    current: CurrentUser,
+    Form(form): Form<CommitForm>,
+) -> Result<Redirect, AppError> {
+    let admin = current.require_admin()?;
+    let message = form.message.trim();
+    if message.is_empty() {
+        return Err(AppError::BadRequest(i18n::tr(
+            "error-commit-message-required",
+        )));
+    }
+
+    let message = format!("{message}\n\nTriggered-by: {}", admin.username);
+    let git = state.git.clone();
+    let committed = tokio::task::spawn_blocking(move || git.commit_ai_changes(&message))
+        .await
+        .map_err(|err| AppError::Other(err.into()))??;
+    Ok(Redirect::to(&format!(
+        "/admin/system?committed={committed}"
+    )))
 }
 
 #[derive(Template)]
@@ -104,3 +326,38 @@ async fn settings_submit(current: CurrentUser) -> Result<Redirect, AppError> {
     current.require_admin()?;
     Err(AppError::NotImplemented)
 }
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+fn format_duration(duration: std::time::Duration) -> String {
+    let secs = duration.as_secs();
+    let (days, hours, minutes) = (secs / 86_400, secs % 86_400 / 3600, secs % 3600 / 60);
+    if days > 0 {
+        format!("{days}d {hours}h {minutes}m")
+    } else {
+        format!("{hours}h {minutes}m")
+    }
+}
+
+/// Hides credentials embedded in a remote URL (`https://user:pw@host/…`).
+fn redact_url(url: &str) -> String {
+    match (url.find("://"), url.find('@')) {
+        (Some(scheme_end), Some(at)) if at > scheme_end => {
+            format!("{}***{}", &url[..scheme_end + 3], &url[at..])
+        }
+        _ => url.to_string(),
+    }
+}
@@ -0,0 +1,464 @@
+//! Versioned JSON API under `/api/v1`, for scripts and mobile shortcuts. It
+//! accepts the web session or a scoped personal API token and validates like
+//! the web pages, via [`journal`]. Errors are JSON bodies ([`ApiError`]);
+//! the endpoints are described by the OpenAPI document at
+//! `/api/v1/openapi.json`.
+
+use std::cmp::Reverse;
+
+use async_trait::async_trait;
+use axum::{
+    extract::{
+        rejection::{JsonRejection, QueryRejection},
+        FromRef, FromRequest, FromRequestParts, Path, Query, Request, State,
+    },
+    http::{header, request::Parts, StatusCode},
+    response::IntoResponse,
+    routing::{get, post},
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    auth::{AuthenticatedUser, CurrentUser},
+    error::{ApiError, AppError},
+    i18n::{self, Lang},
+    models::{
+        api_token::ApiScope,
+        checkin::{Checkin, DrugEntry, PanicEvent},
+        settings::{TrendSensitivity, UserConfig, MAX_LOW_MOOD_COOLDOWN_HOURS},
+        trip::Trip,
+    },
+    services::{
+        journal::{self, normalize_optional, CheckinInput},
+        notify, settings,
+    },
+    state::AppState,
+    timezone,
+};
+
+const OPENAPI: &str = include_str!("openapi.json");
+const DEFAULT_PAGE_SIZE: usize = 50;
+const MAX_PAGE_SIZE: usize = 200;
+
+type ApiResult<T> = Result<T, ApiError>;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/openapi.json", get(openapi))
+        .route("/checkins", get(checkins_list).post(checkin_create))
+        .route(
+            "/checkins/:id",
+            get(checkin_get).put(checkin_update).delete(checkin_delete),
+        )
+        .route("/trips", get(trips_list).post(trip_start))
+        .route("/trips/:id/end", post(trip_end))
+        .route("/panic", post(panic_trigger))
+        .route("/settings", get(settings_get).patch(settings_update))
+        .fallback(not_found)
+}
+
+async fn not_found() -> ApiError {
+    ApiError(AppError::NotFound)
+}
+
+async fn openapi() -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, "application/json")], OPENAPI)
+}
+
+/// The caller, by session cookie or `Authorization: Bearer` token. Errors
+/// are answered as JSON instead of redirecting to the login page.
+pub struct ApiAuth(pub CurrentUser);
+
+impl ApiAuth {
+    /// The user, if the session or token may use `scope`.
+    pub fn require(&self, scope: ApiScope) -> Result<&AuthenticatedUser, ApiError> {
+        Ok(self.0.require_scope(scope)?)
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ApiAuth
+where
+    S: Send + Sync,
+    AppState: FromRef<S>,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self(CurrentUser::from_request_parts(parts, state).await?))
+    }
+}
+
+/// [`Json`] with malformed bodies reported as an [`ApiError`].
+pub struct ApiJson<T>(pub T);
+
+#[async_trait]
+impl<S, T> FromRequest<S> for ApiJson<T>
+where
+    S: Send + Sync,
+    T: DeserializeOwned,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        Json::<T>::from_request(request, state)
+            .await
+            .map(|Json(value)| Self(value))
+            .map_err(|rejection: JsonRejection| {
+                ApiError(AppError::BadRequest(rejection.body_text()))
+            })
+    }
+}
+
+/// [`Query`] with invalid parameters reported as an [`ApiError`].
+pub struct ApiQuery<T>(pub T);
+
+#[async_trait]
+impl<S, T> FromRequestParts<S> for ApiQuery<T>
+where
+    S: Send + Sync,
+    T: DeserializeOwned,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        Query::<T>::from_request_parts(parts, state)
+            .await
+            .map(|Query(value)| Self(value))
+            .map_err(|rejection: QueryRejection| {
+                ApiError(AppError::BadRequest(rejection.body_text()))
+            })
+    }
+}
+
+#[derive(Deserialize)]
+struct PageQuery {
+    limit: Option<usize>,
+    offset: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct Page<T> {
+    items: Vec<T>,
+    total: usize,
+    limit: usize,
+    offset: usize,
+}
+
+impl<T> Page<T> {
+    fn of(items: Vec<T>, query: &PageQuery) -> Self {
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE);
+        let offset = query.offset.unwrap_or(0);
+        let total = items.len();
+        Self {
+            items: items.into_iter().skip(offset).take(limit).collect(),
+            total,
+            limit,
+            offset,
+        }
+    }
+}
+
+/// Check-ins newest first, without the trash.
+async fn checkins_list(
+    State(state): State<AppState>,
+    auth: ApiAuth,
+    ApiQuery(query): ApiQuery<PageQuery>,
+) -> ApiResult<Json<Page<Checkin>>> {
+    let user = auth.require(ApiScope::CheckinsRead)?;
+    let mut checkins: Vec<Checkin> = state
+        .storage
+        .load_user_checkins(&user.uuid)
+        .await?
+        .into_iter()
+        .filter(|checkin| !checkin.is_deleted())
+        .collect();
+    checkins.sort_by_key(|item| Reverse(item.timestamp));
+    Ok(Json(Page::of(checkins, &query)))
+}
+
+#[derive(Deserialize)]
+struct CheckinBody {
+    mood: i32,
+    #[serde(default)]
+    high_level: i32,
+    /// RFC 3339; omitted means "now" on create and "unchanged" on update.
+    timestamp: Option<DateTime<Utc>>,
+    /// Omitted derives it from `safety_answer`.
+    feels_safe: Option<bool>,
+    safety_answer: Option<String>,
+    notes: Option<String>,
+    /// Omitted keeps the substances already logged.
+    drugs: Option<Vec<DrugEntry>>,
+}
+
+impl CheckinBody {
+    /// Validates the timestamp and applies the fields to `checkin`.
+    fn apply(self, checkin: &mut Checkin) -> Result<(), AppError> {
+        if let Some(timestamp) = self.timestamp {
+            checkin.timestamp = journal::check_timestamp(timestamp)?;
+        }
+        CheckinInput {
+            mood: self.mood,
+            high_level: self.high_level,
+            feels_safe: self.feels_safe,
+            safety_answer: self.safety_answer,
+            notes: self.notes,
+            drugs: self.drugs.map(journal::check_drugs).transpose()?,
+        }
+        .apply(checkin);
+        Ok(())
+    }
+}
+
+async fn checkin_create(
+    State(state): State<AppState>,
+    auth: ApiAuth,
+    ApiJson(body): ApiJson<CheckinBody>,
+) -> ApiResult<(StatusCode, Json<Checkin>)> {
+    let user = auth.require(ApiScope::CheckinsWrite)?;
+    let mut checkin = Checkin::new(&user.uuid);
+    body.apply(&mut checkin)?;
+    let saved = journal::create_checkin(&state, user, checkin).await?;
+    Ok((StatusCode::CREATED, Json(saved)))
+}
+
+async fn checkin_get(
+    State(state): State<AppState>,
+    auth: ApiAuth,
+    Path(checkin_id): Path<String>,
+) -> ApiResult<Json<Checkin>> {
+    let user = auth.require(ApiScope::CheckinsRead)?;
+    let checkin = journal::find_live_checkin(&state, &user.uuid, &checkin_id).await?;
+    Ok(Json(checkin))
+}
+
+async fn checkin_update(
+    State(state): State<AppState>,
+    auth: ApiAuth,
+    Path(checkin_id): Path<String>,
+    ApiJson(body): ApiJson<CheckinBody>,
+) -> ApiResult<Json<Checkin>> {
+    let user = auth.require(ApiScope::CheckinsWrite)?;
+    let mut checkin = journal::find_live_checkin(&state, &user.uuid, &checkin_id).await?;
+    body.apply(&mut checkin)?;
+    let saved = journal::update_checkin(&state, user, checkin).await?;
+    Ok(Json(saved))
+}
+
+/// Moves the check-in to the trash, like the web page does.
+async fn checkin_delete(
+    State(state): State<AppState>,
+    auth: ApiAuth,
+    Path(checkin_id): Path<String>,
+) -> ApiResult<StatusCode> {
+    let user = auth.require(ApiScope::CheckinsWrite)?;
+    if journal::trash_checkin(&state, user, &checkin_id).await? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(AppError::NotFound.into())
+    }
+}
+
+/// Trips newest first.
+async fn trips_list(
+    State(state): State<AppState>,
+    auth: ApiAuth,
+    ApiQuery(query): ApiQuery<PageQuery>,
+) -> ApiResult<Json<Page<Trip>>> {
+    let user = auth.require(ApiScope::TripsRead)?;
+    let mut trips = state.storage.load_user_trips(&user.uuid).await?;
+    trips.sort_by_key(|item| Reverse(item.started_at));
+    Ok(Json(Page::of(trips, &query)))
+}
+
+#[derive(Deserialize)]
+struct TripBody {
+    title: String,
+    notes: Option<String>,
+}
+
+async fn trip_start(
+    State(state): State<AppState>,
+    auth: ApiAuth,
+    ApiJson(body): ApiJson<TripBody>,
+) -> ApiResult<(StatusCode, Json<Trip>)> {
+    let user = auth.require(ApiScope::TripsWrite)?;
+    let trip = journal::start_trip(&state, user, &body.title, body.notes).await?;
+    Ok((StatusCode::CREATED, Json(trip)))
+}
+
+async fn trip_end(
+    State(state): State<AppState>,
+    auth: ApiAuth,
+    Path(trip_id): Path<String>,
+) -> ApiResult<Json<Trip>> {
+    let user = auth.require(ApiScope::TripsWrite)?;
+    let trip = journal::end_trip(&state, user, &trip_id).await?;
+    Ok(Json(trip))
+}
+
+async fn panic_trigger(
+    State(state): State<AppState>,
+    auth: ApiAuth,
+) -> ApiResult<(StatusCode, Json<PanicEvent>)> {
+    let user = auth.require(ApiScope::PanicTrigger)?;
+    let event = journal::trigger_panic(&state, user).await?;
+    Ok((StatusCode::CREATED, Json(event)))
+}
+
+/// The user settings without credentials; the Matrix access token can only
+/// be changed on the settings page.
+#[derive(Serialize)]
+struct SettingsView {
+    display_name: String,
+    timezone: String,
+    language: Option<String>,
+    homeserver_url: String,
+    matrix_user_id: String,
+    has_matrix_access_token: bool,
+    matrix_allow_unencrypted: bool,
+    primary_contact: Option<String>,
+    emergency_contacts: Vec<String>,
+    auto_notify_on_low_mood: bool,
+    auto_notify_threshold: i32,
+    low_mood_cooldown_hours: u32,
+    trend_sensitivity: TrendSensitivity,
+    trend_notify_primary_contact: bool,
+    summary_weekly: bool,
+    summary_monthly: bool,
+    therapist_contact: Option<String>,
+    summary_to_therapist: bool,
+}
+
+impl From<UserConfig> for SettingsView {
+    fn from(config: UserConfig) -> Self {
+        Self {
+            has_matrix_access_token: config.matrix_access_token_secret.is_some(),
+            display_name: config.display_name,
+            timezone: config.timezone,
+            language: config.language,
+            homeserver_url: config.homeserver_url,
+            matrix_user_id: config.matrix_user_id,
+            matrix_allow_unencrypted: config.matrix_allow_unencrypted,
+            primary_contact: config.primary_contact.map(|c| c.to_string()),
+            emergency_contacts: config
+                .emergency_contacts
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+            auto_notify_on_low_mood: config.auto_notify_on_low_mood,
+            auto_notify_threshold: config.auto_notify_threshold,
+            low_mood_cooldown_hours: config.low_mood_cooldown_hours,
+            trend_sensitivity: config.trend_sensitivity,
+            trend_notify_primary_contact: config.trend_notify_primary_contact,
+            summary_weekly: config.summary_weekly,
+            summary_monthly: config.summary_monthly,
+            therapist_contact: config.therapist_contact.map(|c| c.to_string()),
+            summary_to_therapist: config.summary_to_therapist,
+        }
+    }
+}
+
+/// Fields that are left out stay unchanged; empty strings clear the
+/// optional ones.
+#[derive(Deserialize)]
+struct SettingsPatch {
+    display_name: Option<String>,
+    timezone: Option<String>,
+    language: Option<String>,
+    homeserver_url: Option<String>,
+    matrix_user_id: Option<String>,
+    matrix_allow_unencrypted: Option<bool>,
+    primary_contact: Option<String>,
+    emergency_contacts: Option<Vec<String>>,
+    auto_notify_on_low_mood: Option<bool>,
+    auto_notify_threshold: Option<i32>,
+    low_mood_cooldown_hours: Option<u32>,
+    trend_sensitivity: Option<TrendSensitivity>,
+    trend_notify_primary_contact: Option<bool>,
+    summary_weekly: Option<bool>,
+    summary_monthly: Option<bool>,
+    therapist_contact: Option<String>,
+    summary_to_therapist: Option<bool>,
+}
+
+async fn settings_get(
+    State(state): State<AppState>,
+    auth: ApiAuth,
+) -> ApiResult<Json<SettingsView>> {
+    let user = auth.require(ApiScope::SettingsRead)?;
+    let config = state.load_user_config(&user.uuid, &user.username).await?;
+    Ok(Json(config.into()))
+}
+
+async fn settings_update(
+    State(state): State<AppState>,
+    auth: ApiAuth,
+    ApiJson(patch): ApiJson<SettingsPatch>,
+) -> ApiResult<Json<SettingsView>> {
+    let user = auth.require(ApiScope::SettingsWrite)?;
+    let (_writing, mut config) = settings::load_for_update(&state, user).await?;
+    if let Some(name) = patch.timezone {
+        let tz = timezone::parse(&name)
+            .ok_or_else(|| AppError::BadRequest(i18n::tr("error-unknown-timezone")))?;
+        config.timezone = tz.name().to_string();
+    }
+    if let Some(display_name) = patch.display_name {
+        config.display_name =
+            normalize_optional(Some(display_name)).unwrap_or_else(|| user.username.clone());
+    }
+    if let Some(code) = patch.language {
+        config.language = Lang::from_code(&code).map(|lang| lang.code().to_string());
+    }
+    if let Some(url) = patch.homeserver_url {
+        config.homeserver_url = url.trim().to_string();
+    }
+    if let Some(id) = patch.matrix_user_id {
+        config.matrix_user_id = id.trim().to_string();
+    }
+    if let Some(allowed) = patch.matrix_allow_unencrypted {
+        config.matrix_allow_unencrypted = allowed;
+    }
+    if let Some(contact) = patch.primary_contact {
+        config.primary_contact = notify::parse_optional_contact(Some(&contact))?;
+    }
+    if let Some(contacts) = patch.emergency_contacts {
+        config.emergency_contacts = notify::parse_contacts(contacts.iter().map(String::as_str))?;
+    }
+    if let Some(enabled) = patch.auto_notify_on_low_mood {
+        config.auto_notify_on_low_mood = enabled;
+    }
+    if let Some(threshold) = patch.auto_notify_threshold {
+        config.auto_notify_threshold = threshold.clamp(-5, 5);
+    }
+    if let Some(hours) = patch.low_mood_cooldown_hours {
+        config.low_mood_cooldown_hours = hours.min(MAX_LOW_MOOD_COOLDOWN_HOURS);
+    }
+    if let Some(sensitivity) = patch.trend_sensitivity {
+        config.trend_sensitivity = sensitivity;
+    }
+    if let Some(enabled) = patch.trend_notify_primary_contact {
+        config.trend_notify_primary_contact = enabled;
+    }
+    if let Some(enabled) = patch.summary_weekly {
+        config.summary_weekly = enabled;
+    }
+    if let Some(enabled) = patch.summary_monthly {
+        config.summary_monthly = enabled;
+    }
+    if let Some(contact) = patch.therapist_contact {
+        config.therapist_contact = notify::parse_optional_contact(Some(&contact))?;
+    }
+    if let Some(enabled) = patch.summary_to_therapist {
+        config.summary_to_therapist = enabled;
+    }
+
+    settings::save(&state, user, &config).await?;
+    Ok(Json(config.into()))
+}
@@ -1,17 +1,31 @@
 pub mod admin;
+pub mod api;
 pub mod public;
 pub mod user;
 
-use axum::Router;
+use axum::{middleware, Router};
 use tower_http::services::ServeDir;
 
-use crate::state::AppState;
+use crate::{
+    error::{self, AppError},
+    i18n,
+    state::AppState,
+};
 
 pub fn create_router(state: AppState) -> Router {
     Router::new()
         .merge(public::router())
         .nest("/me", user::router())
         .nest("/admin", admin::router())
+        .nest("/api/v1", api::router())
         .nest_service("/static", ServeDir::new("static"))
+        .fallback(not_found)
+        // Error pages are rendered inside the language scope.
+        .layer(middleware::from_fn(error::error_pages))
+        .layer(middleware::from_fn(i18n::middleware))
         .with_state(state)
 }
+
+async fn not_found() -> AppError {
+    AppError::NotFound
+}
@@ -1,15 +1,21 @@
 use askama::Template;
 use askama_axum::IntoResponse as AskamaTemplateResponse;
 use axum::{
-    extract::State,
-    response::{IntoResponse, Redirect},
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Redirect, Response},
     routing::{get, post},
     Form, Router,
 };
 use axum_extra::extract::cookie::CookieJar;
 use serde::Deserialize;
 
-use crate::{auth, error::AppError, state::AppState};
+use crate::{
+    auth,
+    error::AppError,
+    i18n::{self, filters},
+    state::AppState,
+};
 
 pub fn router() -> Router<AppState> {
     Router::new()
@@ -29,37 +35,92 @@ async fn landing() -> impl IntoResponse {
 
 #[derive(Template)]
 #[template(path = "auth/login.html")]
-pub struct LoginTemplate;
+pub struct LoginTemplate {
+    identifier: String,
+    next: String,
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct LoginQuery {
+    next: Option<String>,
+}
 
-async fn login_form() -> impl IntoResponse {
-    AskamaTemplateResponse::into_response(LoginTemplate)
+async fn login_form(Query(query): Query<LoginQuery>) -> impl IntoResponse {
+    AskamaTemplateResponse::into_response(LoginTemplate {
+        identifier: String::new(),
+        next: safe_next(query.next.as_deref())
+            .unwrap_or_default()
+            .to_string(),
+        error: None,
+    })
 }
 
 #[derive(Deserialize)]
 struct LoginForm {
     identifier: String,
     password: String,
+    next: Option<String>,
 }
 
 async fn login_submit(
     State(state): State<AppState>,
     jar: CookieJar,
     Form(form): Form<LoginForm>,
-) -> Result<(CookieJar, Redirect), AppError> {
-    let user = auth::authenticate_user(&state, &form.identifier, &form.password).await?;
+) -> Result<Response, AppError> {
+    let next = safe_next(form.next.as_deref());
+    let user = match auth::authenticate_user(&state, &form.identifier, &form.password).await {
+        Ok(user) => user,
+        Err(AppError::Unauthorized) => {
+            let template = LoginTemplate {
+                identifier: form.identifier,
+                next: next.unwrap_or_default().to_string(),
+                error: Some(i18n::tr("error-invalid-credentials")),
+            };
+            return Ok((
+                StatusCode::UNAUTHORIZED,
+                AskamaTemplateResponse::into_response(template),
+            )
+                .into_response());
+        }
+        Err(err) => {
+            return err.rerender_form(|error| LoginTemplate {
+                identifier: form.identifier.clone(),
+                next: next.unwrap_or_default().to_string(),
+                error: Some(error),
+            })
+        }
+    };
     let session_id = auth::create_session(&state, user.id).await?;
+    let config = state.load_user_config(&user.uuid, &user.username).await?;
+    let jar = i18n::apply_lang_cookie(jar, config.lang());
     Ok((
         auth::apply_session_cookie(jar, &session_id),
-        Redirect::to("/me"),
-    ))
+        Redirect::to(next.unwrap_or("/me")),
+    )
+        .into_response())
+}
+
+/// Only same-site paths are followed after login; `//host` and `/\host`
+/// would be read as another origin by browsers.
+fn safe_next(next: Option<&str>) -> Option<&str> {
+    next.filter(|next| next.starts_with('/') && !next.starts_with("//") && !next.starts_with("/\\"))
 }
 
 #[derive(Template)]
 #[template(path = "auth/register.html")]
-pub struct RegisterTemplate;
+pub struct RegisterTemplate {
+    username: String,
+    email: String,
+    error: Option<String>,
+}
 
 async fn register_form() -> impl IntoResponse {
-    AskamaTemplateResponse::into_response(RegisterTemplate)
+    AskamaTemplateResponse::into_response(RegisterTemplate {
+        username: String::new(),
+        email: String::new(),
+        error: None,
+    })
 }
 
 #[derive(Deserialize)]
@@ -74,19 +135,28 @@ async fn register_submit(
     State(state): State<AppState>,
     jar: CookieJar,
     Form(form): Form<RegisterForm>,
-) -> Result<(CookieJar, Redirect), AppError> {
+) -> Result<Response, AppError> {
+    match register(&state, &form).await {
+        Ok(session_id) => Ok((
+            auth::apply_session_cookie(jar, &session_id),
+            Redirect::to("/me"),
+        )
+            .into_response()),
+        Err(err) => err.rerender_form(|error| RegisterTemplate {
+            username: form.username.clone(),
+            email: form.email.clone(),
+            error: Some(error),
+        }),
+    }
+}
+
+async fn register(state: &AppState, form: &RegisterForm) -> Result<String, AppError> {
     if form.password != form.password_confirm {
-        return Err(AppError::BadRequest(
-            "Passwörter stimmen nicht überein.".into(),
-        ));
+        return Err(AppError::BadRequest(i18n::tr("error-password-mismatch")));
     }
 
-    let user = auth::register_user(&state, &form.username, &form.email, &form.password).await?;
-    let session_id = auth::create_session(&state, user.id).await?;
-    Ok((
-        auth::apply_session_cookie(jar, &session_id),
-        Redirect::to("/me"),
-    ))
+    let user = auth::register_user(state, &form.username, &form.email, &form.password).await?;
+    auth::create_session(state, user.id).await
 }
 
 async fn logout(
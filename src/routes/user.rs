@@ -1,41 +1,250 @@
+use std::cmp::Reverse;
+
 use askama::Template;
 use askama_axum::IntoResponse as AskamaTemplateResponse;
 use axum::{
-    extract::{Path, State},
-    response::{IntoResponse, Redirect},
+    body::Bytes,
+    extract::{Path, Query, State},
+    response::{IntoResponse, Redirect, Response},
     routing::{get, post},
     Form, Router,
 };
-use chrono::{Local, Utc};
+use axum_extra::extract::cookie::CookieJar;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use chrono_tz::Tz;
 use serde::Deserialize;
 
-use crate::{auth::CurrentUser, error::AppError, models::checkin::Checkin, state::AppState};
+use crate::{
+    auth::{self, AuthenticatedUser, CurrentUser},
+    charts::{BarChart, Heatmap, LineChart},
+    error::AppError,
+    i18n::{self, filters, Lang},
+    models::{
+        api_token::ApiScope,
+        checkin::{Checkin, TRASH_RETENTION_DAYS},
+        notification::NotificationKind,
+        reminder::{ReminderSchedule, ReminderStatus},
+        settings::{TrendSensitivity, UserConfig, MAX_LOW_MOOD_COOLDOWN_HOURS},
+        trip::Trip,
+        webhook::{DeliveryStatus, WebhookEvent},
+    },
+    services::{
+        analytics::{self, Average, DayStats, TimeOfDay, TrendSignal},
+        git::{DataChange, TrashAction},
+        history::{self, DiffKind},
+        journal::{self, normalize_optional, CheckinInput},
+        matrix_bot, notify, outbox, reminders, secrets, settings, tokens, webhooks,
+    },
+    state::AppState,
+    timezone,
+};
 
 pub fn router() -> Router<AppState> {
     Router::new()
         .route("/", get(dashboard))
+        .route("/insights", get(insights))
         .route("/checkins", get(checkins_list))
         .route(
             "/checkins/new",
             get(checkin_new_form).post(checkin_new_submit),
         )
+        .route("/checkins/quick", post(checkin_quick))
+        .route("/checkins/trash", get(checkins_trash))
+        .route("/checkins/trash/purge", post(checkins_purge_all))
         .route("/checkins/:id", get(checkin_detail))
-        .route("/trips", get(trips_list))
+        .route(
+            "/checkins/:id/edit",
+            get(checkin_edit_form).post(checkin_edit_submit),
+        )
+        .route("/checkins/:id/delete", post(checkin_delete))
+        .route("/checkins/:id/undelete", post(checkin_undelete))
+        .route("/checkins/:id/purge", post(checkin_purge))
+        .route("/checkins/:id/history/restore", post(checkin_restore))
+        .route("/trips", get(trips_list).post(trip_start))
+        .route("/trips/:id/end", post(trip_end))
         .route("/panic", get(panic_page))
         .route("/panic/trigger", post(panic_trigger))
+        .route("/panic/:id", get(panic_sent))
         .route("/settings", get(settings_form).post(settings_submit))
+        .route("/settings/password", post(password_change))
+        .route("/settings/tokens", post(api_token_create))
+        .route("/settings/tokens/:id/revoke", post(api_token_revoke))
+        .route("/settings/webhooks", post(webhook_create))
+        .route("/settings/webhooks/:id/delete", post(webhook_delete))
+        .route("/settings/reminders", post(reminder_create))
+        .route("/settings/reminders/:id/delete", post(reminder_delete))
+        .route("/settings/matrix-bot/link", post(matrix_link_code))
+        .route("/settings/matrix-bot/unlink", post(matrix_unlink))
 }
 
 #[derive(Template)]
 #[template(path = "user/dashboard.html")]
 struct DashboardTemplate {
     display_name: String,
+    quick_moods: Vec<i32>,
+    quick_saved: Option<i32>,
+    trend_nudge: Option<String>,
+    ranges: Vec<RangeOption>,
+    checkin_count: usize,
+    mood_average: String,
+    high_average: String,
+    unsafe_percent: Option<String>,
+    mood_chart: LineChart,
+    high_chart: LineChart,
+    heatmap: Heatmap,
+    weekday_chart: BarChart,
+    time_of_day_chart: BarChart,
+}
+
+struct RangeOption {
+    days: u32,
+    selected: bool,
 }
 
-async fn dashboard(current: CurrentUser) -> Result<impl IntoResponse, AppError> {
+#[derive(Deserialize)]
+struct DashboardQuery {
+    quick_mood: Option<i32>,
+    days: Option<u32>,
+}
+
+const WEEKDAY_KEYS: [&str; 7] = [
+    "weekday-mon",
+    "weekday-tue",
+    "weekday-wed",
+    "weekday-thu",
+    "weekday-fri",
+    "weekday-sat",
+    "weekday-sun",
+];
+
+async fn dashboard(
+    State(state): State<AppState>,
+    current: CurrentUser,
+    Query(query): Query<DashboardQuery>,
+) -> Result<impl IntoResponse, AppError> {
     let user = current.require_user()?;
+    let config = state.load_user_config(&user.uuid, &user.username).await?;
+    let tz = config.tz();
+    let range = query
+        .days
+        .filter(|days| analytics::RANGES.contains(days))
+        .unwrap_or(analytics::DEFAULT_RANGE);
+    let checkins = state.storage.load_user_checkins(&user.uuid).await?;
+    let today = timezone::local_date(Utc::now(), tz);
+    let stats = analytics::mood_stats(&checkins, tz, today, range);
+    let trend_nudge =
+        analytics::detect_trend(&checkins, Utc::now(), config.trend_sensitivity).map(|warning| {
+            match warning.signal {
+                TrendSignal::AverageDrop => i18n::tr_args(
+                    "trend-nudge-drop",
+                    &[
+                        ("recent", format!("{:.1}", warning.recent).into()),
+                        ("baseline", format!("{:.1}", warning.baseline).into()),
+                    ],
+                ),
+                TrendSignal::ConsecutiveDeclines(count) => {
+                    i18n::tr_args("trend-nudge-declines", &[("count", count.into())])
+                }
+            }
+        });
+
+    let daily = |field: fn(&DayStats) -> Average| -> Vec<(String, Option<f64>)> {
+        stats
+            .days
+            .iter()
+            .map(|day| (day.date.format("%d.%m.").to_string(), field(day).mean()))
+            .collect()
+    };
+    let weekdays: Vec<_> = WEEKDAY_KEYS
+        .iter()
+        .zip(&stats.by_weekday)
+        .map(|(key, average)| (*key, average.mean()))
+        .collect();
+    let times_of_day: Vec<_> = TimeOfDay::ALL
+        .iter()
+        .zip(&stats.by_time_of_day)
+        .map(|(slot, average)| (slot.message_key(), average.mean()))
+        .collect();
+
     Ok(AskamaTemplateResponse::into_response(DashboardTemplate {
         display_name: user.username.clone(),
+        quick_moods: (-5..=5).collect(),
+        quick_saved: query.quick_mood,
+        trend_nudge,
+        ranges: analytics::RANGES
+            .iter()
+            .map(|days| RangeOption {
+                days: *days,
+                selected: *days == range,
+            })
+            .collect(),
+        checkin_count: stats.checkin_count,
+        mood_average: format_mean(stats.mood()),
+        high_average: format_mean(stats.high()),
+        unsafe_percent: stats
+            .unsafe_share()
+            .map(|share| format!("{:.0}", share * 100.0)),
+        mood_chart: LineChart::new(&daily(|day| day.mood), -5.0, 5.0, 600, 160),
+        high_chart: LineChart::new(&daily(|day| day.high), 0.0, 10.0, 600, 160),
+        heatmap: Heatmap::new(
+            &stats
+                .days
+                .iter()
+                .map(|day| (day.date, day.mood.mean()))
+                .collect::<Vec<_>>(),
+        ),
+        weekday_chart: BarChart::mood(&weekdays, 420, 160),
+        time_of_day_chart: BarChart::mood(&times_of_day, 300, 160),
+    }))
+}
+
+#[derive(Template)]
+#[template(path = "user/insights.html")]
+struct InsightsTemplate {
+    baseline_mood: String,
+    baseline_count: usize,
+    substances: Vec<SubstanceRow>,
+    window_from: i64,
+    window_to: i64,
+}
+
+struct SubstanceRow {
+    substance: String,
+    uses: usize,
+    samples: usize,
+    mood: String,
+    delta: String,
+    enough_data: bool,
+}
+
+async fn insights(
+    State(state): State<AppState>,
+    current: CurrentUser,
+) -> Result<impl IntoResponse, AppError> {
+    let user = current.require_user()?;
+    let checkins = state.storage.load_user_checkins(&user.uuid).await?;
+    let report = analytics::substance_report(&checkins);
+    let substances = report
+        .substances
+        .iter()
+        .map(|insight| SubstanceRow {
+            substance: insight.substance.clone(),
+            uses: insight.uses,
+            samples: insight.after_use.count(),
+            mood: format_mean(insight.after_use),
+            delta: insight
+                .delta(&report.baseline)
+                .map(|delta| format!("{delta:+.1}"))
+                .unwrap_or_else(|| "–".into()),
+            enough_data: insight.enough_data(),
+        })
+        .collect();
+    Ok(AskamaTemplateResponse::into_response(InsightsTemplate {
+        baseline_mood: format_mean(report.baseline),
+        baseline_count: report.baseline.count(),
+        substances,
+        window_from: analytics::AFTER_USE_HOURS.0,
+        window_to: analytics::AFTER_USE_HOURS.1,
     }))
 }
 
@@ -58,13 +267,15 @@ async fn checkins_list(
     current: CurrentUser,
 ) -> Result<impl IntoResponse, AppError> {
     let user = current.require_user()?;
+    let tz = user_tz(&state, user).await?;
     let mut items = state.storage.load_user_checkins(&user.uuid).await?;
-    items.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+    items.sort_by_key(|item| Reverse(item.timestamp));
     let summaries = items
         .into_iter()
+        .filter(|checkin| !checkin.is_deleted())
         .map(|checkin| CheckinSummary {
             id: checkin.id,
-            timestamp: format_timestamp(checkin.timestamp),
+            timestamp: timezone::format(checkin.timestamp, tz),
             mood: checkin.mood,
             high_level: checkin.high_level,
         })
@@ -78,84 +289,519 @@ async fn checkins_list(
 
 #[derive(Template)]
 #[template(path = "user/checkin_new.html")]
-struct CheckinNewTemplate;
+struct CheckinNewTemplate {
+    fields: CheckinFields,
+    error: Option<String>,
+}
+
+/// Form values as shown in the check-in inputs.
+struct CheckinFields {
+    timestamp: String,
+    mood: String,
+    high_level: String,
+    feels_safe: bool,
+    safety_answer: String,
+    notes: String,
+    substances: String,
+}
+
+impl Default for CheckinFields {
+    fn default() -> Self {
+        Self {
+            timestamp: String::new(),
+            mood: String::new(),
+            high_level: String::new(),
+            feels_safe: true,
+            safety_answer: String::new(),
+            notes: String::new(),
+            substances: String::new(),
+        }
+    }
+}
+
+impl From<&CheckinForm> for CheckinFields {
+    fn from(form: &CheckinForm) -> Self {
+        Self {
+            timestamp: form.timestamp.clone().unwrap_or_default(),
+            mood: form.mood.to_string(),
+            high_level: form.high_level.to_string(),
+            feels_safe: form.feels_safe() != Some(false),
+            safety_answer: form.safety_answer.clone().unwrap_or_default(),
+            notes: form.notes.clone().unwrap_or_default(),
+            substances: form.substances.clone(),
+        }
+    }
+}
 
 async fn checkin_new_form(current: CurrentUser) -> Result<impl IntoResponse, AppError> {
     current.require_user()?;
-    Ok(AskamaTemplateResponse::into_response(CheckinNewTemplate))
+    Ok(AskamaTemplateResponse::into_response(CheckinNewTemplate {
+        fields: CheckinFields::default(),
+        error: None,
+    }))
 }
 
 #[derive(Deserialize)]
 struct CheckinForm {
     mood: i32,
     high_level: i32,
+    /// `datetime-local` value; empty means "now".
+    timestamp: Option<String>,
+    /// `yes` or `no`.
+    feels_safe: Option<String>,
     safety_answer: Option<String>,
     notes: Option<String>,
+    /// One `substance, dose, route` per line.
+    #[serde(default)]
+    substances: String,
+}
+
+impl CheckinForm {
+    fn feels_safe(&self) -> Option<bool> {
+        match self.feels_safe.as_deref() {
+            Some("yes") => Some(true),
+            Some("no") => Some(false),
+            _ => None,
+        }
+    }
+
+    fn to_input(&self) -> Result<CheckinInput, AppError> {
+        Ok(CheckinInput {
+            mood: self.mood,
+            high_level: self.high_level,
+            feels_safe: self.feels_safe(),
+            safety_answer: self.safety_answer.clone(),
+            notes: self.notes.clone(),
+            drugs: Some(journal::parse_drug_lines(&self.substances)?),
+        })
+    }
 }
 
 async fn checkin_new_submit(
     State(state): State<AppState>,
     current: CurrentUser,
     Form(form): Form<CheckinForm>,
+) -> Result<Response, AppError> {
+    let user = current.require_user()?;
+    let tz = user_tz(&state, user).await?;
+    let parsed = parse_checkin_timestamp(form.timestamp.clone(), tz)
+        .and_then(|timestamp| Ok((timestamp, form.to_input()?)));
+    let (timestamp, input) = match parsed {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            return err.rerender_form(|error| CheckinNewTemplate {
+                fields: CheckinFields::from(&form),
+                error: Some(error),
+            })
+        }
+    };
+    let mut checkin = Checkin::new(&user.uuid);
+    if let Some(timestamp) = timestamp {
+        checkin.timestamp = timestamp;
+    }
+    input.apply(&mut checkin);
+
+    let saved = journal::create_checkin(&state, user, checkin).await?;
+    Ok(Redirect::to(&format!("/me/checkins/{}", saved.id)).into_response())
+}
+
+#[derive(Deserialize)]
+struct QuickCheckinForm {
+    mood: i32,
+    #[serde(default)]
+    high_level: i32,
+}
+
+/// One-tap check-in from the dashboard widget: just mood and high level.
+async fn checkin_quick(
+    State(state): State<AppState>,
+    current: CurrentUser,
+    Form(form): Form<QuickCheckinForm>,
 ) -> Result<Redirect, AppError> {
     let user = current.require_user()?;
     let mut checkin = Checkin::new(&user.uuid);
     checkin.mood = form.mood.clamp(-5, 5);
     checkin.high_level = form.high_level.clamp(0, 10);
-    checkin.safety_answer = normalize_optional(form.safety_answer);
-    checkin.notes = normalize_optional(form.notes);
-    checkin.feels_safe = checkin
-        .safety_answer
-        .as_ref()
-        .map(|answer| !answer.to_lowercase().contains("nein"))
-        .unwrap_or(true);
+    let saved = journal::create_checkin(&state, user, checkin).await?;
+    Ok(Redirect::to(&format!("/me?quick_mood={}", saved.mood)))
+}
 
-    let saved = state.storage.append_checkin(&user.uuid, checkin).await?;
+#[derive(Template)]
+#[template(path = "user/checkin_edit.html")]
+struct CheckinEditTemplate {
+    id: String,
+    fields: CheckinFields,
+    error: Option<String>,
+}
+
+async fn checkin_edit_form(
+    State(state): State<AppState>,
+    current: CurrentUser,
+    Path(checkin_id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let user = current.require_user()?;
+    let tz = user_tz(&state, user).await?;
+    let checkin = journal::find_live_checkin(&state, &user.uuid, &checkin_id).await?;
+    Ok(AskamaTemplateResponse::into_response(CheckinEditTemplate {
+        id: checkin.id,
+        fields: CheckinFields {
+            timestamp: timezone::format_input(checkin.timestamp, tz),
+            mood: checkin.mood.to_string(),
+            high_level: checkin.high_level.to_string(),
+            feels_safe: checkin.feels_safe,
+            safety_answer: checkin.safety_answer.unwrap_or_default(),
+            notes: checkin.notes.unwrap_or_default(),
+            substances: journal::format_drug_lines(&checkin.drugs),
+        },
+        error: None,
+    }))
+}
 
-    Ok(Redirect::to(&format!("/me/checkins/{}", saved.id)))
+async fn checkin_edit_submit(
+    State(state): State<AppState>,
+    current: CurrentUser,
+    Path(checkin_id): Path<String>,
+    Form(form): Form<CheckinForm>,
+) -> Result<Response, AppError> {
+    let user = current.require_user()?;
+    let tz = user_tz(&state, user).await?;
+    let mut checkin = journal::find_live_checkin(&state, &user.uuid, &checkin_id).await?;
+    // The form only carries minutes; keep the exact time unless it was changed.
+    let unchanged = form.timestamp.as_deref().map(str::trim)
+        == Some(timezone::format_input(checkin.timestamp, tz).as_str());
+    let timestamp = if unchanged {
+        Ok(None)
+    } else {
+        parse_checkin_timestamp(form.timestamp.clone(), tz)
+    };
+    let (timestamp, input) = match timestamp.and_then(|timestamp| Ok((timestamp, form.to_input()?)))
+    {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            return err.rerender_form(|error| CheckinEditTemplate {
+                id: checkin_id.clone(),
+                fields: CheckinFields::from(&form),
+                error: Some(error),
+            })
+        }
+    };
+    if let Some(timestamp) = timestamp {
+        checkin.timestamp = timestamp;
+    }
+    input.apply(&mut checkin);
+    journal::update_checkin(&state, user, checkin).await?;
+    Ok(Redirect::to(&format!("/me/checkins/{checkin_id}")).into_response())
+}
+
+async fn checkin_delete(
+    State(state): State<AppState>,
+    current: CurrentUser,
+    Path(checkin_id): Path<String>,
+) -> Result<Redirect, AppError> {
+    let user = current.require_user()?;
+    journal::trash_checkin(&state, user, &checkin_id).await?;
+    Ok(Redirect::to("/me/checkins"))
+}
+
+async fn checkin_undelete(
+    State(state): State<AppState>,
+    current: CurrentUser,
+    Path(checkin_id): Path<String>,
+) -> Result<Redirect, AppError> {
+    let user = current.require_user()?;
+    if state
+        .storage
+        .untrash_checkin(&user.uuid, &checkin_id)
+        .await?
+    {
+        state.git.record(DataChange::CheckinTrashed {
+            user_uuid: user.uuid.clone(),
+            action: TrashAction::Undeleted,
+        });
+    }
+    Ok(Redirect::to(&format!("/me/checkins/{checkin_id}")))
+}
+
+async fn checkin_purge(
+    State(state): State<AppState>,
+    current: CurrentUser,
+    Path(checkin_id): Path<String>,
+) -> Result<Redirect, AppError> {
+    let user = current.require_user()?;
+    journal::purge_trash(&state, &user.uuid, |checkin| checkin.id == checkin_id).await?;
+    Ok(Redirect::to("/me/checkins/trash"))
+}
+
+async fn checkins_purge_all(
+    State(state): State<AppState>,
+    current: CurrentUser,
+) -> Result<Redirect, AppError> {
+    let user = current.require_user()?;
+    journal::purge_trash(&state, &user.uuid, |_| true).await?;
+    Ok(Redirect::to("/me/checkins/trash"))
+}
+
+#[derive(Clone)]
+struct TrashedCheckin {
+    id: String,
+    timestamp: String,
+    mood: i32,
+    high_level: i32,
+    deleted_at: String,
+    purge_in_days: i64,
+}
+
+#[derive(Template)]
+#[template(path = "user/checkins_trash.html")]
+struct CheckinsTrashTemplate {
+    checkins: Vec<TrashedCheckin>,
+    retention_days: i64,
+}
+
+async fn checkins_trash(
+    State(state): State<AppState>,
+    current: CurrentUser,
+) -> Result<impl IntoResponse, AppError> {
+    let user = current.require_user()?;
+    let tz = user_tz(&state, user).await?;
+    let now = Utc::now();
+    // Expired entries are purged whenever the trash is looked at.
+    journal::purge_trash(&state, &user.uuid, |checkin| checkin.is_expired(now)).await?;
+
+    let mut items: Vec<Checkin> = state
+        .storage
+        .load_user_checkins(&user.uuid)
+        .await?
+        .into_iter()
+        .filter(Checkin::is_deleted)
+        .collect();
+    items.sort_by_key(|item| Reverse(item.deleted_at));
+    let checkins = items
+        .into_iter()
+        .filter_map(|checkin| {
+            let deleted_at = checkin.deleted_at?;
+            Some(TrashedCheckin {
+                id: checkin.id,
+                timestamp: timezone::format(checkin.timestamp, tz),
+                mood: checkin.mood,
+                high_level: checkin.high_level,
+                deleted_at: timezone::format(deleted_at, tz),
+                purge_in_days: (TRASH_RETENTION_DAYS - (now - deleted_at).num_days()).max(0),
+            })
+        })
+        .collect();
+    Ok(AskamaTemplateResponse::into_response(
+        CheckinsTrashTemplate {
+            checkins,
+            retention_days: TRASH_RETENTION_DAYS,
+        },
+    ))
 }
 
 #[derive(Template)]
 #[template(path = "user/checkin_detail.html")]
 struct CheckinDetailTemplate {
+    id: String,
     mood: i32,
     high_level: i32,
-    notes: String,
+    notes: Option<String>,
     raw_json: String,
+    notifications: Vec<NotificationRow>,
+    notifications_delivered: usize,
+    versions: Vec<VersionView>,
+    restored: bool,
+}
+
+struct VersionView {
+    commit: String,
+    short_commit: String,
+    committed_at: String,
+    summary: String,
+    is_current: bool,
+    diff: Vec<DiffLineView>,
+}
+
+struct DiffLineView {
+    marker: &'static str,
+    class: &'static str,
+    text: String,
+}
+
+#[derive(Deserialize)]
+struct CheckinDetailQuery {
+    restored: Option<bool>,
 }
 
 async fn checkin_detail(
     State(state): State<AppState>,
     current: CurrentUser,
     Path(checkin_id): Path<String>,
+    Query(query): Query<CheckinDetailQuery>,
 ) -> Result<impl IntoResponse, AppError> {
     let user = current.require_user()?;
-    let items = state.storage.load_user_checkins(&user.uuid).await?;
-    let checkin = items
-        .into_iter()
-        .find(|c| c.id == checkin_id)
-        .ok_or(AppError::NotFound)?;
+    let tz = user_tz(&state, user).await?;
+    let checkin = journal::find_live_checkin(&state, &user.uuid, &checkin_id).await?;
     let raw_json =
         serde_json::to_string_pretty(&checkin).map_err(|err| AppError::Other(err.into()))?;
+    let notifications = notification_rows(&state, user, &checkin.id, tz).await?;
+
+    let versions = history::checkin_versions(&state, &user.uuid, &checkin_id)
+        .await?
+        .into_iter()
+        .map(|version| VersionView {
+            commit: version.commit.oid,
+            short_commit: version.commit.id,
+            committed_at: timezone::format(version.commit.time, tz),
+            summary: i18n::tr_args(
+                "checkin-summary",
+                &[
+                    ("mood", version.checkin.mood.into()),
+                    ("high", version.checkin.high_level.into()),
+                ],
+            ),
+            is_current: version.json == raw_json,
+            diff: version
+                .diff
+                .into_iter()
+                .map(|line| {
+                    let (marker, class) = match line.kind {
+                        DiffKind::Added => ("+", "bg-green-100"),
+                        DiffKind::Removed => ("-", "bg-red-100"),
+                        DiffKind::Unchanged => (" ", "text-gray-500"),
+                    };
+                    DiffLineView {
+                        marker,
+                        class,
+                        text: line.text,
+                    }
+                })
+                .collect(),
+        })
+        .collect();
+
     Ok(AskamaTemplateResponse::into_response(
         CheckinDetailTemplate {
+            id: checkin.id,
             mood: checkin.mood,
             high_level: checkin.high_level,
-            notes: checkin
-                .notes
-                .unwrap_or_else(|| "Keine Notizen hinterlegt 🌱".into()),
+            notes: checkin.notes,
             raw_json,
+            notifications_delivered: delivered_count(&notifications),
+            notifications,
+            versions,
+            restored: query.restored.unwrap_or(false),
         },
     ))
 }
 
+#[derive(Deserialize)]
+struct RestoreForm {
+    commit: String,
+}
+
+async fn checkin_restore(
+    State(state): State<AppState>,
+    current: CurrentUser,
+    Path(checkin_id): Path<String>,
+    Form(form): Form<RestoreForm>,
+) -> Result<Redirect, AppError> {
+    let user = current.require_user()?;
+    history::restore_checkin(&state, &user.uuid, &checkin_id, form.commit.trim()).await?;
+    Ok(Redirect::to(&format!(
+        "/me/checkins/{checkin_id}?restored=true"
+    )))
+}
+
+#[derive(Clone)]
+struct TripSummary {
+    id: String,
+    title: String,
+    started_at: String,
+    ended_at: Option<String>,
+    notes: String,
+}
+
 #[derive(Template)]
 #[template(path = "user/trips_list.html")]
-struct TripsListTemplate;
+struct TripsListTemplate {
+    trips: Vec<TripSummary>,
+    has_active: bool,
+    title: String,
+    notes: String,
+    error: Option<String>,
+}
 
-async fn trips_list(current: CurrentUser) -> Result<impl IntoResponse, AppError> {
-    current.require_user()?;
-    Ok(AskamaTemplateResponse::into_response(TripsListTemplate))
+async fn trips_list(
+    State(state): State<AppState>,
+    current: CurrentUser,
+) -> Result<impl IntoResponse, AppError> {
+    let user = current.require_user()?;
+    let trips = state.storage.load_user_trips(&user.uuid).await?;
+    let template = trips_template(&state, user, trips, None).await?;
+    Ok(AskamaTemplateResponse::into_response(template))
+}
+
+/// The trip list with the start form, optionally refilled from a rejected
+/// submission.
+async fn trips_template(
+    state: &AppState,
+    user: &AuthenticatedUser,
+    mut trips: Vec<Trip>,
+    form: Option<&TripForm>,
+) -> Result<TripsListTemplate, AppError> {
+    let tz = user_tz(state, user).await?;
+    trips.sort_by_key(|item| Reverse(item.started_at));
+    let has_active = trips.iter().any(Trip::is_active);
+    let trips = trips
+        .into_iter()
+        .map(|trip| TripSummary {
+            id: trip.id,
+            title: trip.title,
+            started_at: timezone::format(trip.started_at, tz),
+            ended_at: trip.ended_at.map(|ts| timezone::format(ts, tz)),
+            notes: trip.notes.unwrap_or_default(),
+        })
+        .collect();
+    Ok(TripsListTemplate {
+        trips,
+        has_active,
+        title: form.map(|form| form.title.clone()).unwrap_or_default(),
+        notes: form.and_then(|form| form.notes.clone()).unwrap_or_default(),
+        error: None,
+    })
+}
+
+#[derive(Deserialize)]
+struct TripForm {
+    title: String,
+    notes: Option<String>,
+}
+
+async fn trip_start(
+    State(state): State<AppState>,
+    current: CurrentUser,
+    Form(form): Form<TripForm>,
+) -> Result<Response, AppError> {
+    let user = current.require_user()?;
+    if let Err(err) = journal::start_trip(&state, user, &form.title, form.notes.clone()).await {
+        let trips = state.storage.load_user_trips(&user.uuid).await?;
+        let template = trips_template(&state, user, trips, Some(&form)).await?;
+        return err.rerender_form(|error| TripsListTemplate {
+            error: Some(error),
+            ..template
+        });
+    }
+    Ok(Redirect::to("/me/trips").into_response())
+}
+
+async fn trip_end(
+    State(state): State<AppState>,
+    current: CurrentUser,
+    Path(trip_id): Path<String>,
+) -> Result<Redirect, AppError> {
+    let user = current.require_user()?;
+    journal::end_trip(&state, user, &trip_id).await?;
+    Ok(Redirect::to("/me/trips"))
 }
 
 #[derive(Template)]
@@ -167,38 +813,696 @@ async fn panic_page(current: CurrentUser) -> Result<impl IntoResponse, AppError>
     Ok(AskamaTemplateResponse::into_response(PanicTemplate))
 }
 
-async fn panic_trigger(current: CurrentUser) -> Result<Redirect, AppError> {
-    current.require_user()?;
-    Err(AppError::NotImplemented)
+async fn panic_trigger(
+    State(state): State<AppState>,
+    current: CurrentUser,
+) -> Result<Redirect, AppError> {
+    let user = current.require_user()?;
+    let event = journal::trigger_panic(&state, user).await?;
+    Ok(Redirect::to(&format!("/me/panic/{}", event.id)))
+}
+
+#[derive(Template)]
+#[template(path = "user/panic_sent.html")]
+struct PanicSentTemplate {
+    timestamp: String,
+    notifications: Vec<NotificationRow>,
+    notifications_delivered: usize,
+    /// Contacts reached by events from before the outbox existed.
+    notified_contacts: Vec<String>,
+}
+
+async fn panic_sent(
+    State(state): State<AppState>,
+    current: CurrentUser,
+    Path(event_id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let user = current.require_user()?;
+    let tz = user_tz(&state, user).await?;
+    let event = state
+        .storage
+        .load_panic_events(&user.uuid)
+        .await?
+        .into_iter()
+        .find(|event| event.id == event_id)
+        .ok_or(AppError::NotFound)?;
+    let notifications = notification_rows(&state, user, &event.id, tz).await?;
+    Ok(AskamaTemplateResponse::into_response(PanicSentTemplate {
+        timestamp: timezone::format(event.timestamp, tz),
+        notifications_delivered: delivered_count(&notifications),
+        notifications,
+        notified_contacts: event.notified_contacts,
+    }))
+}
+
+/// One contact's alert on the panic and check-in pages.
+struct NotificationRow {
+    contact: String,
+    kind: NotificationKind,
+    status: DeliveryStatus,
+    next_attempt_at: Option<String>,
+    /// Held back by quiet hours before the first attempt.
+    deferred: bool,
+}
+
+async fn notification_rows(
+    state: &AppState,
+    user: &AuthenticatedUser,
+    source_id: &str,
+    tz: Tz,
+) -> Result<Vec<NotificationRow>, AppError> {
+    Ok(outbox::entries_for(state, user.id, source_id)
+        .await?
+        .into_iter()
+        .map(|entry| NotificationRow {
+            next_attempt_at: entry.next_attempt().map(|ts| timezone::format(ts, tz)),
+            deferred: entry.status == DeliveryStatus::Pending && entry.attempts == 0,
+            contact: entry.contact.target(),
+            kind: entry.kind,
+            status: entry.status,
+        })
+        .collect())
+}
+
+fn delivered_count(rows: &[NotificationRow]) -> usize {
+    rows.iter()
+        .filter(|row| row.status == DeliveryStatus::Delivered)
+        .count()
 }
 
 #[derive(Template)]
 #[template(path = "user/settings.html")]
-struct SettingsTemplate;
+struct SettingsTemplate {
+    display_name: String,
+    homeserver_url: String,
+    matrix_user_id: String,
+    has_matrix_access_token: bool,
+    matrix_allow_unencrypted: bool,
+    primary_contact: String,
+    emergency_contacts: String,
+    auto_notify_on_low_mood: bool,
+    auto_notify_threshold: i32,
+    low_mood_cooldown_hours: u32,
+    timezone: String,
+    timezones: Vec<&'static str>,
+    language: String,
+    languages: Vec<SelectOption>,
+    trend_sensitivities: Vec<SelectOption>,
+    trend_notify_primary_contact: bool,
+    summary_weekly: bool,
+    summary_monthly: bool,
+    therapist_contact: String,
+    summary_to_therapist: bool,
+    api_tokens: Vec<ApiTokenRow>,
+    api_scopes: Vec<ApiScope>,
+    /// Secret of a token that was just created; shown only this once.
+    new_api_token: Option<String>,
+    api_token_name: String,
+    api_token_error: Option<String>,
+    webhooks: Vec<WebhookRow>,
+    webhook_events: Vec<WebhookEvent>,
+    /// Signing secret of a webhook that was just added; shown only this once.
+    new_webhook_secret: Option<String>,
+    webhook_url: String,
+    webhook_error: Option<String>,
+    webhook_deliveries: Vec<WebhookDeliveryRow>,
+    reminders: Vec<ReminderRow>,
+    /// Prefilled with the user's Matrix ID.
+    reminder_contact: String,
+    reminder_error: Option<String>,
+    /// The chat bot's Matrix ID; the bot section is hidden without one.
+    matrix_bot_id: Option<String>,
+    matrix_link: Option<MatrixLinkRow>,
+    /// A link code that was just created; only its hash is stored.
+    new_matrix_link_code: Option<String>,
+    password_changed: bool,
+    password_error: Option<String>,
+    error: Option<String>,
+}
 
-async fn settings_form(current: CurrentUser) -> Result<impl IntoResponse, AppError> {
-    current.require_user()?;
-    Ok(AskamaTemplateResponse::into_response(SettingsTemplate))
+struct ApiTokenRow {
+    id: i64,
+    name: String,
+    scopes: Vec<&'static str>,
+    created_at: String,
+    last_used_at: Option<String>,
 }
 
-async fn settings_submit(current: CurrentUser) -> Result<Redirect, AppError> {
-    current.require_user()?;
-    Err(AppError::NotImplemented)
+struct ReminderRow {
+    id: i64,
+    /// "daily at 20:00" or "every 3 hours during a trip".
+    schedule: String,
+    contact: String,
+    next_run_at: Option<String>,
+    last_run: Option<(ReminderStatus, String)>,
+    last_error: Option<String>,
 }
 
-fn normalize_optional(input: Option<String>) -> Option<String> {
-    input.and_then(|value| {
-        let trimmed = value.trim();
-        if trimmed.is_empty() {
-            None
-        } else {
-            Some(trimmed.to_string())
-        }
+struct MatrixLinkRow {
+    account: String,
+    linked_at: String,
+}
+
+/// Deliveries listed in the settings' webhook log.
+const WEBHOOK_LOG_LIMIT: i64 = 20;
+
+struct WebhookRow {
+    id: i64,
+    url: String,
+    events: Vec<&'static str>,
+    created_at: String,
+}
+
+struct WebhookDeliveryRow {
+    created_at: String,
+    event: String,
+    url: String,
+    status: DeliveryStatus,
+    attempts: i64,
+    /// HTTP status of the last attempt, or its error.
+    response: Option<String>,
+    next_attempt_at: Option<String>,
+}
+
+/// A `<select>` option identified by its code.
+struct SelectOption {
+    code: &'static str,
+    selected: bool,
+}
+
+async fn settings_form(
+    State(state): State<AppState>,
+    current: CurrentUser,
+) -> Result<impl IntoResponse, AppError> {
+    let user = current.require_user()?;
+    let config = state.load_user_config(&user.uuid, &user.username).await?;
+    let template = settings_template(&state, user, config).await?;
+    Ok(AskamaTemplateResponse::into_response(template))
+}
+
+async fn settings_template(
+    state: &AppState,
+    user: &AuthenticatedUser,
+    config: UserConfig,
+) -> Result<SettingsTemplate, AppError> {
+    let selected_lang = config.lang();
+    let tz = config.tz();
+    let api_tokens = tokens::list(&state.db, user.id)
+        .await?
+        .into_iter()
+        .map(|token| ApiTokenRow {
+            id: token.id,
+            name: token.name,
+            scopes: token.scopes.iter().map(|scope| scope.code()).collect(),
+            created_at: timezone::format(token.created_at, tz),
+            last_used_at: token.last_used_at.map(|ts| timezone::format(ts, tz)),
+        })
+        .collect();
+    let webhook_rows = webhooks::list(&state.db, user.id)
+        .await?
+        .into_iter()
+        .map(|hook| WebhookRow {
+            id: hook.id,
+            url: hook.url,
+            events: hook.events.iter().map(|event| event.code()).collect(),
+            created_at: timezone::format(hook.created_at, tz),
+        })
+        .collect();
+    let webhook_deliveries = webhooks::recent_deliveries(&state.db, user.id, WEBHOOK_LOG_LIMIT)
+        .await?
+        .into_iter()
+        .map(|delivery| WebhookDeliveryRow {
+            created_at: timezone::format(delivery.created_at, tz),
+            next_attempt_at: delivery.next_attempt().map(|ts| timezone::format(ts, tz)),
+            response: delivery
+                .last_status_code
+                .map(|code| code.to_string())
+                .or(delivery.last_error),
+            event: delivery.event,
+            url: delivery.url,
+            status: delivery.status,
+            attempts: delivery.attempts,
+        })
+        .collect();
+    let reminder_rows = reminders::list(&state.db, user.id)
+        .await?
+        .into_iter()
+        .map(|reminder| ReminderRow {
+            id: reminder.id,
+            schedule: match reminder.schedule {
+                ReminderSchedule::Daily(time) => i18n::tr_args(
+                    "reminders-daily-at",
+                    &[("time", time.format("%H:%M").to_string().into())],
+                ),
+                ReminderSchedule::DuringTrip { every_hours } => i18n::tr_args(
+                    "reminders-during-trip-every",
+                    &[("hours", every_hours.into())],
+                ),
+            },
+            contact: reminder.contact.to_string(),
+            next_run_at: reminder.next_run_at.map(|ts| timezone::format(ts, tz)),
+            last_run: reminder
+                .last_status
+                .zip(reminder.last_run_at)
+                .map(|(status, ts)| (status, timezone::format(ts, tz))),
+            last_error: reminder.last_error,
+        })
+        .collect();
+    let reminder_contact = config.matrix_user_id.trim().to_string();
+    let matrix_link =
+        matrix_bot::linked_account(&state.db, user.id)
+            .await?
+            .map(|(account, linked_at)| MatrixLinkRow {
+                account,
+                linked_at: timezone::format(linked_at, tz),
+            });
+    Ok(SettingsTemplate {
+        display_name: config.display_name,
+        homeserver_url: config.homeserver_url,
+        matrix_user_id: config.matrix_user_id,
+        has_matrix_access_token: config.matrix_access_token_secret.is_some(),
+        matrix_allow_unencrypted: config.matrix_allow_unencrypted,
+        primary_contact: config
+            .primary_contact
+            .map(|contact| contact.to_string())
+            .unwrap_or_default(),
+        emergency_contacts: config
+            .emergency_contacts
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("\n"),
+        auto_notify_on_low_mood: config.auto_notify_on_low_mood,
+        auto_notify_threshold: config.auto_notify_threshold,
+        low_mood_cooldown_hours: config.low_mood_cooldown_hours,
+        timezone: config.timezone,
+        timezones: timezone::names().collect(),
+        languages: Lang::ALL
+            .iter()
+            .map(|lang| SelectOption {
+                code: lang.code(),
+                selected: selected_lang == Some(*lang),
+            })
+            .collect(),
+        language: config.language.unwrap_or_default(),
+        trend_sensitivities: TrendSensitivity::ALL
+            .iter()
+            .map(|level| SelectOption {
+                code: level.code(),
+                selected: *level == config.trend_sensitivity,
+            })
+            .collect(),
+        trend_notify_primary_contact: config.trend_notify_primary_contact,
+        summary_weekly: config.summary_weekly,
+        summary_monthly: config.summary_monthly,
+        therapist_contact: config
+            .therapist_contact
+            .map(|contact| contact.to_string())
+            .unwrap_or_default(),
+        summary_to_therapist: config.summary_to_therapist,
+        api_tokens,
+        api_scopes: ApiScope::ALL.to_vec(),
+        new_api_token: None,
+        api_token_name: String::new(),
+        api_token_error: None,
+        webhooks: webhook_rows,
+        webhook_events: WebhookEvent::ALL.to_vec(),
+        new_webhook_secret: None,
+        webhook_url: String::new(),
+        webhook_error: None,
+        webhook_deliveries,
+        reminders: reminder_rows,
+        reminder_contact,
+        reminder_error: None,
+        matrix_bot_id: state
+            .config
+            .matrix_bot
+            .as_ref()
+            .map(|bot| bot.user_id.clone()),
+        matrix_link,
+        new_matrix_link_code: None,
+        password_changed: false,
+        password_error: None,
+        error: None,
     })
 }
 
-fn format_timestamp(ts: chrono::DateTime<Utc>) -> String {
-    ts.with_timezone(&Local)
-        .format("%d.%m.%Y %H:%M")
-        .to_string()
+#[derive(Deserialize)]
+struct SettingsForm {
+    display_name: String,
+    homeserver_url: String,
+    matrix_user_id: String,
+    matrix_access_token: Option<String>,
+    clear_matrix_access_token: Option<String>,
+    matrix_allow_unencrypted: Option<String>,
+    primary_contact: Option<String>,
+    emergency_contacts: Option<String>,
+    auto_notify_on_low_mood: Option<String>,
+    auto_notify_threshold: i32,
+    low_mood_cooldown_hours: Option<u32>,
+    timezone: String,
+    language: Option<String>,
+    trend_sensitivity: Option<String>,
+    trend_notify_primary_contact: Option<String>,
+    summary_weekly: Option<String>,
+    summary_monthly: Option<String>,
+    therapist_contact: Option<String>,
+    summary_to_therapist: Option<String>,
+}
+
+async fn settings_submit(
+    State(state): State<AppState>,
+    current: CurrentUser,
+    jar: CookieJar,
+    Form(form): Form<SettingsForm>,
+) -> Result<Response, AppError> {
+    let user = current.require_user()?;
+    let (_writing, mut config) = settings::load_for_update(&state, user).await?;
+
+    config.display_name =
+        normalize_optional(Some(form.display_name)).unwrap_or_else(|| user.username.clone());
+    config.homeserver_url = form.homeserver_url.trim().to_string();
+    config.matrix_user_id = form.matrix_user_id.trim().to_string();
+    config.matrix_allow_unencrypted = form.matrix_allow_unencrypted.is_some();
+    let contacts =
+        notify::parse_optional_contact(form.primary_contact.as_deref()).and_then(|primary| {
+            let emergency = notify::parse_contacts(
+                form.emergency_contacts
+                    .as_deref()
+                    .unwrap_or_default()
+                    .lines(),
+            )?;
+            let therapist = notify::parse_optional_contact(form.therapist_contact.as_deref())?;
+            Ok((primary, emergency, therapist))
+        });
+    let (primary, emergency, therapist) = match contacts {
+        Ok(contacts) => contacts,
+        Err(err) => {
+            let template = settings_template(&state, user, config).await?;
+            return err.rerender_form(|error| SettingsTemplate {
+                primary_contact: form.primary_contact.clone().unwrap_or_default(),
+                emergency_contacts: form.emergency_contacts.clone().unwrap_or_default(),
+                therapist_contact: form.therapist_contact.clone().unwrap_or_default(),
+                error: Some(error),
+                ..template
+            });
+        }
+    };
+    config.primary_contact = primary;
+    config.emergency_contacts = emergency;
+    config.therapist_contact = therapist;
+    config.auto_notify_on_low_mood = form.auto_notify_on_low_mood.is_some();
+    config.auto_notify_threshold = form.auto_notify_threshold.clamp(-5, 5);
+    if let Some(hours) = form.low_mood_cooldown_hours {
+        config.low_mood_cooldown_hours = hours.min(MAX_LOW_MOOD_COOLDOWN_HOURS);
+    }
+    config.trend_sensitivity = form
+        .trend_sensitivity
+        .as_deref()
+        .and_then(TrendSensitivity::from_code)
+        .unwrap_or_default();
+    config.trend_notify_primary_contact = form.trend_notify_primary_contact.is_some();
+    config.summary_weekly = form.summary_weekly.is_some();
+    config.summary_monthly = form.summary_monthly.is_some();
+    config.summary_to_therapist = form.summary_to_therapist.is_some();
+    let lang = form.language.as_deref().and_then(Lang::from_code);
+    config.language = lang.map(|lang| lang.code().to_string());
+    let Some(tz) = timezone::parse(&form.timezone) else {
+        let template = settings_template(&state, user, config).await?;
+        return AppError::BadRequest(i18n::tr("error-unknown-timezone")).rerender_form(|error| {
+            SettingsTemplate {
+                timezone: form.timezone.clone(),
+                error: Some(error),
+                ..template
+            }
+        });
+    };
+    config.timezone = tz.name().to_string();
+
+    // The token itself only ever goes into the secrets store.
+    if form.clear_matrix_access_token.is_some() {
+        if let Some(id) = config.matrix_access_token_secret.take() {
+            state.secrets.delete(&id).await?;
+        }
+    } else if let Some(token) = normalize_optional(form.matrix_access_token) {
+        let id = state
+            .secrets
+            .upsert(
+                config.matrix_access_token_secret.as_deref(),
+                &user.uuid,
+                secrets::MATRIX_ACCESS_TOKEN,
+                &token,
+            )
+            .await?;
+        config.matrix_access_token_secret = Some(id);
+    }
+
+    settings::save(&state, user, &config).await?;
+    Ok((
+        i18n::apply_lang_cookie(jar, lang),
+        Redirect::to("/me/settings"),
+    )
+        .into_response())
+}
+
+#[derive(Deserialize)]
+struct PasswordForm {
+    current_password: String,
+    new_password: String,
+    new_password_confirm: String,
+}
+
+async fn password_change(
+    State(state): State<AppState>,
+    current: CurrentUser,
+    jar: CookieJar,
+    Form(form): Form<PasswordForm>,
+) -> Result<Response, AppError> {
+    let user = current.require_user()?;
+    let session_id = jar
+        .get(auth::SESSION_COOKIE)
+        .map(|cookie| cookie.value().to_string())
+        .unwrap_or_default();
+    let changed = if form.new_password != form.new_password_confirm {
+        Err(AppError::BadRequest(i18n::tr("error-password-mismatch")))
+    } else {
+        auth::change_password(
+            &state,
+            user,
+            &session_id,
+            &form.current_password,
+            &form.new_password,
+        )
+        .await
+    };
+    let config = state.load_user_config(&user.uuid, &user.username).await?;
+    let template = settings_template(&state, user, config).await?;
+    match changed {
+        Ok(()) => Ok(AskamaTemplateResponse::into_response(SettingsTemplate {
+            password_changed: true,
+            ..template
+        })),
+        Err(err) => err.rerender_form(|error| SettingsTemplate {
+            password_error: Some(error),
+            ..template
+        }),
+    }
+}
+
+/// Creates a token and shows its secret once on the settings page. Each
+/// checked scope repeats the `scope` field, which [`Form`] can't collect, so
+/// the body is parsed by hand.
+async fn api_token_create(
+    State(state): State<AppState>,
+    current: CurrentUser,
+    body: Bytes,
+) -> Result<Response, AppError> {
+    let user = current.require_user()?;
+    let mut name = String::new();
+    let mut scopes = Vec::new();
+    for (key, value) in form_urlencoded::parse(&body) {
+        match key.as_ref() {
+            "name" => name = value.into_owned(),
+            "scope" => scopes.extend(ApiScope::from_code(&value)),
+            _ => {}
+        }
+    }
+    let created = tokens::create(&state.db, user.id, &name, &scopes).await;
+    let config = state.load_user_config(&user.uuid, &user.username).await?;
+    let template = settings_template(&state, user, config).await?;
+    match created {
+        Ok((_, secret)) => Ok(AskamaTemplateResponse::into_response(SettingsTemplate {
+            new_api_token: Some(secret),
+            ..template
+        })),
+        Err(err) => err.rerender_form(|error| SettingsTemplate {
+            api_token_name: name.clone(),
+            api_token_error: Some(error),
+            ..template
+        }),
+    }
+}
+
+async fn api_token_revoke(
+    State(state): State<AppState>,
+    current: CurrentUser,
+    Path(token_id): Path<i64>,
+) -> Result<Redirect, AppError> {
+    let user = current.require_user()?;
+    if !tokens::revoke(&state.db, user.id, token_id).await? {
+        return Err(AppError::NotFound);
+    }
+    Ok(Redirect::to("/me/settings"))
+}
+
+/// Adds a webhook and shows its signing secret once. Like the token form,
+/// the repeated `event` field is parsed by hand.
+async fn webhook_create(
+    State(state): State<AppState>,
+    current: CurrentUser,
+    body: Bytes,
+) -> Result<Response, AppError> {
+    let user = current.require_user()?;
+    let mut url = String::new();
+    let mut events = Vec::new();
+    for (key, value) in form_urlencoded::parse(&body) {
+        match key.as_ref() {
+            "url" => url = value.into_owned(),
+            "event" => events.extend(WebhookEvent::from_code(&value)),
+            _ => {}
+        }
+    }
+    let created = webhooks::create(&state, user, &url, &events).await;
+    let config = state.load_user_config(&user.uuid, &user.username).await?;
+    let template = settings_template(&state, user, config).await?;
+    match created {
+        Ok((_, secret)) => Ok(AskamaTemplateResponse::into_response(SettingsTemplate {
+            new_webhook_secret: Some(secret),
+            ..template
+        })),
+        Err(err) => err.rerender_form(|error| SettingsTemplate {
+            webhook_url: url.clone(),
+            webhook_error: Some(error),
+            ..template
+        }),
+    }
+}
+
+async fn webhook_delete(
+    State(state): State<AppState>,
+    current: CurrentUser,
+    Path(webhook_id): Path<i64>,
+) -> Result<Redirect, AppError> {
+    let user = current.require_user()?;
+    if !webhooks::delete(&state, user.id, webhook_id).await? {
+        return Err(AppError::NotFound);
+    }
+    Ok(Redirect::to("/me/settings"))
+}
+
+#[derive(Deserialize)]
+struct ReminderForm {
+    kind: String,
+    time: Option<String>,
+    every_hours: Option<String>,
+    contact: String,
+}
+
+async fn reminder_create(
+    State(state): State<AppState>,
+    current: CurrentUser,
+    Form(form): Form<ReminderForm>,
+) -> Result<Response, AppError> {
+    let user = current.require_user()?;
+    let config = state.load_user_config(&user.uuid, &user.username).await?;
+    // An empty interval field is sent for daily reminders.
+    let every_hours = form
+        .every_hours
+        .as_deref()
+        .and_then(|hours| hours.trim().parse().ok());
+    let schedule = reminders::parse_schedule(&form.kind, form.time.as_deref(), every_hours);
+    let created = match (schedule, notify::parse_contact(&form.contact)) {
+        (Ok(schedule), Ok(contact)) => reminders::create(&state, user, &config, schedule, &contact)
+            .await
+            .map(drop),
+        (Err(err), _) | (_, Err(err)) => Err(err),
+    };
+    match created {
+        Ok(()) => Ok(Redirect::to("/me/settings").into_response()),
+        Err(err) => {
+            let template = settings_template(&state, user, config).await?;
+            err.rerender_form(|error| SettingsTemplate {
+                reminder_contact: form.contact.clone(),
+                reminder_error: Some(error),
+                ..template
+            })
+        }
+    }
+}
+
+async fn reminder_delete(
+    State(state): State<AppState>,
+    current: CurrentUser,
+    Path(reminder_id): Path<i64>,
+) -> Result<Redirect, AppError> {
+    let user = current.require_user()?;
+    if !reminders::delete(&state.db, user.id, reminder_id).await? {
+        return Err(AppError::NotFound);
+    }
+    Ok(Redirect::to("/me/settings"))
+}
+
+/// Shows a new code to send to the chat bot with `!link`.
+async fn matrix_link_code(
+    State(state): State<AppState>,
+    current: CurrentUser,
+) -> Result<Response, AppError> {
+    let user = current.require_user()?;
+    let code = matrix_bot::create_link_code(&state.db, user.id).await?;
+    let config = state.load_user_config(&user.uuid, &user.username).await?;
+    let template = settings_template(&state, user, config).await?;
+    Ok(AskamaTemplateResponse::into_response(SettingsTemplate {
+        new_matrix_link_code: Some(code),
+        ..template
+    }))
+}
+
+async fn matrix_unlink(
+    State(state): State<AppState>,
+    current: CurrentUser,
+) -> Result<Redirect, AppError> {
+    let user = current.require_user()?;
+    matrix_bot::unlink(&state.db, user.id).await?;
+    Ok(Redirect::to("/me/settings"))
+}
+
+fn format_mean(average: Average) -> String {
+    average
+        .mean()
+        .map(|mean| format!("{mean:.1}"))
+        .unwrap_or_else(|| "–".into())
+}
+
+/// Parses a `datetime-local` form value in the user's timezone and rejects
+/// timestamps in the future.
+fn parse_checkin_timestamp(
+    input: Option<String>,
+    tz: Tz,
+) -> Result<Option<DateTime<Utc>>, AppError> {
+    let Some(raw) = normalize_optional(input) else {
+        return Ok(None);
+    };
+    let naive = NaiveDateTime::parse_from_str(&raw, "%Y-%m-%dT%H:%M")
+        .or_else(|_| NaiveDateTime::parse_from_str(&raw, "%Y-%m-%dT%H:%M:%S"))
+        .map_err(|_| AppError::BadRequest(i18n::tr("error-invalid-timestamp")))?;
+    let timestamp = timezone::from_local(naive, tz)
+        .ok_or_else(|| AppError::BadRequest(i18n::tr("error-invalid-timestamp")))?;
+    journal::check_timestamp(timestamp).map(Some)
+}
+
+async fn user_tz(state: &AppState, user: &AuthenticatedUser) -> Result<Tz, AppError> {
+    Ok(state
+        .load_user_config(&user.uuid, &user.username)
+        .await?
+        .tz())
 }
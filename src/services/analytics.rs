@@ -0,0 +1,337 @@
+//! Mood statistics for the dashboard, the substance insights and the trend
+//! early warning. Check-ins are bucketed by calendar day and hour in the
+//! user's timezone.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Timelike, Utc};
+use chrono_tz::Tz;
+
+use crate::{
+    models::{checkin::Checkin, settings::TrendSensitivity},
+    timezone,
+};
+
+/// Selectable dashboard ranges, in days.
+pub const RANGES: [u32; 3] = [7, 30, 90];
+pub const DEFAULT_RANGE: u32 = 30;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Average {
+    sum: i64,
+    count: usize,
+}
+
+impl Average {
+    fn add(&mut self, value: i32) {
+        self.sum += i64::from(value);
+        self.count += 1;
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.sum as f64 / self.count as f64)
+    }
+}
+
+/// Six-hour blocks of the day, starting at midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeOfDay {
+    Night,
+    Morning,
+    Afternoon,
+    Evening,
+}
+
+impl TimeOfDay {
+    pub const ALL: [TimeOfDay; 4] = [
+        TimeOfDay::Night,
+        TimeOfDay::Morning,
+        TimeOfDay::Afternoon,
+        TimeOfDay::Evening,
+    ];
+
+    pub fn message_key(self) -> &'static str {
+        match self {
+            TimeOfDay::Night => "analytics-night",
+            TimeOfDay::Morning => "analytics-morning",
+            TimeOfDay::Afternoon => "analytics-afternoon",
+            TimeOfDay::Evening => "analytics-evening",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DayStats {
+    pub date: NaiveDate,
+    pub mood: Average,
+    pub high: Average,
+}
+
+#[derive(Debug, Clone)]
+pub struct MoodStats {
+    /// One entry per day of the range, oldest first, including empty days.
+    pub days: Vec<DayStats>,
+    /// Mood by weekday, Monday first.
+    pub by_weekday: [Average; 7],
+    /// Mood by [`TimeOfDay`], in the order of [`TimeOfDay::ALL`].
+    pub by_time_of_day: [Average; 4],
+    pub checkin_count: usize,
+    pub unsafe_count: usize,
+}
+
+impl MoodStats {
+    /// Share of check-ins where the user did not feel safe, `0.0..=1.0`.
+    pub fn unsafe_share(&self) -> Option<f64> {
+        (self.checkin_count > 0).then(|| self.unsafe_count as f64 / self.checkin_count as f64)
+    }
+
+    pub fn mood(&self) -> Average {
+        self.total(|day| day.mood)
+    }
+
+    pub fn high(&self) -> Average {
+        self.total(|day| day.high)
+    }
+
+    fn total(&self, field: impl Fn(&DayStats) -> Average) -> Average {
+        self.days
+            .iter()
+            .map(field)
+            .fold(Average::default(), |a, b| Average {
+                sum: a.sum + b.sum,
+                count: a.count + b.count,
+            })
+    }
+}
+
+/// Statistics over the `range_days` local days ending with `today`.
+/// Trashed check-ins are ignored.
+pub fn mood_stats(checkins: &[Checkin], tz: Tz, today: NaiveDate, range_days: u32) -> MoodStats {
+    let range_days = range_days.max(1);
+    let first = today - Duration::days(i64::from(range_days) - 1);
+    let mut days: Vec<DayStats> = first
+        .iter_days()
+        .take(range_days as usize)
+        .map(|date| DayStats {
+            date,
+            mood: Average::default(),
+            high: Average::default(),
+        })
+        .collect();
+    let mut stats = MoodStats {
+        days: Vec::new(),
+        by_weekday: [Average::default(); 7],
+        by_time_of_day: [Average::default(); 4],
+        checkin_count: 0,
+        unsafe_count: 0,
+    };
+
+    for checkin in checkins.iter().filter(|checkin| !checkin.is_deleted()) {
+        let local = checkin.timestamp.with_timezone(&tz);
+        let date = timezone::local_date(checkin.timestamp, tz);
+        if date < first || date > today {
+            continue;
+        }
+        let day = &mut days[(date - first).num_days() as usize];
+        day.mood.add(checkin.mood);
+        day.high.add(checkin.high_level);
+        stats.by_weekday[date.weekday().num_days_from_monday() as usize].add(checkin.mood);
+        stats.by_time_of_day[(local.hour() / 6) as usize].add(checkin.mood);
+        stats.checkin_count += 1;
+        if !checkin.feels_safe {
+            stats.unsafe_count += 1;
+        }
+    }
+
+    stats.days = days;
+    stats
+}
+
+/// Mood is compared in this window after a substance was taken, in hours.
+pub const AFTER_USE_HOURS: (i64, i64) = (24, 72);
+/// Fewer check-ins than this in a window are flagged as too little data.
+pub const MIN_SAMPLE_SIZE: usize = 5;
+
+#[derive(Debug, Clone)]
+pub struct SubstanceInsight {
+    /// Spelling of the first entry; entries are grouped case-insensitively.
+    pub substance: String,
+    pub uses: usize,
+    /// Mood of check-ins 24–72 hours after a use.
+    pub after_use: Average,
+}
+
+impl SubstanceInsight {
+    /// Difference of the mean mood after use to the baseline mean.
+    pub fn delta(&self, baseline: &Average) -> Option<f64> {
+        Some(self.after_use.mean()? - baseline.mean()?)
+    }
+
+    pub fn enough_data(&self) -> bool {
+        self.after_use.count() >= MIN_SAMPLE_SIZE
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SubstanceReport {
+    /// Mood of check-ins without any substance use in the 72 hours before.
+    pub baseline: Average,
+    /// Most used substances first.
+    pub substances: Vec<SubstanceInsight>,
+}
+
+/// Compares mood after each substance with the substance-free baseline.
+/// A use is dated by the entry's `start_time`, else by its check-in.
+pub fn substance_report(checkins: &[Checkin]) -> SubstanceReport {
+    let live: Vec<&Checkin> = checkins
+        .iter()
+        .filter(|checkin| !checkin.is_deleted())
+        .collect();
+
+    let mut uses: BTreeMap<String, (String, Vec<DateTime<Utc>>)> = BTreeMap::new();
+    for checkin in &live {
+        for drug in &checkin.drugs {
+            let name = drug.substance.trim();
+            if name.is_empty() {
+                continue;
+            }
+            uses.entry(name.to_lowercase())
+                .or_insert_with(|| (name.to_string(), Vec::new()))
+                .1
+                .push(drug.start_time.unwrap_or(checkin.timestamp));
+        }
+    }
+
+    let (from, to) = (
+        Duration::hours(AFTER_USE_HOURS.0),
+        Duration::hours(AFTER_USE_HOURS.1),
+    );
+    let mut baseline = Average::default();
+    for checkin in &live {
+        let after_any_use = uses.values().flat_map(|(_, times)| times).any(|used| {
+            let since = checkin.timestamp - *used;
+            since >= Duration::zero() && since <= to
+        });
+        if !after_any_use {
+            baseline.add(checkin.mood);
+        }
+    }
+
+    let mut substances: Vec<SubstanceInsight> = uses
+        .into_values()
+        .map(|(substance, times)| {
+            let mut after_use = Average::default();
+            for checkin in &live {
+                let in_window = times.iter().any(|used| {
+                    let since = checkin.timestamp - *used;
+                    since >= from && since <= to
+                });
+                if in_window {
+                    after_use.add(checkin.mood);
+                }
+            }
+            SubstanceInsight {
+                substance,
+                uses: times.len(),
+                after_use,
+            }
+        })
+        .collect();
+    substances.sort_by(|a, b| b.uses.cmp(&a.uses).then(a.substance.cmp(&b.substance)));
+
+    SubstanceReport {
+        baseline,
+        substances,
+    }
+}
+
+/// Check-ins from the last days that are compared with the baseline.
+pub const TREND_RECENT_DAYS: i64 = 3;
+/// The personal baseline is the average of the weeks before that.
+pub const TREND_BASELINE_DAYS: i64 = 28;
+const TREND_MIN_BASELINE: usize = 5;
+const TREND_MIN_RECENT: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrendSignal {
+    /// The recent average is well below the baseline.
+    AverageDrop,
+    /// The last check-ins each went lower, all below the baseline.
+    ConsecutiveDeclines(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrendWarning {
+    pub signal: TrendSignal,
+    pub baseline: f64,
+    pub recent: f64,
+}
+
+/// `(average drop, consecutive declines)` that count as a trend.
+fn trend_rules(sensitivity: TrendSensitivity) -> Option<(f64, usize)> {
+    match sensitivity {
+        TrendSensitivity::Off => None,
+        TrendSensitivity::Low => Some((2.0, 5)),
+        TrendSensitivity::Medium => Some((1.5, 4)),
+        TrendSensitivity::High => Some((1.0, 3)),
+    }
+}
+
+/// Looks for a downward mood trend at `now`. Needs a few check-ins both in
+/// the recent window and in the baseline, so new users aren't flagged.
+pub fn detect_trend(
+    checkins: &[Checkin],
+    now: DateTime<Utc>,
+    sensitivity: TrendSensitivity,
+) -> Option<TrendWarning> {
+    let (max_drop, max_declines) = trend_rules(sensitivity)?;
+    let recent_start = now - Duration::days(TREND_RECENT_DAYS);
+    let baseline_start = recent_start - Duration::days(TREND_BASELINE_DAYS);
+
+    let mut live: Vec<&Checkin> = checkins
+        .iter()
+        .filter(|checkin| !checkin.is_deleted() && checkin.timestamp <= now)
+        .collect();
+    live.sort_by_key(|checkin| checkin.timestamp);
+
+    let mut baseline = Average::default();
+    let mut recent = Average::default();
+    for checkin in &live {
+        if checkin.timestamp > recent_start {
+            recent.add(checkin.mood);
+        } else if checkin.timestamp > baseline_start {
+            baseline.add(checkin.mood);
+        }
+    }
+    if baseline.count() < TREND_MIN_BASELINE || recent.count() < TREND_MIN_RECENT {
+        return None;
+    }
+    let (baseline, recent) = (baseline.mean()?, recent.mean()?);
+
+    if recent <= baseline - max_drop {
+        return Some(TrendWarning {
+            signal: TrendSignal::AverageDrop,
+            baseline,
+            recent,
+        });
+    }
+
+    let tail = &live[live.len().saturating_sub(max_declines)..];
+    let declining = tail.len() == max_declines
+        && tail
+            .last()
+            .is_some_and(|last| last.timestamp > recent_start)
+        && tail.windows(2).all(|pair| pair[1].mood < pair[0].mood)
+        && tail
+            .iter()
+            .all(|checkin| f64::from(checkin.mood) < baseline);
+    declining.then_some(TrendWarning {
+        signal: TrendSignal::ConsecutiveDeclines(max_declines),
+        baseline,
+        recent,
+    })
+}
@@ -0,0 +1,167 @@
+#![allow(dead_code)]
+
+use std::{
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use tracing::{error, info, warn};
+
+use crate::{
+    config::BackupConfig,
+    error::AppError,
+    models::settings::Contact,
+    services::{
+        git::GitService,
+        notify::{Message, Notifier, Origin},
+    },
+};
+
+const MAX_ATTEMPTS: u32 = 5;
+const INITIAL_BACKOFF: Duration = Duration::from_secs(15);
+const MAX_BACKOFF: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Debug, Clone, Default)]
+pub struct BackupStatus {
+    pub last_attempt_at: Option<DateTime<Utc>>,
+    pub last_success_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub failing_since: Option<DateTime<Utc>>,
+    pub consecutive_failures: u32,
+    pub alert_sent: bool,
+}
+
+/// Pushes the `ai/` repository to the configured backup remote and keeps
+/// track of how that went. The `backup-push` job runs it on a schedule.
+#[derive(Clone)]
+pub struct BackupService {
+    config: Option<BackupConfig>,
+    admin_contact: Option<Contact>,
+    notifier: Notifier,
+    status: Arc<RwLock<BackupStatus>>,
+}
+
+impl BackupService {
+    pub fn new(
+        config: Option<BackupConfig>,
+        admin_contact: Option<Contact>,
+        notifier: Notifier,
+    ) -> Self {
+        Self {
+            config,
+            admin_contact,
+            notifier,
+            status: Arc::new(RwLock::new(BackupStatus::default())),
+        }
+    }
+
+    pub fn config(&self) -> Option<&BackupConfig> {
+        self.config.as_ref()
+    }
+
+    pub fn status(&self) -> BackupStatus {
+        self.status.read().expect("backup status poisoned").clone()
+    }
+
+    /// Tries to push, backing off exponentially between failed attempts.
+    /// Returns the last error if every attempt failed.
+    pub async fn push_with_retry(&self, git: &GitService) -> Result<(), AppError> {
+        let mut backoff = INITIAL_BACKOFF;
+        let mut attempt = 1;
+        let result = loop {
+            match self.push_once(git).await {
+                Err(err) if attempt < MAX_ATTEMPTS => {
+                    warn!(
+                        attempt,
+                        "backup push failed, retrying in {backoff:?}: {err}"
+                    );
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                    attempt += 1;
+                }
+                result => break result,
+            }
+        };
+        if let Err(err) = &result {
+            error!(
+                attempt,
+                "backup push failed, giving up until next run: {err}"
+            );
+            self.alert_if_failing_too_long().await;
+        }
+        result
+    }
+
+    /// A single push attempt; updates the status either way.
+    pub async fn push_once(&self, git: &GitService) -> Result<(), AppError> {
+        let Some(config) = self.config.clone() else {
+            return Ok(());
+        };
+        let git = git.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            git.push_backup(&config.remote_url, config.ssh_key.as_deref())
+        })
+        .await
+        .map_err(|err| AppError::Other(err.into()))
+        .and_then(|result| result);
+
+        let now = Utc::now();
+        let mut status = self.status.write().expect("backup status poisoned");
+        status.last_attempt_at = Some(now);
+        match &result {
+            Ok(()) => {
+                if status.failing_since.is_some() {
+                    info!("backup push recovered");
+                }
+                status.last_success_at = Some(now);
+                status.last_error = None;
+                status.failing_since = None;
+                status.consecutive_failures = 0;
+                status.alert_sent = false;
+            }
+            Err(err) => {
+                status.last_error = Some(err.to_string());
+                status.failing_since.get_or_insert(now);
+                status.consecutive_failures += 1;
+            }
+        }
+        result
+    }
+
+    async fn alert_if_failing_too_long(&self) {
+        let Some(config) = &self.config else {
+            return;
+        };
+        let message = {
+            let mut status = self.status.write().expect("backup status poisoned");
+            let Some(since) = status.failing_since else {
+                return;
+            };
+            let failing_for = (Utc::now() - since).to_std().unwrap_or_default();
+            if status.alert_sent || failing_for < config.alert_after {
+                return;
+            }
+            status.alert_sent = true;
+            format!(
+                "Backup-Push nach {} schlägt seit {} fehl ({} Versuche). Letzter Fehler: {}",
+                config.remote_url,
+                since.format("%d.%m.%Y %H:%M UTC"),
+                status.consecutive_failures,
+                status.last_error.as_deref().unwrap_or("unbekannt"),
+            )
+        };
+        let Some(contact) = &self.admin_contact else {
+            warn!(%message, "admin alert (no ADMIN_ALERT_CONTACT configured)");
+            return;
+        };
+        let message = Message {
+            title: "Backup-Alarm".into(),
+            body: message,
+            urgent: false,
+        };
+        if let Err(err) = self.notifier.send(Origin::System, contact, &message).await {
+            error!("could not send backup alert: {err:?}");
+        }
+    }
+}
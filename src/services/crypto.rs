@@ -0,0 +1,295 @@
+#![allow(dead_code)]
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use anyhow::anyhow;
+use argon2::{
+    password_hash::rand_core::{OsRng, RngCore},
+    Argon2,
+};
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit, Payload},
+    Key, XChaCha20Poly1305, XNonce,
+};
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+use sqlx::Row;
+
+use crate::{db::DbPool, error::AppError};
+
+/// Prefix of every sealed file so legacy plaintext JSON can still be read.
+const SEALED_MAGIC: &[u8; 8] = b"MOODENC1";
+const NONCE_LEN: usize = 24;
+const SALT_LEN: usize = 16;
+
+/// Per-user data encryption key. Never leaves the process unwrapped.
+#[derive(Clone)]
+pub struct DataKey(Key);
+
+impl DataKey {
+    fn generate() -> Self {
+        Self(XChaCha20Poly1305::generate_key(&mut OsRng))
+    }
+
+    fn from_slice(bytes: &[u8]) -> Result<Self, AppError> {
+        if bytes.len() != 32 {
+            return Err(AppError::Other(anyhow!("data key has invalid length")));
+        }
+        Ok(Self(*Key::from_slice(bytes)))
+    }
+
+    /// Encrypts `plaintext`, binding it to `aad` (e.g. the file path).
+    pub fn seal(&self, aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, AppError> {
+        let mut out = SEALED_MAGIC.to_vec();
+        out.extend_from_slice(&encrypt(&self.0, aad, plaintext)?);
+        Ok(out)
+    }
+
+    pub fn open(&self, aad: &[u8], sealed: &[u8]) -> Result<Vec<u8>, AppError> {
+        let body = sealed
+            .strip_prefix(SEALED_MAGIC.as_slice())
+            .ok_or_else(|| AppError::Other(anyhow!("data is not sealed")))?;
+        decrypt(&self.0, aad, body)
+    }
+}
+
+pub fn is_sealed(data: &[u8]) -> bool {
+    data.starts_with(SEALED_MAGIC)
+}
+
+/// Manages per-user data keys: wrapping them with a password-derived key
+/// (and optionally an admin escrow key) in SQLite, and caching unwrapped
+/// keys for users that are currently unlocked.
+#[derive(Clone)]
+pub struct CryptoService {
+    db: DbPool,
+    escrow: Option<Arc<Key>>,
+    unlocked: Arc<RwLock<HashMap<String, DataKey>>>,
+}
+
+impl CryptoService {
+    pub fn new(db: DbPool, escrow_secret: Option<&str>) -> Self {
+        let escrow = escrow_secret.map(|secret| Arc::new(derive_server_key(secret)));
+        Self {
+            db,
+            escrow,
+            unlocked: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    pub fn escrow_enabled(&self) -> bool {
+        self.escrow.is_some()
+    }
+
+    /// Creates a fresh data key for a new account and unlocks it.
+    pub async fn provision(&self, user_uuid: &str, password: &str) -> Result<(), AppError> {
+        let key = DataKey::generate();
+        self.store_wrapped(user_uuid, &key, password).await?;
+        self.cache(user_uuid, key);
+        Ok(())
+    }
+
+    /// Unwraps the user's data key with their password. Accounts created
+    /// before encryption existed get a key provisioned on first login.
+    pub async fn unlock(&self, user_uuid: &str, password: &str) -> Result<(), AppError> {
+        let row = sqlx::query(
+            r#"
+            SELECT password_salt, password_wrapped, escrow_wrapped
+            FROM user_keys
+            WHERE user_uuid = ?1
+            "#,
+        )
+        .bind(user_uuid)
+        .fetch_optional(&self.db)
+        .await?;
+
+        let Some(row) = row else {
+            return self.provision(user_uuid, password).await;
+        };
+
+        let salt: Vec<u8> = row.try_get("password_salt")?;
+        let wrapped: Vec<u8> = row.try_get("password_wrapped")?;
+        let escrow_wrapped: Option<Vec<u8>> = row.try_get("escrow_wrapped")?;
+
+        let kek = derive_password_key(password, &salt)?;
+        let key = DataKey::from_slice(&decrypt(&kek, user_uuid.as_bytes(), &wrapped)?)?;
+
+        // Escrow may have been configured after this key was created.
+        if let (Some(escrow), None) = (&self.escrow, escrow_wrapped) {
+            let escrow_wrapped = encrypt(escrow, user_uuid.as_bytes(), &key.0)?;
+            sqlx::query(
+                "UPDATE user_keys SET escrow_wrapped = ?1, updated_at = ?2 WHERE user_uuid = ?3",
+            )
+            .bind(escrow_wrapped)
+            .bind(Utc::now())
+            .bind(user_uuid)
+            .execute(&self.db)
+            .await?;
+        }
+
+        self.cache(user_uuid, key);
+        Ok(())
+    }
+
+    /// Returns the unlocked key, falling back to the escrow copy when the
+    /// server holds an escrow key (e.g. after a restart).
+    pub async fn key_for(&self, user_uuid: &str) -> Result<DataKey, AppError> {
+        if let Some(key) = self.cached(user_uuid) {
+            return Ok(key);
+        }
+
+        let key = self.unwrap_escrow(user_uuid).await?;
+        self.cache(user_uuid, key.clone());
+        Ok(key)
+    }
+
+    pub fn is_unlocked(&self, user_uuid: &str) -> bool {
+        self.cached(user_uuid).is_some()
+    }
+
+    pub fn lock(&self, user_uuid: &str) {
+        self.unlocked
+            .write()
+            .expect("key cache poisoned")
+            .remove(user_uuid);
+    }
+
+    /// Re-wraps the user's unlocked data key for a new password. The data
+    /// itself stays sealed with the same key.
+    pub async fn rewrap(&self, user_uuid: &str, new_password: &str) -> Result<(), AppError> {
+        let key = self.key_for(user_uuid).await?;
+        self.store_wrapped(user_uuid, &key, new_password).await
+    }
+
+    /// Admin recovery: re-wraps the user's data key for a new password using
+    /// the escrow copy.
+    pub async fn recover_with_escrow(
+        &self,
+        user_uuid: &str,
+        new_password: &str,
+    ) -> Result<(), AppError> {
+        let key = self.unwrap_escrow(user_uuid).await?;
+        self.store_wrapped(user_uuid, &key, new_password).await
+    }
+
+    async fn unwrap_escrow(&self, user_uuid: &str) -> Result<DataKey, AppError> {
+        let Some(escrow) = &self.escrow else {
+            return Err(AppError::Locked);
+        };
+
+        let wrapped: Option<Option<Vec<u8>>> =
+            sqlx::query_scalar("SELECT escrow_wrapped FROM user_keys WHERE user_uuid = ?1")
+                .bind(user_uuid)
+                .fetch_optional(&self.db)
+                .await?;
+        let Some(Some(wrapped)) = wrapped else {
+            return Err(AppError::Locked);
+        };
+
+        DataKey::from_slice(&decrypt(escrow, user_uuid.as_bytes(), &wrapped)?)
+    }
+
+    async fn store_wrapped(
+        &self,
+        user_uuid: &str,
+        key: &DataKey,
+        password: &str,
+    ) -> Result<(), AppError> {
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        let kek = derive_password_key(password, &salt)?;
+        let password_wrapped = encrypt(&kek, user_uuid.as_bytes(), &key.0)?;
+        let escrow_wrapped = self
+            .escrow
+            .as_ref()
+            .map(|escrow| encrypt(escrow, user_uuid.as_bytes(), &key.0))
+            .transpose()?;
+        let now = Utc::now();
+
+        sqlx::query(
+            r#"
+            INSERT INTO user_keys (user_uuid, password_salt, password_wrapped, escrow_wrapped, created_at, updated_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?5)
+            ON CONFLICT(user_uuid) DO UPDATE SET
+                password_salt = excluded.password_salt,
+                password_wrapped = excluded.password_wrapped,
+                escrow_wrapped = COALESCE(excluded.escrow_wrapped, user_keys.escrow_wrapped),
+                updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(user_uuid)
+        .bind(salt.as_slice())
+        .bind(password_wrapped)
+        .bind(escrow_wrapped)
+        .bind(now)
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+
+    fn cached(&self, user_uuid: &str) -> Option<DataKey> {
+        self.unlocked
+            .read()
+            .expect("key cache poisoned")
+            .get(user_uuid)
+            .cloned()
+    }
+
+    fn cache(&self, user_uuid: &str, key: DataKey) {
+        self.unlocked
+            .write()
+            .expect("key cache poisoned")
+            .insert(user_uuid.to_string(), key);
+    }
+}
+
+/// Turns a configured server secret into a symmetric key.
+pub(crate) fn derive_server_key(secret: &str) -> Key {
+    let digest = Sha256::digest(secret.as_bytes());
+    *Key::from_slice(&digest)
+}
+
+fn derive_password_key(password: &str, salt: &[u8]) -> Result<Key, AppError> {
+    let mut out = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(password.as_bytes(), salt, &mut out)
+        .map_err(|err| AppError::Other(anyhow!(err.to_string())))?;
+    Ok(*Key::from_slice(&out))
+}
+
+/// Returns `nonce || ciphertext`.
+pub(crate) fn encrypt(key: &Key, aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, AppError> {
+    let cipher = XChaCha20Poly1305::new(key);
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: plaintext,
+                aad,
+            },
+        )
+        .map_err(|_| AppError::Other(anyhow!("encryption failed")))?;
+    let mut out = nonce.to_vec();
+    out.extend_from_slice(&ciphertext);
+    Ok(out)
+}
+
+pub(crate) fn decrypt(key: &Key, aad: &[u8], data: &[u8]) -> Result<Vec<u8>, AppError> {
+    if data.len() < NONCE_LEN {
+        return Err(AppError::Other(anyhow!("ciphertext too short")));
+    }
+    let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+    XChaCha20Poly1305::new(key)
+        .decrypt(
+            XNonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad,
+            },
+        )
+        .map_err(|_| AppError::Other(anyhow!("decryption failed (wrong key or tampered data)")))
+}
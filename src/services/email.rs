@@ -0,0 +1,77 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use lettre::{
+    message::{header::ContentType, Mailbox},
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport, AsyncTransport, Tokio1Executor,
+};
+
+use crate::{
+    config::{SmtpConfig, SmtpTls},
+    error::AppError,
+    models::settings::ChannelKind,
+    services::notify::{Message, NotificationChannel, Origin},
+};
+
+const SMTP_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Sends plain-text mails through the configured SMTP server.
+pub struct EmailChannel {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl EmailChannel {
+    pub fn new(config: &SmtpConfig) -> Result<Self, AppError> {
+        let builder = match config.tls {
+            SmtpTls::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)
+                .map_err(|err| AppError::Config(format!("invalid SMTP_HOST: {err}")))?,
+            SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host)
+                .map_err(|err| AppError::Config(format!("invalid SMTP_HOST: {err}")))?,
+            SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.host),
+        };
+        let mut builder = builder.port(config.port).timeout(Some(SMTP_TIMEOUT));
+        if let (Some(username), Some(password)) = (&config.username, &config.password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+        let from = config
+            .from
+            .parse()
+            .map_err(|err| AppError::Config(format!("invalid SMTP_FROM: {err}")))?;
+        Ok(Self {
+            transport: builder.build(),
+            from,
+        })
+    }
+}
+
+#[async_trait]
+impl NotificationChannel for EmailChannel {
+    fn kind(&self) -> ChannelKind {
+        ChannelKind::Email
+    }
+
+    async fn send(
+        &self,
+        _origin: Origin<'_>,
+        address: &str,
+        message: &Message,
+    ) -> Result<(), AppError> {
+        let to: Mailbox = address
+            .parse()
+            .map_err(|err: lettre::address::AddressError| AppError::Other(err.into()))?;
+        let email = lettre::Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(&message.title)
+            .header(ContentType::TEXT_PLAIN)
+            .body(message.body.clone())
+            .map_err(|err| AppError::Other(err.into()))?;
+        self.transport
+            .send(email)
+            .await
+            .map_err(|err| AppError::Other(err.into()))?;
+        Ok(())
+    }
+}
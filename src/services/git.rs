@@ -1,23 +1,158 @@
 #![allow(dead_code)]
 
 use std::{
+    collections::HashMap,
+    fmt,
     path::{Path, PathBuf},
-    sync::Arc,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
 };
 
-use git2::{IndexAddOption, Repository, Signature};
+use chrono::{DateTime, TimeZone, Utc};
+use git2::{
+    Commit, Cred, CredentialType, IndexAddOption, ObjectType, Oid, PushOptions, RemoteCallbacks,
+    Repository, Signature, Sort, StatusOptions, Tree,
+};
+use tokio::{
+    sync::{mpsc, watch},
+    task::JoinHandle,
+    time::{timeout, Instant},
+};
+use tracing::{debug, error, info};
 
 use crate::error::AppError;
 
+/// Bursts are flushed at the latest after this many debounce windows, so a
+/// steady stream of changes can't postpone the commit forever.
+const MAX_DEBOUNCE_WINDOWS: u32 = 10;
+/// Upper bound of commits inspected when looking for recent `ai/` commits.
+const MAX_STATUS_WALK: usize = 1000;
+/// Upper bound of commits inspected when collecting a file's history.
+const MAX_HISTORY_WALK: usize = 5000;
+const BACKUP_REMOTE: &str = "backup";
+
+/// A change to the `ai/` data that should end up in its git history.
+#[derive(Debug, Clone)]
+pub enum DataChange {
+    Checkin {
+        user_uuid: String,
+        mood: i32,
+    },
+    Trip {
+        user_uuid: String,
+        action: TripAction,
+    },
+    Settings {
+        user_uuid: String,
+    },
+    Panic {
+        user_uuid: String,
+    },
+    CheckinEdited {
+        user_uuid: String,
+        mood: i32,
+    },
+    CheckinTrashed {
+        user_uuid: String,
+        action: TrashAction,
+    },
+    CheckinRestored {
+        user_uuid: String,
+        commit: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum TripAction {
+    Started,
+    Ended,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum TrashAction {
+    Deleted,
+    Undeleted,
+    Purged(usize),
+}
+
+impl fmt::Display for DataChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DataChange::Checkin { user_uuid, mood } => {
+                write!(f, "checkin: {user_uuid} mood {mood}")
+            }
+            DataChange::Trip { user_uuid, action } => {
+                let action = match action {
+                    TripAction::Started => "started",
+                    TripAction::Ended => "ended",
+                };
+                write!(f, "trip: {user_uuid} {action}")
+            }
+            DataChange::Settings { user_uuid } => write!(f, "settings: {user_uuid}"),
+            DataChange::Panic { user_uuid } => write!(f, "panic: {user_uuid}"),
+            DataChange::CheckinEdited { user_uuid, mood } => {
+                write!(f, "checkin: {user_uuid} edited mood {mood}")
+            }
+            DataChange::CheckinTrashed { user_uuid, action } => match action {
+                TrashAction::Deleted => write!(f, "checkin: {user_uuid} deleted"),
+                TrashAction::Undeleted => write!(f, "checkin: {user_uuid} undeleted"),
+                TrashAction::Purged(count) => write!(f, "checkin: {user_uuid} purged {count}"),
+            },
+            DataChange::CheckinRestored { user_uuid, commit } => {
+                write!(f, "checkin: {user_uuid} restored from {commit}")
+            }
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct GitService {
     repo_root: Arc<PathBuf>,
+    changes: Option<mpsc::UnboundedSender<DataChange>>,
+    /// Held while HEAD moves, so the auto-commit worker, a manual commit
+    /// and a history rewrite never build on the same parent at once.
+    head_lock: Arc<Mutex<()>>,
 }
 
 impl GitService {
     pub fn new(root: PathBuf) -> Self {
         Self {
             repo_root: Arc::new(root),
+            changes: None,
+            head_lock: Arc::default(),
+        }
+    }
+
+    /// Starts the background committer. Afterwards every [`record`]ed change
+    /// is committed once no further change arrived for `debounce`. Once
+    /// `shutdown` turns true the pending changes are committed right away
+    /// and the returned handle finishes.
+    ///
+    /// [`record`]: GitService::record
+    pub fn start_auto_commit(
+        &mut self,
+        debounce: Duration,
+        shutdown: watch::Receiver<bool>,
+    ) -> JoinHandle<()> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        // Without the sender, so the channel closes with the last clone.
+        let committer = GitService {
+            changes: None,
+            ..self.clone()
+        };
+        self.changes = Some(tx);
+        tokio::spawn(run_auto_commit(committer, rx, debounce, shutdown))
+    }
+
+    /// Queues a commit for `change`. A no-op when auto-commit isn't running.
+    pub fn record(&self, change: DataChange) {
+        match &self.changes {
+            Some(tx) => {
+                if tx.send(change).is_err() {
+                    error!("auto-commit worker is gone, change not committed");
+                }
+            }
+            None => debug!(%change, "auto-commit disabled"),
         }
     }
 
@@ -25,6 +160,26 @@ impl GitService {
         &self.repo_root
     }
 
+    fn lock_head(&self) -> MutexGuard<'_, ()> {
+        // The guarded state is the repo on disk, which a panic leaves as
+        // consistent as git does.
+        self.head_lock
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Opens the repository for work on its whole history, a mirror push or
+    /// a rewrite. `repo_root` may be the app's own checkout, whose code
+    /// commits must not be pushed or rewritten, so a HEAD that tracks
+    /// anything besides `ai/` is refused.
+    fn open_data_repo(&self) -> Result<Repository, AppError> {
+        let repo = Repository::discover(self.root())?;
+        if let Ok(commit) = repo.head().and_then(|head| head.peel_to_commit()) {
+            ensure_data_only(&commit)?;
+        }
+        Ok(repo)
+    }
+
     pub fn init_repo_if_needed(&self) -> Result<(), AppError> {
         if self.root().join(".git").exists() {
             return Ok(());
@@ -34,12 +189,18 @@ impl GitService {
         Ok(())
     }
 
-    pub fn commit_ai_changes(&self, message: &str) -> Result<(), AppError> {
+    /// Stages everything under `ai/` and commits it. Returns `false` when
+    /// there was nothing to commit.
+    pub fn commit_ai_changes(&self, message: &str) -> Result<bool, AppError> {
+        let _head = self.lock_head();
         let repo = Repository::discover(self.root())?;
         let mut index = repo.index()?;
         index.add_all(["ai"].iter(), IndexAddOption::DEFAULT, None)?;
+        // Also stage deletions (purged files).
+        index.update_all(["ai"].iter(), None)?;
+        index.write()?;
         if index.is_empty() {
-            return Ok(());
+            return Ok(false);
         }
         let tree_id = index.write_tree()?;
         let tree = repo.find_tree(tree_id)?;
@@ -53,6 +214,14 @@ impl GitService {
             .map(|commit| vec![commit])
             .unwrap_or_default();
 
+        // Nothing changed since the last commit.
+        if parent_commits
+            .first()
+            .is_some_and(|parent| parent.tree_id() == tree_id)
+        {
+            return Ok(false);
+        }
+
         let parent_refs = parent_commits.iter().collect::<Vec<_>>();
         repo.commit(
             Some("HEAD"),
@@ -63,6 +232,455 @@ impl GitService {
             &parent_refs,
         )?;
 
-        Ok(())
+        Ok(true)
+    }
+
+    /// Force-pushes the current branch to the `backup` remote, creating or
+    /// re-pointing the remote at `url` first. Does nothing before the first
+    /// commit, and refuses a repository that tracks more than `ai/`.
+    pub fn push_backup(&self, url: &str, ssh_key: Option<&Path>) -> Result<(), AppError> {
+        let repo = self.open_data_repo()?;
+        let Ok(head) = repo.head() else {
+            return Ok(());
+        };
+        let Some(branch) = head.name() else {
+            return Ok(());
+        };
+
+        match repo.find_remote(BACKUP_REMOTE) {
+            Ok(remote) if remote.url() == Some(url) => {}
+            Ok(_) => repo.remote_set_url(BACKUP_REMOTE, url)?,
+            Err(_) => {
+                repo.remote(BACKUP_REMOTE, url)?;
+            }
+        }
+        let mut remote = repo.find_remote(BACKUP_REMOTE)?;
+
+        let mut rejected = None;
+        {
+            let mut callbacks = RemoteCallbacks::new();
+            callbacks.credentials(|_url, username, allowed| {
+                let username = username.unwrap_or("git");
+                if allowed.contains(CredentialType::SSH_KEY) {
+                    match ssh_key {
+                        Some(key) => Cred::ssh_key(username, None, key, None),
+                        None => Cred::ssh_key_from_agent(username),
+                    }
+                } else {
+                    Cred::default()
+                }
+            });
+            callbacks.push_update_reference(|refname, status| {
+                if let Some(status) = status {
+                    rejected = Some(format!("{refname}: {status}"));
+                }
+                Ok(())
+            });
+            let mut options = PushOptions::new();
+            options.remote_callbacks(callbacks);
+            // History may be rewritten (secret scrubbing), so the mirror is forced.
+            remote.push(&[format!("+{branch}:{branch}")], Some(&mut options))?;
+        }
+
+        match rejected {
+            Some(reason) => Err(AppError::Other(anyhow::anyhow!(
+                "backup remote rejected push: {reason}"
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Snapshot of the repository for the admin system page.
+    pub fn status(&self, recent_limit: usize) -> Result<RepoStatus, AppError> {
+        let repo = Repository::discover(self.root())?;
+
+        let head = repo.head().ok();
+        let branch = head
+            .as_ref()
+            .and_then(|head| head.shorthand())
+            .map(str::to_string);
+        let head_commit = head
+            .and_then(|head| head.target())
+            .and_then(|oid| repo.find_commit(oid).ok());
+
+        let mut recent_ai_commits = Vec::new();
+        if head_commit.is_some() {
+            let mut revwalk = repo.revwalk()?;
+            revwalk.push_head()?;
+            revwalk.set_sorting(Sort::TIME)?;
+            for oid in revwalk.take(MAX_STATUS_WALK) {
+                let commit = repo.find_commit(oid?)?;
+                if touches_ai(&commit)? {
+                    recent_ai_commits.push(CommitInfo::from(&commit));
+                    if recent_ai_commits.len() >= recent_limit {
+                        break;
+                    }
+                }
+            }
+        }
+
+        let mut options = StatusOptions::new();
+        options
+            .pathspec("ai")
+            .include_untracked(true)
+            .recurse_untracked_dirs(true);
+        let uncommitted = repo
+            .statuses(Some(&mut options))?
+            .iter()
+            .filter_map(|entry| entry.path().map(str::to_string))
+            .collect();
+
+        Ok(RepoStatus {
+            branch,
+            head: head_commit.as_ref().map(CommitInfo::from),
+            recent_ai_commits,
+            uncommitted,
+            repo_size_bytes: dir_size(repo.path()),
+        })
+    }
+
+    /// Every committed version of the repo-relative file `path`, newest first.
+    /// Only commits that changed the file are returned; commits where it
+    /// didn't exist are skipped.
+    pub fn file_history(&self, path: &str) -> Result<Vec<(CommitInfo, Vec<u8>)>, AppError> {
+        let repo = Repository::discover(self.root())?;
+        if repo.head().is_err() {
+            return Ok(Vec::new());
+        }
+
+        let blob_id = |commit: &Commit<'_>| -> Result<Option<Oid>, AppError> {
+            Ok(commit
+                .tree()?
+                .get_path(Path::new(path))
+                .ok()
+                .map(|entry| entry.id()))
+        };
+
+        let mut revwalk = repo.revwalk()?;
+        revwalk.push_head()?;
+        // Topological, so commits made within the same second still come
+        // newest first.
+        revwalk.set_sorting(Sort::TOPOLOGICAL | Sort::TIME)?;
+        let mut versions = Vec::new();
+        for oid in revwalk.take(MAX_HISTORY_WALK) {
+            let commit = repo.find_commit(oid?)?;
+            let Some(own) = blob_id(&commit)? else {
+                continue;
+            };
+            let parent = match commit.parents().next() {
+                Some(parent) => blob_id(&parent)?,
+                None => None,
+            };
+            if parent == Some(own) {
+                continue;
+            }
+            let blob = repo.find_blob(own)?;
+            versions.push((CommitInfo::from(&commit), blob.content().to_vec()));
+        }
+        Ok(versions)
     }
+
+    /// Contents of the repo-relative file `path` as of `commit`, or `None`
+    /// if the file didn't exist there.
+    pub fn file_at(&self, commit: &str, path: &str) -> Result<Option<Vec<u8>>, AppError> {
+        let repo = Repository::discover(self.root())?;
+        let oid = Oid::from_str(commit).map_err(|_| AppError::NotFound)?;
+        let commit = repo.find_commit(oid).map_err(|_| AppError::NotFound)?;
+        let Ok(entry) = commit.tree()?.get_path(Path::new(path)) else {
+            return Ok(None);
+        };
+        let blob = repo.find_blob(entry.id())?;
+        Ok(Some(blob.content().to_vec()))
+    }
+
+    /// Rewrites every commit reachable from HEAD, passing each blob through
+    /// `filter` (called with the repo-relative path and contents). Blobs for
+    /// which `filter` returns `Some` are replaced. The current branch is moved
+    /// to the rewritten history, the index is reset to the new HEAD and the
+    /// reflogs are dropped so the old objects become unreachable. Nothing is
+    /// rewritten if any commit tracks more than `ai/`.
+    pub fn rewrite_history<F>(&self, mut filter: F) -> Result<RewriteReport, AppError>
+    where
+        F: FnMut(&str, &[u8]) -> Option<Vec<u8>>,
+    {
+        let _head = self.lock_head();
+        let repo = self.open_data_repo()?;
+        let head = repo.head()?;
+        let Some(branch) = head.name().map(str::to_string) else {
+            return Ok(RewriteReport::default());
+        };
+
+        let mut revwalk = repo.revwalk()?;
+        revwalk.push_head()?;
+        revwalk.set_sorting(Sort::TOPOLOGICAL | Sort::REVERSE)?;
+        let revwalk = revwalk.collect::<Result<Vec<_>, _>>()?;
+        for oid in &revwalk {
+            ensure_data_only(&repo.find_commit(*oid)?)?;
+        }
+
+        let mut report = RewriteReport::default();
+        let mut trees: HashMap<Oid, Oid> = HashMap::new();
+        let mut commits: HashMap<Oid, Oid> = HashMap::new();
+        let mut new_head = None;
+
+        for oid in revwalk {
+            let commit = repo.find_commit(oid)?;
+            let tree = commit.tree()?;
+            let new_tree_id = rewrite_tree(&repo, &tree, "", &mut filter, &mut trees, &mut report)?;
+            let parent_ids = commit
+                .parent_ids()
+                .map(|id| commits.get(&id).copied().unwrap_or(id))
+                .collect::<Vec<_>>();
+
+            let unchanged =
+                new_tree_id == tree.id() && parent_ids.iter().copied().eq(commit.parent_ids());
+            let new_id = if unchanged {
+                commit.id()
+            } else {
+                report.commits_rewritten += 1;
+                let parents = parent_ids
+                    .iter()
+                    .map(|id| repo.find_commit(*id))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let parent_refs = parents.iter().collect::<Vec<_>>();
+                repo.commit(
+                    None,
+                    &commit.author(),
+                    &commit.committer(),
+                    commit.message().unwrap_or_default(),
+                    &repo.find_tree(new_tree_id)?,
+                    &parent_refs,
+                )?
+            };
+            commits.insert(commit.id(), new_id);
+            new_head = Some(new_id);
+        }
+
+        let Some(new_head) = new_head else {
+            return Ok(report);
+        };
+        if report.commits_rewritten > 0 {
+            repo.reference(&branch, new_head, true, "scrub secrets from history")?;
+            let mut index = repo.index()?;
+            index.read_tree(&repo.find_commit(new_head)?.tree()?)?;
+            index.write()?;
+            for name in ["HEAD", branch.as_str()] {
+                repo.reflog_delete(name)?;
+            }
+        }
+        Ok(report)
+    }
+}
+
+async fn run_auto_commit(
+    git: GitService,
+    mut rx: mpsc::UnboundedReceiver<DataChange>,
+    debounce: Duration,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    // `biased`: queued changes join the batch before shutdown is noticed.
+    loop {
+        let first = tokio::select! {
+            biased;
+            change = rx.recv() => change,
+            _ = shutdown.wait_for(|stop| *stop) => None,
+        };
+        let Some(first) = first else { break };
+        let deadline = Instant::now() + debounce * MAX_DEBOUNCE_WINDOWS;
+        let mut batch = vec![first];
+        loop {
+            let wait = debounce.min(deadline.saturating_duration_since(Instant::now()));
+            tokio::select! {
+                biased;
+                next = timeout(wait, rx.recv()) => match next {
+                    Ok(Some(change)) => batch.push(change),
+                    // Quiet period elapsed, deadline hit or channel closed.
+                    Ok(None) | Err(_) => break,
+                },
+                _ = shutdown.wait_for(|stop| *stop) => break,
+            }
+        }
+        commit_batch(&git, batch).await;
+    }
+
+    // Shutting down: whatever is still queued goes into one last commit.
+    rx.close();
+    let mut batch = Vec::new();
+    while let Ok(change) = rx.try_recv() {
+        batch.push(change);
+    }
+    if !batch.is_empty() {
+        commit_batch(&git, batch).await;
+    }
+}
+
+async fn commit_batch(git: &GitService, batch: Vec<DataChange>) {
+    let message = commit_message(&batch);
+    let git = git.clone();
+    match tokio::task::spawn_blocking(move || git.commit_ai_changes(&message)).await {
+        Ok(Ok(true)) => info!(changes = batch.len(), "auto-committed ai/ changes"),
+        Ok(Ok(false)) => debug!(changes = batch.len(), "nothing to auto-commit"),
+        Ok(Err(err)) => error!("auto-commit failed: {err:?}"),
+        Err(err) => error!("auto-commit task panicked: {err:?}"),
+    }
+}
+
+/// One change becomes the subject line; a burst gets a summary subject and
+/// lists every change in the body.
+fn commit_message(changes: &[DataChange]) -> String {
+    match changes {
+        [single] => single.to_string(),
+        many => {
+            let body = many
+                .iter()
+                .map(|change| format!("- {change}"))
+                .collect::<Vec<_>>()
+                .join("\n");
+            format!("batch: {} changes\n\n{body}", many.len())
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CommitInfo {
+    /// Abbreviated id for display.
+    pub id: String,
+    /// Full object id.
+    pub oid: String,
+    pub summary: String,
+    pub time: DateTime<Utc>,
+}
+
+impl From<&Commit<'_>> for CommitInfo {
+    fn from(commit: &Commit<'_>) -> Self {
+        let id = commit.id().to_string();
+        Self {
+            id: id[..id.len().min(10)].to_string(),
+            oid: id,
+            summary: commit.summary().unwrap_or_default().to_string(),
+            time: Utc
+                .timestamp_opt(commit.time().seconds(), 0)
+                .single()
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RepoStatus {
+    pub branch: Option<String>,
+    pub head: Option<CommitInfo>,
+    pub recent_ai_commits: Vec<CommitInfo>,
+    pub uncommitted: Vec<String>,
+    pub repo_size_bytes: u64,
+}
+
+/// Whether `commit` changed anything under `ai/` compared to its first parent.
+fn touches_ai(commit: &Commit<'_>) -> Result<bool, AppError> {
+    let ai_tree = |commit: &Commit<'_>| -> Result<Option<Oid>, AppError> {
+        Ok(commit
+            .tree()?
+            .get_path(Path::new("ai"))
+            .ok()
+            .map(|entry| entry.id()))
+    };
+    let own = ai_tree(commit)?;
+    let parent = match commit.parents().next() {
+        Some(parent) => ai_tree(&parent)?,
+        None => None,
+    };
+    Ok(own != parent)
+}
+
+/// Total size of all files below `path`; unreadable entries are skipped.
+pub(crate) fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .filter_map(Result::ok)
+        .map(|entry| match entry.metadata() {
+            Ok(meta) if meta.is_dir() => dir_size(&entry.path()),
+            Ok(meta) => meta.len(),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct RewriteReport {
+    pub commits_rewritten: usize,
+    pub blobs_rewritten: usize,
+}
+
+fn ensure_data_only(commit: &Commit<'_>) -> Result<(), AppError> {
+    let tree = commit.tree()?;
+    let other = tree
+        .iter()
+        .find(|entry| entry.name() != Some("ai"))
+        .map(|entry| String::from_utf8_lossy(entry.name_bytes()).into_owned());
+    match other {
+        Some(name) => Err(AppError::Config(format!(
+            "commit {} tracks {name} besides ai/; REPO_ROOT must hold only the data",
+            commit.id()
+        ))),
+        None => Ok(()),
+    }
+}
+
+fn rewrite_tree<F>(
+    repo: &Repository,
+    tree: &Tree<'_>,
+    prefix: &str,
+    filter: &mut F,
+    memo: &mut HashMap<Oid, Oid>,
+    report: &mut RewriteReport,
+) -> Result<Oid, AppError>
+where
+    F: FnMut(&str, &[u8]) -> Option<Vec<u8>>,
+{
+    // Identical subtrees at different paths must not share a memo entry.
+    let memo_key = Oid::hash_object(
+        ObjectType::Blob,
+        format!("{prefix}\0{}", tree.id()).as_bytes(),
+    )?;
+    if let Some(done) = memo.get(&memo_key) {
+        return Ok(*done);
+    }
+
+    let mut builder = repo.treebuilder(Some(tree))?;
+    let mut changed = false;
+    for entry in tree.iter() {
+        let Some(name) = entry.name() else {
+            continue;
+        };
+        let path = format!("{prefix}{name}");
+        match entry.kind() {
+            Some(ObjectType::Tree) => {
+                let subtree = repo.find_tree(entry.id())?;
+                let new_id =
+                    rewrite_tree(repo, &subtree, &format!("{path}/"), filter, memo, report)?;
+                if new_id != entry.id() {
+                    builder.insert(name, new_id, entry.filemode())?;
+                    changed = true;
+                }
+            }
+            Some(ObjectType::Blob) => {
+                let blob = repo.find_blob(entry.id())?;
+                if let Some(content) = filter(&path, blob.content()) {
+                    let new_id = repo.blob(&content)?;
+                    if new_id != entry.id() {
+                        builder.insert(name, new_id, entry.filemode())?;
+                        report.blobs_rewritten += 1;
+                        changed = true;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let new_id = if changed { builder.write()? } else { tree.id() };
+    memo.insert(memo_key, new_id);
+    Ok(new_id)
 }
@@ -0,0 +1,141 @@
+use similar::{ChangeTag, TextDiff};
+use tracing::warn;
+
+use crate::{
+    error::AppError,
+    models::checkin::Checkin,
+    services::{
+        git::{CommitInfo, DataChange},
+        storage::{repo_path, CHECKINS_FILE},
+    },
+    state::AppState,
+};
+
+/// One committed state of a single check-in.
+pub struct CheckinVersion {
+    pub commit: CommitInfo,
+    pub checkin: Checkin,
+    pub json: String,
+    /// Line diff against the previous (older) version; empty for the first.
+    pub diff: Vec<DiffLine>,
+}
+
+pub struct DiffLine {
+    pub kind: DiffKind,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffKind {
+    Added,
+    Removed,
+    Unchanged,
+}
+
+/// All versions of `checkin_id` found in the git history of the user's
+/// check-in file, newest first. Commits that didn't change this particular
+/// check-in are collapsed.
+pub async fn checkin_versions(
+    state: &AppState,
+    user_uuid: &str,
+    checkin_id: &str,
+) -> Result<Vec<CheckinVersion>, AppError> {
+    let git = state.git.clone();
+    let path = repo_path(user_uuid, CHECKINS_FILE);
+    let history = tokio::task::spawn_blocking(move || git.file_history(&path))
+        .await
+        .map_err(|err| AppError::Other(err.into()))??;
+
+    // Walk oldest to newest so each version can be diffed against its
+    // predecessor.
+    let mut versions: Vec<CheckinVersion> = Vec::new();
+    for (commit, blob) in history.into_iter().rev() {
+        let checkin = match find_in_blob(state, user_uuid, checkin_id, blob).await {
+            Ok(Some(checkin)) => checkin,
+            Ok(None) => continue,
+            Err(AppError::Locked) => return Err(AppError::Locked),
+            Err(err) => {
+                warn!(commit = %commit.id, "skipping unreadable check-in version: {err:?}");
+                continue;
+            }
+        };
+        let json =
+            serde_json::to_string_pretty(&checkin).map_err(|err| AppError::Other(err.into()))?;
+        let previous = versions.last().map(|version| version.json.as_str());
+        if previous == Some(json.as_str()) {
+            continue;
+        }
+        let diff = previous
+            .map(|old| diff_lines(old, &json))
+            .unwrap_or_default();
+        versions.push(CheckinVersion {
+            commit,
+            checkin,
+            json,
+            diff,
+        });
+    }
+    versions.reverse();
+    Ok(versions)
+}
+
+/// Puts the check-in back to how it was in `commit`. Only check-ins still in
+/// the live file can be restored; a purged one is `NotFound`, so purging
+/// stays final even though git keeps the old versions. The check-in stays
+/// in or out of the trash as it is now.
+pub async fn restore_checkin(
+    state: &AppState,
+    user_uuid: &str,
+    checkin_id: &str,
+    commit: &str,
+) -> Result<Checkin, AppError> {
+    let git = state.git.clone();
+    let path = repo_path(user_uuid, CHECKINS_FILE);
+    let oid = commit.to_string();
+    let blob = tokio::task::spawn_blocking(move || git.file_at(&oid, &path))
+        .await
+        .map_err(|err| AppError::Other(err.into()))??
+        .ok_or(AppError::NotFound)?;
+    let checkin = find_in_blob(state, user_uuid, checkin_id, blob)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    let checkin = state.storage.replace_checkin(user_uuid, checkin).await?;
+    state.git.record(DataChange::CheckinRestored {
+        user_uuid: user_uuid.to_string(),
+        commit: commit.chars().take(10).collect(),
+    });
+    Ok(checkin)
+}
+
+async fn find_in_blob(
+    state: &AppState,
+    user_uuid: &str,
+    checkin_id: &str,
+    blob: Vec<u8>,
+) -> Result<Option<Checkin>, AppError> {
+    let plaintext = state
+        .storage
+        .open_user_bytes(user_uuid, CHECKINS_FILE, blob)
+        .await?;
+    if plaintext.is_empty() {
+        return Ok(None);
+    }
+    let checkins: Vec<Checkin> =
+        serde_json::from_slice(&plaintext).map_err(|err| AppError::Other(err.into()))?;
+    Ok(checkins.into_iter().find(|c| c.id == checkin_id))
+}
+
+fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    TextDiff::from_lines(old, new)
+        .iter_all_changes()
+        .map(|change| DiffLine {
+            kind: match change.tag() {
+                ChangeTag::Insert => DiffKind::Added,
+                ChangeTag::Delete => DiffKind::Removed,
+                ChangeTag::Equal => DiffKind::Unchanged,
+            },
+            text: change.value().trim_end_matches('\n').to_string(),
+        })
+        .collect()
+}
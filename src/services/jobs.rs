@@ -0,0 +1,347 @@
+//! Background jobs: reminders, the notification outbox, webhooks,
+//! summaries, the backup push, the session purge and the trash purge. Every
+//! job has a row in `jobs` with its next run. A run is claimed by moving
+//! that on and taking a lease in the same update, so each slot runs at most
+//! once, even with several instances on one database. The lease runs out
+//! after [`LEASE`], which frees jobs of an instance that died mid-run.
+
+use std::{fmt, future::Future, pin::Pin, sync::Arc, time::Duration as StdDuration};
+
+use chrono::{DateTime, Duration, Utc};
+use sqlx::{sqlite::SqliteRow, Row};
+use tokio::{
+    sync::watch,
+    task::{JoinHandle, JoinSet},
+    time::Instant,
+};
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::{
+    auth,
+    db::DbPool,
+    error::AppError,
+    models::job::{JobRecord, JobSchedule},
+    services::{journal, outbox, reminders, summary, webhooks},
+    state::AppState,
+};
+
+/// How often the runner looks for due jobs.
+const TICK: StdDuration = StdDuration::from_secs(5);
+/// How long a claimed run keeps other instances off the job.
+const LEASE: Duration = Duration::minutes(30);
+const MAX_ERROR_LEN: usize = 200;
+
+type JobFuture = Pin<Box<dyn Future<Output = Result<usize, AppError>> + Send>>;
+
+/// A named task and its schedule. The task returns how many things it
+/// handled, for the log.
+#[derive(Clone)]
+pub struct Job {
+    pub name: &'static str,
+    pub schedule: JobSchedule,
+    /// Whether shutdown stops a run instead of waiting for it.
+    cancel_on_shutdown: bool,
+    run: Arc<dyn Fn(AppState) -> JobFuture + Send + Sync>,
+}
+
+impl Job {
+    pub fn new<F, Fut>(name: &'static str, schedule: JobSchedule, run: F) -> Self
+    where
+        F: Fn(AppState) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<usize, AppError>> + Send + 'static,
+    {
+        Self {
+            name,
+            schedule,
+            cancel_on_shutdown: false,
+            run: Arc::new(move |state| Box::pin(run(state))),
+        }
+    }
+
+    /// Lets shutdown stop a run of this job. For jobs that may wait a long
+    /// time and are safe to retry on the next run.
+    pub fn cancel_on_shutdown(mut self) -> Self {
+        self.cancel_on_shutdown = true;
+        self
+    }
+}
+
+impl fmt::Debug for Job {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Job")
+            .field("name", &self.name)
+            .field("schedule", &self.schedule)
+            .finish_non_exhaustive()
+    }
+}
+
+fn schedule(expr: &str) -> JobSchedule {
+    JobSchedule::parse(expr).unwrap_or_else(|| panic!("invalid job schedule {expr}"))
+}
+
+/// The app's jobs. The backup push only exists with a backup remote.
+pub fn builtin(state: &AppState) -> Vec<Job> {
+    let mut jobs = vec![
+        Job::new("reminders", schedule("* * * * *"), |state| async move {
+            reminders::run_due(&state).await
+        }),
+        Job::new(
+            "notification-outbox",
+            schedule("@every 15s"),
+            |state| async move { outbox::deliver_due(&state).await },
+        ),
+        Job::new("webhooks", schedule("@every 15s"), |state| async move {
+            webhooks::deliver_due(&state).await
+        }),
+        Job::new("summaries", schedule("@every 1h"), |state| async move {
+            summary::deliver_due(&state).await
+        }),
+        Job::new(
+            "session-purge",
+            schedule("30 3 * * *"),
+            |state| async move { auth::purge_stale_sessions(&state).await },
+        ),
+        Job::new("trash-purge", schedule("45 3 * * *"), |state| async move {
+            journal::purge_expired_trash(&state).await
+        }),
+    ];
+    if let Some(config) = state.backup.config() {
+        // Retries back off for minutes; the next run pushes anyway.
+        jobs.push(
+            Job::new(
+                "backup-push",
+                JobSchedule::Every(config.push_interval),
+                |state| async move {
+                    state.backup.push_with_retry(&state.git).await?;
+                    Ok(1)
+                },
+            )
+            .cancel_on_shutdown(),
+        );
+    }
+    jobs
+}
+
+/// Runs registered jobs when they are due.
+#[derive(Clone)]
+pub struct JobRunner {
+    state: AppState,
+    jobs: Arc<[Job]>,
+    instance: String,
+    shutdown: watch::Receiver<bool>,
+}
+
+impl JobRunner {
+    pub fn new(state: AppState, jobs: Vec<Job>) -> Self {
+        let instance = format!("{}-{}", std::process::id(), Uuid::new_v4().simple());
+        // Never signalled unless `start` gets a real one.
+        let (_, shutdown) = watch::channel(false);
+        Self {
+            state,
+            jobs: jobs.into(),
+            instance,
+            shutdown,
+        }
+    }
+
+    /// Adds rows for new jobs and drops those of jobs that are gone. A job
+    /// whose schedule changed is rescheduled; otherwise its next run is
+    /// kept across restarts.
+    pub async fn register(&self) -> Result<(), AppError> {
+        let now = Utc::now();
+        for job in self.jobs.iter() {
+            sqlx::query(
+                r#"
+                INSERT INTO jobs (name, schedule, next_run_at)
+                VALUES (?1, ?2, ?3)
+                ON CONFLICT (name) DO UPDATE
+                SET schedule = excluded.schedule, next_run_at = excluded.next_run_at
+                WHERE jobs.schedule <> excluded.schedule
+                "#,
+            )
+            .bind(job.name)
+            .bind(job.schedule.to_string())
+            .bind(job.schedule.first_run(now))
+            .execute(&self.state.db)
+            .await?;
+        }
+
+        let names: Vec<String> = sqlx::query_scalar("SELECT name FROM jobs")
+            .fetch_all(&self.state.db)
+            .await?;
+        for name in names {
+            if !self.jobs.iter().any(|job| job.name == name) {
+                sqlx::query("DELETE FROM jobs WHERE name = ?1")
+                    .bind(&name)
+                    .execute(&self.state.db)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Spawns the runner. Once `shutdown` turns true it claims nothing new,
+    /// stops the runs of jobs marked [`Job::cancel_on_shutdown`], and the
+    /// returned handle finishes when the other running jobs have.
+    pub fn start(mut self, mut shutdown: watch::Receiver<bool>) -> JoinHandle<()> {
+        self.shutdown = shutdown.clone();
+        tokio::spawn(async move {
+            let mut running = JoinSet::new();
+            let mut ticker = tokio::time::interval(TICK);
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {}
+                    Some(_) = running.join_next(), if !running.is_empty() => continue,
+                    _ = shutdown.wait_for(|stop| *stop) => break,
+                }
+                match self.claim_due().await {
+                    Ok(jobs) => {
+                        for job in jobs {
+                            let runner = self.clone();
+                            running.spawn(async move { runner.run(&job).await });
+                        }
+                    }
+                    Err(err) => error!("job scheduler run failed: {err:?}"),
+                }
+            }
+            if !running.is_empty() {
+                info!(jobs = running.len(), "waiting for running jobs to finish");
+            }
+            while running.join_next().await.is_some() {}
+        })
+    }
+
+    /// Runs every due job one after the other. Returns how many ran.
+    pub async fn run_due(&self) -> Result<usize, AppError> {
+        let jobs = self.claim_due().await?;
+        for job in &jobs {
+            self.run(job).await;
+        }
+        Ok(jobs.len())
+    }
+
+    async fn claim_due(&self) -> Result<Vec<Job>, AppError> {
+        let now = Utc::now();
+        let mut claimed = Vec::new();
+        for job in self.jobs.iter() {
+            if self.claim(job, now).await? {
+                claimed.push(job.clone());
+            }
+        }
+        Ok(claimed)
+    }
+
+    /// Moves the job on to its next run and takes the lease, unless it
+    /// isn't due or another run holds it.
+    async fn claim(&self, job: &Job, now: DateTime<Utc>) -> Result<bool, AppError> {
+        let result = sqlx::query(
+            r#"
+            UPDATE jobs
+            SET next_run_at = ?1, locked_by = ?2, locked_until = ?3, last_started_at = ?4
+            WHERE name = ?5
+              AND next_run_at <= ?4
+              AND (locked_until IS NULL OR locked_until <= ?4)
+            "#,
+        )
+        .bind(job.schedule.next_after(now))
+        .bind(&self.instance)
+        .bind(now + LEASE)
+        .bind(now)
+        .bind(job.name)
+        .execute(&self.state.db)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Runs a claimed job in its own task, so a panic is recorded as its
+    /// error, and releases the lease.
+    async fn run(&self, job: &Job) {
+        let started = Instant::now();
+        let mut task = tokio::spawn((job.run)(self.state.clone()));
+        let mut shutdown = self.shutdown.clone();
+        let result = tokio::select! {
+            result = &mut task => result,
+            _ = stopped(&mut shutdown), if job.cancel_on_shutdown => {
+                task.abort();
+                task.await
+            }
+        };
+        let error = match result {
+            Ok(Ok(0)) => None,
+            Ok(Ok(handled)) => {
+                info!(job = job.name, handled, "job done");
+                None
+            }
+            Ok(Err(err)) => {
+                error!(job = job.name, "job failed: {err:?}");
+                Some(err.to_string())
+            }
+            Err(err) if err.is_cancelled() => {
+                info!(job = job.name, "job stopped for shutdown");
+                Some("stopped for shutdown".to_string())
+            }
+            Err(err) => {
+                error!(job = job.name, "job panicked: {err}");
+                Some(err.to_string())
+            }
+        };
+        let error: Option<String> = error.map(|err| err.chars().take(MAX_ERROR_LEN).collect());
+        let duration_ms = i64::try_from(started.elapsed().as_millis()).unwrap_or(i64::MAX);
+
+        let result = sqlx::query(
+            r#"
+            UPDATE jobs
+            SET locked_by = NULL, locked_until = NULL,
+                last_finished_at = ?1, last_duration_ms = ?2, last_error = ?3
+            WHERE name = ?4 AND locked_by = ?5
+            "#,
+        )
+        .bind(Utc::now())
+        .bind(duration_ms)
+        .bind(error)
+        .bind(job.name)
+        .bind(&self.instance)
+        .execute(&self.state.db)
+        .await;
+        if let Err(err) = result {
+            error!(job = job.name, "recording job run failed: {err:?}");
+        }
+    }
+}
+
+/// Resolves once `shutdown` turns true; never if its sender is gone.
+async fn stopped(shutdown: &mut watch::Receiver<bool>) {
+    if shutdown.wait_for(|stop| *stop).await.is_err() {
+        std::future::pending::<()>().await;
+    }
+}
+
+fn job_from_row(row: &SqliteRow) -> Result<JobRecord, AppError> {
+    Ok(JobRecord {
+        name: row.try_get("name")?,
+        schedule: row.try_get("schedule")?,
+        next_run_at: row.try_get("next_run_at")?,
+        locked_by: row.try_get("locked_by")?,
+        locked_until: row.try_get("locked_until")?,
+        last_started_at: row.try_get("last_started_at")?,
+        last_finished_at: row.try_get("last_finished_at")?,
+        last_duration_ms: row.try_get("last_duration_ms")?,
+        last_error: row.try_get("last_error")?,
+    })
+}
+
+/// Every registered job, by name.
+pub async fn list(db: &DbPool) -> Result<Vec<JobRecord>, AppError> {
+    let rows = sqlx::query(
+        r#"
+        SELECT name, schedule, next_run_at, locked_by, locked_until,
+               last_started_at, last_finished_at, last_duration_ms, last_error
+        FROM jobs
+        ORDER BY name
+        "#,
+    )
+    .fetch_all(db)
+    .await?;
+    rows.iter().map(job_from_row).collect()
+}
@@ -0,0 +1,489 @@
+//! Check-in, trip and panic operations shared by the HTML pages and the JSON
+//! API, so both validate input, alert contacts, queue webhooks and commit
+//! changes the same way.
+
+use chrono::{DateTime, Duration, Utc};
+use serde_json::json;
+use tracing::{debug, error};
+
+use crate::{
+    auth::AuthenticatedUser,
+    error::AppError,
+    i18n,
+    models::{
+        checkin::{Checkin, DrugEntry, PanicEvent},
+        notification::NotificationKind,
+        settings::{Contact, UserConfig},
+        trip::Trip,
+        webhook::WebhookEvent,
+    },
+    services::{
+        analytics,
+        git::{DataChange, TrashAction, TripAction},
+        notify::{self, Message},
+        outbox, reminders, webhooks,
+    },
+    state::AppState,
+};
+
+/// Allowed clock skew between client and server for "future" timestamps.
+const FUTURE_TOLERANCE_MINUTES: i64 = 5;
+/// Backdated check-ins older than this don't alert contacts anymore.
+const AUTO_NOTIFY_MAX_AGE_HOURS: i64 = 1;
+/// Minimum time between two trend alerts to the primary contact.
+const TREND_ALERT_COOLDOWN_HOURS: i64 = 72;
+
+/// The user-editable fields of a check-in.
+#[derive(Debug, Clone, Default)]
+pub struct CheckinInput {
+    pub mood: i32,
+    pub high_level: i32,
+    /// The explicit yes/no answer; `None` derives it from `safety_answer`.
+    pub feels_safe: Option<bool>,
+    pub safety_answer: Option<String>,
+    pub notes: Option<String>,
+    /// Substances taken; `None` keeps those already logged.
+    pub drugs: Option<Vec<DrugEntry>>,
+}
+
+/// Words that mark a free-text safety answer as "not safe", in every
+/// shipped language.
+const UNSAFE_WORDS: &[&str] = &["nein", "nicht", "unsicher", "no", "not", "unsafe"];
+
+impl CheckinInput {
+    /// Clamps the scales and sets `feels_safe`, from the safety answer when
+    /// no explicit one was given.
+    pub fn apply(self, checkin: &mut Checkin) {
+        if let Some(drugs) = self.drugs {
+            checkin.drugs = drugs;
+        }
+        checkin.mood = self.mood.clamp(-5, 5);
+        checkin.high_level = self.high_level.clamp(0, 10);
+        checkin.safety_answer = normalize_optional(self.safety_answer);
+        checkin.notes = normalize_optional(self.notes);
+        checkin.feels_safe = self.feels_safe.unwrap_or_else(|| {
+            !checkin
+                .safety_answer
+                .as_deref()
+                .is_some_and(answer_is_unsafe)
+        });
+    }
+}
+
+fn answer_is_unsafe(answer: &str) -> bool {
+    answer
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .any(|word| UNSAFE_WORDS.contains(&word))
+}
+
+/// Rejects check-in timestamps in the future.
+pub fn check_timestamp(timestamp: DateTime<Utc>) -> Result<DateTime<Utc>, AppError> {
+    if timestamp > Utc::now() + Duration::minutes(FUTURE_TOLERANCE_MINUTES) {
+        return Err(AppError::BadRequest(i18n::tr("error-checkin-in-future")));
+    }
+    Ok(timestamp)
+}
+
+/// Trims the substance entries and rejects ones without a substance.
+pub fn check_drugs(drugs: Vec<DrugEntry>) -> Result<Vec<DrugEntry>, AppError> {
+    drugs
+        .into_iter()
+        .map(|drug| {
+            let substance = drug.substance.trim().to_string();
+            if substance.is_empty() {
+                return Err(AppError::BadRequest(i18n::tr("error-substance-required")));
+            }
+            Ok(DrugEntry {
+                substance,
+                dose: drug.dose.trim().to_string(),
+                route: normalize_optional(drug.route),
+                start_time: drug.start_time,
+                notes: normalize_optional(drug.notes),
+            })
+        })
+        .collect()
+}
+
+/// Parses the substance field of the check-in forms: one
+/// `substance, dose, route` per line, dose and route optional.
+pub fn parse_drug_lines(input: &str) -> Result<Vec<DrugEntry>, AppError> {
+    let drugs = input
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let mut parts = line.splitn(3, ',').map(str::trim);
+            DrugEntry {
+                substance: parts.next().unwrap_or_default().to_string(),
+                dose: parts.next().unwrap_or_default().to_string(),
+                route: parts.next().map(str::to_string),
+                start_time: None,
+                notes: None,
+            }
+        })
+        .collect();
+    check_drugs(drugs)
+}
+
+/// The substances as [`parse_drug_lines`] reads them back.
+pub fn format_drug_lines(drugs: &[DrugEntry]) -> String {
+    drugs
+        .iter()
+        .map(|drug| {
+            let mut line = drug.substance.clone();
+            if !drug.dose.is_empty() || drug.route.is_some() {
+                line.push_str(", ");
+                line.push_str(&drug.dose);
+            }
+            if let Some(route) = &drug.route {
+                line.push_str(", ");
+                line.push_str(route);
+            }
+            line
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub fn normalize_optional(input: Option<String>) -> Option<String> {
+    input.and_then(|value| {
+        let trimmed = value.trim();
+        if trimmed.is_empty() {
+            None
+        } else {
+            Some(trimmed.to_string())
+        }
+    })
+}
+
+pub async fn find_live_checkin(
+    state: &AppState,
+    user_uuid: &str,
+    checkin_id: &str,
+) -> Result<Checkin, AppError> {
+    state
+        .storage
+        .load_user_checkins(user_uuid)
+        .await?
+        .into_iter()
+        .find(|c| c.id == checkin_id && !c.is_deleted())
+        .ok_or(AppError::NotFound)
+}
+
+/// Stores a new check-in, then queues the notifications it triggers in the
+/// outbox, so contacts are never alerted about a check-in that wasn't saved.
+pub async fn create_checkin(
+    state: &AppState,
+    user: &AuthenticatedUser,
+    mut checkin: Checkin,
+) -> Result<Checkin, AppError> {
+    let alerts = evaluate_auto_notifications(state, user, &mut checkin).await?;
+
+    let saved = state.storage.append_checkin(&user.uuid, checkin).await?;
+    let low_mood = alerts.low_mood;
+    alerts.queue(state, user, &saved.id).await;
+    state.git.record(DataChange::Checkin {
+        user_uuid: user.uuid.clone(),
+        mood: saved.mood,
+    });
+    // Notes and the safety answer stay out of webhook payloads.
+    let data = json!({
+        "id": saved.id,
+        "timestamp": saved.timestamp,
+        "mood": saved.mood,
+        "high_level": saved.high_level,
+        "feels_safe": saved.feels_safe,
+    });
+    if low_mood {
+        queue_webhooks(state, user, WebhookEvent::LowMood, data.clone()).await;
+    }
+    queue_webhooks(state, user, WebhookEvent::CheckinCreated, data).await;
+    Ok(saved)
+}
+
+/// Saves an edited check-in. Its `auto_notifications` are kept as recorded
+/// at creation, so edits never re-alert contacts.
+pub async fn update_checkin(
+    state: &AppState,
+    user: &AuthenticatedUser,
+    checkin: Checkin,
+) -> Result<Checkin, AppError> {
+    let checkin = state.storage.replace_checkin(&user.uuid, checkin).await?;
+    state.git.record(DataChange::CheckinEdited {
+        user_uuid: user.uuid.clone(),
+        mood: checkin.mood,
+    });
+    Ok(checkin)
+}
+
+/// Moves a check-in to the trash. Returns whether a live check-in was found.
+pub async fn trash_checkin(
+    state: &AppState,
+    user: &AuthenticatedUser,
+    checkin_id: &str,
+) -> Result<bool, AppError> {
+    let trashed = state.storage.trash_checkin(&user.uuid, checkin_id).await?;
+    if trashed {
+        state.git.record(DataChange::CheckinTrashed {
+            user_uuid: user.uuid.clone(),
+            action: TrashAction::Deleted,
+        });
+    }
+    Ok(trashed)
+}
+
+/// Deletes the trashed check-ins `purge` picks for good. Returns how many
+/// went.
+pub async fn purge_trash<F>(state: &AppState, user_uuid: &str, purge: F) -> Result<usize, AppError>
+where
+    F: Fn(&Checkin) -> bool,
+{
+    let purged = state.storage.purge_checkins(user_uuid, purge).await?;
+    if purged > 0 {
+        state.git.record(DataChange::CheckinTrashed {
+            user_uuid: user_uuid.to_string(),
+            action: TrashAction::Purged(purged),
+        });
+    }
+    Ok(purged)
+}
+
+/// Purges every user's check-ins that sat in the trash past the retention
+/// period. Returns how many went.
+pub async fn purge_expired_trash(state: &AppState) -> Result<usize, AppError> {
+    let user_uuids: Vec<String> = sqlx::query_scalar("SELECT uuid FROM users ORDER BY id")
+        .fetch_all(&state.db)
+        .await?;
+    let now = Utc::now();
+    let mut purged = 0;
+    for user_uuid in user_uuids {
+        match purge_trash(state, &user_uuid, |checkin| checkin.is_expired(now)).await {
+            Ok(count) => purged += count,
+            // Sealed data is purged on a later run, or when its trash is viewed.
+            Err(AppError::Locked) => debug!(%user_uuid, "data locked, trash purge postponed"),
+            Err(err) => error!(%user_uuid, "trash purge failed: {err:?}"),
+        }
+    }
+    Ok(purged)
+}
+
+/// The alerts a new check-in triggers, queued once it is saved.
+#[derive(Default)]
+struct CheckinAlerts {
+    /// Whether the check-in crossed the low-mood threshold.
+    low_mood: bool,
+    user_cfg: Option<UserConfig>,
+    messages: Vec<(NotificationKind, Vec<Contact>, Message)>,
+}
+
+impl CheckinAlerts {
+    /// As with panic events, a failed enqueue is logged and never undoes
+    /// the check-in.
+    async fn queue(self, state: &AppState, user: &AuthenticatedUser, checkin_id: &str) {
+        let Some(user_cfg) = self.user_cfg else {
+            return;
+        };
+        for (kind, contacts, message) in self.messages {
+            if let Err(err) = outbox::queue(
+                state, user, &user_cfg, kind, checkin_id, &contacts, &message,
+            )
+            .await
+            {
+                error!(user = %user.uuid, kind = kind.code(), "queueing alerts failed: {err:?}");
+            }
+        }
+    }
+}
+
+/// Decides which low-mood and trend notifications a new check-in triggers
+/// and marks them on it. Only called on creation, so edits never re-alert
+/// contacts, and skipped for check-ins logged well after the fact.
+async fn evaluate_auto_notifications(
+    state: &AppState,
+    user: &AuthenticatedUser,
+    checkin: &mut Checkin,
+) -> Result<CheckinAlerts, AppError> {
+    let mut alerts = CheckinAlerts::default();
+    if Utc::now() - checkin.timestamp > Duration::hours(AUTO_NOTIFY_MAX_AGE_HOURS) {
+        return Ok(alerts);
+    }
+    let user_cfg = state.load_user_config(&user.uuid, &user.username).await?;
+    let global_cfg = state.storage.load_global_config().await?;
+    let low_mood =
+        user_cfg.auto_notify_on_low_mood && checkin.mood <= user_cfg.auto_notify_threshold;
+    alerts.low_mood = low_mood;
+    let mut history = state.storage.load_user_checkins(&user.uuid).await?;
+    let recently = |triggered: fn(&Checkin) -> bool, window: Duration| {
+        history.iter().any(|earlier| {
+            triggered(earlier)
+                && earlier.timestamp <= checkin.timestamp
+                && checkin.timestamp - earlier.timestamp < window
+        })
+    };
+
+    // One alert per low phase: stay quiet while an earlier one is recent.
+    let low_mood_cooldown = Duration::hours(i64::from(user_cfg.low_mood_cooldown_hours));
+    if low_mood
+        && !recently(
+            |earlier| earlier.auto_notifications.mood_threshold_triggered,
+            low_mood_cooldown,
+        )
+    {
+        let contacts = user_cfg.alert_contacts();
+        let message = notify::low_mood_message(&user_cfg, &global_cfg, checkin);
+        checkin.auto_notifications.mood_threshold_triggered = !contacts.is_empty();
+        alerts
+            .messages
+            .push((NotificationKind::LowMood, contacts, message));
+    }
+
+    let trend_contact = user_cfg
+        .primary_contact
+        .clone()
+        .filter(|_| user_cfg.trend_notify_primary_contact);
+    // One alert per trend, likewise.
+    if let Some(contact) = trend_contact.filter(|_| {
+        !recently(
+            |earlier| earlier.auto_notifications.trend_triggered,
+            Duration::hours(TREND_ALERT_COOLDOWN_HOURS),
+        )
+    }) {
+        history.push(checkin.clone());
+        if let Some(warning) =
+            analytics::detect_trend(&history, Utc::now(), user_cfg.trend_sensitivity)
+        {
+            let message = notify::trend_message(&user_cfg, &global_cfg, &warning);
+            checkin.auto_notifications.trend_triggered = true;
+            alerts
+                .messages
+                .push((NotificationKind::Trend, vec![contact], message));
+        }
+    }
+    alerts.user_cfg = Some(user_cfg);
+    Ok(alerts)
+}
+
+/// Starts a trip. Only one trip can be active at a time.
+pub async fn start_trip(
+    state: &AppState,
+    user: &AuthenticatedUser,
+    title: &str,
+    notes: Option<String>,
+) -> Result<Trip, AppError> {
+    let _writing = state.storage.lock_user(&user.uuid).await;
+    let mut trips = state.storage.load_user_trips(&user.uuid).await?;
+    let title = normalize_optional(Some(title.to_string()))
+        .ok_or_else(|| AppError::BadRequest(i18n::tr("error-trip-title-required")))?;
+    if trips.iter().any(Trip::is_active) {
+        return Err(AppError::BadRequest(i18n::tr("error-trip-already-active")));
+    }
+
+    let mut trip = Trip::new(&user.uuid, title);
+    trip.notes = normalize_optional(notes);
+    trips.push(trip.clone());
+    state.storage.save_user_trips(&user.uuid, &trips).await?;
+    state.git.record(DataChange::Trip {
+        user_uuid: user.uuid.clone(),
+        action: TripAction::Started,
+    });
+    if let Err(err) = reminders::trip_started(state, user.id, &trip).await {
+        error!(user = %user.uuid, "scheduling trip reminders failed: {err:?}");
+    }
+    Ok(trip)
+}
+
+/// Ends a trip; ending one that already ended changes nothing.
+pub async fn end_trip(
+    state: &AppState,
+    user: &AuthenticatedUser,
+    trip_id: &str,
+) -> Result<Trip, AppError> {
+    let _writing = state.storage.lock_user(&user.uuid).await;
+    let mut trips = state.storage.load_user_trips(&user.uuid).await?;
+    let trip = trips
+        .iter_mut()
+        .find(|trip| trip.id == trip_id)
+        .ok_or(AppError::NotFound)?;
+    if !trip.is_active() {
+        return Ok(trip.clone());
+    }
+    trip.ended_at = Some(Utc::now());
+    let ended = trip.clone();
+    state.storage.save_user_trips(&user.uuid, &trips).await?;
+    state.git.record(DataChange::Trip {
+        user_uuid: user.uuid.clone(),
+        action: TripAction::Ended,
+    });
+    if let Err(err) = reminders::trip_ended(state, user.id).await {
+        error!(user = %user.uuid, "pausing trip reminders failed: {err:?}");
+    }
+    Ok(ended)
+}
+
+/// Alerts the user's contacts and records the panic event together with
+/// the latest mood.
+pub async fn trigger_panic(
+    state: &AppState,
+    user: &AuthenticatedUser,
+) -> Result<PanicEvent, AppError> {
+    let user_cfg = state.load_user_config(&user.uuid, &user.username).await?;
+    let global_cfg = state.storage.load_global_config().await?;
+    let latest = state
+        .storage
+        .load_user_checkins(&user.uuid)
+        .await?
+        .into_iter()
+        .filter(|checkin| !checkin.is_deleted())
+        .max_by_key(|checkin| checkin.timestamp);
+
+    let mut event = PanicEvent::new(&user.uuid);
+    event.mood_at_panic = latest.as_ref().map(|checkin| checkin.mood);
+    event.high_level_at_panic = latest.as_ref().map(|checkin| checkin.high_level);
+
+    // A failed notification must never stop the event from being recorded;
+    // `notified_contacts` lists the contacts reached right away, and the
+    // outbox keeps retrying the others.
+    let message = notify::panic_message(&user_cfg, &global_cfg, latest.as_ref());
+    event.notified_contacts = outbox::send(
+        state,
+        user,
+        &user_cfg,
+        NotificationKind::Panic,
+        &event.id,
+        &user_cfg.alert_contacts(),
+        &message,
+    )
+    .await
+    .iter()
+    .map(Contact::target)
+    .collect();
+
+    state
+        .storage
+        .append_panic_event(&user.uuid, event.clone())
+        .await?;
+    state.git.record(DataChange::Panic {
+        user_uuid: user.uuid.clone(),
+    });
+    let data = json!({
+        "id": event.id,
+        "timestamp": event.timestamp,
+        "mood_at_panic": event.mood_at_panic,
+        "high_level_at_panic": event.high_level_at_panic,
+    });
+    queue_webhooks(state, user, WebhookEvent::PanicTriggered, data).await;
+    Ok(event)
+}
+
+/// Like a failed notification, a failed enqueue is logged and never undoes
+/// the change that caused it.
+async fn queue_webhooks(
+    state: &AppState,
+    user: &AuthenticatedUser,
+    event: WebhookEvent,
+    data: serde_json::Value,
+) {
+    if let Err(err) = webhooks::enqueue(state, user, event, data).await {
+        error!(user = %user.uuid, event = event.code(), "queueing webhooks failed: {err:?}");
+    }
+}
@@ -1,55 +1,272 @@
-#![allow(dead_code)]
+//! Matrix delivery from the user's own account. Alerts carry health and
+//! drug details, so they are only sent end-to-end encrypted: each Matrix
+//! login gets its own crypto store under `MATRIX_STORE`, and messages go to
+//! an encrypted direct chat with the contact, which is created if there is
+//! none. Unencrypted rooms are only used when the user allowed them in the
+//! settings.
 
-use tracing::info;
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use matrix_sdk::{
+    config::SyncSettings,
+    matrix_auth::{MatrixSession, MatrixSessionTokens},
+    ruma::{
+        api::client::room::create_room,
+        events::{
+            room::{encryption::RoomEncryptionEventContent, message::RoomMessageEventContent},
+            InitialStateEvent,
+        },
+        OwnedDeviceId, OwnedUserId, UserId,
+    },
+    Client, Room, SessionMeta,
+};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
 
 use crate::{
     error::AppError,
-    models::{
-        checkin::Checkin,
-        settings::{GlobalConfig, UserConfig},
+    models::settings::{ChannelKind, UserConfig},
+    services::{
+        notify::{Message, NotificationChannel, Origin},
+        secrets::SecretStore,
     },
 };
 
-pub struct MatrixService;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
 
-impl MatrixService {
-    pub async fn send_low_mood_notification(
-        user_cfg: &UserConfig,
-        global_cfg: &GlobalConfig,
-        checkin: &Checkin,
-    ) -> Result<(), AppError> {
-        info!(
-            user = %user_cfg.username,
-            template = %global_cfg.low_mood_message_template,
-            mood = checkin.mood,
-            "matrix low mood notification would be sent"
-        );
-        Ok(())
+fn matrix_error(err: impl Into<anyhow::Error>) -> AppError {
+    AppError::Other(err.into())
+}
+
+/// Delivers to Matrix IDs as direct messages from the user's account.
+pub struct MatrixChannel {
+    secrets: SecretStore,
+    store_root: PathBuf,
+    http: reqwest::Client,
+    /// One client per access token, so sync state stays warm between alerts.
+    clients: Mutex<HashMap<String, Arc<UserClient>>>,
+}
+
+struct UserClient {
+    client: Client,
+    /// Sends one message at a time, so two alerts can't both create a room
+    /// for the same contact.
+    sending: tokio::sync::Mutex<()>,
+}
+
+#[derive(Deserialize)]
+struct WhoAmI {
+    user_id: OwnedUserId,
+    device_id: OwnedDeviceId,
+}
+
+impl MatrixChannel {
+    pub fn new(secrets: SecretStore, store_root: PathBuf) -> Self {
+        Self {
+            secrets,
+            store_root,
+            http: reqwest::Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .expect("HTTP client with default TLS settings"),
+            clients: Mutex::new(HashMap::new()),
+        }
     }
 
-    pub async fn send_panic_notification(
-        user_cfg: &UserConfig,
-        global_cfg: &GlobalConfig,
-        checkin: Option<&Checkin>,
-    ) -> Result<(), AppError> {
-        info!(
-            user = %user_cfg.username,
-            template = %global_cfg.panic_message_template,
-            mood = checkin.map(|c| c.mood),
-            "matrix panic notification would be sent"
-        );
-        Ok(())
+    async fn client_for(&self, user_cfg: &UserConfig) -> Result<Arc<UserClient>, AppError> {
+        let homeserver = user_cfg.homeserver_url.trim();
+        let secret_id = user_cfg.matrix_access_token_secret.as_deref();
+        let (homeserver, secret_id) = match (homeserver, secret_id) {
+            ("", _) | (_, None) => {
+                return Err(AppError::Config(format!(
+                    "{} has no Matrix homeserver and access token",
+                    user_cfg.username
+                )))
+            }
+            (homeserver, Some(secret_id)) => (homeserver, secret_id),
+        };
+        let access_token = self
+            .secrets
+            .get(secret_id)
+            .await?
+            .ok_or_else(|| AppError::Config("stored Matrix access token is gone".into()))?;
+
+        let cache_key = hex::encode(Sha256::digest(access_token.as_bytes()));
+        if let Some(client) = self.clients.lock().expect("client cache").get(&cache_key) {
+            return Ok(client.clone());
+        }
+
+        // The crypto store belongs to one device, so the session has to
+        // name the device the token was issued for.
+        let response = self
+            .http
+            .get(format!(
+                "{}/_matrix/client/v3/account/whoami",
+                homeserver.trim_end_matches('/')
+            ))
+            .bearer_auth(&access_token)
+            .send()
+            .await
+            .map_err(matrix_error)?;
+        if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+            return Err(AppError::Config(
+                "the homeserver rejected the Matrix access token".into(),
+            ));
+        }
+        let whoami: WhoAmI = response
+            .error_for_status()
+            .map_err(matrix_error)?
+            .json()
+            .await
+            .map_err(matrix_error)?;
+        let store_name = format!("{}-{}", whoami.user_id, whoami.device_id)
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect::<String>();
+        let passphrase = self
+            .secrets
+            .derive_passphrase(&format!("matrix-store:{store_name}"));
+        let client = Client::builder()
+            .homeserver_url(homeserver)
+            .sqlite_store(self.store_root.join(&store_name), Some(&passphrase))
+            .build()
+            .await
+            .map_err(matrix_error)?;
+        client
+            .restore_session(MatrixSession {
+                meta: SessionMeta {
+                    user_id: whoami.user_id,
+                    device_id: whoami.device_id,
+                },
+                tokens: MatrixSessionTokens {
+                    access_token,
+                    refresh_token: None,
+                },
+            })
+            .await
+            .map_err(matrix_error)?;
+        info!(user = %user_cfg.username, store = %store_name, "matrix client ready");
+
+        let client = Arc::new(UserClient {
+            client,
+            sending: tokio::sync::Mutex::new(()),
+        });
+        self.clients
+            .lock()
+            .expect("client cache")
+            .insert(cache_key, client.clone());
+        Ok(client)
+    }
+}
+
+/// Finds the direct chat with `target`, preferring an encrypted one, or
+/// creates a new encrypted one. An unencrypted chat is only returned when
+/// the user allowed it.
+async fn direct_room(
+    client: &Client,
+    target: &UserId,
+    allow_unencrypted: bool,
+) -> Result<Room, AppError> {
+    let mut unencrypted = None;
+    for room in client.joined_rooms() {
+        let targets = room.direct_targets();
+        if targets.len() != 1 || !targets.contains(target) {
+            continue;
+        }
+        if room.is_encrypted().await.map_err(matrix_error)? {
+            return Ok(room);
+        }
+        unencrypted.get_or_insert(room);
     }
+    match unencrypted {
+        Some(room) if allow_unencrypted => Ok(room),
+        _ => create_direct_room(client, target).await,
+    }
+}
 
-    pub async fn send_test_message(
-        user_cfg: &UserConfig,
-        _global_cfg: &GlobalConfig,
+/// Alerts to the user's own Matrix ID (summaries) go to a room of their
+/// own, marked as a direct chat with themselves so it is found again.
+async fn create_direct_room(client: &Client, target: &UserId) -> Result<Room, AppError> {
+    let own_room = client.user_id() == Some(target);
+    let mut request = create_room::v3::Request::new();
+    request.is_direct = true;
+    request.preset = Some(create_room::v3::RoomPreset::TrustedPrivateChat);
+    request.initial_state =
+        vec![
+            InitialStateEvent::new(RoomEncryptionEventContent::with_recommended_defaults())
+                .to_raw_any(),
+        ];
+    if !own_room {
+        request.invite = vec![target.to_owned()];
+    }
+    let room = client.create_room(request).await.map_err(matrix_error)?;
+    if own_room {
+        client
+            .account()
+            .mark_as_dm(room.room_id(), &[target.to_owned()])
+            .await
+            .map_err(matrix_error)?;
+    }
+    info!(room = %room.room_id(), to = %target, "created encrypted direct chat");
+    Ok(room)
+}
+
+#[async_trait]
+impl NotificationChannel for MatrixChannel {
+    fn kind(&self) -> ChannelKind {
+        ChannelKind::Matrix
+    }
+
+    async fn send(
+        &self,
+        origin: Origin<'_>,
+        address: &str,
+        message: &Message,
     ) -> Result<(), AppError> {
-        info!(
-            user = %user_cfg.username,
-            contact = %user_cfg.matrix_user_id,
-            "matrix test notification would be sent"
-        );
+        let Origin::User(user_cfg) = origin else {
+            return Err(AppError::Config(
+                "Matrix messages need a user's Matrix account".into(),
+            ));
+        };
+        let target = UserId::parse(address)
+            .map_err(|err| AppError::Config(format!("invalid Matrix ID {address}: {err}")))?;
+        let user_client = self.client_for(user_cfg).await?;
+        let _sending = user_client.sending.lock().await;
+        let client = &user_client.client;
+
+        // Brings room state and the contact's devices up to date; the first
+        // sync of a new store also uploads this device's keys.
+        client
+            .sync_once(SyncSettings::default().timeout(Duration::ZERO))
+            .await
+            .map_err(matrix_error)?;
+        let room = direct_room(client, &target, user_cfg.matrix_allow_unencrypted).await?;
+        if !room.is_encrypted().await.map_err(matrix_error)? {
+            if !user_cfg.matrix_allow_unencrypted {
+                return Err(AppError::Config(format!(
+                    "refusing to send to the unencrypted room {}",
+                    room.room_id()
+                )));
+            }
+            warn!(room = %room.room_id(), to = %target, "sending to an unencrypted room as allowed");
+        }
+        room.send(RoomMessageEventContent::text_plain(&message.body))
+            .await
+            .map_err(|err| matrix_error(anyhow!("sending to {target} failed: {err}")))?;
         Ok(())
     }
 }
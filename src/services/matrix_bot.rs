@@ -0,0 +1,560 @@
+//! Chat bot for users who live in their Matrix client. Linked Matrix
+//! accounts can log check-ins, start and end trips and raise the alarm with
+//! `!` commands; these go through [`journal`] like the web UI and the API.
+//!
+//! A Matrix account is linked by sending the bot a short-lived code from
+//! the settings page, so only someone logged in to the app can link one.
+
+use std::path::Path;
+
+use anyhow::anyhow;
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use chrono::{DateTime, Duration, Utc};
+use matrix_sdk::{
+    config::SyncSettings,
+    event_handler::Ctx,
+    matrix_auth::MatrixSession,
+    ruma::events::room::{
+        member::StrippedRoomMemberEvent,
+        message::{MessageType, OriginalSyncRoomMessageEvent, RoomMessageEventContent},
+    },
+    Client, Room, RoomState,
+};
+use sha2::{Digest, Sha256};
+use sqlx::Row;
+use tokio::{sync::watch, task::JoinHandle};
+use tracing::{error, info, warn};
+
+use crate::{
+    auth::{self, AuthenticatedUser},
+    config::MatrixBotConfig,
+    db::DbPool,
+    error::AppError,
+    i18n::{self, Lang},
+    models::checkin::Checkin,
+    services::{
+        journal::{self, CheckinInput},
+        outbox,
+    },
+    state::AppState,
+};
+
+/// Link codes avoid characters that are easy to mix up when typed on a
+/// phone.
+const CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const CODE_LENGTH: usize = 8;
+const CODE_VALID_MINUTES: i64 = 15;
+const SESSION_FILE: &str = "session";
+const SESSION_AAD: &[u8] = b"matrix-bot-session";
+
+fn hash_code(code: &str) -> String {
+    hex::encode(Sha256::digest(normalize_code(code).as_bytes()))
+}
+
+/// Codes are shown as `ABCD-EFGH` but accepted in any case and grouping.
+fn normalize_code(code: &str) -> String {
+    code.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+/// Creates a link code for the user, replacing any earlier one. The
+/// current link stays until the new code is used.
+pub async fn create_link_code(db: &DbPool, user_id: i64) -> Result<String, AppError> {
+    let mut bytes = [0u8; CODE_LENGTH];
+    OsRng.fill_bytes(&mut bytes);
+    let mut code: String = bytes
+        .iter()
+        .map(|byte| CODE_ALPHABET[usize::from(*byte) % CODE_ALPHABET.len()] as char)
+        .collect();
+    code.insert(CODE_LENGTH / 2, '-');
+
+    let now = Utc::now();
+    sqlx::query(
+        r#"
+        INSERT INTO matrix_links (user_id, code_hash, code_expires_at, created_at)
+        VALUES (?1, ?2, ?3, ?4)
+        ON CONFLICT(user_id) DO UPDATE
+            SET code_hash = excluded.code_hash, code_expires_at = excluded.code_expires_at
+        "#,
+    )
+    .bind(user_id)
+    .bind(hash_code(&code))
+    .bind(now + Duration::minutes(CODE_VALID_MINUTES))
+    .bind(now)
+    .execute(db)
+    .await?;
+    Ok(code)
+}
+
+/// Links `matrix_user_id` to the user the code belongs to and returns that
+/// user's ID, or `None` for unknown and expired codes. A Matrix account can
+/// only be linked to one user, so an earlier link of it is dropped.
+pub async fn redeem_link_code(
+    db: &DbPool,
+    code: &str,
+    matrix_user_id: &str,
+) -> Result<Option<i64>, AppError> {
+    let mut tx = db.begin().await?;
+    let user_id: Option<i64> = sqlx::query_scalar(
+        "SELECT user_id FROM matrix_links WHERE code_hash = ?1 AND code_expires_at > ?2",
+    )
+    .bind(hash_code(code))
+    .bind(Utc::now())
+    .fetch_optional(&mut *tx)
+    .await?;
+    let Some(user_id) = user_id else {
+        return Ok(None);
+    };
+    sqlx::query(
+        r#"
+        UPDATE matrix_links SET matrix_user_id = NULL, linked_at = NULL
+        WHERE matrix_user_id = ?1 AND user_id != ?2
+        "#,
+    )
+    .bind(matrix_user_id)
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query(
+        r#"
+        UPDATE matrix_links
+        SET matrix_user_id = ?1, linked_at = ?2, code_hash = NULL, code_expires_at = NULL
+        WHERE user_id = ?3
+        "#,
+    )
+    .bind(matrix_user_id)
+    .bind(Utc::now())
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(Some(user_id))
+}
+
+/// The Matrix account linked to the user and since when.
+pub async fn linked_account(
+    db: &DbPool,
+    user_id: i64,
+) -> Result<Option<(String, DateTime<Utc>)>, AppError> {
+    let row = sqlx::query(
+        r#"
+        SELECT matrix_user_id, linked_at FROM matrix_links
+        WHERE user_id = ?1 AND matrix_user_id IS NOT NULL
+        "#,
+    )
+    .bind(user_id)
+    .fetch_optional(db)
+    .await?;
+    row.map(|row| Ok((row.try_get("matrix_user_id")?, row.try_get("linked_at")?)))
+        .transpose()
+}
+
+/// Removes the user's link and any pending code. Returns whether there was
+/// anything to remove.
+pub async fn unlink(db: &DbPool, user_id: i64) -> Result<bool, AppError> {
+    let result = sqlx::query("DELETE FROM matrix_links WHERE user_id = ?1")
+        .bind(user_id)
+        .execute(db)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+async fn linked_user(
+    state: &AppState,
+    matrix_user_id: &str,
+) -> Result<Option<AuthenticatedUser>, AppError> {
+    let user_id: Option<i64> =
+        sqlx::query_scalar("SELECT user_id FROM matrix_links WHERE matrix_user_id = ?1")
+            .bind(matrix_user_id)
+            .fetch_optional(&state.db)
+            .await?;
+    match user_id {
+        Some(user_id) => auth::load_user(state, user_id).await,
+        None => Ok(None),
+    }
+}
+
+/// A chat message the bot reacts to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BotCommand {
+    /// `!mood -2 high 4 feeling off`
+    Mood {
+        mood: i32,
+        high_level: i32,
+        notes: Option<String>,
+    },
+    /// `!panic`
+    Panic,
+    /// `!trip start Festival`
+    TripStart(String),
+    /// `!trip end`
+    TripEnd,
+    /// `!link ABCD-EFGH`
+    Link(String),
+    /// `!unlink`
+    Unlink,
+    /// `!help`
+    Help,
+    /// A known command with missing or invalid arguments; holds the message
+    /// key of its usage text.
+    Usage(&'static str),
+    Unknown,
+}
+
+impl BotCommand {
+    /// Parses a message body; messages that don't start with `!` aren't
+    /// meant for the bot.
+    pub fn parse(body: &str) -> Option<Self> {
+        let mut words = body.trim().strip_prefix('!')?.split_whitespace();
+        let command = match words.next()?.to_lowercase().as_str() {
+            "mood" => Self::parse_mood(words),
+            "panic" => BotCommand::Panic,
+            "trip" => match words.next().map(str::to_lowercase).as_deref() {
+                Some("start") => {
+                    let title = words.collect::<Vec<_>>().join(" ");
+                    if title.is_empty() {
+                        BotCommand::Usage("bot-usage-trip")
+                    } else {
+                        BotCommand::TripStart(title)
+                    }
+                }
+                Some("end") => BotCommand::TripEnd,
+                _ => BotCommand::Usage("bot-usage-trip"),
+            },
+            "link" => {
+                let code: String = words.collect();
+                if code.is_empty() {
+                    BotCommand::Usage("bot-usage-link")
+                } else {
+                    BotCommand::Link(code)
+                }
+            }
+            "unlink" => BotCommand::Unlink,
+            "help" => BotCommand::Help,
+            _ => BotCommand::Unknown,
+        };
+        Some(command)
+    }
+
+    /// `<mood> [high <level>] [notes…]`
+    fn parse_mood<'a>(mut words: impl Iterator<Item = &'a str>) -> Self {
+        let usage = BotCommand::Usage("bot-usage-mood");
+        let Some(mood) = words.next().and_then(|word| word.parse().ok()) else {
+            return usage;
+        };
+        let mut words = words.peekable();
+        let mut high_level = 0;
+        if words
+            .next_if(|word| word.eq_ignore_ascii_case("high"))
+            .is_some()
+        {
+            match words.next().and_then(|word| word.parse().ok()) {
+                Some(level) => high_level = level,
+                None => return usage,
+            }
+        }
+        let notes = words.collect::<Vec<_>>().join(" ");
+        BotCommand::Mood {
+            mood,
+            high_level,
+            notes: (!notes.is_empty()).then_some(notes),
+        }
+    }
+}
+
+/// Handles a message from `sender` and returns the reply, or `None` if the
+/// message isn't a command. Replies are in the linked user's language.
+pub async fn handle_message(state: &AppState, sender: &str, body: &str) -> Option<String> {
+    let command = BotCommand::parse(body)?;
+    let user = match linked_user(state, sender).await {
+        Ok(user) => user,
+        Err(err) => {
+            error!(%sender, "looking up matrix link failed: {err:?}");
+            return Some(i18n::tr_in(Lang::default(), "bot-error", &[]));
+        }
+    };
+    let lang = match &user {
+        Some(user) => user_lang(state, user).await,
+        None => Lang::default(),
+    };
+    let reply = i18n::scope(lang, async {
+        execute(state, sender, user.as_ref(), command)
+            .await
+            .unwrap_or_else(|err| error_reply(sender, err))
+    })
+    .await;
+    Some(reply)
+}
+
+async fn user_lang(state: &AppState, user: &AuthenticatedUser) -> Lang {
+    state
+        .load_user_config(&user.uuid, &user.username)
+        .await
+        .ok()
+        .and_then(|config| config.lang())
+        .unwrap_or_default()
+}
+
+async fn execute(
+    state: &AppState,
+    sender: &str,
+    user: Option<&AuthenticatedUser>,
+    command: BotCommand,
+) -> Result<String, AppError> {
+    match (command, user) {
+        (BotCommand::Help, _) => Ok(i18n::tr("bot-help")),
+        (BotCommand::Unknown, _) => Ok(format!(
+            "{}\n\n{}",
+            i18n::tr("bot-unknown-command"),
+            i18n::tr("bot-help")
+        )),
+        (BotCommand::Usage(key), _) => Ok(i18n::tr(key)),
+        (BotCommand::Link(code), _) => link(state, sender, &code).await,
+        (_, None) => Ok(i18n::tr("bot-not-linked")),
+        (
+            BotCommand::Mood {
+                mood,
+                high_level,
+                notes,
+            },
+            Some(user),
+        ) => {
+            let input = CheckinInput {
+                mood,
+                high_level,
+                feels_safe: None,
+                safety_answer: None,
+                notes,
+                drugs: None,
+            };
+            log_mood(state, user, input).await
+        }
+        (BotCommand::Panic, Some(user)) => panic(state, user).await,
+        (BotCommand::TripStart(title), Some(user)) => {
+            let trip = journal::start_trip(state, user, &title, None).await?;
+            Ok(i18n::tr_args(
+                "bot-trip-started",
+                &[("title", trip.title.into())],
+            ))
+        }
+        (BotCommand::TripEnd, Some(user)) => end_trip(state, user).await,
+        (BotCommand::Unlink, Some(user)) => {
+            unlink(&state.db, user.id).await?;
+            Ok(i18n::tr("bot-unlinked"))
+        }
+    }
+}
+
+async fn log_mood(
+    state: &AppState,
+    user: &AuthenticatedUser,
+    input: CheckinInput,
+) -> Result<String, AppError> {
+    let mut checkin = Checkin::new(&user.uuid);
+    input.apply(&mut checkin);
+    let saved = journal::create_checkin(state, user, checkin).await?;
+    let mut reply = i18n::tr_args(
+        "bot-checkin-saved",
+        &[
+            ("mood", saved.mood.into()),
+            ("high", saved.high_level.into()),
+        ],
+    );
+    if saved.auto_notifications.mood_threshold_triggered {
+        reply.push(' ');
+        reply.push_str(&i18n::tr("bot-checkin-contacts-alerted"));
+    }
+    Ok(reply)
+}
+
+/// Answers with how many contacts were reached, like the panic page.
+async fn panic(state: &AppState, user: &AuthenticatedUser) -> Result<String, AppError> {
+    let event = journal::trigger_panic(state, user).await?;
+    let total = outbox::entries_for(state, user.id, &event.id).await?.len();
+    if total == 0 {
+        return Ok(i18n::tr("panic-sent-nobody"));
+    }
+    let sent = event.notified_contacts.len();
+    let mut reply = format!(
+        "{} {}.",
+        i18n::tr("bot-panic-sent"),
+        i18n::tr_args(
+            "notify-delivered-count",
+            &[("sent", sent.into()), ("total", total.into())],
+        )
+    );
+    if sent == 0 {
+        reply.push(' ');
+        reply.push_str(&i18n::tr("panic-sent-retrying"));
+    }
+    Ok(reply)
+}
+
+async fn end_trip(state: &AppState, user: &AuthenticatedUser) -> Result<String, AppError> {
+    let active = state
+        .storage
+        .load_user_trips(&user.uuid)
+        .await?
+        .into_iter()
+        .find(|trip| trip.is_active());
+    let Some(active) = active else {
+        return Ok(i18n::tr("bot-trip-none-active"));
+    };
+    let trip = journal::end_trip(state, user, &active.id).await?;
+    Ok(i18n::tr_args(
+        "bot-trip-ended",
+        &[("title", trip.title.into())],
+    ))
+}
+
+/// Replies in the language of the user the code belongs to, which may not
+/// be the one the sender was linked to before.
+async fn link(state: &AppState, sender: &str, code: &str) -> Result<String, AppError> {
+    let Some(user_id) = redeem_link_code(&state.db, code, sender).await? else {
+        return Ok(i18n::tr("bot-link-invalid"));
+    };
+    let user = auth::load_user(state, user_id)
+        .await?
+        .ok_or_else(|| AppError::Other(anyhow!("linked user {user_id} vanished")))?;
+    info!(user = %user.uuid, matrix_user = %sender, "matrix account linked");
+    Ok(i18n::tr_in(
+        user_lang(state, &user).await,
+        "bot-linked",
+        &[("username", user.username.into())],
+    ))
+}
+
+/// Validation errors are already worded for the user; anything else gets a
+/// generic reply and is logged.
+fn error_reply(sender: &str, err: AppError) -> String {
+    match err {
+        AppError::BadRequest(message) => message,
+        AppError::Locked => i18n::tr("bot-locked"),
+        err => {
+            error!(%sender, "matrix bot command failed: {err:?}");
+            i18n::tr("bot-error")
+        }
+    }
+}
+
+/// Logs the bot in and spawns its sync loop, which ends once `shutdown`
+/// turns true. Failures are logged; the app keeps running without the bot.
+pub fn start(
+    state: AppState,
+    config: MatrixBotConfig,
+    mut shutdown: watch::Receiver<bool>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        tokio::select! {
+            result = run(state, &config) => {
+                if let Err(err) = result {
+                    error!(bot = %config.user_id, "matrix bot stopped: {err:?}");
+                }
+            }
+            _ = shutdown.wait_for(|stop| *stop) => info!(bot = %config.user_id, "matrix bot stopped"),
+        }
+    })
+}
+
+fn matrix_error(err: impl Into<anyhow::Error>) -> AppError {
+    AppError::Other(err.into())
+}
+
+async fn run(state: AppState, config: &MatrixBotConfig) -> Result<(), AppError> {
+    tokio::fs::create_dir_all(&config.store_path).await?;
+    let client = Client::builder()
+        .homeserver_url(&config.homeserver_url)
+        .sqlite_store(&config.store_path, None)
+        .build()
+        .await
+        .map_err(matrix_error)?;
+    login(&state, &client, config).await?;
+
+    client.add_event_handler_context(state);
+    client.add_event_handler(on_invite);
+    // Commands sent while the bot was offline are skipped: an old `!mood`
+    // would be logged with the wrong time, and an old `!panic` may be over.
+    let response = client
+        .sync_once(SyncSettings::default())
+        .await
+        .map_err(matrix_error)?;
+    client.add_event_handler(on_message);
+    info!(bot = %config.user_id, "matrix bot running");
+    client
+        .sync(SyncSettings::default().token(response.next_batch))
+        .await
+        .map_err(matrix_error)
+}
+
+/// Restores the saved session so the bot keeps its device across restarts,
+/// or logs in with the password and saves the new session sealed under the
+/// server key.
+async fn login(
+    state: &AppState,
+    client: &Client,
+    config: &MatrixBotConfig,
+) -> Result<(), AppError> {
+    let session_path = config.store_path.join(SESSION_FILE);
+    if let Some(session) = load_session(state, &session_path).await? {
+        return client.restore_session(session).await.map_err(matrix_error);
+    }
+    client
+        .matrix_auth()
+        .login_username(&config.user_id, &config.password)
+        .initial_device_display_name("Mood Tracker")
+        .send()
+        .await
+        .map_err(matrix_error)?;
+    let session = client
+        .matrix_auth()
+        .session()
+        .ok_or_else(|| AppError::Other(anyhow!("no session after login")))?;
+    let json = serde_json::to_vec(&session).map_err(matrix_error)?;
+    tokio::fs::write(&session_path, state.secrets.seal(SESSION_AAD, &json)?).await?;
+    Ok(())
+}
+
+async fn load_session(state: &AppState, path: &Path) -> Result<Option<MatrixSession>, AppError> {
+    let sealed = match tokio::fs::read(path).await {
+        Ok(sealed) => sealed,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    let json = state.secrets.open(SESSION_AAD, &sealed)?;
+    serde_json::from_slice(&json)
+        .map(Some)
+        .map_err(matrix_error)
+}
+
+/// Joins every room the bot is invited to; commands are only answered for
+/// linked senders anyway.
+async fn on_invite(event: StrippedRoomMemberEvent, room: Room, client: Client) {
+    if client.user_id() != Some(&*event.state_key) {
+        return;
+    }
+    if let Err(err) = room.join().await {
+        warn!(room = %room.room_id(), "joining room failed: {err:?}");
+    }
+}
+
+async fn on_message(
+    event: OriginalSyncRoomMessageEvent,
+    room: Room,
+    client: Client,
+    Ctx(state): Ctx<AppState>,
+) {
+    if room.state() != RoomState::Joined || client.user_id() == Some(&*event.sender) {
+        return;
+    }
+    let MessageType::Text(text) = event.content.msgtype else {
+        return;
+    };
+    let Some(reply) = handle_message(&state, event.sender.as_str(), &text.body).await else {
+        return;
+    };
+    if let Err(err) = room.send(RoomMessageEventContent::text_plain(reply)).await {
+        warn!(room = %room.room_id(), "sending bot reply failed: {err:?}");
+    }
+}
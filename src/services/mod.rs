@@ -1,3 +1,21 @@
+pub mod analytics;
+pub mod backup;
+pub mod crypto;
+pub mod email;
 pub mod git;
+pub mod history;
+pub mod jobs;
+pub mod journal;
 pub mod matrix;
+pub mod matrix_bot;
+pub mod notify;
+pub mod outbox;
+pub mod push;
+pub mod reminders;
+pub mod secrets;
+pub mod settings;
 pub mod storage;
+pub mod summary;
+pub mod system;
+pub mod tokens;
+pub mod webhooks;
@@ -0,0 +1,246 @@
+//! Notifications over pluggable channels. Each contact names its channel
+//! (Matrix, email, ntfy/Gotify push or a plain webhook), so one panic can
+//! fan out to different channels per contact. [`Notifier`] holds one
+//! [`NotificationChannel`] per [`ChannelKind`].
+
+use std::{collections::HashMap, fmt, sync::Arc};
+
+use async_trait::async_trait;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tracing::{error, warn};
+
+use crate::{
+    config::AppConfig,
+    error::AppError,
+    i18n::{self, Lang},
+    models::{
+        checkin::Checkin,
+        settings::{ChannelKind, Contact, GlobalConfig, UserConfig},
+    },
+    services::{
+        analytics::TrendWarning,
+        email::EmailChannel,
+        matrix::MatrixChannel,
+        push::{PushChannel, WebhookChannel},
+        secrets::SecretStore,
+    },
+    timezone,
+};
+
+/// A rendered notification. Channels without a subject line (Matrix) only
+/// send the body.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Message {
+    pub title: String,
+    pub body: String,
+    /// Panic alerts; push channels raise the priority for these.
+    pub urgent: bool,
+}
+
+/// On whose behalf a message goes out.
+#[derive(Debug, Clone, Copy)]
+pub enum Origin<'a> {
+    User(&'a UserConfig),
+    /// Operational alerts for the admins.
+    System,
+}
+
+impl fmt::Display for Origin<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Origin::User(user_cfg) => f.write_str(&user_cfg.username),
+            Origin::System => f.write_str("system"),
+        }
+    }
+}
+
+#[async_trait]
+pub trait NotificationChannel: Send + Sync {
+    fn kind(&self) -> ChannelKind;
+
+    async fn send(
+        &self,
+        origin: Origin<'_>,
+        address: &str,
+        message: &Message,
+    ) -> Result<(), AppError>;
+}
+
+#[derive(Clone)]
+pub struct Notifier {
+    channels: HashMap<ChannelKind, Arc<dyn NotificationChannel>>,
+}
+
+impl Notifier {
+    /// Registers every channel the configuration allows. Email needs
+    /// `SMTP_HOST`; the others always work. Matrix reads the user's access
+    /// token from `secrets`.
+    pub fn new(config: &AppConfig, secrets: &SecretStore) -> Self {
+        let mut notifier = Self {
+            channels: HashMap::new(),
+        };
+        notifier.register(Arc::new(MatrixChannel::new(
+            secrets.clone(),
+            config.matrix_store.clone(),
+        )));
+        notifier.register(Arc::new(PushChannel::new()));
+        notifier.register(Arc::new(WebhookChannel::new()));
+        if let Some(smtp) = &config.smtp {
+            match EmailChannel::new(smtp) {
+                Ok(channel) => notifier.register(Arc::new(channel)),
+                Err(err) => error!("email channel disabled: {err:?}"),
+            }
+        }
+        notifier
+    }
+
+    /// Adds a channel, replacing any earlier one of the same kind.
+    pub fn register(&mut self, channel: Arc<dyn NotificationChannel>) {
+        self.channels.insert(channel.kind(), channel);
+    }
+
+    pub async fn send(
+        &self,
+        origin: Origin<'_>,
+        contact: &Contact,
+        message: &Message,
+    ) -> Result<(), AppError> {
+        let channel = self.channels.get(&contact.channel).ok_or_else(|| {
+            AppError::Config(format!("no {} channel configured", contact.channel.code()))
+        })?;
+        channel.send(origin, &contact.address, message).await
+    }
+
+    /// Sends `message` to every contact and returns those that got it. A
+    /// failing contact is logged and never stops the others.
+    pub async fn fan_out(
+        &self,
+        origin: Origin<'_>,
+        contacts: &[Contact],
+        message: &Message,
+    ) -> Vec<Contact> {
+        let mut notified = Vec::new();
+        for contact in contacts {
+            match self.send(origin, contact, message).await {
+                Ok(()) => notified.push(contact.clone()),
+                Err(err) => warn!(from = %origin, %contact, "notification failed: {err:?}"),
+            }
+        }
+        notified
+    }
+}
+
+/// Parses a contact from a form or API field.
+pub fn parse_contact(input: &str) -> Result<Contact, AppError> {
+    Contact::parse(input).ok_or_else(|| {
+        AppError::BadRequest(i18n::tr_args(
+            "error-contact-invalid",
+            &[("contact", input.trim().to_string().into())],
+        ))
+    })
+}
+
+/// Like [`parse_contact`], but blank input means "no contact".
+pub fn parse_optional_contact(input: Option<&str>) -> Result<Option<Contact>, AppError> {
+    match input.map(str::trim).filter(|input| !input.is_empty()) {
+        Some(input) => parse_contact(input).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Parses one contact per entry, skipping blank ones.
+pub fn parse_contacts<'a>(
+    inputs: impl IntoIterator<Item = &'a str>,
+) -> Result<Vec<Contact>, AppError> {
+    inputs
+        .into_iter()
+        .filter_map(|input| parse_optional_contact(Some(input)).transpose())
+        .collect()
+}
+
+pub fn low_mood_message(
+    user_cfg: &UserConfig,
+    global_cfg: &GlobalConfig,
+    checkin: &Checkin,
+) -> Message {
+    let lang = notification_lang(user_cfg);
+    Message {
+        title: title(lang, "notify-title-low-mood", user_cfg),
+        body: render_template(
+            global_cfg.low_mood_message_template.get(lang),
+            user_cfg,
+            Some(checkin),
+        ),
+        urgent: false,
+    }
+}
+
+pub fn panic_message(
+    user_cfg: &UserConfig,
+    global_cfg: &GlobalConfig,
+    checkin: Option<&Checkin>,
+) -> Message {
+    let lang = notification_lang(user_cfg);
+    Message {
+        title: title(lang, "notify-title-panic", user_cfg),
+        body: render_template(
+            global_cfg.panic_message_template.get(lang),
+            user_cfg,
+            checkin,
+        ),
+        urgent: true,
+    }
+}
+
+/// Goes to the primary contact only; the trend is a nudge, not an alarm.
+pub fn trend_message(
+    user_cfg: &UserConfig,
+    global_cfg: &GlobalConfig,
+    warning: &TrendWarning,
+) -> Message {
+    let lang = notification_lang(user_cfg);
+    Message {
+        title: title(lang, "notify-title-trend", user_cfg),
+        body: render_template(global_cfg.trend_message_template.get(lang), user_cfg, None)
+            .replace("{baseline}", &format!("{:.1}", warning.baseline))
+            .replace("{recent}", &format!("{:.1}", warning.recent)),
+        urgent: false,
+    }
+}
+
+fn title(lang: Lang, key: &str, user_cfg: &UserConfig) -> String {
+    i18n::tr_in(lang, key, &[("name", user_cfg.display_name.clone().into())])
+}
+
+/// Notifications go out in the user's chosen language, else the language of
+/// the request that triggered them.
+fn notification_lang(user_cfg: &UserConfig) -> Lang {
+    user_cfg.lang().unwrap_or_else(i18n::current)
+}
+
+/// Fills the `{username}`, `{display_name}`, `{mood}`, `{high_level}` and
+/// `{timestamp}` placeholders. Times are shown in the user's timezone;
+/// without a check-in, `{timestamp}` is the current time.
+pub(crate) fn render_template(
+    template: &str,
+    user_cfg: &UserConfig,
+    checkin: Option<&Checkin>,
+) -> String {
+    let unknown = || "?".to_string();
+    let timestamp = checkin.map(|c| c.timestamp).unwrap_or_else(Utc::now);
+    template
+        .replace("{username}", &user_cfg.username)
+        .replace("{display_name}", &user_cfg.display_name)
+        .replace(
+            "{mood}",
+            &checkin.map(|c| c.mood.to_string()).unwrap_or_else(unknown),
+        )
+        .replace(
+            "{high_level}",
+            &checkin
+                .map(|c| c.high_level.to_string())
+                .unwrap_or_else(unknown),
+        )
+        .replace("{timestamp}", &timezone::format(timestamp, user_cfg.tz()))
+}
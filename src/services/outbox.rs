@@ -0,0 +1,435 @@
+//! Outbox for alerts to contacts. Every notification is written to
+//! `notification_outbox`, one row per contact, before it is sent, so a
+//! failed send is retried by a background job instead of being lost, and
+//! alerts to contacts in their quiet hours wait until these end. The
+//! contact and message are sealed under the server key; the rows back the
+//! "delivered to 2 of 3 contacts" status on the panic and check-in pages.
+
+use std::collections::{hash_map::Entry, HashMap};
+
+use anyhow::anyhow;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+use tracing::{error, warn};
+
+use crate::{
+    auth::AuthenticatedUser,
+    error::AppError,
+    models::{
+        notification::{NotificationKind, OutboxEntry},
+        settings::{Contact, UserConfig},
+        webhook::DeliveryStatus,
+    },
+    services::notify::{Message, Origin},
+    state::AppState,
+};
+
+const BATCH_SIZE: i64 = 50;
+const MAX_ATTEMPTS: i64 = 10;
+/// Alerts are time-critical, so retries start sooner than for webhooks.
+const INITIAL_BACKOFF_SECS: i64 = 30;
+const MAX_BACKOFF_SECS: i64 = 60 * 60;
+const MAX_ERROR_LEN: usize = 200;
+/// How long a send may take before its entry is due again. Entries being
+/// sent are kept out of [`deliver_due`] by moving their next attempt this
+/// far ahead.
+const SEND_LEASE: Duration = Duration::minutes(5);
+
+#[derive(Serialize, Deserialize)]
+struct Payload {
+    contact: Contact,
+    message: Message,
+}
+
+/// Payloads are bound to their user and source, so a row can't be moved to
+/// another alert.
+fn payload_aad(user_id: i64, source_id: &str) -> String {
+    format!("outbox:{user_id}:{source_id}")
+}
+
+/// Queues `message` for every contact, then sends it right away unless the
+/// contact is in quiet hours. Returns the contacts that got it; the others
+/// are retried in the background.
+pub async fn send(
+    state: &AppState,
+    user: &AuthenticatedUser,
+    user_cfg: &UserConfig,
+    kind: NotificationKind,
+    source_id: &str,
+    contacts: &[Contact],
+    message: &Message,
+) -> Vec<Contact> {
+    let origin = Origin::User(user_cfg);
+    let now = Utc::now();
+    let deferrals: Vec<_> = contacts
+        .iter()
+        .map(|contact| deferral_end(user_cfg, contact, message, now))
+        .collect();
+    // Entries sent right away are leased, so the retry job leaves them alone.
+    let first_attempts: Vec<_> = deferrals
+        .iter()
+        .map(|deferred| deferred.unwrap_or(now + SEND_LEASE))
+        .collect();
+    let ids = match enqueue(
+        state,
+        user,
+        kind,
+        source_id,
+        contacts,
+        &first_attempts,
+        message,
+    )
+    .await
+    {
+        Ok(ids) => ids,
+        // Without the outbox the message is still sent once.
+        Err(err) => {
+            error!(user = %user.uuid, "notification outbox unavailable: {err:?}");
+            return state.notifier.fan_out(origin, contacts, message).await;
+        }
+    };
+
+    let mut notified = Vec::new();
+    for ((id, contact), deferred) in ids.into_iter().zip(contacts).zip(deferrals) {
+        if deferred.is_some() {
+            continue;
+        }
+        let outcome = state.notifier.send(origin, contact, message).await;
+        if outcome.is_ok() {
+            notified.push(contact.clone());
+        }
+        if let Err(err) = record_attempt(state, id, 1, outcome).await {
+            error!(entry = id, "recording notification attempt failed: {err:?}");
+        }
+    }
+    notified
+}
+
+/// Queues `message` for every contact without sending it; the outbox job
+/// sends it on its next run, or once the contact's quiet hours end.
+pub async fn queue(
+    state: &AppState,
+    user: &AuthenticatedUser,
+    user_cfg: &UserConfig,
+    kind: NotificationKind,
+    source_id: &str,
+    contacts: &[Contact],
+    message: &Message,
+) -> Result<(), AppError> {
+    let now = Utc::now();
+    let first_attempts: Vec<_> = contacts
+        .iter()
+        .map(|contact| deferral_end(user_cfg, contact, message, now).unwrap_or(now))
+        .collect();
+    enqueue(
+        state,
+        user,
+        kind,
+        source_id,
+        contacts,
+        &first_attempts,
+        message,
+    )
+    .await?;
+    Ok(())
+}
+
+/// When the contact's quiet hours end, if `now` falls into them. Panic
+/// alerts are never held back.
+fn deferral_end(
+    user_cfg: &UserConfig,
+    contact: &Contact,
+    message: &Message,
+    now: DateTime<Utc>,
+) -> Option<DateTime<Utc>> {
+    if message.urgent {
+        return None;
+    }
+    contact.quiet_hours?.deferral_end(now, user_cfg.tz())
+}
+
+async fn enqueue(
+    state: &AppState,
+    user: &AuthenticatedUser,
+    kind: NotificationKind,
+    source_id: &str,
+    contacts: &[Contact],
+    first_attempts: &[DateTime<Utc>],
+    message: &Message,
+) -> Result<Vec<i64>, AppError> {
+    let now = Utc::now();
+    let aad = payload_aad(user.id, source_id);
+    let mut tx = state.db.begin().await?;
+    let mut ids = Vec::with_capacity(contacts.len());
+    for (contact, first_attempt) in contacts.iter().zip(first_attempts) {
+        let payload = serde_json::to_vec(&Payload {
+            contact: contact.clone(),
+            message: message.clone(),
+        })
+        .map_err(|err| AppError::Other(err.into()))?;
+        let id = sqlx::query(
+            r#"
+            INSERT INTO notification_outbox
+                (user_id, kind, source_id, channel, payload, status, next_attempt_at, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+            "#,
+        )
+        .bind(user.id)
+        .bind(kind.code())
+        .bind(source_id)
+        .bind(contact.channel.code())
+        .bind(state.secrets.seal(aad.as_bytes(), &payload)?)
+        .bind(DeliveryStatus::Pending.code())
+        .bind(first_attempt)
+        .bind(now)
+        .execute(&mut *tx)
+        .await?
+        .last_insert_rowid();
+        ids.push(id);
+    }
+    tx.commit().await?;
+    Ok(ids)
+}
+
+/// The alerts sent about one check-in or panic event, oldest first.
+pub async fn entries_for(
+    state: &AppState,
+    user_id: i64,
+    source_id: &str,
+) -> Result<Vec<OutboxEntry>, AppError> {
+    let rows = sqlx::query(
+        r#"
+        SELECT id, kind, source_id, payload, status, attempts, next_attempt_at,
+               last_error, created_at, sent_at
+        FROM notification_outbox
+        WHERE user_id = ?1 AND source_id = ?2
+        ORDER BY id
+        "#,
+    )
+    .bind(user_id)
+    .bind(source_id)
+    .fetch_all(&state.db)
+    .await?;
+    let aad = payload_aad(user_id, source_id);
+    let mut entries = Vec::with_capacity(rows.len());
+    for row in &rows {
+        let id: i64 = row.try_get("id")?;
+        // Like in [`deliver_due`], an unreadable entry is left out instead
+        // of failing the whole page.
+        let payload = match open_payload(state, &aad, &row.try_get::<Vec<u8>, _>("payload")?) {
+            Ok(payload) => payload,
+            Err(err) => {
+                warn!(entry = id, "unreadable notification left out: {err:?}");
+                continue;
+            }
+        };
+        let kind: String = row.try_get("kind")?;
+        let status: String = row.try_get("status")?;
+        entries.push(OutboxEntry {
+            id,
+            kind: NotificationKind::from_code(&kind)
+                .ok_or_else(|| AppError::Other(anyhow!("unknown notification kind {kind}")))?,
+            source_id: row.try_get("source_id")?,
+            contact: payload.contact,
+            status: DeliveryStatus::from_code(&status)
+                .ok_or_else(|| AppError::Other(anyhow!("unknown delivery status {status}")))?,
+            attempts: row.try_get("attempts")?,
+            next_attempt_at: row.try_get("next_attempt_at")?,
+            last_error: row.try_get("last_error")?,
+            created_at: row.try_get("created_at")?,
+            sent_at: row.try_get("sent_at")?,
+        });
+    }
+    Ok(entries)
+}
+
+fn open_payload(state: &AppState, aad: &str, sealed: &[u8]) -> Result<Payload, AppError> {
+    let plaintext = state.secrets.open(aad.as_bytes(), sealed)?;
+    serde_json::from_slice(&plaintext).map_err(|err| AppError::Other(err.into()))
+}
+
+/// Sends every pending entry whose next attempt is due. The entries are
+/// claimed first by leasing them, so an entry is never sent twice at once.
+/// Sending on the user's behalf needs their config, so entries of a user
+/// whose data key is locked wait until it is unlocked. Returns how many
+/// entries were sent.
+pub async fn deliver_due(state: &AppState) -> Result<usize, AppError> {
+    let now = Utc::now();
+    let mut rows = sqlx::query(
+        r#"
+        UPDATE notification_outbox
+        SET next_attempt_at = ?1
+        WHERE id IN (
+            SELECT id
+            FROM notification_outbox
+            WHERE status = ?2 AND next_attempt_at <= ?3
+            ORDER BY id
+            LIMIT ?4
+        )
+        RETURNING id, user_id, source_id, payload, attempts
+        "#,
+    )
+    .bind(now + SEND_LEASE)
+    .bind(DeliveryStatus::Pending.code())
+    .bind(now)
+    .bind(BATCH_SIZE)
+    .fetch_all(&state.db)
+    .await?;
+    rows.sort_by_key(|row| row.get::<i64, _>("id"));
+
+    let mut users: HashMap<i64, (String, String)> = HashMap::new();
+    let mut configs: HashMap<String, UserConfig> = HashMap::new();
+    let mut sent = 0;
+    for row in rows {
+        let id: i64 = row.try_get("id")?;
+        let attempts: i64 = row.try_get::<i64, _>("attempts")? + 1;
+        let user_id: i64 = row.try_get("user_id")?;
+        let aad = payload_aad(user_id, &row.try_get::<String, _>("source_id")?);
+        // An entry that can't be opened, e.g. after the server key changed,
+        // never will be; it fails instead of holding up the others.
+        let payload = match open_payload(state, &aad, &row.try_get::<Vec<u8>, _>("payload")?) {
+            Ok(payload) => payload,
+            Err(err) => {
+                error!(entry = id, "unreadable notification dropped: {err:?}");
+                fail_entry(state, id, attempts, &err).await?;
+                continue;
+            }
+        };
+
+        if let Entry::Vacant(entry) = users.entry(user_id) {
+            let user: (String, String) =
+                sqlx::query_as("SELECT uuid, username FROM users WHERE id = ?1")
+                    .bind(user_id)
+                    .fetch_one(&state.db)
+                    .await?;
+            entry.insert(user);
+        }
+        let (user_uuid, username) = &users[&user_id];
+        if !configs.contains_key(user_uuid) {
+            match state.load_user_config(user_uuid, username).await {
+                Ok(config) => {
+                    configs.insert(user_uuid.clone(), config);
+                }
+                // Not the contact's fault; the entry waits without using up
+                // an attempt.
+                Err(AppError::Locked) => {
+                    sqlx::query(
+                        "UPDATE notification_outbox SET last_error = ?1, next_attempt_at = ?2 WHERE id = ?3",
+                    )
+                    .bind(AppError::Locked.to_string())
+                    .bind(Utc::now() + Duration::seconds(INITIAL_BACKOFF_SECS))
+                    .bind(id)
+                    .execute(&state.db)
+                    .await?;
+                    continue;
+                }
+                Err(err) => {
+                    warn!(entry = id, user = %user_uuid, "notification retry skipped: {err:?}");
+                    record_attempt(state, id, attempts, Err(err)).await?;
+                    continue;
+                }
+            }
+        }
+        let user_cfg = &configs[user_uuid];
+        // Deferred alerts and retries wait for the end of quiet hours.
+        if let Some(end) = deferral_end(user_cfg, &payload.contact, &payload.message, Utc::now()) {
+            sqlx::query("UPDATE notification_outbox SET next_attempt_at = ?1 WHERE id = ?2")
+                .bind(end)
+                .bind(id)
+                .execute(&state.db)
+                .await?;
+            continue;
+        }
+        let origin = Origin::User(user_cfg);
+        let outcome = state
+            .notifier
+            .send(origin, &payload.contact, &payload.message)
+            .await;
+        if let Err(err) = &outcome {
+            warn!(entry = id, attempts, contact = %payload.contact, "notification retry failed: {err:?}");
+        } else {
+            sent += 1;
+        }
+        record_attempt(state, id, attempts, outcome).await?;
+    }
+    Ok(sent)
+}
+
+async fn fail_entry(
+    state: &AppState,
+    entry_id: i64,
+    attempts: i64,
+    err: &AppError,
+) -> Result<(), AppError> {
+    let message: String = err.to_string().chars().take(MAX_ERROR_LEN).collect();
+    sqlx::query(
+        "UPDATE notification_outbox SET status = ?1, attempts = ?2, last_error = ?3 WHERE id = ?4",
+    )
+    .bind(DeliveryStatus::Failed.code())
+    .bind(attempts)
+    .bind(message)
+    .bind(entry_id)
+    .execute(&state.db)
+    .await?;
+    Ok(())
+}
+
+async fn record_attempt(
+    state: &AppState,
+    entry_id: i64,
+    attempts: i64,
+    outcome: Result<(), AppError>,
+) -> Result<(), AppError> {
+    let now = Utc::now();
+    match outcome {
+        Ok(()) => {
+            sqlx::query(
+                r#"
+                UPDATE notification_outbox
+                SET status = ?1, attempts = ?2, last_error = NULL, sent_at = ?3
+                WHERE id = ?4
+                "#,
+            )
+            .bind(DeliveryStatus::Delivered.code())
+            .bind(attempts)
+            .bind(now)
+            .bind(entry_id)
+            .execute(&state.db)
+            .await?;
+        }
+        Err(err) => {
+            // A channel that isn't configured won't appear by retrying.
+            let permanent = matches!(err, AppError::Config(_));
+            let status = if permanent || attempts >= MAX_ATTEMPTS {
+                DeliveryStatus::Failed
+            } else {
+                DeliveryStatus::Pending
+            };
+            let message: String = err.to_string().chars().take(MAX_ERROR_LEN).collect();
+            sqlx::query(
+                r#"
+                UPDATE notification_outbox
+                SET status = ?1, attempts = ?2, last_error = ?3, next_attempt_at = ?4
+                WHERE id = ?5
+                "#,
+            )
+            .bind(status.code())
+            .bind(attempts)
+            .bind(message)
+            .bind(now + retry_delay(attempts))
+            .bind(entry_id)
+            .execute(&state.db)
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+/// 30 seconds after the first failure, doubling up to an hour.
+fn retry_delay(attempts: i64) -> Duration {
+    let exponent = u32::try_from(attempts.saturating_sub(1))
+        .unwrap_or(u32::MAX)
+        .min(20);
+    Duration::seconds((INITIAL_BACKOFF_SECS << exponent).min(MAX_BACKOFF_SECS))
+}
@@ -0,0 +1,127 @@
+//! HTTP based channels: ntfy/Gotify style push and generic JSON webhooks.
+
+use std::time::Duration;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use serde_json::json;
+
+use crate::{
+    error::AppError,
+    models::settings::ChannelKind,
+    services::notify::{Message, NotificationChannel, Origin},
+};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .expect("HTTP client with default TLS settings")
+}
+
+async fn check(response: Result<reqwest::Response, reqwest::Error>) -> Result<(), AppError> {
+    let response = response.map_err(|err| AppError::Other(err.into()))?;
+    let status = response.status();
+    if status.is_success() {
+        Ok(())
+    } else {
+        Err(AppError::Other(anyhow!(
+            "{} answered HTTP {status}",
+            response.url().host_str().unwrap_or("receiver")
+        )))
+    }
+}
+
+/// Push notifications to phones. A URL ending in `/message` is treated as a
+/// Gotify server (the app token goes in `?token=`); anything else as an
+/// ntfy topic such as `https://ntfy.sh/my-topic`.
+pub struct PushChannel {
+    client: reqwest::Client,
+}
+
+impl PushChannel {
+    pub fn new() -> Self {
+        Self { client: client() }
+    }
+}
+
+impl Default for PushChannel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl NotificationChannel for PushChannel {
+    fn kind(&self) -> ChannelKind {
+        ChannelKind::Push
+    }
+
+    async fn send(
+        &self,
+        _origin: Origin<'_>,
+        address: &str,
+        message: &Message,
+    ) -> Result<(), AppError> {
+        let mut url = reqwest::Url::parse(address).map_err(|err| AppError::Other(anyhow!(err)))?;
+        let request = if url.path().ends_with("/message") {
+            self.client.post(url).json(&json!({
+                "title": message.title,
+                "message": message.body,
+                "priority": if message.urgent { 10 } else { 5 },
+            }))
+        } else {
+            // Query parameters instead of headers, which can't carry UTF-8.
+            url.query_pairs_mut()
+                .append_pair("title", &message.title)
+                .append_pair(
+                    "priority",
+                    if message.urgent { "urgent" } else { "default" },
+                );
+            self.client.post(url).body(message.body.clone())
+        };
+        check(request.send().await).await
+    }
+}
+
+/// `POST`s `{"title", "message", "urgent", "from"}` as JSON to any URL, for
+/// receivers that aren't covered by the other channels.
+pub struct WebhookChannel {
+    client: reqwest::Client,
+}
+
+impl WebhookChannel {
+    pub fn new() -> Self {
+        Self { client: client() }
+    }
+}
+
+impl Default for WebhookChannel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl NotificationChannel for WebhookChannel {
+    fn kind(&self) -> ChannelKind {
+        ChannelKind::Webhook
+    }
+
+    async fn send(
+        &self,
+        origin: Origin<'_>,
+        address: &str,
+        message: &Message,
+    ) -> Result<(), AppError> {
+        let request = self.client.post(address).json(&json!({
+            "title": message.title,
+            "message": message.body,
+            "urgent": message.urgent,
+            "from": origin.to_string(),
+        }));
+        check(request.send().await).await
+    }
+}
@@ -0,0 +1,399 @@
+//! Check-in reminders, daily at a fixed time or every few hours during a
+//! trip. Each schedule keeps its next slot in `reminders`, so a restart
+//! picks up where it left off. A slot is claimed before anything is sent,
+//! so it goes out at most once; slots missed by more than
+//! [`MISSED_SLOT_GRACE`] while the app was down are skipped instead of
+//! arriving late.
+
+use anyhow::anyhow;
+use chrono::{DateTime, Duration, NaiveTime, Utc};
+use sqlx::{sqlite::SqliteRow, Row};
+use tracing::warn;
+
+use crate::{
+    auth::AuthenticatedUser,
+    db::DbPool,
+    error::AppError,
+    i18n,
+    models::{
+        reminder::{Reminder, ReminderSchedule, ReminderStatus},
+        settings::{Contact, UserConfig},
+        trip::Trip,
+    },
+    services::notify::{Message, Origin},
+    state::AppState,
+};
+
+const BATCH_SIZE: i64 = 50;
+const MAX_EVERY_HOURS: u32 = 24;
+/// Slots older than this when they are picked up are skipped.
+const MISSED_SLOT_GRACE: i64 = 30;
+const MAX_ERROR_LEN: usize = 200;
+
+fn reminder_from_row(row: &SqliteRow) -> Result<Reminder, AppError> {
+    let kind: String = row.try_get("kind")?;
+    let schedule = match kind.as_str() {
+        "daily" => {
+            let time: String = row.try_get("time_of_day")?;
+            ReminderSchedule::Daily(
+                NaiveTime::parse_from_str(&time, "%H:%M")
+                    .map_err(|err| AppError::Other(anyhow!("bad reminder time {time}: {err}")))?,
+            )
+        }
+        "trip" => ReminderSchedule::DuringTrip {
+            every_hours: row.try_get("every_hours")?,
+        },
+        other => return Err(AppError::Other(anyhow!("unknown reminder kind {other}"))),
+    };
+    let contact: String = row.try_get("contact")?;
+    let last_status: Option<String> = row.try_get("last_status")?;
+    Ok(Reminder {
+        id: row.try_get("id")?,
+        user_id: row.try_get("user_id")?,
+        schedule,
+        contact: Contact::parse(&contact)
+            .ok_or_else(|| AppError::Other(anyhow!("bad reminder contact {contact}")))?,
+        next_run_at: row.try_get("next_run_at")?,
+        last_run_at: row.try_get("last_run_at")?,
+        last_status: last_status.as_deref().and_then(ReminderStatus::from_code),
+        last_error: row.try_get("last_error")?,
+        created_at: row.try_get("created_at")?,
+    })
+}
+
+/// Reads a schedule from the settings form: `daily` with a `HH:MM` time or
+/// `trip` with an interval of 1 to 24 hours.
+pub fn parse_schedule(
+    kind: &str,
+    time: Option<&str>,
+    every_hours: Option<u32>,
+) -> Result<ReminderSchedule, AppError> {
+    match kind {
+        "daily" => time
+            .and_then(|time| NaiveTime::parse_from_str(time.trim(), "%H:%M").ok())
+            .map(ReminderSchedule::Daily)
+            .ok_or_else(|| AppError::BadRequest(i18n::tr("error-reminder-time-invalid"))),
+        "trip" => every_hours
+            .filter(|hours| (1..=MAX_EVERY_HOURS).contains(hours))
+            .map(|every_hours| ReminderSchedule::DuringTrip { every_hours })
+            .ok_or_else(|| {
+                AppError::BadRequest(i18n::tr_args(
+                    "error-reminder-interval-invalid",
+                    &[("max", MAX_EVERY_HOURS.into())],
+                ))
+            }),
+        _ => Err(AppError::BadRequest(i18n::tr(
+            "error-reminder-kind-invalid",
+        ))),
+    }
+}
+
+async fn active_trip_start(
+    state: &AppState,
+    user_uuid: &str,
+) -> Result<Option<DateTime<Utc>>, AppError> {
+    let trips = state.storage.load_user_trips(user_uuid).await?;
+    Ok(trips
+        .iter()
+        .find(|trip| trip.is_active())
+        .map(|trip| trip.started_at))
+}
+
+/// Adds a reminder and schedules its first slot. A trip reminder added
+/// during a trip starts right away.
+pub async fn create(
+    state: &AppState,
+    user: &AuthenticatedUser,
+    user_cfg: &UserConfig,
+    schedule: ReminderSchedule,
+    contact: &Contact,
+) -> Result<Reminder, AppError> {
+    let now = Utc::now();
+    let trip_started_at = match schedule {
+        ReminderSchedule::DuringTrip { .. } => active_trip_start(state, &user.uuid).await?,
+        ReminderSchedule::Daily(_) => None,
+    };
+    let next_run_at = schedule.next_after(now, user_cfg.tz(), trip_started_at);
+    let (time_of_day, every_hours) = match schedule {
+        ReminderSchedule::Daily(time) => (Some(time.format("%H:%M").to_string()), None),
+        ReminderSchedule::DuringTrip { every_hours } => (None, Some(every_hours)),
+    };
+    let id = sqlx::query(
+        r#"
+        INSERT INTO reminders
+            (user_id, kind, time_of_day, every_hours, contact, next_run_at, created_at)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+        "#,
+    )
+    .bind(user.id)
+    .bind(schedule.code())
+    .bind(time_of_day)
+    .bind(every_hours)
+    .bind(contact.to_string())
+    .bind(next_run_at)
+    .bind(now)
+    .execute(&state.db)
+    .await?
+    .last_insert_rowid();
+    Ok(Reminder {
+        id,
+        user_id: user.id,
+        schedule,
+        contact: contact.clone(),
+        next_run_at,
+        last_run_at: None,
+        last_status: None,
+        last_error: None,
+        created_at: now,
+    })
+}
+
+/// The user's reminders, oldest first.
+pub async fn list(db: &DbPool, user_id: i64) -> Result<Vec<Reminder>, AppError> {
+    let rows = sqlx::query(
+        r#"
+        SELECT id, user_id, kind, time_of_day, every_hours, contact, next_run_at,
+               last_run_at, last_status, last_error, created_at
+        FROM reminders
+        WHERE user_id = ?1
+        ORDER BY id
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(db)
+    .await?;
+    rows.iter().map(reminder_from_row).collect()
+}
+
+/// Deletes one of the user's reminders. Returns whether it existed.
+pub async fn delete(db: &DbPool, user_id: i64, reminder_id: i64) -> Result<bool, AppError> {
+    let result = sqlx::query("DELETE FROM reminders WHERE id = ?1 AND user_id = ?2")
+        .bind(reminder_id)
+        .bind(user_id)
+        .execute(db)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Schedules the user's trip reminders for a trip that just started.
+pub async fn trip_started(state: &AppState, user_id: i64, trip: &Trip) -> Result<(), AppError> {
+    let reminders = list(&state.db, user_id).await?;
+    for reminder in reminders {
+        if let ReminderSchedule::DuringTrip { .. } = reminder.schedule {
+            // Trip slots don't depend on the timezone.
+            let next = reminder.schedule.next_after(
+                trip.started_at,
+                chrono_tz::UTC,
+                Some(trip.started_at),
+            );
+            set_next_run(&state.db, reminder.id, next).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Pauses the user's trip reminders until the next trip.
+pub async fn trip_ended(state: &AppState, user_id: i64) -> Result<(), AppError> {
+    sqlx::query("UPDATE reminders SET next_run_at = NULL WHERE user_id = ?1 AND kind = 'trip'")
+        .bind(user_id)
+        .execute(&state.db)
+        .await?;
+    Ok(())
+}
+
+/// Moves daily reminders to the user's new timezone.
+pub async fn reschedule_daily(
+    state: &AppState,
+    user_id: i64,
+    user_cfg: &UserConfig,
+) -> Result<(), AppError> {
+    let now = Utc::now();
+    for reminder in list(&state.db, user_id).await? {
+        if let ReminderSchedule::Daily(_) = reminder.schedule {
+            let next = reminder.schedule.next_after(now, user_cfg.tz(), None);
+            set_next_run(&state.db, reminder.id, next).await?;
+        }
+    }
+    Ok(())
+}
+
+async fn set_next_run(
+    db: &DbPool,
+    reminder_id: i64,
+    next_run_at: Option<DateTime<Utc>>,
+) -> Result<(), AppError> {
+    sqlx::query("UPDATE reminders SET next_run_at = ?1 WHERE id = ?2")
+        .bind(next_run_at)
+        .bind(reminder_id)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+/// Handles every reminder whose slot has come and schedules its next one.
+/// Returns how many reminders were sent.
+pub async fn run_due(state: &AppState) -> Result<usize, AppError> {
+    let now = Utc::now();
+    let rows = sqlx::query(
+        r#"
+        SELECT r.id, r.user_id, r.kind, r.time_of_day, r.every_hours, r.contact,
+               r.next_run_at, r.last_run_at, r.last_status, r.last_error, r.created_at,
+               u.uuid, u.username
+        FROM reminders r
+        JOIN users u ON u.id = r.user_id
+        WHERE r.next_run_at <= ?1
+        ORDER BY r.next_run_at
+        LIMIT ?2
+        "#,
+    )
+    .bind(now)
+    .bind(BATCH_SIZE)
+    .fetch_all(&state.db)
+    .await?;
+
+    let mut sent = 0;
+    for row in rows {
+        let reminder = reminder_from_row(&row)?;
+        let user_uuid: String = row.try_get("uuid")?;
+        let username: String = row.try_get("username")?;
+        let outcome = run_one(state, &reminder, &user_uuid, &username, now).await;
+        let (status, error) = match outcome {
+            Ok(None) => continue,
+            Ok(Some(status)) => (status, None),
+            Err(err) => {
+                warn!(reminder = reminder.id, user = %user_uuid, "reminder failed: {err:?}");
+                let message: String = err.to_string().chars().take(MAX_ERROR_LEN).collect();
+                (ReminderStatus::Failed, Some(message))
+            }
+        };
+        if status == ReminderStatus::Sent {
+            sent += 1;
+        }
+        sqlx::query(
+            "UPDATE reminders SET last_run_at = ?1, last_status = ?2, last_error = ?3 WHERE id = ?4",
+        )
+        .bind(now)
+        .bind(status.code())
+        .bind(error)
+        .bind(reminder.id)
+        .execute(&state.db)
+        .await?;
+    }
+    Ok(sent)
+}
+
+/// Claims the due slot and sends the reminder unless it is pointless.
+/// `None` means someone else claimed the slot first.
+async fn run_one(
+    state: &AppState,
+    reminder: &Reminder,
+    user_uuid: &str,
+    username: &str,
+    now: DateTime<Utc>,
+) -> Result<Option<ReminderStatus>, AppError> {
+    let Some(slot) = reminder.next_run_at else {
+        return Ok(None);
+    };
+    // Without the user's config there is neither a timezone nor a sender;
+    // the slot is given up so a locked account doesn't stall the job.
+    let user_cfg = match state.load_user_config(user_uuid, username).await {
+        Ok(config) => config,
+        Err(err) => {
+            let step = match reminder.schedule {
+                ReminderSchedule::Daily(_) => Duration::days(1),
+                ReminderSchedule::DuringTrip { every_hours } => Duration::hours(every_hours.into()),
+            };
+            let mut next = slot + step;
+            while next <= now {
+                next += step;
+            }
+            if !claim(&state.db, reminder.id, slot, Some(next)).await? {
+                return Ok(None);
+            }
+            return Err(err);
+        }
+    };
+    let trip = match reminder.schedule {
+        ReminderSchedule::DuringTrip { .. } => {
+            let trips = state.storage.load_user_trips(user_uuid).await?;
+            match trips.into_iter().find(Trip::is_active) {
+                Some(trip) => Some(trip),
+                // The trip ended without the hook running; wait for the next.
+                None => {
+                    claim(&state.db, reminder.id, slot, None).await?;
+                    return Ok(None);
+                }
+            }
+        }
+        ReminderSchedule::Daily(_) => None,
+    };
+    let next = reminder.schedule.next_after(
+        now,
+        user_cfg.tz(),
+        trip.as_ref().map(|trip| trip.started_at),
+    );
+    if !claim(&state.db, reminder.id, slot, next).await? {
+        return Ok(None);
+    }
+
+    if now - slot > Duration::minutes(MISSED_SLOT_GRACE) {
+        return Ok(Some(ReminderStatus::Skipped));
+    }
+    let since = now - reminder.schedule.recent_checkin_window();
+    let checkins = state.storage.load_user_checkins(user_uuid).await?;
+    let checked_in = checkins
+        .iter()
+        .any(|checkin| !checkin.is_deleted() && checkin.timestamp > since);
+    let quiet = reminder
+        .contact
+        .quiet_hours
+        .and_then(|quiet_hours| quiet_hours.deferral_end(now, user_cfg.tz()))
+        .is_some();
+    if checked_in || quiet {
+        return Ok(Some(ReminderStatus::Skipped));
+    }
+
+    let message = reminder_message(&user_cfg, trip.as_ref(), now);
+    state
+        .notifier
+        .send(Origin::User(&user_cfg), &reminder.contact, &message)
+        .await?;
+    Ok(Some(ReminderStatus::Sent))
+}
+
+/// Moves the reminder from `slot` to `next`, unless another run already did.
+async fn claim(
+    db: &DbPool,
+    reminder_id: i64,
+    slot: DateTime<Utc>,
+    next: Option<DateTime<Utc>>,
+) -> Result<bool, AppError> {
+    let result =
+        sqlx::query("UPDATE reminders SET next_run_at = ?1 WHERE id = ?2 AND next_run_at = ?3")
+            .bind(next)
+            .bind(reminder_id)
+            .bind(slot)
+            .execute(db)
+            .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+fn reminder_message(user_cfg: &UserConfig, trip: Option<&Trip>, now: DateTime<Utc>) -> Message {
+    let lang = user_cfg.lang().unwrap_or_default();
+    let body = match trip {
+        Some(trip) => i18n::tr_in(
+            lang,
+            "reminder-body-trip",
+            &[
+                ("title", trip.title.clone().into()),
+                ("hours", (now - trip.started_at).num_hours().into()),
+            ],
+        ),
+        None => i18n::tr_in(lang, "reminder-body-daily", &[]),
+    };
+    Message {
+        title: i18n::tr_in(lang, "reminder-title", &[]),
+        body,
+        urgent: false,
+    }
+}
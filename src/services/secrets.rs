@@ -0,0 +1,196 @@
+#![allow(dead_code)]
+
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::anyhow;
+use chacha20poly1305::Key;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use uuid::Uuid;
+
+use crate::{
+    db::DbPool,
+    error::AppError,
+    services::{
+        crypto::{decrypt, derive_server_key, encrypt, is_sealed, DataKey},
+        git::RewriteReport,
+        storage::file_aad,
+    },
+    state::AppState,
+};
+
+pub const MATRIX_ACCESS_TOKEN: &str = "matrix_access_token";
+pub const WEBHOOK_SECRET: &str = "webhook_secret";
+
+/// Credentials (Matrix access tokens, …) kept out of the git-tracked `ai/`
+/// tree. Values live in SQLite, encrypted with a server key; JSON models
+/// only store the secret ID.
+#[derive(Clone)]
+pub struct SecretStore {
+    db: DbPool,
+    key: Arc<Key>,
+}
+
+impl SecretStore {
+    pub fn new(db: DbPool, server_secret: &str) -> Self {
+        Self {
+            db,
+            key: Arc::new(derive_server_key(server_secret)),
+        }
+    }
+
+    /// Stores a new secret and returns its ID.
+    pub async fn put(&self, user_uuid: &str, kind: &str, value: &str) -> Result<String, AppError> {
+        let id = Uuid::new_v4().to_string();
+        let now = Utc::now();
+        sqlx::query(
+            r#"
+            INSERT INTO secrets (id, user_uuid, kind, ciphertext, created_at, updated_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?5)
+            "#,
+        )
+        .bind(&id)
+        .bind(user_uuid)
+        .bind(kind)
+        .bind(encrypt(&self.key, id.as_bytes(), value.as_bytes())?)
+        .bind(now)
+        .execute(&self.db)
+        .await?;
+        Ok(id)
+    }
+
+    /// Replaces the value behind `id`, or creates a new secret if `id` is
+    /// `None` or unknown. Returns the (possibly new) ID.
+    pub async fn upsert(
+        &self,
+        id: Option<&str>,
+        user_uuid: &str,
+        kind: &str,
+        value: &str,
+    ) -> Result<String, AppError> {
+        if let Some(id) = id {
+            let result = sqlx::query(
+                "UPDATE secrets SET ciphertext = ?1, updated_at = ?2 WHERE id = ?3 AND user_uuid = ?4",
+            )
+            .bind(encrypt(&self.key, id.as_bytes(), value.as_bytes())?)
+            .bind(Utc::now())
+            .bind(id)
+            .bind(user_uuid)
+            .execute(&self.db)
+            .await?;
+            if result.rows_affected() == 1 {
+                return Ok(id.to_string());
+            }
+        }
+        self.put(user_uuid, kind, value).await
+    }
+
+    pub async fn get(&self, id: &str) -> Result<Option<String>, AppError> {
+        let ciphertext: Option<Vec<u8>> =
+            sqlx::query_scalar("SELECT ciphertext FROM secrets WHERE id = ?1")
+                .bind(id)
+                .fetch_optional(&self.db)
+                .await?;
+        let Some(ciphertext) = ciphertext else {
+            return Ok(None);
+        };
+        let plaintext = decrypt(&self.key, id.as_bytes(), &ciphertext)?;
+        String::from_utf8(plaintext)
+            .map(Some)
+            .map_err(|err| AppError::Other(anyhow!(err)))
+    }
+
+    pub async fn delete(&self, id: &str) -> Result<(), AppError> {
+        sqlx::query("DELETE FROM secrets WHERE id = ?1")
+            .bind(id)
+            .execute(&self.db)
+            .await?;
+        Ok(())
+    }
+
+    /// Encrypts data kept outside the `secrets` table (e.g. queued webhook
+    /// payloads) with the same server key.
+    pub fn seal(&self, aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, AppError> {
+        encrypt(&self.key, aad, plaintext)
+    }
+
+    pub fn open(&self, aad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, AppError> {
+        decrypt(&self.key, aad, ciphertext)
+    }
+
+    /// A passphrase for stores encrypted by libraries (e.g. the Matrix
+    /// crypto stores), stable per `context` and server key.
+    pub fn derive_passphrase(&self, context: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.key.as_slice())
+            .expect("HMAC accepts any key length");
+        mac.update(context.as_bytes());
+        hex::encode(mac.finalize().into_bytes())
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ScrubReport {
+    pub history: RewriteReport,
+    /// Users whose data key is unavailable (no escrow, not logged in). Their
+    /// live config could not be migrated and sealed history was left as is.
+    pub locked_users: Vec<String>,
+}
+
+/// Moves live Matrix tokens into the secrets store, then strips
+/// `matrix_access_token` from every `ai/users/<uuid>/config.json` ever
+/// committed. Sealed blobs are re-sealed when the user's key is available.
+pub async fn scrub_committed_tokens(state: &AppState) -> Result<ScrubReport, AppError> {
+    let users: Vec<(String, String)> = sqlx::query_as("SELECT uuid, username FROM users")
+        .fetch_all(&state.db)
+        .await?;
+
+    let mut keys = HashMap::new();
+    let mut locked_users = Vec::new();
+    for (uuid, username) in users {
+        match state.crypto.key_for(&uuid).await {
+            Ok(key) => {
+                state.load_user_config(&uuid, &username).await?;
+                keys.insert(uuid, key);
+            }
+            Err(AppError::Locked) => locked_users.push(uuid),
+            Err(err) => return Err(err),
+        }
+    }
+
+    let git = state.git.clone();
+    let history = tokio::task::spawn_blocking(move || {
+        git.rewrite_history(|path, blob| scrub_config_blob(path, blob, &keys))
+    })
+    .await
+    .map_err(|err| AppError::Other(err.into()))??;
+
+    Ok(ScrubReport {
+        history,
+        locked_users,
+    })
+}
+
+fn scrub_config_blob(path: &str, blob: &[u8], keys: &HashMap<String, DataKey>) -> Option<Vec<u8>> {
+    let user_uuid = path
+        .strip_prefix("ai/users/")?
+        .strip_suffix("/config.json")?;
+    if user_uuid.contains('/') {
+        return None;
+    }
+
+    if !is_sealed(blob) {
+        return strip_token(blob);
+    }
+    let key = keys.get(user_uuid)?;
+    let aad = file_aad(user_uuid, "config.json");
+    let plaintext = key.open(aad.as_bytes(), blob).ok()?;
+    let scrubbed = strip_token(&plaintext)?;
+    key.seal(aad.as_bytes(), &scrubbed).ok()
+}
+
+fn strip_token(json: &[u8]) -> Option<Vec<u8>> {
+    let mut value: serde_json::Value = serde_json::from_slice(json).ok()?;
+    value.as_object_mut()?.remove("matrix_access_token")?;
+    serde_json::to_vec_pretty(&value).ok()
+}
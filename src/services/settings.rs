@@ -0,0 +1,50 @@
+//! Saving user settings, shared by the settings page and the JSON API so
+//! both commit the change and move reminders the same way.
+
+use tokio::sync::OwnedMutexGuard;
+
+use crate::{
+    auth::AuthenticatedUser,
+    error::AppError,
+    models::settings::UserConfig,
+    services::{git::DataChange, reminders},
+    state::AppState,
+};
+
+/// Loads the user's settings to change them. The returned guard holds the
+/// user's write lock; keep it until [`save`] returned, so no other write
+/// lands in between and is overwritten.
+pub async fn load_for_update(
+    state: &AppState,
+    user: &AuthenticatedUser,
+) -> Result<(OwnedMutexGuard<()>, UserConfig), AppError> {
+    let writing = state.storage.lock_user(&user.uuid).await;
+    let config = state
+        .load_user_config_locked(&user.uuid, &user.username)
+        .await?;
+    Ok((writing, config))
+}
+
+/// Saves the user's settings and commits them. Daily reminders are moved
+/// to the new slot when the timezone changed. Call it with the guard from
+/// [`load_for_update`] held.
+pub async fn save(
+    state: &AppState,
+    user: &AuthenticatedUser,
+    config: &UserConfig,
+) -> Result<(), AppError> {
+    let previous_timezone = state
+        .storage
+        .load_user_config(&user.uuid)
+        .await?
+        .unwrap_or_default()
+        .timezone;
+    state.storage.save_user_config(&user.uuid, config).await?;
+    state.git.record(DataChange::Settings {
+        user_uuid: user.uuid.clone(),
+    });
+    if previous_timezone != config.timezone {
+        reminders::reschedule_daily(state, user.id, config).await?;
+    }
+    Ok(())
+}
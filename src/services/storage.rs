@@ -1,29 +1,71 @@
 #![allow(dead_code)]
 
 use std::{
+    cmp::Reverse,
+    collections::HashMap,
     path::{Path, PathBuf},
-    sync::Arc,
+    sync::{Arc, Mutex},
 };
 
+use chrono::Utc;
+use serde::{de::DeserializeOwned, Serialize};
 use serde_json::Value;
-use tokio::fs;
+use tokio::{
+    fs,
+    sync::{Mutex as AsyncMutex, OwnedMutexGuard},
+};
 
-use crate::{error::AppError, models::checkin::Checkin};
+use crate::{
+    error::AppError,
+    models::{
+        checkin::{Checkin, PanicEvent},
+        settings::{GlobalConfig, UserConfig},
+        trip::Trip,
+    },
+    services::crypto::{is_sealed, CryptoService},
+};
 
-const CHECKINS_FILE: &str = "checkins.json";
+pub const CHECKINS_FILE: &str = "checkins.json";
+const CONFIG_FILE: &str = "config.json";
+const TRIPS_FILE: &str = "trips.json";
+const PANIC_EVENTS_FILE: &str = "panic_events.json";
+const GLOBAL_CONFIG_FILE: &str = "config.json";
 
+/// Per-user JSON files under `ai/users/<uuid>/`. Everything written there is
+/// sealed with the user's data key; plaintext files from before encryption
+/// are still readable and get sealed on their next write.
 #[derive(Clone)]
 pub struct StorageService {
     root: Arc<PathBuf>,
+    crypto: CryptoService,
+    /// One lock per user, held across each load-change-save, so concurrent
+    /// writers (pages, API, bot, jobs) can't overwrite each other's changes.
+    writers: Arc<Mutex<HashMap<String, Arc<AsyncMutex<()>>>>>,
 }
 
 impl StorageService {
-    pub fn new(root: PathBuf) -> Self {
+    pub fn new(root: PathBuf, crypto: CryptoService) -> Self {
         Self {
             root: Arc::new(root),
+            crypto,
+            writers: Arc::default(),
         }
     }
 
+    /// Waits until no other change to the user's files is in progress. Hold
+    /// the guard across loading, changing and saving a file; the methods
+    /// below that change a list take it themselves.
+    pub async fn lock_user(&self, user_uuid: &str) -> OwnedMutexGuard<()> {
+        let lock = self
+            .writers
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .entry(user_uuid.to_string())
+            .or_default()
+            .clone();
+        lock.lock_owned().await
+    }
+
     pub fn root(&self) -> &Path {
         &self.root
     }
@@ -46,18 +88,77 @@ impl StorageService {
         Ok(dir)
     }
 
-    pub async fn load_user_checkins(&self, user_uuid: &str) -> Result<Vec<Checkin>, AppError> {
-        let path = self.user_dir(user_uuid).join(CHECKINS_FILE);
+    /// Reads and decrypts a per-user file. Returns `None` if it is missing.
+    pub async fn read_user_file(
+        &self,
+        user_uuid: &str,
+        filename: &str,
+    ) -> Result<Option<Vec<u8>>, AppError> {
+        let path = self.user_dir(user_uuid).join(filename);
         if !fs::try_exists(&path).await? {
-            return Ok(Vec::new());
+            return Ok(None);
         }
         let raw = fs::read(&path).await?;
+        self.open_user_bytes(user_uuid, filename, raw)
+            .await
+            .map(Some)
+    }
+
+    /// Encrypts and writes a per-user file.
+    pub async fn write_user_file(
+        &self,
+        user_uuid: &str,
+        filename: &str,
+        plaintext: &[u8],
+    ) -> Result<(), AppError> {
+        let dir = self.ensure_user_dir(user_uuid).await?;
+        let key = self.crypto.key_for(user_uuid).await?;
+        let sealed = key.seal(file_aad(user_uuid, filename).as_bytes(), plaintext)?;
+        fs::write(dir.join(filename), sealed).await?;
+        Ok(())
+    }
+
+    /// Decrypts the raw contents of a per-user file, e.g. a blob read from
+    /// git history. Unsealed (legacy) data is returned unchanged.
+    pub async fn open_user_bytes(
+        &self,
+        user_uuid: &str,
+        filename: &str,
+        raw: Vec<u8>,
+    ) -> Result<Vec<u8>, AppError> {
+        if !is_sealed(&raw) {
+            return Ok(raw);
+        }
+        let key = self.crypto.key_for(user_uuid).await?;
+        key.open(file_aad(user_uuid, filename).as_bytes(), &raw)
+    }
+
+    async fn load_user_list<T: DeserializeOwned>(
+        &self,
+        user_uuid: &str,
+        filename: &str,
+    ) -> Result<Vec<T>, AppError> {
+        let Some(raw) = self.read_user_file(user_uuid, filename).await? else {
+            return Ok(Vec::new());
+        };
         if raw.is_empty() {
             return Ok(Vec::new());
         }
-        let checkins: Vec<Checkin> =
-            serde_json::from_slice(&raw).map_err(|err| AppError::Other(err.into()))?;
-        Ok(checkins)
+        serde_json::from_slice(&raw).map_err(|err| AppError::Other(err.into()))
+    }
+
+    async fn save_user_list<T: Serialize>(
+        &self,
+        user_uuid: &str,
+        filename: &str,
+        items: &[T],
+    ) -> Result<(), AppError> {
+        let data = serde_json::to_vec_pretty(items).map_err(|err| AppError::Other(err.into()))?;
+        self.write_user_file(user_uuid, filename, &data).await
+    }
+
+    pub async fn load_user_checkins(&self, user_uuid: &str) -> Result<Vec<Checkin>, AppError> {
+        self.load_user_list(user_uuid, CHECKINS_FILE).await
     }
 
     pub async fn save_user_checkins(
@@ -65,12 +166,8 @@ impl StorageService {
         user_uuid: &str,
         checkins: &[Checkin],
     ) -> Result<(), AppError> {
-        let dir = self.ensure_user_dir(user_uuid).await?;
-        let path = dir.join(CHECKINS_FILE);
-        let data =
-            serde_json::to_vec_pretty(checkins).map_err(|err| AppError::Other(err.into()))?;
-        fs::write(path, data).await?;
-        Ok(())
+        self.save_user_list(user_uuid, CHECKINS_FILE, checkins)
+            .await
     }
 
     pub async fn append_checkin(
@@ -78,9 +175,10 @@ impl StorageService {
         user_uuid: &str,
         checkin: Checkin,
     ) -> Result<Checkin, AppError> {
+        let _writing = self.lock_user(user_uuid).await;
         let mut items = self.load_user_checkins(user_uuid).await?;
         items.push(checkin.clone());
-        items.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+        items.sort_by_key(|item| Reverse(item.timestamp));
         self.save_user_checkins(user_uuid, &items).await?;
         // Return the canonical record (after sorting) in case timestamps moved.
         let saved = items
@@ -90,16 +188,150 @@ impl StorageService {
         Ok(saved)
     }
 
+    /// Replaces the check-in with the same id. A check-in that is gone,
+    /// e.g. purged from the trash, is `NotFound` and stays gone. Whether it
+    /// is in the trash is kept; only [`Self::trash_checkin`] and
+    /// [`Self::untrash_checkin`] change that. Returns the stored check-in.
+    pub async fn replace_checkin(
+        &self,
+        user_uuid: &str,
+        mut checkin: Checkin,
+    ) -> Result<Checkin, AppError> {
+        let _writing = self.lock_user(user_uuid).await;
+        let mut items = self.load_user_checkins(user_uuid).await?;
+        let existing = items
+            .iter_mut()
+            .find(|c| c.id == checkin.id)
+            .ok_or(AppError::NotFound)?;
+        checkin.deleted_at = existing.deleted_at;
+        *existing = checkin.clone();
+        items.sort_by_key(|item| Reverse(item.timestamp));
+        self.save_user_checkins(user_uuid, &items).await?;
+        Ok(checkin)
+    }
+
+    /// Moves a check-in to the trash. Returns `false` if it was already there.
+    pub async fn trash_checkin(&self, user_uuid: &str, checkin_id: &str) -> Result<bool, AppError> {
+        let _writing = self.lock_user(user_uuid).await;
+        let mut items = self.load_user_checkins(user_uuid).await?;
+        let checkin = items
+            .iter_mut()
+            .find(|c| c.id == checkin_id)
+            .ok_or(AppError::NotFound)?;
+        if checkin.is_deleted() {
+            return Ok(false);
+        }
+        checkin.deleted_at = Some(Utc::now());
+        self.save_user_checkins(user_uuid, &items).await?;
+        Ok(true)
+    }
+
+    /// Takes a check-in back out of the trash.
+    pub async fn untrash_checkin(
+        &self,
+        user_uuid: &str,
+        checkin_id: &str,
+    ) -> Result<bool, AppError> {
+        let _writing = self.lock_user(user_uuid).await;
+        let mut items = self.load_user_checkins(user_uuid).await?;
+        let checkin = items
+            .iter_mut()
+            .find(|c| c.id == checkin_id)
+            .ok_or(AppError::NotFound)?;
+        if checkin.deleted_at.take().is_none() {
+            return Ok(false);
+        }
+        self.save_user_checkins(user_uuid, &items).await?;
+        Ok(true)
+    }
+
+    /// Permanently removes trashed check-ins matching `purge`. Returns how
+    /// many were removed; live check-ins are never touched.
+    pub async fn purge_checkins<F>(&self, user_uuid: &str, purge: F) -> Result<usize, AppError>
+    where
+        F: Fn(&Checkin) -> bool,
+    {
+        let _writing = self.lock_user(user_uuid).await;
+        let mut items = self.load_user_checkins(user_uuid).await?;
+        let before = items.len();
+        items.retain(|c| !(c.is_deleted() && purge(c)));
+        let purged = before - items.len();
+        if purged > 0 {
+            self.save_user_checkins(user_uuid, &items).await?;
+        }
+        Ok(purged)
+    }
+
+    pub async fn load_user_trips(&self, user_uuid: &str) -> Result<Vec<Trip>, AppError> {
+        self.load_user_list(user_uuid, TRIPS_FILE).await
+    }
+
+    pub async fn save_user_trips(&self, user_uuid: &str, trips: &[Trip]) -> Result<(), AppError> {
+        self.save_user_list(user_uuid, TRIPS_FILE, trips).await
+    }
+
+    pub async fn load_panic_events(&self, user_uuid: &str) -> Result<Vec<PanicEvent>, AppError> {
+        self.load_user_list(user_uuid, PANIC_EVENTS_FILE).await
+    }
+
+    pub async fn append_panic_event(
+        &self,
+        user_uuid: &str,
+        event: PanicEvent,
+    ) -> Result<(), AppError> {
+        let _writing = self.lock_user(user_uuid).await;
+        let mut events = self.load_panic_events(user_uuid).await?;
+        events.push(event);
+        self.save_user_list(user_uuid, PANIC_EVENTS_FILE, &events)
+            .await
+    }
+
+    /// Global templates and defaults from `ai/config.json`. Not per-user, so
+    /// stored in plaintext.
+    pub async fn load_global_config(&self) -> Result<GlobalConfig, AppError> {
+        let path = self.root().join(GLOBAL_CONFIG_FILE);
+        if !fs::try_exists(&path).await? {
+            return Ok(GlobalConfig::default());
+        }
+        let raw = fs::read(&path).await?;
+        serde_json::from_slice(&raw).map_err(|err| AppError::Other(err.into()))
+    }
+
+    pub async fn load_user_config(&self, user_uuid: &str) -> Result<Option<UserConfig>, AppError> {
+        let Some(raw) = self.read_user_file(user_uuid, CONFIG_FILE).await? else {
+            return Ok(None);
+        };
+        let config = serde_json::from_slice(&raw).map_err(|err| AppError::Other(err.into()))?;
+        Ok(Some(config))
+    }
+
+    pub async fn save_user_config(
+        &self,
+        user_uuid: &str,
+        config: &UserConfig,
+    ) -> Result<(), AppError> {
+        let data = serde_json::to_vec_pretty(config).map_err(|err| AppError::Other(err.into()))?;
+        self.write_user_file(user_uuid, CONFIG_FILE, &data).await
+    }
+
     pub async fn write_user_json(
         &self,
         user_uuid: &str,
         filename: &str,
         value: &Value,
     ) -> Result<(), AppError> {
-        let dir = self.ensure_user_dir(user_uuid).await?;
-        let path = dir.join(filename);
         let data = serde_json::to_vec_pretty(value).map_err(|err| AppError::Other(err.into()))?;
-        fs::write(path, data).await?;
-        Ok(())
+        self.write_user_file(user_uuid, filename, &data).await
     }
 }
+
+/// Binds ciphertext to its location so files can't be swapped between users.
+pub(crate) fn file_aad(user_uuid: &str, filename: &str) -> String {
+    format!("users/{user_uuid}/{filename}")
+}
+
+/// Path of a per-user file relative to the repository root, as it appears in
+/// git history.
+pub(crate) fn repo_path(user_uuid: &str, filename: &str) -> String {
+    format!("ai/{}", file_aad(user_uuid, filename))
+}
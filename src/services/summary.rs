@@ -0,0 +1,334 @@
+//! Opt-in weekly and monthly summaries, sent to the user's own Matrix
+//! account and optionally to their therapist on any channel. Every delivery
+//! is recorded in `summary_deliveries`, so restarts never send a period
+//! twice.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Datelike, Duration, Months, NaiveDate, Utc};
+use chrono_tz::Tz;
+use sqlx::Row;
+use tracing::{debug, error};
+
+use crate::{
+    error::AppError,
+    i18n::{self, Lang},
+    models::{
+        checkin::Checkin,
+        settings::{Contact, UserConfig},
+        trip::Trip,
+    },
+    services::notify::{self, Message, Origin},
+    state::AppState,
+    timezone,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SummaryPeriod {
+    Weekly,
+    Monthly,
+}
+
+impl SummaryPeriod {
+    pub fn code(self) -> &'static str {
+        match self {
+            SummaryPeriod::Weekly => "weekly",
+            SummaryPeriod::Monthly => "monthly",
+        }
+    }
+
+    /// The last full period (Monday–Sunday or a calendar month) that ended
+    /// before `today`, as inclusive local dates.
+    pub fn last_completed(self, today: NaiveDate) -> (NaiveDate, NaiveDate) {
+        match self {
+            SummaryPeriod::Weekly => {
+                let this_monday =
+                    today - Duration::days(i64::from(today.weekday().num_days_from_monday()));
+                (
+                    this_monday - Duration::days(7),
+                    this_monday - Duration::days(1),
+                )
+            }
+            SummaryPeriod::Monthly => {
+                let this_month = today.with_day(1).unwrap_or(today);
+                let start = this_month - Months::new(1);
+                (start, this_month - Duration::days(1))
+            }
+        }
+    }
+
+    fn enabled(self, config: &UserConfig) -> bool {
+        match self {
+            SummaryPeriod::Weekly => config.summary_weekly,
+            SummaryPeriod::Monthly => config.summary_monthly,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Summary {
+    pub period: SummaryPeriod,
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub checkin_count: usize,
+    pub average_mood: Option<f64>,
+    /// Lowest mood and when it was logged; the earliest one on ties.
+    pub lowest: Option<(i32, DateTime<Utc>)>,
+    pub trip_count: usize,
+    /// Substance names with how often they were logged, most frequent first.
+    pub substances: Vec<(String, usize)>,
+}
+
+impl Summary {
+    pub fn is_empty(&self) -> bool {
+        self.checkin_count == 0 && self.trip_count == 0
+    }
+}
+
+/// Compiles the summary of the local days `start..=end`. Trashed check-ins
+/// don't count.
+pub fn compile(
+    period: SummaryPeriod,
+    (start, end): (NaiveDate, NaiveDate),
+    tz: Tz,
+    checkins: &[Checkin],
+    trips: &[Trip],
+) -> Summary {
+    let in_period = |ts: DateTime<Utc>| (start..=end).contains(&timezone::local_date(ts, tz));
+    let mut checkins: Vec<&Checkin> = checkins
+        .iter()
+        .filter(|checkin| !checkin.is_deleted() && in_period(checkin.timestamp))
+        .collect();
+    checkins.sort_by_key(|checkin| checkin.timestamp);
+
+    let mut substances: BTreeMap<String, (String, usize)> = BTreeMap::new();
+    for drug in checkins.iter().flat_map(|checkin| &checkin.drugs) {
+        let name = drug.substance.trim();
+        if !name.is_empty() {
+            substances
+                .entry(name.to_lowercase())
+                .or_insert_with(|| (name.to_string(), 0))
+                .1 += 1;
+        }
+    }
+    let mut substances: Vec<(String, usize)> = substances.into_values().collect();
+    substances.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    Summary {
+        period,
+        start,
+        end,
+        checkin_count: checkins.len(),
+        average_mood: (!checkins.is_empty()).then(|| {
+            checkins.iter().map(|c| f64::from(c.mood)).sum::<f64>() / checkins.len() as f64
+        }),
+        lowest: checkins
+            .iter()
+            .min_by_key(|checkin| checkin.mood)
+            .map(|checkin| (checkin.mood, checkin.timestamp)),
+        trip_count: trips
+            .iter()
+            .filter(|trip| in_period(trip.started_at))
+            .count(),
+        substances,
+    }
+}
+
+/// Fills the summary placeholders on top of the ones of
+/// [`notify::render_template`].
+pub fn render(template: &str, user_cfg: &UserConfig, summary: &Summary, lang: Lang) -> String {
+    let none = || "–".to_string();
+    let period_name = i18n::tr_in(
+        lang,
+        "summary-period-name",
+        &[("period", summary.period.code().into())],
+    );
+    let substances = if summary.substances.is_empty() {
+        none()
+    } else {
+        summary
+            .substances
+            .iter()
+            .map(|(name, count)| format!("{name} ×{count}"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    notify::render_template(template, user_cfg, None)
+        .replace("{period_name}", &period_name)
+        .replace(
+            "{period}",
+            &format!(
+                "{} – {}",
+                summary.start.format("%d.%m.%Y"),
+                summary.end.format("%d.%m.%Y")
+            ),
+        )
+        .replace("{checkin_count}", &summary.checkin_count.to_string())
+        .replace(
+            "{average_mood}",
+            &summary
+                .average_mood
+                .map(|mood| format!("{mood:.1}"))
+                .unwrap_or_else(none),
+        )
+        .replace(
+            "{lowest_mood}",
+            &summary
+                .lowest
+                .map(|(mood, _)| mood.to_string())
+                .unwrap_or_else(none),
+        )
+        .replace(
+            "{lowest_at}",
+            &summary
+                .lowest
+                .map(|(_, ts)| timezone::format(ts, user_cfg.tz()))
+                .unwrap_or_else(none),
+        )
+        .replace("{trip_count}", &summary.trip_count.to_string())
+        .replace("{substances}", &substances)
+}
+
+/// Sends every summary whose period has ended and that wasn't delivered
+/// yet. Returns how many messages went out.
+pub async fn deliver_due(state: &AppState) -> Result<usize, AppError> {
+    let users = sqlx::query("SELECT uuid, username FROM users ORDER BY id")
+        .fetch_all(&state.db)
+        .await?;
+    let mut sent = 0;
+    for row in users {
+        let user_uuid: String = row.try_get("uuid")?;
+        let username: String = row.try_get("username")?;
+        match deliver_for_user(state, &user_uuid, &username).await {
+            Ok(count) => sent += count,
+            // Without escrow the data stays sealed until the next login.
+            Err(AppError::Locked) => debug!(%user_uuid, "data locked, summary postponed"),
+            Err(err) => error!(%user_uuid, "summary failed: {err:?}"),
+        }
+    }
+    Ok(sent)
+}
+
+async fn deliver_for_user(
+    state: &AppState,
+    user_uuid: &str,
+    username: &str,
+) -> Result<usize, AppError> {
+    let config = state.load_user_config(user_uuid, username).await?;
+    let periods: Vec<SummaryPeriod> = [SummaryPeriod::Weekly, SummaryPeriod::Monthly]
+        .into_iter()
+        .filter(|period| period.enabled(&config))
+        .collect();
+    if periods.is_empty() {
+        return Ok(0);
+    }
+
+    let mut recipients = Vec::new();
+    if !config.matrix_user_id.trim().is_empty() {
+        recipients.push(Contact::matrix(config.matrix_user_id.trim()));
+    }
+    if config.summary_to_therapist {
+        recipients.extend(config.therapist_contact.clone());
+    }
+
+    let tz = config.tz();
+    let today = timezone::local_date(Utc::now(), tz);
+    let checkins = state.storage.load_user_checkins(user_uuid).await?;
+    let trips = state.storage.load_user_trips(user_uuid).await?;
+    let global_cfg = state.storage.load_global_config().await?;
+    let lang = config.lang().unwrap_or_default();
+
+    let mut sent = 0;
+    for period in periods {
+        let range = period.last_completed(today);
+        let summary = compile(period, range, tz, &checkins, &trips);
+        if summary.is_empty() {
+            continue;
+        }
+        let message = Message {
+            title: i18n::tr_in(
+                lang,
+                "summary-period-name",
+                &[("period", period.code().into())],
+            ),
+            body: render(
+                global_cfg.summary_message_template.get(lang),
+                &config,
+                &summary,
+                lang,
+            ),
+            urgent: false,
+        };
+        for recipient in &recipients {
+            // Matrix IDs are recorded bare, as before contacts had channels.
+            let recipient_key = recipient.target();
+            if already_delivered(state, user_uuid, period, range.0, &recipient_key).await? {
+                continue;
+            }
+            // One failing recipient mustn't hold up the others; it is
+            // retried on the next run.
+            if let Err(err) = state
+                .notifier
+                .send(Origin::User(&config), recipient, &message)
+                .await
+            {
+                error!(
+                    user = %user_uuid,
+                    period = period.code(),
+                    recipient = %recipient_key,
+                    "summary delivery failed: {err:?}"
+                );
+                continue;
+            }
+            record_delivery(state, user_uuid, period, range.0, &recipient_key).await?;
+            sent += 1;
+        }
+    }
+    Ok(sent)
+}
+
+async fn already_delivered(
+    state: &AppState,
+    user_uuid: &str,
+    period: SummaryPeriod,
+    period_start: NaiveDate,
+    recipient: &str,
+) -> Result<bool, AppError> {
+    let row = sqlx::query(
+        r#"
+        SELECT 1 FROM summary_deliveries
+        WHERE user_uuid = ?1 AND period = ?2 AND period_start = ?3 AND recipient = ?4
+        "#,
+    )
+    .bind(user_uuid)
+    .bind(period.code())
+    .bind(period_start.to_string())
+    .bind(recipient)
+    .fetch_optional(&state.db)
+    .await?;
+    Ok(row.is_some())
+}
+
+async fn record_delivery(
+    state: &AppState,
+    user_uuid: &str,
+    period: SummaryPeriod,
+    period_start: NaiveDate,
+    recipient: &str,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        INSERT OR IGNORE INTO summary_deliveries
+            (user_uuid, period, period_start, recipient, delivered_at)
+        VALUES (?1, ?2, ?3, ?4, ?5)
+        "#,
+    )
+    .bind(user_uuid)
+    .bind(period.code())
+    .bind(period_start.to_string())
+    .bind(recipient)
+    .bind(Utc::now())
+    .execute(&state.db)
+    .await?;
+    Ok(())
+}
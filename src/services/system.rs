@@ -0,0 +1,81 @@
+#![allow(dead_code)]
+
+use std::time::Duration;
+
+use chrono::Utc;
+
+use crate::{error::AppError, services::git::RepoStatus, state::AppState};
+
+/// Tables whose row counts are shown on the admin system page.
+const COUNTED_TABLES: &[&str] = &[
+    "users",
+    "sessions",
+    "user_keys",
+    "secrets",
+    "summary_deliveries",
+    "api_tokens",
+    "webhooks",
+    "webhook_deliveries",
+    "notification_outbox",
+    "matrix_links",
+    "reminders",
+    "jobs",
+];
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Optionally baked in by CI, e.g. `MOOD_BUILD_COMMIT=$(git rev-parse --short HEAD)`.
+pub const BUILD_COMMIT: Option<&str> = option_env!("MOOD_BUILD_COMMIT");
+
+pub struct SystemStatus {
+    pub uptime: Duration,
+    pub repo: Result<RepoStatus, String>,
+    pub database_size_bytes: u64,
+    pub table_counts: Vec<(&'static str, i64)>,
+    pub ai_free_bytes: Option<u64>,
+    pub ai_total_bytes: Option<u64>,
+}
+
+pub async fn collect_status(
+    state: &AppState,
+    recent_commits: usize,
+) -> Result<SystemStatus, AppError> {
+    let git = state.git.clone();
+    let repo = tokio::task::spawn_blocking(move || git.status(recent_commits))
+        .await
+        .map_err(|err| AppError::Other(err.into()))?
+        .map_err(|err| err.to_string());
+
+    let page_count: i64 = sqlx::query_scalar("PRAGMA page_count")
+        .fetch_one(&state.db)
+        .await?;
+    let page_size: i64 = sqlx::query_scalar("PRAGMA page_size")
+        .fetch_one(&state.db)
+        .await?;
+
+    let mut table_counts = Vec::with_capacity(COUNTED_TABLES.len());
+    for table in COUNTED_TABLES {
+        let count: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {table}"))
+            .fetch_one(&state.db)
+            .await?;
+        table_counts.push((*table, count));
+    }
+
+    let ai_root = state.config.ai_root.clone();
+    let (ai_free_bytes, ai_total_bytes) = tokio::task::spawn_blocking(move || {
+        (
+            fs2::available_space(&ai_root).ok(),
+            fs2::total_space(&ai_root).ok(),
+        )
+    })
+    .await
+    .map_err(|err| AppError::Other(err.into()))?;
+
+    Ok(SystemStatus {
+        uptime: (Utc::now() - state.started_at).to_std().unwrap_or_default(),
+        repo,
+        database_size_bytes: (page_count * page_size).max(0) as u64,
+        table_counts,
+        ai_free_bytes,
+        ai_total_bytes,
+    })
+}
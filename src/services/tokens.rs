@@ -0,0 +1,151 @@
+//! Personal API tokens. A token is `kmt_` plus 32 random bytes in hex; the
+//! database only keeps its SHA-256 hash, which is enough for secrets with
+//! that much entropy and keeps lookups a single indexed query.
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+use sqlx::{sqlite::SqliteRow, Row};
+
+use crate::{
+    db::DbPool,
+    error::AppError,
+    i18n,
+    models::api_token::{ApiScope, ApiToken},
+};
+
+pub const TOKEN_PREFIX: &str = "kmt_";
+const TOKEN_BYTES: usize = 32;
+
+fn hash(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+fn encode_scopes(scopes: &[ApiScope]) -> String {
+    scopes
+        .iter()
+        .map(|scope| scope.code())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn decode_scopes(raw: &str) -> Vec<ApiScope> {
+    raw.split_whitespace()
+        .filter_map(ApiScope::from_code)
+        .collect()
+}
+
+fn token_from_row(row: &SqliteRow) -> Result<ApiToken, AppError> {
+    Ok(ApiToken {
+        id: row.try_get("id")?,
+        user_id: row.try_get("user_id")?,
+        name: row.try_get("name")?,
+        scopes: decode_scopes(&row.try_get::<String, _>("scopes")?),
+        created_at: row.try_get("created_at")?,
+        last_used_at: row.try_get("last_used_at")?,
+    })
+}
+
+/// Creates a token and returns it together with the secret, which can't be
+/// recovered later.
+pub async fn create(
+    db: &DbPool,
+    user_id: i64,
+    name: &str,
+    scopes: &[ApiScope],
+) -> Result<(ApiToken, String), AppError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::BadRequest(i18n::tr(
+            "error-api-token-name-required",
+        )));
+    }
+    if scopes.is_empty() {
+        return Err(AppError::BadRequest(i18n::tr(
+            "error-api-token-scope-required",
+        )));
+    }
+
+    let mut bytes = [0u8; TOKEN_BYTES];
+    OsRng.fill_bytes(&mut bytes);
+    let secret = format!("{TOKEN_PREFIX}{}", hex::encode(bytes));
+    let now = Utc::now();
+    let id = sqlx::query(
+        r#"
+        INSERT INTO api_tokens (user_id, name, token_hash, scopes, created_at)
+        VALUES (?1, ?2, ?3, ?4, ?5)
+        "#,
+    )
+    .bind(user_id)
+    .bind(name)
+    .bind(hash(&secret))
+    .bind(encode_scopes(scopes))
+    .bind(now)
+    .execute(db)
+    .await?
+    .last_insert_rowid();
+
+    let token = ApiToken {
+        id,
+        user_id,
+        name: name.to_string(),
+        scopes: scopes.to_vec(),
+        created_at: now,
+        last_used_at: None,
+    };
+    Ok((token, secret))
+}
+
+/// The user's tokens, newest first.
+pub async fn list(db: &DbPool, user_id: i64) -> Result<Vec<ApiToken>, AppError> {
+    let rows = sqlx::query(
+        r#"
+        SELECT id, user_id, name, scopes, created_at, last_used_at
+        FROM api_tokens
+        WHERE user_id = ?1
+        ORDER BY id DESC
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(db)
+    .await?;
+    rows.iter().map(token_from_row).collect()
+}
+
+/// Deletes one of the user's tokens. Returns whether it existed.
+pub async fn revoke(db: &DbPool, user_id: i64, token_id: i64) -> Result<bool, AppError> {
+    let result = sqlx::query("DELETE FROM api_tokens WHERE id = ?1 AND user_id = ?2")
+        .bind(token_id)
+        .bind(user_id)
+        .execute(db)
+        .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+/// Finds the token for a presented secret and records that it was used.
+pub async fn verify(db: &DbPool, secret: &str) -> Result<Option<ApiToken>, AppError> {
+    if !secret.starts_with(TOKEN_PREFIX) {
+        return Ok(None);
+    }
+    let row = sqlx::query(
+        r#"
+        SELECT id, user_id, name, scopes, created_at, last_used_at
+        FROM api_tokens
+        WHERE token_hash = ?1
+        "#,
+    )
+    .bind(hash(secret))
+    .fetch_optional(db)
+    .await?;
+    let Some(row) = row else {
+        return Ok(None);
+    };
+    let token = token_from_row(&row)?;
+
+    sqlx::query("UPDATE api_tokens SET last_used_at = ?1 WHERE id = ?2")
+        .bind(Utc::now())
+        .bind(token.id)
+        .execute(db)
+        .await?;
+    Ok(Some(token))
+}
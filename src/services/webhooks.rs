@@ -0,0 +1,426 @@
+//! Outgoing webhooks. Events are queued in `webhook_deliveries` with their
+//! payload sealed under the server key, and a background job posts them,
+//! retrying with exponential backoff. The rows double as the delivery log on
+//! the settings page.
+//!
+//! Every request carries `X-Mood-Event`, `X-Mood-Delivery`,
+//! `X-Mood-Timestamp` and `X-Mood-Signature: sha256=<hex>`, an HMAC-SHA256
+//! over `<timestamp>.<body>` keyed with the webhook's secret.
+
+use std::time::Duration as StdDuration;
+
+use anyhow::anyhow;
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use chrono::{Duration, Utc};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use sqlx::{sqlite::SqliteRow, Row};
+use tracing::warn;
+
+use crate::{
+    auth::AuthenticatedUser,
+    db::DbPool,
+    error::AppError,
+    i18n,
+    models::webhook::{DeliveryStatus, Webhook, WebhookDelivery, WebhookEvent},
+    services::secrets,
+    state::AppState,
+};
+
+pub const SECRET_PREFIX: &str = "whsec_";
+const SECRET_BYTES: usize = 32;
+const REQUEST_TIMEOUT: StdDuration = StdDuration::from_secs(10);
+/// Deliveries handled per run, so one slow receiver can't stall the job.
+const BATCH_SIZE: i64 = 50;
+const MAX_ATTEMPTS: i64 = 8;
+const INITIAL_BACKOFF_SECS: i64 = 60;
+const MAX_BACKOFF_SECS: i64 = 6 * 60 * 60;
+/// Receiver errors are cut to this length for the delivery log.
+const MAX_ERROR_LEN: usize = 200;
+
+fn encode_events(events: &[WebhookEvent]) -> String {
+    events
+        .iter()
+        .map(|event| event.code())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn decode_events(raw: &str) -> Vec<WebhookEvent> {
+    raw.split_whitespace()
+        .filter_map(WebhookEvent::from_code)
+        .collect()
+}
+
+fn webhook_from_row(row: &SqliteRow) -> Result<Webhook, AppError> {
+    Ok(Webhook {
+        id: row.try_get("id")?,
+        user_id: row.try_get("user_id")?,
+        url: row.try_get("url")?,
+        events: decode_events(&row.try_get::<String, _>("events")?),
+        secret_id: row.try_get("secret_id")?,
+        created_at: row.try_get("created_at")?,
+    })
+}
+
+/// Payloads are bound to their webhook, so a row can't be replayed to
+/// another receiver.
+fn payload_aad(webhook_id: i64) -> String {
+    format!("webhook:{webhook_id}")
+}
+
+/// `sha256=<hex>` HMAC of `<timestamp>.<body>`, as sent in
+/// `X-Mood-Signature`.
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Registers a webhook and returns it together with its signing secret,
+/// which can't be recovered later.
+pub async fn create(
+    state: &AppState,
+    user: &AuthenticatedUser,
+    url: &str,
+    events: &[WebhookEvent],
+) -> Result<(Webhook, String), AppError> {
+    let url = url.trim();
+    let valid = reqwest::Url::parse(url)
+        .map(|parsed| matches!(parsed.scheme(), "http" | "https") && parsed.has_host())
+        .unwrap_or(false);
+    if !valid {
+        return Err(AppError::BadRequest(i18n::tr("error-webhook-url-invalid")));
+    }
+    if events.is_empty() {
+        return Err(AppError::BadRequest(i18n::tr(
+            "error-webhook-event-required",
+        )));
+    }
+
+    let mut bytes = [0u8; SECRET_BYTES];
+    OsRng.fill_bytes(&mut bytes);
+    let secret = format!("{SECRET_PREFIX}{}", hex::encode(bytes));
+    let secret_id = state
+        .secrets
+        .put(&user.uuid, secrets::WEBHOOK_SECRET, &secret)
+        .await?;
+    let now = Utc::now();
+    let id = sqlx::query(
+        r#"
+        INSERT INTO webhooks (user_id, url, events, secret_id, created_at)
+        VALUES (?1, ?2, ?3, ?4, ?5)
+        "#,
+    )
+    .bind(user.id)
+    .bind(url)
+    .bind(encode_events(events))
+    .bind(&secret_id)
+    .bind(now)
+    .execute(&state.db)
+    .await?
+    .last_insert_rowid();
+
+    let webhook = Webhook {
+        id,
+        user_id: user.id,
+        url: url.to_string(),
+        events: events.to_vec(),
+        secret_id,
+        created_at: now,
+    };
+    Ok((webhook, secret))
+}
+
+/// The user's webhooks, newest first.
+pub async fn list(db: &DbPool, user_id: i64) -> Result<Vec<Webhook>, AppError> {
+    let rows = sqlx::query(
+        r#"
+        SELECT id, user_id, url, events, secret_id, created_at
+        FROM webhooks
+        WHERE user_id = ?1
+        ORDER BY id DESC
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(db)
+    .await?;
+    rows.iter().map(webhook_from_row).collect()
+}
+
+/// Deletes one of the user's webhooks with its secret and queued
+/// deliveries. Returns whether it existed.
+pub async fn delete(state: &AppState, user_id: i64, webhook_id: i64) -> Result<bool, AppError> {
+    let secret_id: Option<String> =
+        sqlx::query_scalar("SELECT secret_id FROM webhooks WHERE id = ?1 AND user_id = ?2")
+            .bind(webhook_id)
+            .bind(user_id)
+            .fetch_optional(&state.db)
+            .await?;
+    let Some(secret_id) = secret_id else {
+        return Ok(false);
+    };
+    sqlx::query("DELETE FROM webhooks WHERE id = ?1")
+        .bind(webhook_id)
+        .execute(&state.db)
+        .await?;
+    state.secrets.delete(&secret_id).await?;
+    Ok(true)
+}
+
+/// Queues `event` for every webhook of the user that subscribed to it.
+/// Returns how many deliveries were queued.
+pub async fn enqueue(
+    state: &AppState,
+    user: &AuthenticatedUser,
+    event: WebhookEvent,
+    data: serde_json::Value,
+) -> Result<usize, AppError> {
+    let hooks: Vec<Webhook> = list(&state.db, user.id)
+        .await?
+        .into_iter()
+        .filter(|hook| hook.events.contains(&event))
+        .collect();
+    if hooks.is_empty() {
+        return Ok(0);
+    }
+
+    let now = Utc::now();
+    let payload = serde_json::to_vec(&serde_json::json!({
+        "event": event.code(),
+        "occurred_at": now,
+        "user": user.username,
+        "data": data,
+    }))
+    .map_err(|err| AppError::Other(err.into()))?;
+    for hook in &hooks {
+        let sealed = state
+            .secrets
+            .seal(payload_aad(hook.id).as_bytes(), &payload)?;
+        sqlx::query(
+            r#"
+            INSERT INTO webhook_deliveries
+                (webhook_id, event, payload, status, next_attempt_at, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?5)
+            "#,
+        )
+        .bind(hook.id)
+        .bind(event.code())
+        .bind(sealed)
+        .bind(DeliveryStatus::Pending.code())
+        .bind(now)
+        .execute(&state.db)
+        .await?;
+    }
+    Ok(hooks.len())
+}
+
+/// The most recent deliveries across the user's webhooks, newest first.
+pub async fn recent_deliveries(
+    db: &DbPool,
+    user_id: i64,
+    limit: i64,
+) -> Result<Vec<WebhookDelivery>, AppError> {
+    let rows = sqlx::query(
+        r#"
+        SELECT d.id, d.webhook_id, w.url, d.event, d.status, d.attempts,
+               d.next_attempt_at, d.last_status_code, d.last_error,
+               d.created_at, d.delivered_at
+        FROM webhook_deliveries d
+        JOIN webhooks w ON w.id = d.webhook_id
+        WHERE w.user_id = ?1
+        ORDER BY d.id DESC
+        LIMIT ?2
+        "#,
+    )
+    .bind(user_id)
+    .bind(limit)
+    .fetch_all(db)
+    .await?;
+    rows.iter()
+        .map(|row| {
+            let status: String = row.try_get("status")?;
+            Ok(WebhookDelivery {
+                id: row.try_get("id")?,
+                webhook_id: row.try_get("webhook_id")?,
+                url: row.try_get("url")?,
+                event: row.try_get("event")?,
+                status: DeliveryStatus::from_code(&status)
+                    .ok_or_else(|| AppError::Other(anyhow!("unknown delivery status {status}")))?,
+                attempts: row.try_get("attempts")?,
+                next_attempt_at: row.try_get("next_attempt_at")?,
+                last_status_code: row.try_get("last_status_code")?,
+                last_error: row.try_get("last_error")?,
+                created_at: row.try_get("created_at")?,
+                delivered_at: row.try_get("delivered_at")?,
+            })
+        })
+        .collect()
+}
+
+/// Posts every pending delivery whose next attempt is due. Failed attempts
+/// are rescheduled with exponential backoff until [`MAX_ATTEMPTS`]. Returns
+/// how many deliveries succeeded.
+pub async fn deliver_due(state: &AppState) -> Result<usize, AppError> {
+    let rows = sqlx::query(
+        r#"
+        SELECT d.id, d.webhook_id, d.event, d.payload, d.attempts, w.url, w.secret_id
+        FROM webhook_deliveries d
+        JOIN webhooks w ON w.id = d.webhook_id
+        WHERE d.status = ?1 AND d.next_attempt_at <= ?2
+        ORDER BY d.id
+        LIMIT ?3
+        "#,
+    )
+    .bind(DeliveryStatus::Pending.code())
+    .bind(Utc::now())
+    .bind(BATCH_SIZE)
+    .fetch_all(&state.db)
+    .await?;
+    if rows.is_empty() {
+        return Ok(0);
+    }
+
+    let client = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .map_err(|err| AppError::Other(err.into()))?;
+    let mut delivered = 0;
+    for row in rows {
+        let id: i64 = row.try_get("id")?;
+        let webhook_id: i64 = row.try_get("webhook_id")?;
+        let attempts: i64 = row.try_get::<i64, _>("attempts")? + 1;
+        let url: String = row.try_get("url")?;
+        let outcome = attempt(
+            state,
+            &client,
+            id,
+            &url,
+            &row.try_get::<String, _>("event")?,
+            &row.try_get::<String, _>("secret_id")?,
+            &state.secrets.open(
+                payload_aad(webhook_id).as_bytes(),
+                &row.try_get::<Vec<u8>, _>("payload")?,
+            )?,
+        )
+        .await;
+        match outcome {
+            Ok(code) => {
+                record_success(state, id, attempts, code).await?;
+                delivered += 1;
+            }
+            Err((code, message)) => {
+                warn!(delivery = id, %url, attempts, "webhook delivery failed: {message}");
+                record_failure(state, id, attempts, code, &message).await?;
+            }
+        }
+    }
+    Ok(delivered)
+}
+
+/// Sends one delivery. Any 2xx response counts as delivered; everything
+/// else, redirects included, is an error with the status code if there was
+/// one.
+async fn attempt(
+    state: &AppState,
+    client: &reqwest::Client,
+    delivery_id: i64,
+    url: &str,
+    event: &str,
+    secret_id: &str,
+    payload: &[u8],
+) -> Result<u16, (Option<u16>, String)> {
+    let secret = state
+        .secrets
+        .get(secret_id)
+        .await
+        .map_err(|err| (None, err.to_string()))?
+        .ok_or_else(|| (None, "signing secret is missing".to_string()))?;
+    let timestamp = Utc::now().timestamp();
+    let response = client
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header("X-Mood-Event", event)
+        .header("X-Mood-Delivery", delivery_id.to_string())
+        .header("X-Mood-Timestamp", timestamp.to_string())
+        .header("X-Mood-Signature", sign(&secret, timestamp, payload))
+        .body(payload.to_vec())
+        .send()
+        .await
+        .map_err(|err| (None, err.to_string()))?;
+    let status = response.status();
+    if status.is_success() {
+        Ok(status.as_u16())
+    } else {
+        Err((Some(status.as_u16()), format!("HTTP {status}")))
+    }
+}
+
+async fn record_success(
+    state: &AppState,
+    delivery_id: i64,
+    attempts: i64,
+    status_code: u16,
+) -> Result<(), AppError> {
+    let now = Utc::now();
+    sqlx::query(
+        r#"
+        UPDATE webhook_deliveries
+        SET status = ?1, attempts = ?2, last_status_code = ?3, last_error = NULL,
+            delivered_at = ?4
+        WHERE id = ?5
+        "#,
+    )
+    .bind(DeliveryStatus::Delivered.code())
+    .bind(attempts)
+    .bind(i64::from(status_code))
+    .bind(now)
+    .bind(delivery_id)
+    .execute(&state.db)
+    .await?;
+    Ok(())
+}
+
+async fn record_failure(
+    state: &AppState,
+    delivery_id: i64,
+    attempts: i64,
+    status_code: Option<u16>,
+    message: &str,
+) -> Result<(), AppError> {
+    let status = if attempts >= MAX_ATTEMPTS {
+        DeliveryStatus::Failed
+    } else {
+        DeliveryStatus::Pending
+    };
+    let message: String = message.chars().take(MAX_ERROR_LEN).collect();
+    sqlx::query(
+        r#"
+        UPDATE webhook_deliveries
+        SET status = ?1, attempts = ?2, last_status_code = ?3, last_error = ?4,
+            next_attempt_at = ?5
+        WHERE id = ?6
+        "#,
+    )
+    .bind(status.code())
+    .bind(attempts)
+    .bind(status_code.map(i64::from))
+    .bind(message)
+    .bind(Utc::now() + retry_delay(attempts))
+    .bind(delivery_id)
+    .execute(&state.db)
+    .await?;
+    Ok(())
+}
+
+/// 1 minute after the first failure, doubling up to 6 hours.
+fn retry_delay(attempts: i64) -> Duration {
+    let exponent = u32::try_from(attempts.saturating_sub(1))
+        .unwrap_or(u32::MAX)
+        .min(20);
+    Duration::seconds((INITIAL_BACKOFF_SECS << exponent).min(MAX_BACKOFF_SECS))
+}
@@ -1,33 +1,123 @@
 #![allow(dead_code)]
 
 use axum_extra::extract::cookie::Key;
+use chrono::{DateTime, Utc};
 use sha2::{Digest, Sha512};
 
 use crate::{
     config::AppConfig,
     db::DbPool,
-    services::{git::GitService, storage::StorageService},
+    error::AppError,
+    models::settings::UserConfig,
+    services::{
+        backup::BackupService,
+        crypto::CryptoService,
+        git::GitService,
+        notify::Notifier,
+        secrets::{self, SecretStore},
+        storage::StorageService,
+    },
 };
 
 #[derive(Clone)]
 pub struct AppState {
     pub config: AppConfig,
     pub db: DbPool,
+    pub crypto: CryptoService,
     pub storage: StorageService,
+    pub secrets: SecretStore,
     pub git: GitService,
+    pub backup: BackupService,
+    pub notifier: Notifier,
     pub cookie_key: Key,
+    pub started_at: DateTime<Utc>,
 }
 
 impl AppState {
-    pub fn new(config: AppConfig, db: DbPool, storage: StorageService, git: GitService) -> Self {
+    pub fn new(
+        config: AppConfig,
+        db: DbPool,
+        crypto: CryptoService,
+        storage: StorageService,
+        git: GitService,
+    ) -> Self {
         let digest = Sha512::digest(config.cookie_secret.as_bytes());
         let cookie_key = Key::from(&digest[..]);
+        let secrets = SecretStore::new(db.clone(), &config.secrets_key);
+        let notifier = Notifier::new(&config, &secrets);
+        let backup = BackupService::new(
+            config.backup.clone(),
+            config.admin_alert_contact.clone(),
+            notifier.clone(),
+        );
         Self {
             config,
             db,
+            crypto,
             storage,
+            secrets,
             git,
+            backup,
+            notifier,
             cookie_key,
+            started_at: Utc::now(),
         }
     }
+
+    /// Loads a user's settings, falling back to defaults for new accounts.
+    /// Plaintext Matrix tokens from older configs are moved into the
+    /// secrets store and the config is rewritten without them.
+    pub async fn load_user_config(
+        &self,
+        user_uuid: &str,
+        username: &str,
+    ) -> Result<UserConfig, AppError> {
+        let config = self.read_user_config(user_uuid, username).await?;
+        if config.legacy_matrix_access_token.is_none() {
+            return Ok(config);
+        }
+        // Migrating rewrites the file, so it starts over under the lock.
+        let _writing = self.storage.lock_user(user_uuid).await;
+        self.load_user_config_locked(user_uuid, username).await
+    }
+
+    /// [`Self::load_user_config`] for callers that already hold the user's
+    /// write lock.
+    pub async fn load_user_config_locked(
+        &self,
+        user_uuid: &str,
+        username: &str,
+    ) -> Result<UserConfig, AppError> {
+        let mut config = self.read_user_config(user_uuid, username).await?;
+        if let Some(token) = config.legacy_matrix_access_token.take() {
+            let id = self
+                .secrets
+                .upsert(
+                    config.matrix_access_token_secret.as_deref(),
+                    user_uuid,
+                    secrets::MATRIX_ACCESS_TOKEN,
+                    &token,
+                )
+                .await?;
+            config.matrix_access_token_secret = Some(id);
+            self.storage.save_user_config(user_uuid, &config).await?;
+        }
+        Ok(config)
+    }
+
+    async fn read_user_config(
+        &self,
+        user_uuid: &str,
+        username: &str,
+    ) -> Result<UserConfig, AppError> {
+        Ok(self
+            .storage
+            .load_user_config(user_uuid)
+            .await?
+            .unwrap_or_else(|| UserConfig {
+                username: username.to_string(),
+                display_name: username.to_string(),
+                ..UserConfig::default()
+            }))
+    }
 }
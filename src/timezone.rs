@@ -0,0 +1,52 @@
+//! Converting between stored UTC timestamps and a user's IANA timezone.
+
+use chrono::{DateTime, Duration, LocalResult, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+
+pub const DEFAULT_TIMEZONE: &str = "Europe/Berlin";
+
+/// Parses an IANA name such as `Europe/Berlin`.
+pub fn parse(name: &str) -> Option<Tz> {
+    name.trim().parse().ok()
+}
+
+/// All known IANA names, for the settings form.
+pub fn names() -> impl Iterator<Item = &'static str> {
+    chrono_tz::TZ_VARIANTS.iter().map(|tz| tz.name())
+}
+
+pub fn format(ts: DateTime<Utc>, tz: Tz) -> String {
+    ts.with_timezone(&tz).format("%d.%m.%Y %H:%M").to_string()
+}
+
+/// Value for a `datetime-local` input.
+pub fn format_input(ts: DateTime<Utc>, tz: Tz) -> String {
+    ts.with_timezone(&tz).format("%Y-%m-%dT%H:%M").to_string()
+}
+
+/// Resolves a wall-clock time in `tz`. Times that occur twice when clocks go
+/// back resolve to the first occurrence; times skipped when clocks go
+/// forward are moved past the gap, like a wall clock would be.
+pub fn from_local(naive: NaiveDateTime, tz: Tz) -> Option<DateTime<Utc>> {
+    let resolved = match tz.from_local_datetime(&naive) {
+        LocalResult::Single(dt) => dt,
+        LocalResult::Ambiguous(earliest, _) => earliest,
+        LocalResult::None => (1..=3)
+            .map(|hours| naive + Duration::hours(hours))
+            .find_map(|shifted| tz.from_local_datetime(&shifted).earliest())?,
+    };
+    Some(resolved.with_timezone(&Utc))
+}
+
+/// Calendar day of `ts` in `tz`, for bucketing statistics by day.
+pub fn local_date(ts: DateTime<Utc>, tz: Tz) -> NaiveDate {
+    ts.with_timezone(&tz).date_naive()
+}
+
+/// UTC range `[start, end)` covered by `date` in `tz`. Days around DST
+/// transitions are 23 or 25 hours long.
+pub fn day_bounds(date: NaiveDate, tz: Tz) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+    let start = from_local(date.and_hms_opt(0, 0, 0)?, tz)?;
+    let end = from_local(date.succ_opt()?.and_hms_opt(0, 0, 0)?, tz)?;
+    Some((start, end))
+}
@@ -1,23 +1,184 @@
 #![allow(dead_code)]
 
-use std::{fmt, fs::File, net::SocketAddr};
+use std::{
+    cmp::Reverse,
+    fmt,
+    fs::File,
+    net::SocketAddr,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU16, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
 
 use anyhow::Context;
+use async_trait::async_trait;
+use axum::{
+    body::{Body, Bytes},
+    extract::State,
+    http::{header, HeaderMap, Method, Request, StatusCode, Uri},
+    routing::post,
+    Router,
+};
 use cucumber::{given, then, when, World as _};
 use mood::{
     auth::{self, AuthenticatedUser},
-    config::AppConfig,
+    charts::LineChart,
+    config::{self, AppConfig, BackupConfig, MatrixBotConfig},
     db::init_pool,
-    models::checkin::Checkin,
-    services::{git::GitService, storage::StorageService},
+    error::AppError,
+    i18n::{self, Lang},
+    models::{
+        api_token::ApiScope,
+        checkin::{Checkin, DrugEntry},
+        job::{JobRecord, JobSchedule},
+        settings::{ChannelKind, Contact, TrendSensitivity, UserConfig},
+        webhook::WebhookEvent,
+    },
+    routes,
+    services::{
+        analytics::{self, MoodStats, SubstanceReport, TrendSignal},
+        crypto::CryptoService,
+        git::GitService,
+        history,
+        jobs::{self, Job, JobRunner},
+        matrix::MatrixChannel,
+        matrix_bot,
+        notify::{Message, NotificationChannel, Origin},
+        outbox, reminders, secrets,
+        storage::StorageService,
+        summary::{self, SummaryPeriod},
+        tokens, webhooks,
+    },
     state::AppState,
+    timezone,
 };
 use tempfile::TempDir;
+use tokio::{sync::watch, task::JoinHandle};
+use tower::Service;
 
 #[derive(Debug, cucumber::World, Default)]
 struct AppWorld {
     state: Option<TestState>,
     registered_user: Option<AuthenticatedUser>,
+    user_config: Option<UserConfig>,
+    response: Option<TestResponse>,
+    summaries_sent: Option<usize>,
+    session_id: Option<String>,
+    /// `id` of the last JSON object the API returned, or of the last
+    /// check-in deleted, for `{id}` in paths.
+    last_id: Option<String>,
+    /// Sent as `Authorization: Bearer` when set.
+    api_token: Option<String>,
+    /// Sent as `Accept-Language` when set.
+    browser_language: Option<String>,
+    api_token_id: Option<i64>,
+    webhook_receiver: Option<WebhookReceiver>,
+    webhook_secret: Option<String>,
+    /// What the Matrix bot answered to the last message; `Some(None)` if it
+    /// ignored it.
+    bot_reply: Option<Option<String>>,
+    /// Jobs added by the scenario, and how often any of them ran.
+    jobs: Vec<Job>,
+    job_runs: Arc<AtomicUsize>,
+    scrub_report: Option<secrets::ScrubReport>,
+    /// A started job runner and the sender that shuts it down.
+    running_jobs: Option<(watch::Sender<bool>, JoinHandle<()>)>,
+}
+
+const UNREACHABLE_HOMESERVER: &str = ":unreachable.example";
+
+/// Stands in for the Matrix channel, which needs a real homeserver, and
+/// accepts and records every message, except to IDs on
+/// [`UNREACHABLE_HOMESERVER`].
+#[derive(Default)]
+struct AcceptingMatrixChannel {
+    sent: Arc<Mutex<Vec<(String, Message)>>>,
+}
+
+#[async_trait]
+impl NotificationChannel for AcceptingMatrixChannel {
+    fn kind(&self) -> ChannelKind {
+        ChannelKind::Matrix
+    }
+
+    async fn send(
+        &self,
+        _origin: Origin<'_>,
+        address: &str,
+        message: &Message,
+    ) -> Result<(), AppError> {
+        if address.ends_with(UNREACHABLE_HOMESERVER) {
+            return Err(AppError::Other(anyhow::anyhow!("homeserver unreachable")));
+        }
+        self.sent
+            .lock()
+            .expect("sent messages lock")
+            .push((address.to_string(), message.clone()));
+        Ok(())
+    }
+}
+
+/// A local HTTP server that records the webhooks it receives and answers
+/// with a configurable status.
+#[derive(Debug, Clone)]
+struct WebhookReceiver {
+    url: String,
+    status: Arc<AtomicU16>,
+    received: Arc<Mutex<Vec<ReceivedRequest>>>,
+}
+
+#[derive(Debug, Clone)]
+struct ReceivedRequest {
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+impl WebhookReceiver {
+    async fn start(status: u16) -> Self {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind webhook receiver");
+        let addr = listener.local_addr().expect("receiver address");
+        let receiver = Self {
+            url: format!("http://{addr}/hook"),
+            status: Arc::new(AtomicU16::new(status)),
+            received: Arc::default(),
+        };
+        let app = Router::new()
+            .route("/hook", post(receive_webhook))
+            .with_state(receiver.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        receiver
+    }
+
+    fn received(&self) -> Vec<ReceivedRequest> {
+        self.received.lock().expect("receiver lock").clone()
+    }
+}
+
+async fn receive_webhook(
+    State(receiver): State<WebhookReceiver>,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> StatusCode {
+    receiver
+        .received
+        .lock()
+        .expect("receiver lock")
+        .push(ReceivedRequest { uri, headers, body });
+    StatusCode::from_u16(receiver.status.load(Ordering::SeqCst)).expect("status code")
+}
+
+#[derive(Debug)]
+struct TestResponse {
+    status: StatusCode,
+    location: Option<String>,
+    body: String,
 }
 
 impl AppWorld {
@@ -31,6 +192,12 @@ impl AppWorld {
 
 struct TestState {
     app: AppState,
+    backup_remote: PathBuf,
+    /// Messages the stand-in Matrix channel accepted.
+    matrix_sent: Arc<Mutex<Vec<(String, Message)>>>,
+    /// The auto-commit worker, once a scenario starts it, and the sender
+    /// that shuts it down.
+    auto_commit: Option<(watch::Sender<bool>, JoinHandle<()>)>,
     _root: TempDir,
 }
 
@@ -43,10 +210,12 @@ impl fmt::Debug for TestState {
 impl TestState {
     async fn new() -> anyhow::Result<Self> {
         let root = TempDir::new().context("create temp dir for bdd world")?;
-        let ai_root = root.path().join("ai");
         let repo_root = root.path().join("repo");
+        let ai_root = repo_root.join("ai");
         std::fs::create_dir_all(&ai_root)?;
-        std::fs::create_dir_all(&repo_root)?;
+
+        let backup_remote = root.path().join("backup.git");
+        git2::Repository::init_bare(&backup_remote)?;
 
         let db_path = root.path().join("bdd.sqlite");
         File::create(&db_path)?;
@@ -58,19 +227,42 @@ impl TestState {
             ai_root: ai_root.clone(),
             repo_root: repo_root.clone(),
             cookie_secret: "bdd-cookie-secret".into(),
+            data_escrow_key: None,
+            secrets_key: "bdd-secrets-key".into(),
+            git_commit_debounce: Duration::from_millis(50),
+            backup: Some(BackupConfig {
+                remote_url: format!("file://{}", backup_remote.to_string_lossy()),
+                ssh_key: None,
+                push_interval: Duration::from_secs(3600),
+                alert_after: Duration::from_secs(3600),
+            }),
+            admin_alert_contact: None,
+            smtp: None,
+            matrix_bot: None,
+            matrix_store: root.path().join("matrix-store"),
         };
 
         let db = init_pool(&config.database_url).await?;
         sqlx::migrate!("./migrations").run(&db).await?;
 
-        let storage = StorageService::new(config.ai_root.clone());
+        let crypto = CryptoService::new(db.clone(), config.data_escrow_key.as_deref());
+        let storage = StorageService::new(config.ai_root.clone(), crypto.clone());
         storage.ensure_structure().await?;
 
         let git = GitService::new(config.repo_root.clone());
         git.init_repo_if_needed()?;
 
-        let app = AppState::new(config, db, storage, git);
-        Ok(Self { app, _root: root })
+        let mut app = AppState::new(config, db, crypto, storage, git);
+        let matrix = AcceptingMatrixChannel::default();
+        let matrix_sent = matrix.sent.clone();
+        app.notifier.register(Arc::new(matrix));
+        Ok(Self {
+            app,
+            backup_remote,
+            matrix_sent,
+            auto_commit: None,
+            _root: root,
+        })
     }
 
     fn app(&self) -> &AppState {
@@ -116,6 +308,12 @@ async fn then_can_authenticate(world: &mut AppWorld, identifier: String, passwor
     assert_eq!(authed.username, identifier);
 }
 
+#[then(regex = r#"^I can't authenticate as \"([^\"]+)\" using password \"([^\"]+)\"$"#)]
+async fn then_cannot_authenticate(world: &mut AppWorld, identifier: String, password: String) {
+    let result = auth::authenticate_user(world.app_state(), &identifier, &password).await;
+    assert!(matches!(result, Err(AppError::Unauthorized)), "{result:?}");
+}
+
 #[when(regex = r#"^I submit a check-in with mood (-?\d+) and high (\d+) and notes \"([^\"]*)\"$"#)]
 async fn when_submit_checkin(world: &mut AppWorld, mood: i32, high: i32, notes: String) {
     let user = world
@@ -136,6 +334,35 @@ async fn when_submit_checkin(world: &mut AppWorld, mood: i32, high: i32, notes:
         .expect("append checkin");
 }
 
+#[when(regex = r"^I submit (\d+) check-ins at once$")]
+async fn when_submit_concurrent_checkins(world: &mut AppWorld, count: usize) {
+    let user = world
+        .registered_user
+        .as_ref()
+        .expect("user must exist before creating checkins");
+    let mut saves = tokio::task::JoinSet::new();
+    for mood in 0..count {
+        let storage = world.app_state().storage.clone();
+        let user_uuid = user.uuid.clone();
+        let mut checkin = Checkin::new(&user_uuid);
+        checkin.mood = (mood % 5) as i32;
+        saves.spawn(async move { storage.append_checkin(&user_uuid, checkin).await });
+    }
+    while let Some(saved) = saves.join_next().await {
+        saved.expect("save task").expect("append checkin");
+    }
+}
+
+#[then(regex = r"^the latest stored check-in (feels|doesn't feel) safe$")]
+async fn then_latest_feels_safe(world: &mut AppWorld, answer: String) {
+    let user = world
+        .registered_user
+        .as_ref()
+        .expect("user must exist before assertions");
+    let latest = latest_checkin(world.app_state(), &user.uuid).await;
+    assert_eq!(latest.feels_safe, answer == "feels");
+}
+
 #[then(regex = r"^the user has (\d+) stored check-ins$")]
 async fn then_user_has_checkins(world: &mut AppWorld, expected: usize) {
     let user = world
@@ -163,12 +390,1895 @@ async fn then_latest_has_values(world: &mut AppWorld, mood: i32, high: i32) {
         .load_user_checkins(&user.uuid)
         .await
         .expect("load checkins");
-    checkins.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+    checkins.sort_by_key(|item| Reverse(item.timestamp));
     let latest = checkins.first().expect("at least one checkin expected");
     assert_eq!(latest.mood, mood);
     assert_eq!(latest.high_level, high);
 }
 
+#[then(regex = r#"^the stored check-in file does not contain \"([^\"]+)\"$"#)]
+async fn then_checkin_file_is_sealed(world: &mut AppWorld, needle: String) {
+    let user = world
+        .registered_user
+        .as_ref()
+        .expect("user must exist before assertions");
+    let path = world
+        .app_state()
+        .storage
+        .user_dir(&user.uuid)
+        .join("checkins.json");
+    let raw = std::fs::read(path).expect("read checkins file");
+    assert!(!raw.windows(needle.len()).any(|w| w == needle.as_bytes()));
+}
+
+#[when("the user's data key is locked")]
+async fn when_data_key_locked(world: &mut AppWorld) {
+    let user = world
+        .registered_user
+        .as_ref()
+        .expect("user must exist before locking");
+    world.app_state().crypto.lock(&user.uuid);
+}
+
+#[then("loading the user's check-ins fails as locked")]
+async fn then_loading_is_locked(world: &mut AppWorld) {
+    let user = world
+        .registered_user
+        .as_ref()
+        .expect("user must exist before assertions");
+    let result = world
+        .app_state()
+        .storage
+        .load_user_checkins(&user.uuid)
+        .await;
+    assert!(matches!(result, Err(mood::error::AppError::Locked)));
+}
+
+#[when("the data repository is committed")]
+async fn when_repository_committed(world: &mut AppWorld) {
+    world
+        .app_state()
+        .git
+        .commit_ai_changes("bdd: commit")
+        .expect("commit ai changes");
+}
+
+#[given(regex = r"^the auto-commit worker runs with a debounce of (\d+) ms$")]
+async fn given_auto_commit(world: &mut AppWorld, debounce_ms: u64) {
+    let state = world
+        .state
+        .as_mut()
+        .expect("state must be initialised first");
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+    let handle = state
+        .app
+        .git
+        .start_auto_commit(Duration::from_millis(debounce_ms), shutdown_rx);
+    state.auto_commit = Some((shutdown_tx, handle));
+}
+
+#[when("the auto-commit worker is shut down")]
+async fn when_auto_commit_shut_down(world: &mut AppWorld) {
+    let state = world
+        .state
+        .as_mut()
+        .expect("state must be initialised first");
+    let (shutdown_tx, handle) = state
+        .auto_commit
+        .take()
+        .expect("a running auto-commit worker");
+    shutdown_tx.send_replace(true);
+    tokio::time::timeout(Duration::from_secs(5), handle)
+        .await
+        .expect("worker stops in time")
+        .expect("worker task");
+}
+
+/// The full message of the data repository's latest commit, if any.
+fn latest_commit_message(world: &AppWorld) -> Option<String> {
+    let repo = git2::Repository::open(&world.app_state().config.repo_root).expect("open repo");
+    let commit = repo.head().ok()?.peel_to_commit().expect("head commit");
+    Some(commit.message().unwrap_or_default().to_string())
+}
+
+/// `text` with `{user}` replaced by the registered user's UUID.
+fn with_user(world: &AppWorld, text: &str) -> String {
+    let user = world.registered_user.as_ref().expect("a registered user");
+    text.replace("{user}", &user.uuid)
+}
+
+#[then(regex = r#"^the latest data commit is \"([^\"]+)\" within (\d+) seconds?$"#)]
+async fn then_latest_commit_within(world: &mut AppWorld, expected: String, seconds: u64) {
+    let expected = with_user(world, &expected);
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(seconds);
+    loop {
+        let message = latest_commit_message(world);
+        if message
+            .as_deref()
+            .is_some_and(|message| message.lines().next() == Some(expected.as_str()))
+        {
+            return;
+        }
+        assert!(
+            tokio::time::Instant::now() < deadline,
+            "latest commit is {message:?}, expected {expected:?}"
+        );
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+}
+
+#[then(regex = r#"^the latest data commit is \"([^\"]+)\"$"#)]
+async fn then_latest_commit(world: &mut AppWorld, expected: String) {
+    let expected = with_user(world, &expected);
+    let message = latest_commit_message(world).expect("a commit");
+    assert_eq!(message.lines().next(), Some(expected.as_str()));
+}
+
+#[then(regex = r#"^the latest data commit lists \"([^\"]+)\"$"#)]
+async fn then_latest_commit_lists(world: &mut AppWorld, expected: String) {
+    let expected = format!("- {}", with_user(world, &expected));
+    let message = latest_commit_message(world).expect("a commit");
+    assert!(
+        message.lines().any(|line| line == expected),
+        "{expected:?} missing from {message:?}"
+    );
+}
+
+#[then(regex = r#"^the latest data commit mentions \"([^\"]+)\"$"#)]
+async fn then_latest_commit_mentions(world: &mut AppWorld, expected: String) {
+    let expected = with_user(world, &expected);
+    let message = latest_commit_message(world).expect("a commit");
+    assert!(
+        message.contains(&expected),
+        "{expected:?} missing from {message:?}"
+    );
+}
+
+#[when(regex = r"^(\d+) data files are changed and committed at once$")]
+async fn when_commits_race(world: &mut AppWorld, count: usize) {
+    let state = world.app_state();
+    let tasks: Vec<_> = (0..count)
+        .map(|i| {
+            let git = state.git.clone();
+            let path = state.config.ai_root.join(format!("race-{i}.txt"));
+            tokio::task::spawn_blocking(move || {
+                std::fs::write(&path, format!("change {i}")).expect("write data file");
+                git.commit_ai_changes(&format!("bdd: race {i}"))
+            })
+        })
+        .collect();
+    for task in tasks {
+        task.await.expect("commit task").expect("commit");
+    }
+}
+
+#[then("the data repository has no uncommitted changes")]
+async fn then_nothing_uncommitted(world: &mut AppWorld) {
+    let status = world.app_state().git.status(10).expect("repo status");
+    assert_eq!(status.uncommitted, Vec::<String>::new());
+}
+
+#[when("the data repository is pushed to the backup remote")]
+async fn when_pushed_to_backup(world: &mut AppWorld) {
+    let state = world.app_state();
+    state
+        .backup
+        .push_once(&state.git)
+        .await
+        .expect("push to backup remote");
+}
+
+#[then("the backup remote has the current HEAD")]
+async fn then_backup_has_head(world: &mut AppWorld) {
+    let test_state = world.state.as_ref().expect("state");
+    let local = git2::Repository::open(&test_state.app().config.repo_root).expect("open repo");
+    let head = local.head().expect("local head");
+    let remote = git2::Repository::open_bare(&test_state.backup_remote).expect("open backup");
+    let mirrored = remote
+        .find_reference(head.name().expect("branch name"))
+        .expect("branch pushed to backup");
+    assert_eq!(mirrored.target(), head.target());
+}
+
+#[given(regex = r#"^the file \"([^\"]+)\" is committed to the data repository$"#)]
+async fn given_file_committed(world: &mut AppWorld, path: String) {
+    let repo_root = &world.app_state().config.repo_root;
+    let file = repo_root.join(&path);
+    std::fs::create_dir_all(file.parent().expect("parent dir")).expect("create dir");
+    std::fs::write(&file, "fn main() {}\n").expect("write file");
+    let repo = git2::Repository::open(repo_root).expect("open repo");
+    let mut index = repo.index().expect("index");
+    index
+        .add_path(std::path::Path::new(&path))
+        .expect("stage file");
+    index.write().expect("write index");
+    let tree = repo
+        .find_tree(index.write_tree().expect("write tree"))
+        .expect("tree");
+    let signature = git2::Signature::now("bdd", "bdd@local").expect("signature");
+    repo.commit(Some("HEAD"), &signature, &signature, "code", &tree, &[])
+        .expect("commit file");
+}
+
+#[then("pushing to the backup remote is refused")]
+async fn then_backup_push_refused(world: &mut AppWorld) {
+    let state = world.app_state();
+    let result = state.backup.push_once(&state.git).await;
+    assert!(matches!(result, Err(AppError::Config(_))), "{result:?}");
+}
+
+#[then("scrubbing committed tokens is refused")]
+async fn then_scrub_refused(world: &mut AppWorld) {
+    let result = secrets::scrub_committed_tokens(world.app_state()).await;
+    assert!(matches!(result, Err(AppError::Config(_))), "{result:?}");
+}
+
+#[then("the backup remote has no branches")]
+async fn then_backup_empty(world: &mut AppWorld) {
+    let test_state = world.state.as_ref().expect("state");
+    let remote = git2::Repository::open_bare(&test_state.backup_remote).expect("open backup");
+    assert!(remote.branches(None).expect("branches").next().is_none());
+}
+
+#[then("the backup status reports a recent success")]
+async fn then_backup_status_ok(world: &mut AppWorld) {
+    let status = world.app_state().backup.status();
+    assert!(status.last_success_at.is_some());
+    assert!(status.last_error.is_none());
+    assert_eq!(status.consecutive_failures, 0);
+}
+
+#[when(regex = r"^I change the latest check-in's mood to (-?\d+)$")]
+async fn when_change_latest_mood(world: &mut AppWorld, mood: i32) {
+    let user = world
+        .registered_user
+        .as_ref()
+        .expect("user must exist before editing");
+    let state = world.app_state();
+    let mut latest = latest_checkin(state, &user.uuid).await;
+    latest.mood = mood;
+    state
+        .storage
+        .replace_checkin(&user.uuid, latest)
+        .await
+        .expect("update checkin");
+}
+
+#[then(regex = r"^the latest check-in has (\d+) historical versions$")]
+async fn then_version_count(world: &mut AppWorld, expected: usize) {
+    let user = world
+        .registered_user
+        .as_ref()
+        .expect("user must exist before assertions");
+    let state = world.app_state();
+    let latest = latest_checkin(state, &user.uuid).await;
+    let versions = history::checkin_versions(state, &user.uuid, &latest.id)
+        .await
+        .expect("load history");
+    assert_eq!(versions.len(), expected);
+}
+
+#[when("I restore the oldest version of the latest check-in")]
+async fn when_restore_oldest(world: &mut AppWorld) {
+    let user = world
+        .registered_user
+        .as_ref()
+        .expect("user must exist before restoring");
+    let state = world.app_state();
+    let latest = latest_checkin(state, &user.uuid).await;
+    let versions = history::checkin_versions(state, &user.uuid, &latest.id)
+        .await
+        .expect("load history");
+    let oldest = versions.last().expect("at least one version");
+    history::restore_checkin(state, &user.uuid, &latest.id, &oldest.commit.oid)
+        .await
+        .expect("restore version");
+}
+
+#[when("I restore the trashed version of the latest check-in")]
+async fn when_restore_trashed(world: &mut AppWorld) {
+    let user = world
+        .registered_user
+        .as_ref()
+        .expect("user must exist before restoring");
+    let state = world.app_state();
+    let latest = latest_checkin(state, &user.uuid).await;
+    let versions = history::checkin_versions(state, &user.uuid, &latest.id)
+        .await
+        .expect("load history");
+    let trashed = versions
+        .iter()
+        .find(|version| version.checkin.is_deleted())
+        .expect("a version from the trash");
+    history::restore_checkin(state, &user.uuid, &latest.id, &trashed.commit.oid)
+        .await
+        .expect("restore version");
+}
+
+#[then("the oldest version of the deleted check-in can't be restored")]
+async fn then_restore_refused(world: &mut AppWorld) {
+    let user = world
+        .registered_user
+        .as_ref()
+        .expect("user must exist before restoring");
+    let checkin_id = world.last_id.as_deref().expect("a deleted check-in");
+    let state = world.app_state();
+    let versions = history::checkin_versions(state, &user.uuid, checkin_id)
+        .await
+        .expect("load history");
+    let oldest = versions.last().expect("at least one version");
+    let result = history::restore_checkin(state, &user.uuid, checkin_id, &oldest.commit.oid).await;
+    assert!(
+        matches!(result, Err(AppError::NotFound)),
+        "restore of a purged check-in: {result:?}"
+    );
+}
+
+#[when("I delete the latest check-in")]
+async fn when_delete_latest(world: &mut AppWorld) {
+    let user = world
+        .registered_user
+        .as_ref()
+        .expect("user must exist before deleting");
+    let state = world.app_state();
+    let latest = latest_checkin(state, &user.uuid).await;
+    let trashed = state
+        .storage
+        .trash_checkin(&user.uuid, &latest.id)
+        .await
+        .expect("trash checkin");
+    assert!(trashed);
+    world.last_id = Some(latest.id);
+}
+
+#[when("I take the latest check-in out of the trash")]
+async fn when_untrash_latest(world: &mut AppWorld) {
+    let user = world
+        .registered_user
+        .as_ref()
+        .expect("user must exist before undeleting");
+    let state = world.app_state();
+    let latest = latest_checkin(state, &user.uuid).await;
+    let untrashed = state
+        .storage
+        .untrash_checkin(&user.uuid, &latest.id)
+        .await
+        .expect("untrash checkin");
+    assert!(untrashed);
+}
+
+#[when("I empty the trash")]
+async fn when_empty_trash(world: &mut AppWorld) {
+    let user = world
+        .registered_user
+        .as_ref()
+        .expect("user must exist before purging");
+    world
+        .app_state()
+        .storage
+        .purge_checkins(&user.uuid, |_| true)
+        .await
+        .expect("purge trash");
+}
+
+#[when(regex = r"^the trashed check-ins were deleted (\d+) days ago$")]
+async fn when_trash_aged(world: &mut AppWorld, days: i64) {
+    let user = world
+        .registered_user
+        .as_ref()
+        .expect("user must exist before aging the trash");
+    let state = world.app_state();
+    let mut checkins = state
+        .storage
+        .load_user_checkins(&user.uuid)
+        .await
+        .expect("load checkins");
+    for checkin in checkins.iter_mut().filter(|checkin| checkin.is_deleted()) {
+        checkin.deleted_at = Some(chrono::Utc::now() - chrono::Duration::days(days));
+    }
+    state
+        .storage
+        .save_user_checkins(&user.uuid, &checkins)
+        .await
+        .expect("save checkins");
+}
+
+#[then(regex = r"^the user has (\d+) check-ins? in the trash$")]
+async fn then_trash_count(world: &mut AppWorld, expected: usize) {
+    let user = world
+        .registered_user
+        .as_ref()
+        .expect("user must exist before assertions");
+    let trashed = world
+        .app_state()
+        .storage
+        .load_user_checkins(&user.uuid)
+        .await
+        .expect("load checkins")
+        .into_iter()
+        .filter(Checkin::is_deleted)
+        .count();
+    assert_eq!(trashed, expected);
+}
+
+#[given(regex = r#"^a user config with timezone \"([^\"]+)\"$"#)]
+async fn given_user_timezone(world: &mut AppWorld, name: String) {
+    world.user_config = Some(UserConfig {
+        timezone: name,
+        ..UserConfig::default()
+    });
+}
+
+#[given(regex = r#"^the user's timezone is \"([^\"]+)\"$"#)]
+async fn given_registered_user_timezone(world: &mut AppWorld, name: String) {
+    update_user_config(world, |config| config.timezone = name).await;
+}
+
+/// Submits the check-in form with a `datetime-local` value `minutes` away
+/// from now in the user's timezone.
+#[when(regex = r"^I submit a check-in with mood (-?\d+) dated (\d+) minutes (ago|from now)$")]
+async fn when_submit_dated_checkin(
+    world: &mut AppWorld,
+    mood: i32,
+    minutes: i64,
+    direction: String,
+) {
+    let user = world.registered_user.as_ref().expect("registered user");
+    let config = world
+        .app_state()
+        .load_user_config(&user.uuid, &user.username)
+        .await
+        .expect("load config");
+    let offset = chrono::Duration::minutes(minutes);
+    let timestamp = match direction.as_str() {
+        "ago" => chrono::Utc::now() - offset,
+        _ => chrono::Utc::now() + offset,
+    };
+    let local = timestamp
+        .with_timezone(&config.tz())
+        .format("%Y-%m-%dT%H:%M");
+    let form = format!("timestamp={local}&mood={mood}&high_level=0");
+    when_submit_form(world, form, "/me/checkins/new".to_string()).await;
+}
+
+#[then(regex = r#"^the latest stored check-in was made at \"([^\"]+)\" UTC$"#)]
+async fn then_latest_checkin_at(world: &mut AppWorld, expected: String) {
+    let user = world.registered_user.as_ref().expect("registered user");
+    let latest = latest_checkin(world.app_state(), &user.uuid).await;
+    let expected: chrono::DateTime<chrono::Utc> = expected.parse().expect("RFC 3339 timestamp");
+    assert_eq!(latest.timestamp, expected);
+}
+
+#[then(regex = r#"^\"([^\"]+)\" is shown as \"([^\"]+)\"$"#)]
+async fn then_shown_as(world: &mut AppWorld, utc: String, expected: String) {
+    let config = world.user_config.as_ref().expect("user config");
+    let ts = utc.parse().expect("RFC 3339 timestamp");
+    assert_eq!(timezone::format(ts, config.tz()), expected);
+}
+
+#[then(regex = r#"^the message \"([^\"]+)\" in \"([^\"]+)\" reads \"([^\"]+)\"$"#)]
+async fn then_message_reads(_world: &mut AppWorld, key: String, lang: String, expected: String) {
+    let lang = Lang::from_code(&lang).expect("supported language");
+    assert_eq!(i18n::tr_in(lang, &key, &[]), expected);
+}
+
+/// Keys passed to the `t`, `t1` and `t2` filters anywhere in `templates/`.
+fn template_message_keys() -> Vec<(PathBuf, String)> {
+    let mut dirs = vec![PathBuf::from("templates")];
+    let mut keys = Vec::new();
+    while let Some(dir) = dirs.pop() {
+        for entry in std::fs::read_dir(&dir).expect("read templates dir") {
+            let path = entry.expect("templates entry").path();
+            if path.is_dir() {
+                dirs.push(path);
+                continue;
+            }
+            let source = std::fs::read_to_string(&path).expect("read template");
+            for (end, _) in source.match_indices("\"|t") {
+                let start = source[..end].rfind('"').expect("opening quote") + 1;
+                keys.push((path.clone(), source[start..end].to_string()));
+            }
+        }
+    }
+    keys
+}
+
+#[then(regex = r#"^every message the templates use exists in \"([^\"]+)\"$"#)]
+async fn then_template_messages_exist(_world: &mut AppWorld, lang: String) {
+    let lang = Lang::from_code(&lang).expect("supported language");
+    let keys = template_message_keys();
+    assert!(
+        !keys.is_empty(),
+        "no translated messages found in templates/"
+    );
+    let missing: Vec<_> = keys
+        .iter()
+        .filter(|(_, key)| !i18n::has_message(lang, key))
+        .map(|(path, key)| format!("{} uses {key}", path.display()))
+        .collect();
+    assert!(missing.is_empty(), "missing in {lang}.ftl: {missing:#?}");
+}
+
+#[then(regex = r#"^the Accept-Language header \"([^\"]+)\" selects \"([^\"]+)\"$"#)]
+async fn then_accept_language_selects(_world: &mut AppWorld, header: String, expected: String) {
+    let lang = Lang::from_accept_language(&header).unwrap_or_default();
+    assert_eq!(lang.code(), expected);
+}
+
+#[when(regex = r#"^I request \"([^\"]+)\"$"#)]
+async fn when_request(world: &mut AppWorld, path: String) {
+    let request = Request::get(path).body(Body::empty()).expect("request");
+    send_request(world, request).await;
+}
+
+#[when(regex = r#"^I submit the form \"([^\"]*)\" to \"([^\"]+)\"$"#)]
+async fn when_submit_form(world: &mut AppWorld, form: String, path: String) {
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri(path)
+        .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body(Body::from(form))
+        .expect("request");
+    send_request(world, request).await;
+}
+
+#[then(regex = r"^the response status is (\d+)$")]
+async fn then_response_status(world: &mut AppWorld, expected: u16) {
+    let response = world.response.as_ref().expect("a response");
+    assert_eq!(
+        response.status.as_u16(),
+        expected,
+        "body: {}",
+        response.body
+    );
+}
+
+#[then(regex = r#"^the response redirects to \"([^\"]+)\"$"#)]
+async fn then_response_redirects(world: &mut AppWorld, expected: String) {
+    let response = world.response.as_ref().expect("a response");
+    assert!(
+        response.status.is_redirection(),
+        "status {}",
+        response.status
+    );
+    assert_eq!(response.location.as_deref(), Some(expected.as_str()));
+}
+
+#[when("I follow the redirect")]
+async fn when_follow_redirect(world: &mut AppWorld) {
+    let response = world.response.as_ref().expect("a response");
+    let location = response.location.clone().expect("a redirect");
+    let request = Request::get(location).body(Body::empty()).expect("request");
+    send_request(world, request).await;
+}
+
+#[then(regex = r#"^the response contains \"([^\"]+)\"$"#)]
+async fn then_response_contains(world: &mut AppWorld, needle: String) {
+    let response = world.response.as_ref().expect("a response");
+    assert!(response.body.contains(&needle), "body: {}", response.body);
+}
+
+#[then(regex = r#"^the form field \"([^\"]+)\" has the value \"([^\"]*)\"$"#)]
+async fn then_form_field_value(world: &mut AppWorld, name: String, value: String) {
+    let response = world.response.as_ref().expect("a response");
+    let needle = format!(r#"name="{name}" value="{value}""#);
+    assert!(response.body.contains(&needle), "body: {}", response.body);
+}
+
+#[given("the registered user is logged in")]
+async fn given_logged_in(world: &mut AppWorld) {
+    let user = world.registered_user.as_ref().expect("registered user");
+    let session_id = auth::create_session(world.app_state(), user.id)
+        .await
+        .expect("create session");
+    world.session_id = Some(session_id);
+}
+
+#[when(regex = r#"^I send \"(GET|POST|DELETE)\" to \"([^\"]+)\"$"#)]
+async fn when_send_api(world: &mut AppWorld, method: String, path: String) {
+    send_json(world, &method, &path, None).await;
+}
+
+#[when(regex = r#"^I send \"(POST|PUT|PATCH)\" to \"([^\"]+)\" with the JSON '(.*)'$"#)]
+async fn when_send_api_json(world: &mut AppWorld, method: String, path: String, json: String) {
+    send_json(world, &method, &path, Some(json)).await;
+}
+
+#[when(regex = r"^the settings patches '(.+)' are sent at once$")]
+async fn when_settings_patched_at_once(world: &mut AppWorld, patches: String) {
+    let mut requests = tokio::task::JoinSet::new();
+    for json in patches.split("' and '") {
+        let mut request = Request::builder()
+            .method(Method::PATCH)
+            .uri("/api/v1/settings")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(json.to_string()))
+            .expect("request");
+        authorize(world, &mut request);
+        let mut router = routes::create_router(world.app_state().clone());
+        requests.spawn(async move { router.call(request).await });
+    }
+    while let Some(response) = requests.join_next().await {
+        let response = response
+            .expect("request task")
+            .expect("router is infallible");
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
+
+#[then(regex = r#"^the JSON at \"([^\"]*)\" is (.+)$"#)]
+async fn then_json_at(world: &mut AppWorld, pointer: String, expected: String) {
+    let response = world.response.as_ref().expect("a response");
+    let body: serde_json::Value = serde_json::from_str(&response.body)
+        .unwrap_or_else(|err| panic!("JSON body ({err}): {}", response.body));
+    let expected: serde_json::Value = serde_json::from_str(&expected).expect("expected JSON");
+    assert_eq!(body.pointer(&pointer), Some(&expected), "body: {body}");
+}
+
+#[given(regex = r#"^the user has an API token \"([^\"]+)\" with scopes \"([^\"]+)\"$"#)]
+async fn given_api_token(world: &mut AppWorld, name: String, scopes: String) {
+    let user = world.registered_user.as_ref().expect("registered user");
+    let scopes: Vec<ApiScope> = scopes
+        .split_whitespace()
+        .map(|code| ApiScope::from_code(code).expect("known scope"))
+        .collect();
+    let (token, secret) = tokens::create(&world.app_state().db, user.id, &name, &scopes)
+        .await
+        .expect("create api token");
+    world.api_token_id = Some(token.id);
+    world.api_token = Some(secret);
+}
+
+#[given(regex = r#"^I present the API token \"([^\"]+)\"$"#)]
+async fn given_present_api_token(world: &mut AppWorld, secret: String) {
+    world.api_token = Some(secret);
+}
+
+#[when("the API token is revoked")]
+async fn when_api_token_revoked(world: &mut AppWorld) {
+    let user = world.registered_user.as_ref().expect("registered user");
+    let token_id = world.api_token_id.expect("an api token");
+    let revoked = tokens::revoke(&world.app_state().db, user.id, token_id)
+        .await
+        .expect("revoke api token");
+    assert!(revoked);
+}
+
+#[then("the API token has a last-used time")]
+async fn then_api_token_used(world: &mut AppWorld) {
+    let user = world.registered_user.as_ref().expect("registered user");
+    let token_id = world.api_token_id.expect("an api token");
+    let tokens = tokens::list(&world.app_state().db, user.id)
+        .await
+        .expect("list api tokens");
+    let token = tokens
+        .iter()
+        .find(|token| token.id == token_id)
+        .expect("token still exists");
+    assert!(token.last_used_at.is_some());
+}
+
+async fn send_json(world: &mut AppWorld, method: &str, path: &str, json: Option<String>) {
+    let path = match &world.last_id {
+        Some(id) => path.replace("{id}", id),
+        None => path.to_string(),
+    };
+    let request = Request::builder()
+        .method(Method::from_bytes(method.as_bytes()).expect("method"))
+        .uri(path)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(json.map(Body::from).unwrap_or_else(Body::empty))
+        .expect("request");
+    send_request(world, request).await;
+    let response = world.response.as_ref().expect("a response");
+    if response.status.is_success() {
+        if let Ok(body) = serde_json::from_str::<serde_json::Value>(&response.body) {
+            if let Some(id) = body.get("id").and_then(|id| id.as_str()) {
+                world.last_id = Some(id.to_string());
+            }
+        }
+    }
+}
+
+/// Adds the session cookie, browser language and API token of `world`.
+fn authorize(world: &AppWorld, request: &mut Request<Body>) {
+    if let Some(session_id) = &world.session_id {
+        let cookie = format!("{}={session_id}", auth::SESSION_COOKIE);
+        request
+            .headers_mut()
+            .insert(header::COOKIE, cookie.parse().expect("cookie header"));
+    }
+    if let Some(language) = &world.browser_language {
+        request.headers_mut().insert(
+            header::ACCEPT_LANGUAGE,
+            language.parse().expect("accept-language header"),
+        );
+    }
+    if let Some(secret) = &world.api_token {
+        let bearer = format!("Bearer {secret}");
+        request.headers_mut().insert(
+            header::AUTHORIZATION,
+            bearer.parse().expect("authorization header"),
+        );
+    }
+}
+
+async fn send_request(world: &mut AppWorld, mut request: Request<Body>) {
+    authorize(world, &mut request);
+    let mut router = routes::create_router(world.app_state().clone());
+    let response = router.call(request).await.expect("router is infallible");
+    let status = response.status();
+    let location = response
+        .headers()
+        .get(header::LOCATION)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .expect("read body");
+    world.response = Some(TestResponse {
+        status,
+        location,
+        body: String::from_utf8_lossy(&body).into_owned(),
+    });
+}
+
+#[given(regex = r"^a check-in with mood (-?\d+) logged (\d+) hours ago$")]
+async fn given_checkin_hours_ago(world: &mut AppWorld, mood: i32, hours: i64) {
+    store_checkin_hours_ago(world, mood, hours, true).await;
+}
+
+#[given(regex = r"^an unsafe check-in with mood (-?\d+) logged (\d+) hours ago$")]
+async fn given_unsafe_checkin_hours_ago(world: &mut AppWorld, mood: i32, hours: i64) {
+    store_checkin_hours_ago(world, mood, hours, false).await;
+}
+
+#[then(
+    regex = r"^the (\d+)-day statistics count (\d+) check-ins with an average mood of (-?[\d.]+)$"
+)]
+async fn then_stats_average(world: &mut AppWorld, days: u32, count: usize, mood: f64) {
+    let stats = mood_stats(world, days).await;
+    assert_eq!(stats.checkin_count, count);
+    let average = stats.mood().mean().expect("check-ins in range");
+    assert!((average - mood).abs() < 0.05, "average mood {average}");
+}
+
+#[then(regex = r"^(\d+)% of the (\d+)-day check-ins were unsafe$")]
+async fn then_stats_unsafe_share(world: &mut AppWorld, percent: u32, days: u32) {
+    let stats = mood_stats(world, days).await;
+    let share = stats.unsafe_share().expect("check-ins in range");
+    assert_eq!((share * 100.0).round() as u32, percent);
+}
+
+#[then(regex = r"^the (\d+)-day mood chart has (\d+) points$")]
+async fn then_mood_chart_points(world: &mut AppWorld, days: u32, expected: usize) {
+    let stats = mood_stats(world, days).await;
+    let points: Vec<_> = stats
+        .days
+        .iter()
+        .map(|day| (day.date.to_string(), day.mood.mean()))
+        .collect();
+    let chart = LineChart::new(&points, -5.0, 5.0, 600, 160);
+    assert_eq!(chart.dots.len(), expected);
+}
+
+#[given(
+    regex = r#"^a check-in with mood (-?\d+) and substance \"([^\"]+)\" logged (\d+) hours ago$"#
+)]
+async fn given_checkin_with_substance(
+    world: &mut AppWorld,
+    mood: i32,
+    substance: String,
+    hours: i64,
+) {
+    let user = world.registered_user.as_ref().expect("registered user");
+    let mut checkin = Checkin::new(&user.uuid);
+    checkin.timestamp = chrono::Utc::now() - chrono::Duration::hours(hours);
+    checkin.mood = mood;
+    checkin.drugs.push(DrugEntry {
+        substance,
+        dose: "1".into(),
+        route: None,
+        start_time: None,
+        notes: None,
+    });
+    world
+        .app_state()
+        .storage
+        .append_checkin(&user.uuid, checkin)
+        .await
+        .expect("append checkin");
+}
+
+#[then(
+    regex = r#"^the insights for \"([^\"]+)\" show (\d+) uses, (\d+) check-ins after and a difference of (-?[\d.]+)$"#
+)]
+async fn then_substance_insight(
+    world: &mut AppWorld,
+    substance: String,
+    uses: usize,
+    samples: usize,
+    delta: f64,
+) {
+    let report = substance_report(world).await;
+    let insight = report
+        .substances
+        .iter()
+        .find(|insight| insight.substance.eq_ignore_ascii_case(&substance))
+        .expect("substance in report");
+    assert_eq!(insight.uses, uses);
+    assert_eq!(insight.after_use.count(), samples);
+    let actual = insight.delta(&report.baseline).expect("delta");
+    assert!((actual - delta).abs() < 0.05, "delta {actual}");
+}
+
+#[then(
+    regex = r#"^the latest stored check-in has the substance \"([^\"]+)\" at \"([^\"]*)\" taken \"([^\"]+)\"$"#
+)]
+async fn then_latest_substance(
+    world: &mut AppWorld,
+    substance: String,
+    dose: String,
+    route: String,
+) {
+    let user = world.registered_user.as_ref().expect("registered user");
+    let checkin = latest_checkin(world.app_state(), &user.uuid).await;
+    let [drug] = checkin.drugs.as_slice() else {
+        panic!("expected one substance, got {:?}", checkin.drugs);
+    };
+    assert_eq!(drug.substance, substance);
+    assert_eq!(drug.dose, dose);
+    assert_eq!(drug.route.as_deref(), Some(route.as_str()));
+}
+
+#[then(regex = r#"^the substance report lists \"([^\"]+)\" with (\d+) uses?$"#)]
+async fn then_substance_listed(world: &mut AppWorld, substance: String, uses: usize) {
+    let report = substance_report(world).await;
+    let insight = report
+        .substances
+        .iter()
+        .find(|insight| insight.substance == substance)
+        .expect("substance in report");
+    assert_eq!(insight.uses, uses);
+}
+
+#[then(regex = r"^the substance baseline has (\d+) check-ins$")]
+async fn then_substance_baseline(world: &mut AppWorld, expected: usize) {
+    assert_eq!(substance_report(world).await.baseline.count(), expected);
+}
+
+#[then(regex = r#"^(no|a|a declining) downward trend is detected at sensitivity \"([^\"]+)\"$"#)]
+async fn then_trend_detected(world: &mut AppWorld, expected: String, sensitivity: String) {
+    let sensitivity = TrendSensitivity::from_code(&sensitivity).expect("sensitivity code");
+    let user = world.registered_user.as_ref().expect("registered user");
+    let checkins = world
+        .app_state()
+        .storage
+        .load_user_checkins(&user.uuid)
+        .await
+        .expect("load checkins");
+    let warning = analytics::detect_trend(&checkins, chrono::Utc::now(), sensitivity);
+    match expected.as_str() {
+        "no" => assert_eq!(warning, None),
+        "a" => assert!(warning.is_some()),
+        _ => assert!(matches!(
+            warning.map(|warning| warning.signal),
+            Some(TrendSignal::ConsecutiveDeclines(_))
+        )),
+    }
+}
+
+#[given(regex = r#"^the user opted into weekly summaries for \"([^\"]+)\"$"#)]
+async fn given_weekly_summaries(world: &mut AppWorld, matrix_user_id: String) {
+    update_user_config(world, |config| {
+        config.matrix_user_id = matrix_user_id;
+        config.summary_weekly = true;
+    })
+    .await;
+}
+
+#[given(regex = r#"^the user shares summaries with the therapist \"([^\"]+)\"$"#)]
+async fn given_summary_therapist(world: &mut AppWorld, therapist: String) {
+    update_user_config(world, |config| {
+        config.therapist_contact = Some(Contact::parse(&therapist).expect("valid contact"));
+        config.summary_to_therapist = true;
+    })
+    .await;
+}
+
+#[given(regex = r"^a check-in with mood (-?\d+) logged on day (\d) of last week$")]
+async fn given_checkin_last_week(world: &mut AppWorld, mood: i32, day: i64) {
+    let tz = timezone::parse(timezone::DEFAULT_TIMEZONE).expect("default timezone");
+    let today = timezone::local_date(chrono::Utc::now(), tz);
+    let (monday, _) = SummaryPeriod::Weekly.last_completed(today);
+    let noon = (monday + chrono::Duration::days(day - 1))
+        .and_hms_opt(12, 0, 0)
+        .expect("noon");
+    let user = world.registered_user.as_ref().expect("registered user");
+    let mut checkin = Checkin::new(&user.uuid);
+    checkin.timestamp = timezone::from_local(noon, tz).expect("local noon");
+    checkin.mood = mood;
+    world
+        .app_state()
+        .storage
+        .append_checkin(&user.uuid, checkin)
+        .await
+        .expect("append checkin");
+}
+
+#[when(regex = r#"^I log a check-in with the substances \"([^\"]+)\" on day (\d) of last week$"#)]
+async fn when_log_substances_last_week(world: &mut AppWorld, substances: String, day: i64) {
+    let tz = timezone::parse(timezone::DEFAULT_TIMEZONE).expect("default timezone");
+    let today = timezone::local_date(chrono::Utc::now(), tz);
+    let (monday, _) = SummaryPeriod::Weekly.last_completed(today);
+    let noon = (monday + chrono::Duration::days(day - 1))
+        .and_hms_opt(12, 0, 0)
+        .expect("noon");
+    let timestamp = timezone::from_local(noon, tz).expect("local noon");
+    let drugs: Vec<_> = substances
+        .split(", ")
+        .map(|substance| serde_json::json!({ "substance": substance }))
+        .collect();
+    let body = serde_json::json!({ "mood": 0, "timestamp": timestamp, "drugs": drugs });
+    send_json(world, "POST", "/api/v1/checkins", Some(body.to_string())).await;
+    then_response_status(world, 201).await;
+}
+
+#[when("the summary job runs")]
+async fn when_summary_job_runs(world: &mut AppWorld) {
+    let sent = summary::deliver_due(world.app_state())
+        .await
+        .expect("deliver summaries");
+    world.summaries_sent = Some(sent);
+}
+
+#[then(regex = r"^(\d+) summaries were sent$")]
+async fn then_summaries_sent(world: &mut AppWorld, expected: usize) {
+    assert_eq!(world.summaries_sent, Some(expected));
+}
+
+#[then(regex = r#"^the summary sent to \"([^\"]+)\" contains \"([^\"]+)\"$"#)]
+async fn then_summary_contains(world: &mut AppWorld, address: String, expected: String) {
+    let sent = world
+        .state
+        .as_ref()
+        .expect("state")
+        .matrix_sent
+        .lock()
+        .expect("sent messages lock")
+        .clone();
+    let (_, message) = sent
+        .iter()
+        .rev()
+        .find(|(to, _)| *to == address)
+        .expect("a message to the address");
+    assert!(message.body.contains(&expected), "body: {}", message.body);
+}
+
+#[then(regex = r"^(\d+) summary deliveries are recorded$")]
+async fn then_summary_deliveries(world: &mut AppWorld, expected: i64) {
+    let recorded: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM summary_deliveries")
+        .fetch_one(&world.app_state().db)
+        .await
+        .expect("count deliveries");
+    assert_eq!(recorded, expected);
+}
+
+#[given(regex = r"^a local webhook receiver answering (\d+)$")]
+async fn given_webhook_receiver(world: &mut AppWorld, status: u16) {
+    world.webhook_receiver = Some(WebhookReceiver::start(status).await);
+}
+
+#[when(regex = r"^the webhook receiver answers (\d+)$")]
+async fn when_receiver_answers(world: &mut AppWorld, status: u16) {
+    let receiver = world.webhook_receiver.as_ref().expect("webhook receiver");
+    receiver.status.store(status, Ordering::SeqCst);
+}
+
+#[given(regex = r#"^the user has a webhook for \"([^\"]+)\"$"#)]
+async fn given_webhook(world: &mut AppWorld, events: String) {
+    let user = world.registered_user.as_ref().expect("registered user");
+    let receiver = world.webhook_receiver.as_ref().expect("webhook receiver");
+    let events: Vec<WebhookEvent> = events
+        .split_whitespace()
+        .map(|code| WebhookEvent::from_code(code).expect("known event"))
+        .collect();
+    let (_, secret) = webhooks::create(world.app_state(), user, &receiver.url, &events)
+        .await
+        .expect("create webhook");
+    world.webhook_secret = Some(secret);
+}
+
+#[when("the webhook queue runs")]
+async fn when_webhook_queue_runs(world: &mut AppWorld) {
+    webhooks::deliver_due(world.app_state())
+        .await
+        .expect("deliver webhooks");
+}
+
+#[when("the webhook retries are due")]
+async fn when_webhook_retries_due(world: &mut AppWorld) {
+    sqlx::query("UPDATE webhook_deliveries SET next_attempt_at = ?1")
+        .bind(chrono::Utc::now())
+        .execute(&world.app_state().db)
+        .await
+        .expect("reschedule deliveries");
+}
+
+#[then(regex = r"^the webhook receiver got (\d+) requests?$")]
+async fn then_receiver_got(world: &mut AppWorld, expected: usize) {
+    let receiver = world.webhook_receiver.as_ref().expect("webhook receiver");
+    assert_eq!(receiver.received().len(), expected);
+}
+
+#[then(regex = r#"^request (\d+) is a signed \"([^\"]+)\" webhook$"#)]
+async fn then_signed_webhook(world: &mut AppWorld, index: usize, event: String) {
+    let receiver = world.webhook_receiver.as_ref().expect("webhook receiver");
+    let secret = world.webhook_secret.as_deref().expect("webhook secret");
+    let ReceivedRequest { headers, body, .. } = receiver.received()[index - 1].clone();
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_string()
+    };
+    assert_eq!(header("x-mood-event"), event);
+    let timestamp: i64 = header("x-mood-timestamp").parse().expect("timestamp");
+    assert_eq!(
+        header("x-mood-signature"),
+        webhooks::sign(secret, timestamp, &body)
+    );
+    let payload: serde_json::Value = serde_json::from_slice(&body).expect("JSON payload");
+    assert_eq!(payload["event"], serde_json::json!(event));
+}
+
+#[then(regex = r#"^request (\d+) has (\S+) at \"([^\"]*)\"$"#)]
+async fn then_webhook_payload_at(
+    world: &mut AppWorld,
+    index: usize,
+    expected: String,
+    pointer: String,
+) {
+    let receiver = world.webhook_receiver.as_ref().expect("webhook receiver");
+    let body = receiver.received()[index - 1].body.clone();
+    let payload: serde_json::Value = serde_json::from_slice(&body).expect("JSON payload");
+    let expected = match expected.as_str() {
+        "nothing" => None,
+        json => Some(serde_json::from_str(json).expect("expected JSON")),
+    };
+    assert_eq!(payload.pointer(&pointer).cloned(), expected, "{payload}");
+}
+
+#[then(regex = r#"^(\d+) webhook deliver(?:y is|ies are) \"([^\"]+)\"$"#)]
+async fn then_webhook_deliveries(world: &mut AppWorld, expected: i64, status: String) {
+    let count: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM webhook_deliveries WHERE status = ?1")
+            .bind(status)
+            .fetch_one(&world.app_state().db)
+            .await
+            .expect("count deliveries");
+    assert_eq!(count, expected);
+}
+
+#[given(regex = r#"^the user's contacts are \"([^\"]+)\"$"#)]
+async fn given_contacts(world: &mut AppWorld, contacts: String) {
+    let receiver_url = world
+        .webhook_receiver
+        .as_ref()
+        .map(|receiver| receiver.url.clone())
+        .unwrap_or_default();
+    // `{quiet_now}` is a two-hour quiet window around the current time.
+    let tz = timezone::parse(timezone::DEFAULT_TIMEZONE).expect("default timezone");
+    let now = chrono::Utc::now().with_timezone(&tz);
+    let quiet_now = format!(
+        "{}-{}",
+        (now - chrono::Duration::hours(1)).format("%H:%M"),
+        (now + chrono::Duration::hours(1)).format("%H:%M")
+    );
+    let mut contacts = contacts.split(',').map(|contact| {
+        let contact = contact
+            .trim()
+            .replace("{receiver}", &receiver_url)
+            .replace("{quiet_now}", &quiet_now);
+        Contact::parse(&contact).expect("valid contact")
+    });
+    let primary = contacts.next();
+    let emergency = contacts.collect();
+    update_user_config(world, |config| {
+        config.primary_contact = primary;
+        config.emergency_contacts = emergency;
+    })
+    .await;
+}
+
+#[then(regex = r#"^the contact \"([^\"]+)\" uses the \"([^\"]+)\" channel$"#)]
+async fn then_contact_channel(_world: &mut AppWorld, contact: String, channel: String) {
+    let contact = Contact::parse(&contact).expect("valid contact");
+    assert_eq!(Some(contact.channel), ChannelKind::from_code(&channel));
+}
+
+#[then(regex = r#"^the contact \"([^\"]+)\" has quiet hours \"([^\"]+)\"$"#)]
+async fn then_contact_quiet_hours(_world: &mut AppWorld, contact: String, expected: String) {
+    let contact = Contact::parse(&contact).expect("valid contact");
+    let quiet_hours = contact.quiet_hours.expect("quiet hours");
+    assert_eq!(quiet_hours.to_string(), expected);
+}
+
+#[then(regex = r#"^the admin alert contact \"([^\"]+)\" is (accepted|rejected)$"#)]
+async fn then_admin_contact(_world: &mut AppWorld, raw: String, outcome: String) {
+    let parsed = config::parse_admin_alert_contact(&raw);
+    assert_eq!(parsed.is_ok(), outcome == "accepted", "{parsed:?}");
+}
+
+#[then(regex = r#"^the contact \"([^\"]+)\" is rejected$"#)]
+async fn then_contact_rejected(_world: &mut AppWorld, contact: String) {
+    assert_eq!(Contact::parse(&contact), None);
+}
+
+#[given(regex = r"^the user's low-mood cooldown is (\d+) hours?$")]
+async fn given_low_mood_cooldown(world: &mut AppWorld, hours: u32) {
+    update_user_config(world, |config| config.low_mood_cooldown_hours = hours).await;
+}
+
+#[then(
+    regex = r#"^a stored config with the primary contact \"([^\"]+)\" loads it as \"([^\"]+)\"$"#
+)]
+async fn then_legacy_contact(_world: &mut AppWorld, stored: String, expected: String) {
+    let mut json = serde_json::to_value(UserConfig::default()).expect("config JSON");
+    json["primary_contact"] = serde_json::json!(stored);
+    let config: UserConfig = serde_json::from_value(json).expect("config JSON");
+    assert_eq!(
+        config.primary_contact.map(|contact| contact.to_string()),
+        Some(expected)
+    );
+}
+
+#[then(regex = r#"^request (\d+) asks for \"([^\"]+)\" priority$"#)]
+async fn then_push_priority(world: &mut AppWorld, index: usize, priority: String) {
+    let receiver = world.webhook_receiver.as_ref().expect("webhook receiver");
+    let uri = receiver.received()[index - 1].uri.clone();
+    let query = uri.query().unwrap_or_default();
+    assert!(
+        form_urlencoded::parse(query.as_bytes())
+            .any(|(key, value)| key == "priority" && value == priority),
+        "{uri}"
+    );
+}
+
+#[when("I request the detail page of the latest check-in")]
+async fn when_request_latest_checkin(world: &mut AppWorld) {
+    let user = world.registered_user.as_ref().expect("registered user");
+    let checkin = latest_checkin(world.app_state(), &user.uuid).await;
+    when_request(world, format!("/me/checkins/{}", checkin.id)).await;
+}
+
+#[when("I request the edit page of the latest check-in")]
+async fn when_request_latest_checkin_edit(world: &mut AppWorld) {
+    let user = world.registered_user.as_ref().expect("registered user");
+    let checkin = latest_checkin(world.app_state(), &user.uuid).await;
+    when_request(world, format!("/me/checkins/{}/edit", checkin.id)).await;
+}
+
+#[when("I request the page of the latest panic event")]
+async fn when_request_latest_panic(world: &mut AppWorld) {
+    let user = world.registered_user.as_ref().expect("registered user");
+    let event = world
+        .app_state()
+        .storage
+        .load_panic_events(&user.uuid)
+        .await
+        .expect("load panic events")
+        .into_iter()
+        .max_by_key(|event| event.timestamp)
+        .expect("a panic event");
+    when_request(world, format!("/me/panic/{}", event.id)).await;
+}
+
+#[when("the notification outbox runs")]
+async fn when_outbox_runs(world: &mut AppWorld) {
+    outbox::deliver_due(world.app_state())
+        .await
+        .expect("deliver notifications");
+}
+
+#[when("the notification retries are due")]
+async fn when_notification_retries_due(world: &mut AppWorld) {
+    sqlx::query("UPDATE notification_outbox SET next_attempt_at = ?1")
+        .bind(chrono::Utc::now())
+        .execute(&world.app_state().db)
+        .await
+        .expect("reschedule notifications");
+}
+
+#[when("two notification outbox runs happen at once")]
+async fn when_outbox_runs_twice(world: &mut AppWorld) {
+    let state = world.app_state();
+    let (first, second) = tokio::join!(outbox::deliver_due(state), outbox::deliver_due(state));
+    first.expect("first outbox run");
+    second.expect("second outbox run");
+}
+
+#[when("the oldest queued notification can't be opened")]
+async fn when_notification_unreadable(world: &mut AppWorld) {
+    sqlx::query(
+        "UPDATE notification_outbox SET payload = x'00' WHERE id = (SELECT MIN(id) FROM notification_outbox)",
+    )
+    .execute(&world.app_state().db)
+    .await
+    .expect("corrupt notification");
+}
+
+#[then(regex = r"^the queued notifications used (\d+) attempts?$")]
+async fn then_notification_attempts(world: &mut AppWorld, expected: i64) {
+    let attempts: i64 = sqlx::query_scalar("SELECT SUM(attempts) FROM notification_outbox")
+        .fetch_one(&world.app_state().db)
+        .await
+        .expect("sum attempts");
+    assert_eq!(attempts, expected);
+}
+
+#[then(regex = r#"^(\d+) queued notifications? (?:is|are) \"([^\"]+)\"$"#)]
+async fn then_queued_notifications(world: &mut AppWorld, expected: i64, status: String) {
+    let count: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM notification_outbox WHERE status = ?1")
+            .bind(status)
+            .fetch_one(&world.app_state().db)
+            .await
+            .expect("count notifications");
+    assert_eq!(count, expected);
+}
+
+#[when(regex = r#"^I add a daily reminder at \"([^\"]*)\" to \"([^\"]+)\"$"#)]
+async fn when_add_daily_reminder(world: &mut AppWorld, time: String, contact: String) {
+    submit_reminder(world, &format!("kind=daily&time={time}"), &contact).await;
+}
+
+#[when(regex = r#"^I add a trip reminder every (\d+) hours? to \"([^\"]+)\"$"#)]
+async fn when_add_trip_reminder(world: &mut AppWorld, every_hours: u32, contact: String) {
+    submit_reminder(
+        world,
+        &format!("kind=trip&every_hours={every_hours}"),
+        &contact,
+    )
+    .await;
+}
+
+async fn submit_reminder(world: &mut AppWorld, schedule: &str, contact: &str) {
+    let receiver_url = world
+        .webhook_receiver
+        .as_ref()
+        .map(|receiver| receiver.url.clone())
+        .unwrap_or_default();
+    let contact = contact.replace("{receiver}", &receiver_url);
+    let form = format!(
+        "{schedule}&contact={}",
+        form_urlencoded::byte_serialize(contact.as_bytes()).collect::<String>()
+    );
+    when_submit_form(world, form, "/me/settings/reminders".into()).await;
+}
+
+async fn reminder_next_run(world: &AppWorld) -> Option<chrono::DateTime<chrono::Utc>> {
+    sqlx::query_scalar("SELECT next_run_at FROM reminders ORDER BY id DESC LIMIT 1")
+        .fetch_one(&world.app_state().db)
+        .await
+        .expect("latest reminder")
+}
+
+#[when(regex = r"^the reminder was due (\d+) minutes? ago$")]
+async fn when_reminder_due(world: &mut AppWorld, minutes: i64) {
+    sqlx::query("UPDATE reminders SET next_run_at = ?1")
+        .bind(chrono::Utc::now() - chrono::Duration::minutes(minutes))
+        .execute(&world.app_state().db)
+        .await
+        .expect("move reminder");
+}
+
+#[when("the reminders run")]
+async fn when_reminders_run(world: &mut AppWorld) {
+    reminders::run_due(world.app_state())
+        .await
+        .expect("run reminders");
+}
+
+#[then(regex = r#"^the reminder was \"([^\"]+)\"$"#)]
+async fn then_reminder_status(world: &mut AppWorld, expected: String) {
+    let status: Option<String> =
+        sqlx::query_scalar("SELECT last_status FROM reminders ORDER BY id DESC LIMIT 1")
+            .fetch_one(&world.app_state().db)
+            .await
+            .expect("latest reminder");
+    assert_eq!(status.as_deref(), Some(expected.as_str()));
+}
+
+#[then(regex = r"^the reminder is scheduled in about (\d+) hours?$")]
+async fn then_reminder_scheduled_in(world: &mut AppWorld, hours: i64) {
+    let next = reminder_next_run(world).await.expect("a next run");
+    let expected = chrono::Utc::now() + chrono::Duration::hours(hours);
+    assert!(
+        (next - expected).num_minutes().abs() <= 1,
+        "next run {next}, expected about {expected}"
+    );
+}
+
+#[then("the reminder is scheduled in the future")]
+async fn then_reminder_scheduled(world: &mut AppWorld) {
+    let next = reminder_next_run(world).await.expect("a next run");
+    assert!(next > chrono::Utc::now(), "next run {next} has passed");
+}
+
+#[then(regex = r#"^the reminder is scheduled at \"([^\"]+)\" in \"([^\"]+)\"$"#)]
+async fn then_reminder_scheduled_at(world: &mut AppWorld, time: String, zone: String) {
+    let tz = timezone::parse(&zone).expect("known timezone");
+    let next = reminder_next_run(world).await.expect("a next run");
+    assert_eq!(next.with_timezone(&tz).format("%H:%M").to_string(), time);
+}
+
+#[then("the reminder isn't scheduled")]
+async fn then_reminder_not_scheduled(world: &mut AppWorld) {
+    assert_eq!(reminder_next_run(world).await, None);
+}
+
+#[given("Matrix alerts go through the user's homeserver")]
+async fn given_real_matrix_channel(world: &mut AppWorld) {
+    let app = &mut world.state.as_mut().expect("state").app;
+    let channel = MatrixChannel::new(app.secrets.clone(), app.config.matrix_store.clone());
+    app.notifier.register(Arc::new(channel));
+}
+
+#[given(regex = r#"^the user's Matrix account is on \"([^\"]+)\" with a stored access token$"#)]
+async fn given_matrix_account(world: &mut AppWorld, homeserver_url: String) {
+    let user = world.registered_user.clone().expect("registered user");
+    let secret_id = world
+        .app_state()
+        .secrets
+        .put(&user.uuid, secrets::MATRIX_ACCESS_TOKEN, "syt_bdd_token")
+        .await
+        .expect("store token");
+    update_user_config(world, |config| {
+        config.homeserver_url = homeserver_url;
+        config.matrix_access_token_secret = Some(secret_id);
+    })
+    .await;
+}
+
+#[when(regex = r#"^the user stores the Matrix access token \"([^\"]+)\"$"#)]
+async fn when_store_matrix_token(world: &mut AppWorld, token: String) {
+    let user = world.registered_user.clone().expect("registered user");
+    let state = world.app_state();
+    let config = state
+        .load_user_config(&user.uuid, &user.username)
+        .await
+        .expect("load config");
+    let secret_id = state
+        .secrets
+        .upsert(
+            config.matrix_access_token_secret.as_deref(),
+            &user.uuid,
+            secrets::MATRIX_ACCESS_TOKEN,
+            &token,
+        )
+        .await
+        .expect("store token");
+    update_user_config(world, |config| {
+        config.matrix_access_token_secret = Some(secret_id);
+    })
+    .await;
+}
+
+async fn stored_matrix_token(
+    world: &AppWorld,
+    store: &secrets::SecretStore,
+) -> Result<Option<String>, AppError> {
+    let user = world.registered_user.as_ref().expect("registered user");
+    let config = world
+        .app_state()
+        .load_user_config(&user.uuid, &user.username)
+        .await
+        .expect("load config");
+    let secret_id = config
+        .matrix_access_token_secret
+        .expect("a stored access token");
+    store.get(&secret_id).await
+}
+
+#[then(regex = r#"^the user's Matrix access token is \"([^\"]+)\"$"#)]
+async fn then_matrix_token(world: &mut AppWorld, expected: String) {
+    let token = stored_matrix_token(world, &world.app_state().secrets)
+        .await
+        .expect("read token");
+    assert_eq!(token.as_deref(), Some(expected.as_str()));
+}
+
+#[then("a secrets store with another key can't read the user's Matrix access token")]
+async fn then_matrix_token_needs_key(world: &mut AppWorld) {
+    let other = secrets::SecretStore::new(world.app_state().db.clone(), "some-other-key");
+    let result = stored_matrix_token(world, &other).await;
+    assert!(result.is_err(), "read with the wrong key: {result:?}");
+}
+
+#[then(regex = r#"^the secrets table does not contain \"([^\"]+)\"$"#)]
+async fn then_secrets_table_opaque(world: &mut AppWorld, needle: String) {
+    let rows: Vec<Vec<u8>> = sqlx::query_scalar("SELECT ciphertext FROM secrets")
+        .fetch_all(&world.app_state().db)
+        .await
+        .expect("load secrets");
+    assert!(!rows.is_empty(), "no secrets stored");
+    for ciphertext in rows {
+        assert!(!String::from_utf8_lossy(&ciphertext).contains(&needle));
+    }
+}
+
+#[when(
+    regex = r#"^a user config with the Matrix access token \"([^\"]+)\" is committed (sealed|unsealed)$"#
+)]
+async fn when_legacy_config_committed(world: &mut AppWorld, token: String, sealing: String) {
+    let user = world.registered_user.clone().expect("registered user");
+    let state = world.app_state();
+    let config = state
+        .load_user_config(&user.uuid, &user.username)
+        .await
+        .expect("load config");
+    let mut value = serde_json::to_value(&config).expect("config json");
+    value["matrix_access_token"] = serde_json::Value::String(token);
+    if sealing == "sealed" {
+        state
+            .storage
+            .write_user_json(&user.uuid, "config.json", &value)
+            .await
+            .expect("write sealed config");
+    } else {
+        let dir = state
+            .storage
+            .ensure_user_dir(&user.uuid)
+            .await
+            .expect("user dir");
+        let plaintext = serde_json::to_vec_pretty(&value).expect("config json");
+        std::fs::write(dir.join("config.json"), plaintext).expect("write config");
+    }
+    state
+        .git
+        .commit_ai_changes("bdd: legacy config")
+        .expect("commit config");
+}
+
+/// Every committed version of the user's config, decrypted.
+async fn committed_configs(world: &AppWorld) -> Vec<String> {
+    let user = world.registered_user.as_ref().expect("registered user");
+    let state = world.app_state();
+    let history = state
+        .git
+        .file_history(&format!("ai/users/{}/config.json", user.uuid))
+        .expect("config history");
+    let mut versions = Vec::new();
+    for (_, blob) in history {
+        let plaintext = state
+            .storage
+            .open_user_bytes(&user.uuid, "config.json", blob)
+            .await
+            .expect("open config");
+        versions.push(String::from_utf8(plaintext).expect("utf-8 config"));
+    }
+    versions
+}
+
+#[then(regex = r#"^a committed version of the user config contains \"([^\"]+)\"$"#)]
+async fn then_committed_config_contains(world: &mut AppWorld, needle: String) {
+    let versions = committed_configs(world).await;
+    assert!(versions.iter().any(|config| config.contains(&needle)));
+}
+
+#[then(regex = r#"^no committed version of the user config contains \"([^\"]+)\"$"#)]
+async fn then_committed_configs_clean(world: &mut AppWorld, needle: String) {
+    let versions = committed_configs(world).await;
+    assert!(!versions.is_empty(), "no committed config");
+    for config in versions {
+        assert!(!config.contains(&needle), "{needle:?} left in {config}");
+    }
+}
+
+#[when("committed tokens are scrubbed")]
+async fn when_tokens_scrubbed(world: &mut AppWorld) {
+    let report = secrets::scrub_committed_tokens(world.app_state())
+        .await
+        .expect("scrub tokens");
+    world.scrub_report = Some(report);
+}
+
+#[then(regex = r"^(\d+) commits? (?:was|were) rewritten$")]
+async fn then_commits_rewritten(world: &mut AppWorld, expected: usize) {
+    let report = world.scrub_report.as_ref().expect("a scrub report");
+    assert_eq!(report.history.commits_rewritten, expected);
+    assert!(report.locked_users.is_empty());
+}
+
+/// `name` in the scenario's temporary directory, outside the repository.
+fn scratch_path(world: &AppWorld, name: &str) -> PathBuf {
+    let repo_root = &world.app_state().config.repo_root;
+    repo_root.parent().expect("temp dir").join(name)
+}
+
+fn load_secrets_key(world: &AppWorld, name: &str) -> Result<String, AppError> {
+    let path = scratch_path(world, name);
+    config::load_or_create_secrets_key(&path, &world.app_state().config.ai_root)
+}
+
+#[then(regex = r#"^the secrets key in \"([^\"]+)\" is generated once and kept$"#)]
+async fn then_secrets_key_kept(world: &mut AppWorld, name: String) {
+    assert!(!scratch_path(world, &name).exists());
+    let first = load_secrets_key(world, &name).expect("generate key");
+    assert_eq!(first.len(), 64);
+    assert!(first.chars().all(|c| c.is_ascii_hexdigit()));
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(scratch_path(world, &name))
+            .expect("key file")
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+    let second = load_secrets_key(world, &name).expect("read key");
+    assert_eq!(first, second);
+}
+
+#[then(regex = r#"^a secrets key in \"([^\"]+)\" is refused$"#)]
+async fn then_secrets_key_refused(world: &mut AppWorld, name: String) {
+    let result = load_secrets_key(world, &name);
+    assert!(matches!(result, Err(AppError::Config(_))), "{result:?}");
+    assert!(!scratch_path(world, &name).exists());
+}
+
+#[then(regex = r#"^the latest queued notification failed with \"([^\"]+)\"$"#)]
+async fn then_queued_notification_error(world: &mut AppWorld, expected: String) {
+    let error: Option<String> =
+        sqlx::query_scalar("SELECT last_error FROM notification_outbox ORDER BY id DESC LIMIT 1")
+            .fetch_one(&world.app_state().db)
+            .await
+            .expect("latest notification");
+    let error = error.expect("an error was recorded");
+    assert!(error.contains(&expected), "{error:?} lacks {expected:?}");
+}
+
+#[given(regex = r#"^the Matrix bot runs as \"([^\"]+)\"$"#)]
+async fn given_matrix_bot(world: &mut AppWorld, user_id: String) {
+    let test_state = world.state.as_mut().expect("state");
+    test_state.app.config.matrix_bot = Some(MatrixBotConfig {
+        homeserver_url: "https://example.org".into(),
+        user_id,
+        password: "bot-password".into(),
+        store_path: PathBuf::from("matrix-bot"),
+    });
+}
+
+#[given(regex = r#"^the Matrix account \"([^\"]+)\" is linked$"#)]
+async fn given_matrix_account_linked(world: &mut AppWorld, matrix_user_id: String) {
+    let user = world.registered_user.as_ref().expect("registered user");
+    let db = &world.app_state().db;
+    let code = matrix_bot::create_link_code(db, user.id)
+        .await
+        .expect("create link code");
+    let linked = matrix_bot::redeem_link_code(db, &code, &matrix_user_id)
+        .await
+        .expect("redeem link code");
+    assert_eq!(linked, Some(user.id));
+}
+
+#[given(regex = r#"^my browser prefers \"([^\"]+)\"$"#)]
+async fn given_browser_language(world: &mut AppWorld, language: String) {
+    world.browser_language = Some(language);
+}
+
+#[given(regex = r#"^the user's language is \"([^\"]+)\"$"#)]
+async fn given_user_language(world: &mut AppWorld, lang: String) {
+    update_user_config(world, |config| config.language = Some(lang)).await;
+}
+
+#[when(regex = r#"^\"([^\"]+)\" sends \"([^\"]*)\" to the bot$"#)]
+async fn when_bot_message(world: &mut AppWorld, sender: String, body: String) {
+    let reply = matrix_bot::handle_message(world.app_state(), &sender, &body).await;
+    world.bot_reply = Some(reply);
+}
+
+/// Sends the `!link` command shown on the settings page.
+#[when(regex = r#"^\"([^\"]+)\" sends the link code from the page to the bot$"#)]
+async fn when_bot_link_from_page(world: &mut AppWorld, sender: String) {
+    let response = world.response.as_ref().expect("a response");
+    let start = response.body.find("!link ").expect("a link command");
+    let command: String = response.body[start..]
+        .chars()
+        .take_while(|c| *c != '<')
+        .collect();
+    when_bot_message(world, sender, command).await;
+}
+
+#[then(regex = r#"^the bot replies \"([^\"]+)\"$"#)]
+async fn then_bot_replies(world: &mut AppWorld, needle: String) {
+    let reply = world.bot_reply.clone().expect("a message to the bot");
+    let reply = reply.expect("a reply");
+    assert!(reply.contains(&needle), "reply: {reply}");
+}
+
+#[then("the bot doesn't reply")]
+async fn then_bot_silent(world: &mut AppWorld) {
+    assert_eq!(world.bot_reply, Some(None));
+}
+
+#[then(regex = r#"^the latest stored check-in has the notes \"([^\"]+)\"$"#)]
+async fn then_latest_notes(world: &mut AppWorld, notes: String) {
+    let user = world.registered_user.as_ref().expect("registered user");
+    let checkin = latest_checkin(world.app_state(), &user.uuid).await;
+    assert_eq!(checkin.notes.as_deref(), Some(notes.as_str()));
+}
+
+#[given("the registered user is an admin")]
+async fn given_admin(world: &mut AppWorld) {
+    let user = world.registered_user.as_ref().expect("registered user");
+    sqlx::query("UPDATE users SET role = 'admin' WHERE id = ?1")
+        .bind(user.id)
+        .execute(&world.app_state().db)
+        .await
+        .expect("make admin");
+}
+
+#[given(regex = r#"^a (counting|failing) test job \"([^\"]+)\" on the schedule \"([^\"]+)\"$"#)]
+async fn given_test_job(world: &mut AppWorld, kind: String, name: String, schedule: String) {
+    let schedule = JobSchedule::parse(&schedule).expect("valid schedule");
+    let name: &'static str = Box::leak(name.into_boxed_str());
+    let runs = world.job_runs.clone();
+    let fails = kind == "failing";
+    world.jobs.push(Job::new(name, schedule, move |_state| {
+        let runs = runs.clone();
+        async move {
+            runs.fetch_add(1, Ordering::SeqCst);
+            if fails {
+                return Err(AppError::Other(anyhow::anyhow!("the test job broke")));
+            }
+            Ok(1)
+        }
+    }));
+}
+
+#[given(regex = r#"^a slow test job \"([^\"]+)\" that stops at shutdown$"#)]
+async fn given_slow_job(world: &mut AppWorld, name: String) {
+    let name: &'static str = Box::leak(name.into_boxed_str());
+    let runs = world.job_runs.clone();
+    let job = Job::new(
+        name,
+        JobSchedule::Every(Duration::from_secs(600)),
+        move |_state| {
+            let runs = runs.clone();
+            async move {
+                runs.fetch_add(1, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_secs(3600)).await;
+                Ok(1)
+            }
+        },
+    );
+    world.jobs.push(job.cancel_on_shutdown());
+}
+
+fn job_runner(world: &AppWorld) -> JobRunner {
+    JobRunner::new(world.app_state().clone(), world.jobs.clone())
+}
+
+#[when("the jobs are registered")]
+async fn when_jobs_registered(world: &mut AppWorld) {
+    job_runner(world).register().await.expect("register jobs");
+}
+
+#[when(regex = r#"^the job \"([^\"]+)\" was due (\d+) minutes? ago$"#)]
+async fn when_job_due(world: &mut AppWorld, name: String, minutes: i64) {
+    sqlx::query("UPDATE jobs SET next_run_at = ?1 WHERE name = ?2")
+        .bind(chrono::Utc::now() - chrono::Duration::minutes(minutes))
+        .bind(name)
+        .execute(&world.app_state().db)
+        .await
+        .expect("move job");
+}
+
+#[when(regex = r#"^another instance is running the job \"([^\"]+)\"$"#)]
+async fn when_job_locked(world: &mut AppWorld, name: String) {
+    sqlx::query("UPDATE jobs SET locked_by = 'other', locked_until = ?1 WHERE name = ?2")
+        .bind(chrono::Utc::now() + chrono::Duration::minutes(5))
+        .bind(name)
+        .execute(&world.app_state().db)
+        .await
+        .expect("lock job");
+}
+
+#[when("the built-in jobs are registered")]
+async fn when_builtin_jobs_registered(world: &mut AppWorld) {
+    world.jobs = jobs::builtin(world.app_state());
+    job_runner(world).register().await.expect("register jobs");
+}
+
+#[when("the job runner is started")]
+async fn when_job_runner_started(world: &mut AppWorld) {
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+    let handle = job_runner(world).start(shutdown_rx);
+    world.running_jobs = Some((shutdown_tx, handle));
+    for _ in 0..100 {
+        if world.job_runs.load(Ordering::SeqCst) > 0 {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("no job started");
+}
+
+#[when("the job runner is shut down")]
+async fn when_job_runner_shut_down(world: &mut AppWorld) {
+    let (shutdown_tx, handle) = world.running_jobs.take().expect("a started job runner");
+    shutdown_tx.send_replace(true);
+    tokio::time::timeout(Duration::from_secs(5), handle)
+        .await
+        .expect("runner stops in time")
+        .expect("runner task");
+}
+
+#[when("the job runner runs due jobs")]
+async fn when_run_due_jobs(world: &mut AppWorld) {
+    job_runner(world).run_due().await.expect("run due jobs");
+}
+
+#[when("two job runners run due jobs at once")]
+async fn when_two_runners(world: &mut AppWorld) {
+    let (first, second) = (job_runner(world), job_runner(world));
+    let (first, second) = tokio::join!(first.run_due(), second.run_due());
+    first.expect("first runner");
+    second.expect("second runner");
+}
+
+#[then(regex = r"^the test jobs ran (\d+) times?$")]
+async fn then_job_runs(world: &mut AppWorld, expected: usize) {
+    assert_eq!(world.job_runs.load(Ordering::SeqCst), expected);
+}
+
+async fn job_record(world: &AppWorld, name: &str) -> JobRecord {
+    jobs::list(&world.app_state().db)
+        .await
+        .expect("list jobs")
+        .into_iter()
+        .find(|job| job.name == name)
+        .expect("registered job")
+}
+
+#[then(regex = r#"^the job \"([^\"]+)\" is scheduled in the future$"#)]
+async fn then_job_scheduled(world: &mut AppWorld, name: String) {
+    let job = job_record(world, &name).await;
+    let next = job.next_run_at.expect("a next run");
+    assert!(next > chrono::Utc::now(), "next run {next} has passed");
+    assert!(!job.is_running(chrono::Utc::now()), "job still locked");
+}
+
+#[then(regex = r#"^the job \"([^\"]+)\" last failed with \"([^\"]+)\"$"#)]
+async fn then_job_failed(world: &mut AppWorld, name: String, expected: String) {
+    let job = job_record(world, &name).await;
+    let error = job.last_error.expect("a last error");
+    assert!(error.contains(&expected), "last error {error:?}");
+}
+
+#[then(regex = r#"^the schedule \"([^\"]+)\" runs next at \"([^\"]+)\" after \"([^\"]+)\"$"#)]
+async fn then_schedule_next(
+    _world: &mut AppWorld,
+    schedule: String,
+    expected: String,
+    after: String,
+) {
+    let schedule = JobSchedule::parse(&schedule).expect("valid schedule");
+    let after = chrono::DateTime::parse_from_rfc3339(&after)
+        .expect("after timestamp")
+        .to_utc();
+    let next = schedule.next_after(after).expect("a next run");
+    assert_eq!(next.to_rfc3339(), expected);
+}
+
+#[then(regex = r#"^the schedule \"([^\"]+)\" is rejected$"#)]
+async fn then_schedule_rejected(_world: &mut AppWorld, schedule: String) {
+    assert_eq!(JobSchedule::parse(&schedule), None);
+}
+
+#[when(regex = r"^the session was last seen (\d+) days ago$")]
+async fn when_session_idle(world: &mut AppWorld, days: i64) {
+    sqlx::query("UPDATE sessions SET last_seen_at = ?1 WHERE id = ?2")
+        .bind(chrono::Utc::now() - chrono::Duration::days(days))
+        .bind(world.session_id.as_deref().expect("session"))
+        .execute(&world.app_state().db)
+        .await
+        .expect("age session");
+}
+
+#[given("the user is also logged in on another device")]
+async fn given_other_session(world: &mut AppWorld) {
+    let user = world.registered_user.as_ref().expect("registered user");
+    auth::create_session(world.app_state(), user.id)
+        .await
+        .expect("create session");
+}
+
+#[when("stale sessions are purged")]
+async fn when_sessions_purged(world: &mut AppWorld) {
+    auth::purge_stale_sessions(world.app_state())
+        .await
+        .expect("purge sessions");
+}
+
+#[then(regex = r"^(\d+) sessions? (?:is|are) left$")]
+async fn then_sessions_left(world: &mut AppWorld, expected: i64) {
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM sessions")
+        .fetch_one(&world.app_state().db)
+        .await
+        .expect("count sessions");
+    assert_eq!(count, expected);
+}
+
+async fn update_user_config(world: &mut AppWorld, change: impl FnOnce(&mut UserConfig)) {
+    let user = world.registered_user.as_ref().expect("registered user");
+    let state = world.app_state();
+    let mut config = state
+        .load_user_config(&user.uuid, &user.username)
+        .await
+        .expect("load config");
+    change(&mut config);
+    state
+        .storage
+        .save_user_config(&user.uuid, &config)
+        .await
+        .expect("save config");
+}
+
+async fn substance_report(world: &AppWorld) -> SubstanceReport {
+    let user = world.registered_user.as_ref().expect("registered user");
+    let checkins = world
+        .app_state()
+        .storage
+        .load_user_checkins(&user.uuid)
+        .await
+        .expect("load checkins");
+    analytics::substance_report(&checkins)
+}
+
+async fn store_checkin_hours_ago(world: &mut AppWorld, mood: i32, hours: i64, feels_safe: bool) {
+    let user = world
+        .registered_user
+        .as_ref()
+        .expect("user must exist before creating checkins");
+    let mut checkin = Checkin::new(&user.uuid);
+    checkin.timestamp = chrono::Utc::now() - chrono::Duration::hours(hours);
+    checkin.mood = mood;
+    checkin.feels_safe = feels_safe;
+    world
+        .app_state()
+        .storage
+        .append_checkin(&user.uuid, checkin)
+        .await
+        .expect("append checkin");
+}
+
+async fn mood_stats(world: &AppWorld, days: u32) -> MoodStats {
+    let user = world.registered_user.as_ref().expect("registered user");
+    let checkins = world
+        .app_state()
+        .storage
+        .load_user_checkins(&user.uuid)
+        .await
+        .expect("load checkins");
+    let tz = timezone::parse(timezone::DEFAULT_TIMEZONE).expect("default timezone");
+    let today = timezone::local_date(chrono::Utc::now(), tz);
+    analytics::mood_stats(&checkins, tz, today, days)
+}
+
+async fn latest_checkin(state: &AppState, user_uuid: &str) -> Checkin {
+    let mut checkins = state
+        .storage
+        .load_user_checkins(user_uuid)
+        .await
+        .expect("load checkins");
+    checkins.sort_by_key(|item| Reverse(item.timestamp));
+    checkins.into_iter().next().expect("at least one checkin")
+}
+
 async fn register_user(world: &mut AppWorld, username: String, email: String, password: String) {
     let created = auth::register_user(world.app_state(), &username, &email, &password)
         .await
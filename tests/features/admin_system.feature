@@ -0,0 +1,44 @@
+Feature: Admin system page
+  Admins see the state of the data repository and can commit `ai/` by hand.
+  Manual commits and the auto-commit worker take turns on HEAD.
+
+  Background:
+    Given a fresh application state
+    And a registered user "cutie" with email "cutie@example.com" and password "supersecret1"
+    And the registered user is logged in
+
+  Scenario: Only admins see the system page
+    When I request "/admin/system"
+    Then the response status is 403
+    When I submit the form "message=Snapshot" to "/admin/system/commit"
+    Then the response status is 403
+
+  Scenario: A manual commit names the admin who triggered it
+    Given the registered user is an admin
+    When I submit the form "timestamp=&mood=2&high_level=0" to "/me/checkins/new"
+    And I request "/admin/system"
+    Then the response status is 200
+    And the response contains "Git-Repository"
+    And the response contains "Nicht committete Änderungen unter ai/"
+    When I submit the form "message=Manual+snapshot" to "/admin/system/commit"
+    Then the response redirects to "/admin/system?committed=true"
+    And the latest data commit is "Manual snapshot"
+    And the latest data commit mentions "Triggered-by: cutie"
+    When I follow the redirect
+    Then the response contains "Commit erstellt"
+    And the response contains "Manual snapshot"
+    And the response contains "Alles committet"
+    When I submit the form "message=Again" to "/admin/system/commit"
+    Then the response redirects to "/admin/system?committed=false"
+
+  Scenario: A manual commit needs a message
+    Given the registered user is an admin
+    When I submit the form "message=+" to "/admin/system/commit"
+    Then the response status is 400
+
+  Scenario: Concurrent commits don't trip over each other
+    Given the auto-commit worker runs with a debounce of 1 ms
+    When I submit the form "timestamp=&mood=1&high_level=0" to "/me/checkins/new"
+    And 8 data files are changed and committed at once
+    And the auto-commit worker is shut down
+    Then the data repository has no uncommitted changes
@@ -0,0 +1,13 @@
+Feature: Mood analytics
+  The dashboard summarises check-ins over the last 7, 30 or 90 days.
+
+  Scenario: Averages and the unsafe share cover only the selected range
+    Given a fresh application state
+    And a registered user "cutie" with email "cutie@example.com" and password "supersecret1"
+    And a check-in with mood 3 logged 1 hours ago
+    And an unsafe check-in with mood -1 logged 50 hours ago
+    And a check-in with mood 5 logged 480 hours ago
+    Then the 7-day statistics count 2 check-ins with an average mood of 1.0
+    And 50% of the 7-day check-ins were unsafe
+    And the 30-day statistics count 3 check-ins with an average mood of 2.3
+    And the 7-day mood chart has 2 points
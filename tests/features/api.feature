@@ -0,0 +1,86 @@
+Feature: JSON API
+  /api/v1 offers check-ins, trips, panic and settings as JSON, with the same
+  validation as the web forms and JSON error bodies.
+
+  Background:
+    Given a fresh application state
+    And a registered user "cutie" with email "cutie@example.com" and password "supersecret1"
+
+  Scenario: Requests without a session get a JSON error
+    When I send "GET" to "/api/v1/checkins"
+    Then the response status is 401
+    And the JSON at "/error" is "unauthorized"
+
+  Scenario: Check-ins can be created, edited and deleted
+    Given the registered user is logged in
+    When I send "POST" to "/api/v1/checkins" with the JSON '{"mood": -9, "notes": " tired "}'
+    Then the response status is 201
+    And the JSON at "/mood" is -5
+    And the JSON at "/notes" is "tired"
+    When I send "PUT" to "/api/v1/checkins/{id}" with the JSON '{"mood": 3, "high_level": 2}'
+    Then the response status is 200
+    And the JSON at "/mood" is 3
+    When I send "GET" to "/api/v1/checkins?limit=10"
+    Then the JSON at "/total" is 1
+    And the JSON at "/items/0/high_level" is 2
+    When I send "DELETE" to "/api/v1/checkins/{id}"
+    Then the response status is 204
+    When I send "GET" to "/api/v1/checkins/{id}"
+    Then the response status is 404
+    And the JSON at "/error" is "not_found"
+
+  Scenario: Validation errors are reported as JSON
+    Given the registered user is logged in
+    When I send "POST" to "/api/v1/checkins" with the JSON '{"mood": 1, "timestamp": "2099-01-01T00:00:00Z"}'
+    Then the response status is 400
+    And the JSON at "/error" is "bad_request"
+    And the JSON at "/message" is "Check-ins können nicht in der Zukunft liegen."
+    When I send "POST" to "/api/v1/checkins" with the JSON '{"mood": "low"}'
+    Then the response status is 400
+    And the JSON at "/error" is "bad_request"
+
+  Scenario: Check-ins are paginated newest first
+    Given the registered user is logged in
+    And a check-in with mood 1 logged 3 hours ago
+    And a check-in with mood 2 logged 2 hours ago
+    And a check-in with mood 3 logged 1 hours ago
+    When I send "GET" to "/api/v1/checkins?limit=2&offset=1"
+    Then the JSON at "/total" is 3
+    And the JSON at "/items/0/mood" is 2
+    And the JSON at "/items/1/mood" is 1
+
+  Scenario: Only one trip can be active
+    Given the registered user is logged in
+    When I send "POST" to "/api/v1/trips" with the JSON '{"title": "Festival"}'
+    Then the response status is 201
+    When I send "POST" to "/api/v1/trips" with the JSON '{"title": "Afterparty"}'
+    Then the response status is 400
+    When I send "POST" to "/api/v1/trips/{id}/end"
+    Then the response status is 200
+    And the JSON at "/title" is "Festival"
+
+  Scenario: Settings can be patched
+    Given the registered user is logged in
+    When I send "PATCH" to "/api/v1/settings" with the JSON '{"timezone": "Mars/Olympus"}'
+    Then the response status is 400
+    When I send "PATCH" to "/api/v1/settings" with the JSON '{"timezone": "Europe/London", "trend_sensitivity": "high"}'
+    Then the response status is 200
+    And the JSON at "/timezone" is "Europe/London"
+    And the JSON at "/trend_sensitivity" is "high"
+    And the JSON at "/summary_weekly" is false
+
+  Scenario: Concurrent settings patches both stick
+    Given the registered user is logged in
+    When the settings patches '{"display_name": "Bunny"}' and '{"summary_weekly": true}' and '{"summary_monthly": true}' and '{"auto_notify_on_low_mood": false}' and '{"trend_notify_primary_contact": true}' and '{"low_mood_cooldown_hours": 12}' are sent at once
+    And I send "GET" to "/api/v1/settings"
+    Then the JSON at "/display_name" is "Bunny"
+    And the JSON at "/summary_weekly" is true
+    And the JSON at "/summary_monthly" is true
+    And the JSON at "/auto_notify_on_low_mood" is false
+    And the JSON at "/trend_notify_primary_contact" is true
+    And the JSON at "/low_mood_cooldown_hours" is 12
+
+  Scenario: The OpenAPI document is public
+    When I send "GET" to "/api/v1/openapi.json"
+    Then the response status is 200
+    And the JSON at "/openapi" is "3.0.3"
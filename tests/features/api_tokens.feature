@@ -0,0 +1,48 @@
+Feature: Personal API tokens
+  Named tokens with scopes let shortcuts and scripts use /api/v1 without a
+  session. Only a hash is stored, and tokens can be revoked at any time.
+
+  Background:
+    Given a fresh application state
+    And a registered user "cutie" with email "cutie@example.com" and password "supersecret1"
+
+  Scenario: A token can do what its scopes allow
+    Given the user has an API token "Shortcut" with scopes "checkins:read checkins:write"
+    When I send "POST" to "/api/v1/checkins" with the JSON '{"mood": 2}'
+    Then the response status is 201
+    When I send "GET" to "/api/v1/checkins"
+    Then the JSON at "/total" is 1
+    And the API token has a last-used time
+
+  Scenario: A token without the scope is refused
+    Given the user has an API token "Checkins only" with scopes "checkins:read"
+    When I send "POST" to "/api/v1/panic"
+    Then the response status is 403
+    And the JSON at "/error" is "forbidden"
+    When I send "POST" to "/api/v1/checkins" with the JSON '{"mood": 2}'
+    Then the response status is 403
+
+  Scenario: Unknown and revoked tokens are rejected
+    Given I present the API token "kmt_not-a-real-token"
+    When I send "GET" to "/api/v1/checkins"
+    Then the response status is 401
+    Given the user has an API token "Old phone" with scopes "checkins:read"
+    When the API token is revoked
+    And I send "GET" to "/api/v1/checkins"
+    Then the response status is 401
+    And the JSON at "/error" is "unauthorized"
+
+  Scenario: Tokens don't open the web pages
+    Given the user has an API token "Shortcut" with scopes "settings:read settings:write"
+    When I request "/me/settings"
+    Then the response status is 403
+
+  Scenario: Tokens are created in the settings and shown once
+    Given the registered user is logged in
+    When I submit the form "name=Shortcut&scope=panic%3Atrigger" to "/me/settings/tokens"
+    Then the response status is 200
+    And the response contains "kmt_"
+    When I request "/me/settings"
+    Then the response contains "panic:trigger"
+    When I submit the form "name=&scope=panic%3Atrigger" to "/me/settings/tokens"
+    Then the response status is 400
@@ -0,0 +1,23 @@
+Feature: Auto-commit
+  Data changes are committed to the data repository once no further change
+  arrived for the debounce period. A burst becomes one commit, and shutdown
+  commits whatever is still waiting.
+
+  Background:
+    Given a fresh application state
+    And a registered user "cutie" with email "cutie@example.com" and password "supersecret1"
+    And the registered user is logged in
+
+  Scenario: A check-in is committed after the debounce
+    Given the auto-commit worker runs with a debounce of 50 ms
+    When I submit the form "timestamp=&mood=2&high_level=0" to "/me/checkins/new"
+    Then the latest data commit is "checkin: {user} mood 2" within 5 seconds
+
+  Scenario: Shutdown commits a pending burst at once
+    Given the auto-commit worker runs with a debounce of 600000 ms
+    When I submit the form "timestamp=&mood=2&high_level=0" to "/me/checkins/new"
+    And I submit the form "timestamp=&mood=-1&high_level=3" to "/me/checkins/new"
+    And the auto-commit worker is shut down
+    Then the latest data commit is "batch: 2 changes"
+    And the latest data commit lists "checkin: {user} mood 2"
+    And the latest data commit lists "checkin: {user} mood -1"
@@ -0,0 +1,24 @@
+Feature: Backup remote
+  The ai/ data repository is mirrored to a configured remote.
+
+  Scenario: Pushing the data repository to a local bare remote
+    Given a fresh application state
+    And a registered user "cutie" with email "cutie@example.com" and password "supersecret1"
+    When I submit a check-in with mood 0 and high 2 and notes "backup me"
+    And the data repository is committed
+    And the data repository is pushed to the backup remote
+    Then the backup remote has the current HEAD
+    And the backup status reports a recent success
+
+  Scenario: A repository that tracks more than ai/ is neither pushed nor rewritten
+    Given a fresh application state
+    And the file "src/main.rs" is committed to the data repository
+    Then pushing to the backup remote is refused
+    And scrubbing committed tokens is refused
+    And the backup remote has no branches
+
+  Scenario: Backup alerts can't go to a Matrix ID
+    Then the admin alert contact "@admin:example.org" is rejected
+    And the admin alert contact "matrix:@admin:example.org" is rejected
+    And the admin alert contact "email:admin@example.com" is accepted
+    And the admin alert contact "webhook:https://example.org/alerts" is accepted
@@ -12,3 +12,47 @@ Feature: Sessions and check-ins
     When I submit a check-in with mood 2 and high 3 and notes "Feeling cozy"
     Then the user has 1 stored check-ins
     And the latest stored check-in has mood 2 and high 3
+
+  Scenario: Check-ins saved at the same time are all kept
+    Given a fresh application state
+    And a registered user "cutie" with email "cutie@example.com" and password "supersecret1"
+    When I submit 20 check-ins at once
+    Then the user has 20 stored check-ins
+
+  Scenario: A check-in can be backdated in the user's timezone
+    Given a fresh application state
+    And a registered user "cutie" with email "cutie@example.com" and password "supersecret1"
+    And the registered user is logged in
+    And the user's timezone is "Europe/Berlin"
+    When I submit the form "timestamp=2026-07-01T14%3A30&mood=-2&high_level=1" to "/me/checkins/new"
+    Then the response status is 303
+    And the latest stored check-in was made at "2026-07-01T12:30:00Z" UTC
+    And the latest stored check-in has mood -2 and high 1
+
+  Scenario: Check-ins in the future are refused past a small clock skew
+    Given a fresh application state
+    And a registered user "cutie" with email "cutie@example.com" and password "supersecret1"
+    And the registered user is logged in
+    And the user's timezone is "Europe/Berlin"
+    When I submit a check-in with mood 1 dated 2 minutes from now
+    Then the response status is 303
+    When I submit a check-in with mood 3 dated 30 minutes from now
+    Then the response status is 400
+    And the response contains "Check-ins können nicht in der Zukunft liegen."
+    And the form field "mood" has the value "3"
+    And the user has 1 stored check-ins
+    When I submit a check-in with mood -1 dated 90 minutes ago
+    Then the response status is 303
+    And the user has 2 stored check-ins
+
+  Scenario: The one-tap check-in on the dashboard
+    Given a fresh application state
+    And a registered user "cutie" with email "cutie@example.com" and password "supersecret1"
+    And the registered user is logged in
+    When I submit the form "mood=4&high_level=2" to "/me/checkins/quick"
+    Then the response redirects to "/me?quick_mood=4"
+    And the latest stored check-in has mood 4 and high 2
+    When I follow the redirect
+    Then the response contains "Mood 4 gespeichert"
+    When I submit the form "mood=9" to "/me/checkins/quick"
+    Then the latest stored check-in has mood 5 and high 0
@@ -0,0 +1,54 @@
+Feature: Encryption at rest
+  Per-user journal files only ever hit the disk (and git) as ciphertext.
+
+  Scenario: Check-in files are sealed with the user's data key
+    Given a fresh application state
+    And a registered user "cutie" with email "cutie@example.com" and password "supersecret1"
+    When I submit a check-in with mood -1 and high 4 and notes "secret diary entry"
+    Then the stored check-in file does not contain "secret diary entry"
+    And the latest stored check-in has mood -1 and high 4
+
+  Scenario: Data stays unreadable without the password or an escrow key
+    Given a fresh application state
+    And a registered user "cutie" with email "cutie@example.com" and password "supersecret1"
+    When I submit a check-in with mood 1 and high 0 and notes "cozy"
+    And the user's data key is locked
+    Then loading the user's check-ins fails as locked
+    And I can authenticate as "cutie" using password "supersecret1"
+    And the user has 1 stored check-ins
+
+  Scenario: A session whose data key is gone asks to log in again
+    Given a fresh application state
+    And a registered user "cutie" with email "cutie@example.com" and password "supersecret1"
+    And the registered user is logged in
+    When the user's data key is locked
+    And I request "/me/settings"
+    Then the response redirects to "/login?next=%2Fme%2Fsettings"
+    And 0 sessions are left
+
+  Scenario: Changing the password re-wraps the data key
+    Given a fresh application state
+    And a registered user "cutie" with email "cutie@example.com" and password "supersecret1"
+    And the registered user is logged in
+    And the user is also logged in on another device
+    When I submit a check-in with mood 2 and high 0 and notes "before the change"
+    And I submit the form "current_password=supersecret1&new_password=evenmoresecret2&new_password_confirm=evenmoresecret2" to "/me/settings/password"
+    Then the response status is 200
+    And the response contains "Passwort geändert"
+    And 1 session is left
+    When the user's data key is locked
+    Then I can't authenticate as "cutie" using password "supersecret1"
+    And I can authenticate as "cutie" using password "evenmoresecret2"
+    And the user has 1 stored check-ins
+
+  Scenario: The password only changes with the current one
+    Given a fresh application state
+    And a registered user "cutie" with email "cutie@example.com" and password "supersecret1"
+    And the registered user is logged in
+    When I submit the form "current_password=wrongpassword&new_password=evenmoresecret2&new_password_confirm=evenmoresecret2" to "/me/settings/password"
+    Then the response status is 400
+    And the response contains "Das aktuelle Passwort stimmt nicht."
+    When I submit the form "current_password=supersecret1&new_password=evenmoresecret2&new_password_confirm=different2" to "/me/settings/password"
+    Then the response status is 400
+    And the response contains "Passwörter stimmen nicht überein."
+    And I can authenticate as "cutie" using password "supersecret1"
@@ -0,0 +1,37 @@
+Feature: Error pages
+  Errors are shown as themed pages, never as raw error text.
+
+  Scenario: Anonymous visitors are sent to the login
+    Given a fresh application state
+    When I request "/me/checkins"
+    Then the response redirects to "/login?next=%2Fme%2Fcheckins"
+
+  Scenario: Unknown pages render the not-found page
+    Given a fresh application state
+    When I request "/does-not-exist"
+    Then the response status is 404
+    And the response contains "Nicht gefunden"
+
+  Scenario: A failed login keeps the entered identifier
+    Given a fresh application state
+    And a registered user "cutie" with email "cutie@example.com" and password "supersecret1"
+    When I submit the form "identifier=cutie&password=wrong" to "/login"
+    Then the response status is 401
+    And the response contains "Nutzername oder Passwort stimmt nicht."
+    And the form field "identifier" has the value "cutie"
+
+  Scenario: Logging in returns to the requested page
+    Given a fresh application state
+    And a registered user "cutie" with email "cutie@example.com" and password "supersecret1"
+    When I submit the form "identifier=cutie&password=supersecret1&next=%2Fme%2Fcheckins" to "/login"
+    Then the response redirects to "/me/checkins"
+    When I submit the form "identifier=cutie&password=supersecret1&next=%2F%2Fevil.example" to "/login"
+    Then the response redirects to "/me"
+
+  Scenario: A rejected registration keeps the input
+    Given a fresh application state
+    When I submit the form "username=bunny&email=bunny%40example.com&password=supersecret1&password_confirm=other" to "/register"
+    Then the response status is 400
+    And the response contains "Passwörter stimmen nicht überein."
+    And the form field "username" has the value "bunny"
+    And the form field "email" has the value "bunny@example.com"
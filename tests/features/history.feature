@@ -0,0 +1,50 @@
+Feature: Check-in history
+  Every committed version of a check-in can be inspected and restored.
+
+  Scenario: Restoring an earlier version of an edited check-in
+    Given a fresh application state
+    And a registered user "cutie" with email "cutie@example.com" and password "supersecret1"
+    When I submit a check-in with mood -1 and high 2 and notes "first draft"
+    And the data repository is committed
+    And I change the latest check-in's mood to 3
+    And the data repository is committed
+    Then the latest check-in has 2 historical versions
+    When I restore the oldest version of the latest check-in
+    Then the latest stored check-in has mood -1 and high 2
+
+  Scenario: A purged check-in stays gone
+    Given a fresh application state
+    And a registered user "cutie" with email "cutie@example.com" and password "supersecret1"
+    When I submit a check-in with mood -1 and high 2 and notes "first draft"
+    And the data repository is committed
+    And I delete the latest check-in
+    And I empty the trash
+    And the data repository is committed
+    Then the oldest version of the deleted check-in can't be restored
+    And the user has 0 stored check-ins
+
+  Scenario: Restoring an old version keeps a trashed check-in in the trash
+    Given a fresh application state
+    And a registered user "cutie" with email "cutie@example.com" and password "supersecret1"
+    When I submit a check-in with mood -1 and high 2 and notes "first draft"
+    And the data repository is committed
+    And I change the latest check-in's mood to 3
+    And the data repository is committed
+    And I delete the latest check-in
+    And the data repository is committed
+    And I restore the oldest version of the latest check-in
+    Then the latest stored check-in has mood -1 and high 2
+    And the user has 1 check-in in the trash
+
+  Scenario: Restoring a version from the trash keeps the check-in live
+    Given a fresh application state
+    And a registered user "cutie" with email "cutie@example.com" and password "supersecret1"
+    When I submit a check-in with mood -1 and high 2 and notes "first draft"
+    And I delete the latest check-in
+    And the data repository is committed
+    And I take the latest check-in out of the trash
+    And I change the latest check-in's mood to 3
+    And the data repository is committed
+    And I restore the trashed version of the latest check-in
+    Then the latest stored check-in has mood -1 and high 2
+    And the user has 0 check-ins in the trash
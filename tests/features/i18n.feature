@@ -0,0 +1,45 @@
+Feature: Internationalization
+  UI copy comes from the German and English message catalogs.
+
+  Scenario: Messages are looked up per language
+    Then the message "checkin-notes" in "de" reads "Notizen"
+    And the message "checkin-notes" in "en" reads "Notes"
+    And the message "no-such-message" in "en" reads "no-such-message"
+
+  Scenario: Every message the templates use is in the German catalog
+    Then every message the templates use exists in "de"
+
+  Scenario: The browser language is negotiated
+    Then the Accept-Language header "fr-FR, en-GB;q=0.8, de;q=0.5" selects "en"
+    And the Accept-Language header "de-AT, en;q=0.9" selects "de"
+    And the Accept-Language header "fr" selects "de"
+
+  Scenario: An English user logs an unsafe check-in
+    Given a fresh application state
+    And a registered user "cutie" with email "cutie@example.com" and password "supersecret1"
+    And the registered user is logged in
+    And my browser prefers "en"
+    When I request "/me/checkins/new"
+    Then the response contains "Feeling safe?"
+    When I submit the form "timestamp=&mood=-1&high_level=0&feels_safe=no&safety_answer=" to "/me/checkins/new"
+    Then the latest stored check-in doesn't feel safe
+    When I send "POST" to "/api/v1/checkins" with the JSON '{"mood": 0, "safety_answer": "No, not really"}'
+    Then the latest stored check-in doesn't feel safe
+    When I send "POST" to "/api/v1/checkins" with the JSON '{"mood": 1, "feels_safe": true, "safety_answer": "no idea"}'
+    Then the latest stored check-in feels safe
+
+  Scenario Outline: The check-in detail page is translated
+    Given a fresh application state
+    And a registered user "cutie" with email "cutie@example.com" and password "supersecret1"
+    And the registered user is logged in
+    And my browser prefers "<lang>"
+    When I submit the form "timestamp=&mood=-2&high_level=3" to "/me/checkins/new"
+    And the data repository is committed
+    And I follow the redirect
+    Then the response contains "<heading>"
+    And the response contains "<version>"
+
+    Examples:
+      | lang | heading                         | version                           |
+      | de   | >Mood -2 · High-Level 3</h2>    | >Mood -2 · High-Level 3</span>    |
+      | en   | >Mood -2 · High 3</h2>          | >Mood -2 · High 3</span>          |
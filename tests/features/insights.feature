@@ -0,0 +1,51 @@
+Feature: Substance insights
+  Mood 24–72 hours after a substance is compared with the substance-free baseline.
+
+  Scenario: Mood after use is compared with the baseline
+    Given a fresh application state
+    And a registered user "cutie" with email "cutie@example.com" and password "supersecret1"
+    And a check-in with mood 1 logged 500 hours ago
+    And a check-in with mood 2 logged 400 hours ago
+    And a check-in with mood 0 and substance "MDMA" logged 200 hours ago
+    And a check-in with mood -3 logged 170 hours ago
+    And a check-in with mood -2 logged 150 hours ago
+    And a check-in with mood 0 and substance "mdma" logged 100 hours ago
+    And a check-in with mood -1 logged 60 hours ago
+    Then the substance baseline has 2 check-ins
+    And the insights for "MDMA" show 2 uses, 3 check-ins after and a difference of -3.5
+
+  Scenario: Substances are logged with the check-in form
+    Given a fresh application state
+    And a registered user "cutie" with email "cutie@example.com" and password "supersecret1"
+    And the registered user is logged in
+    And a check-in with mood 2 logged 500 hours ago
+    And a check-in with mood -1 logged 30 hours ago
+    When I submit the form "timestamp=&mood=1&high_level=4&substances=MDMA%2C+80+mg%2C+oral" to "/me/checkins/new"
+    Then the latest stored check-in has the substance "MDMA" at "80 mg" taken "oral"
+    And the substance report lists "MDMA" with 1 use
+    When I request "/me/insights"
+    Then the response contains "MDMA"
+    When I request the edit page of the latest check-in
+    Then the response contains "MDMA, 80 mg, oral"
+
+  Scenario: Substances are logged through the API
+    Given a fresh application state
+    And a registered user "cutie" with email "cutie@example.com" and password "supersecret1"
+    And the registered user is logged in
+    When I send "POST" to "/api/v1/checkins" with the JSON '{"mood": 0, "drugs": [{"substance": " Ketamin ", "dose": "50 mg"}]}'
+    Then the response status is 201
+    And the JSON at "/drugs/0/substance" is "Ketamin"
+    And the substance report lists "Ketamin" with 1 use
+    When I send "PUT" to "/api/v1/checkins/{id}" with the JSON '{"mood": 1}'
+    Then the JSON at "/drugs/0/dose" is "50 mg"
+
+  Scenario: Substances need a name
+    Given a fresh application state
+    And a registered user "cutie" with email "cutie@example.com" and password "supersecret1"
+    And the registered user is logged in
+    When I submit the form "timestamp=&mood=1&high_level=0&substances=%2C+80+mg" to "/me/checkins/new"
+    Then the response status is 400
+    And the response contains "Bitte gib bei jeder Substanz einen Namen an."
+    And the user has 0 stored check-ins
+    When I send "POST" to "/api/v1/checkins" with the JSON '{"mood": 0, "drugs": [{"substance": ""}]}'
+    Then the response status is 400
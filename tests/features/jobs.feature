@@ -0,0 +1,75 @@
+Feature: Background jobs
+  Periodic work runs as jobs with cron-like schedules. Every job has a row
+  in the database with its next run, and claiming a run takes a lease, so a
+  run happens at most once even with two runners on one database.
+
+  Background:
+    Given a fresh application state
+    And a registered user "cutie" with email "cutie@example.com" and password "supersecret1"
+    And the registered user is logged in
+
+  Scenario: A due job runs once and is rescheduled
+    Given a counting test job "tick" on the schedule "@every 10m"
+    When the jobs are registered
+    And the job "tick" was due 1 minute ago
+    And two job runners run due jobs at once
+    Then the test jobs ran 1 time
+    And the job "tick" is scheduled in the future
+    When the job runner runs due jobs
+    Then the test jobs ran 1 time
+
+  Scenario: A job another instance holds is left alone
+    Given a counting test job "tick" on the schedule "@every 10m"
+    When the jobs are registered
+    And the job "tick" was due 1 minute ago
+    And another instance is running the job "tick"
+    And the job runner runs due jobs
+    Then the test jobs ran 0 times
+
+  Scenario: Jobs and their last error are shown to admins
+    Given the registered user is an admin
+    And a failing test job "broken" on the schedule "*/5 * * * *"
+    When the jobs are registered
+    And the job "broken" was due 1 minute ago
+    And the job runner runs due jobs
+    Then the test jobs ran 1 time
+    And the job "broken" last failed with "the test job broke"
+    And the job "broken" is scheduled in the future
+    When I request "/admin/system"
+    Then the response status is 200
+    And the response contains "Hintergrundjobs"
+    And the response contains "*/5 * * * *"
+    And the response contains "the test job broke"
+
+  Scenario: Shutdown stops a job that may be cancelled
+    Given a slow test job "slow-push" that stops at shutdown
+    When the jobs are registered
+    And the job "slow-push" was due 1 minute ago
+    And the job runner is started
+    And the job runner is shut down
+    Then the test jobs ran 1 time
+    And the job "slow-push" last failed with "stopped for shutdown"
+
+  Scenario Outline: Cron schedules
+    Then the schedule "<schedule>" runs next at "<next>" after "2026-10-18T17:42:10+00:00"
+
+    Examples:
+      | schedule       | next                      |
+      | @every 15s     | 2026-10-18T17:42:25+00:00 |
+      | @hourly        | 2026-10-18T18:00:00+00:00 |
+      | */15 * * * *   | 2026-10-18T17:45:00+00:00 |
+      | 30 3 * * *     | 2026-10-19T03:30:00+00:00 |
+      | 0 9 * * 1-5    | 2026-10-19T09:00:00+00:00 |
+      | 0 0 1 */3 *    | 2027-01-01T00:00:00+00:00 |
+
+  Scenario: Invalid schedules are rejected
+    Then the schedule "61 * * * *" is rejected
+    And the schedule "@every 5x" is rejected
+    And the schedule "* * *" is rejected
+
+  Scenario: Stale sessions are purged
+    When the session was last seen 31 days ago
+    And I request "/me/settings"
+    Then the response redirects to "/login?next=%2Fme%2Fsettings"
+    When stale sessions are purged
+    Then 0 sessions are left
@@ -0,0 +1,81 @@
+Feature: Matrix bot
+  Linked Matrix accounts can log check-ins, start and end trips and raise
+  the alarm by chatting with the bot. Accounts are linked with a code from
+  the settings page.
+
+  Background:
+    Given a fresh application state
+    And a registered user "cutie" with email "cutie@example.com" and password "supersecret1"
+    And the registered user is logged in
+    And the Matrix bot runs as "@moodbot:example.org"
+
+  Scenario: An account is linked with the code from the settings page
+    When I submit the form "" to "/me/settings/matrix-bot/link"
+    Then the response status is 200
+    And the response contains "@moodbot:example.org"
+    When "@kiki:example.org" sends the link code from the page to the bot
+    Then the bot replies "Verknüpft mit cutie"
+    When I request "/me/settings"
+    Then the response contains "@kiki:example.org"
+
+  Scenario: Unknown codes don't link anything
+    When "@kiki:example.org" sends "!link ABCD-EFGH" to the bot
+    Then the bot replies "Diesen Code kenne ich nicht"
+    When "@kiki:example.org" sends "!mood 2" to the bot
+    Then the bot replies "noch nicht verknüpft"
+    And the user has 0 stored check-ins
+
+  Scenario: A check-in from chat is stored like one from the web
+    Given the Matrix account "@kiki:example.org" is linked
+    When "@kiki:example.org" sends "!mood -2 high 4 feeling off" to the bot
+    Then the bot replies "Check-in gespeichert: Mood -2, High 4"
+    And the user has 1 stored check-ins
+    And the latest stored check-in has mood -2 and high 4
+    And the latest stored check-in has the notes "feeling off"
+
+  Scenario: A low check-in from chat alerts the contacts
+    Given a local webhook receiver answering 200
+    And the user's contacts are "webhook:{receiver}"
+    And the Matrix account "@kiki:example.org" is linked
+    When "@kiki:example.org" sends "!mood -4" to the bot
+    Then the bot replies "Deine Kontakte werden benachrichtigt"
+    When the notification outbox runs
+    Then the webhook receiver got 1 request
+
+  Scenario: The alarm can be raised from chat
+    Given a local webhook receiver answering 200
+    And the user's contacts are "webhook:{receiver}"
+    And the Matrix account "@kiki:example.org" is linked
+    When "@kiki:example.org" sends "!panic" to the bot
+    Then the bot replies "An 1 von 1 Kontakt zugestellt"
+    And 1 queued notification is "delivered"
+    And request 1 has true at "/urgent"
+
+  Scenario: Trips are started and ended from chat
+    Given the Matrix account "@kiki:example.org" is linked
+    When "@kiki:example.org" sends "!trip start Festival" to the bot
+    Then the bot replies "Trip „Festival“ gestartet"
+    When "@kiki:example.org" sends "!trip start Afterparty" to the bot
+    Then the bot replies "Es läuft schon ein Trip"
+    When "@kiki:example.org" sends "!trip end" to the bot
+    Then the bot replies "Trip „Festival“ beendet"
+    When I send "GET" to "/api/v1/trips"
+    Then the JSON at "/items/0/title" is "Festival"
+
+  Scenario: Replies follow the user's language
+    Given the user's language is "en"
+    And the Matrix account "@kiki:example.org" is linked
+    When "@kiki:example.org" sends "!mood" to the bot
+    Then the bot replies "Usage: !mood"
+
+  Scenario: Other messages are ignored
+    Given the Matrix account "@kiki:example.org" is linked
+    When "@kiki:example.org" sends "good morning" to the bot
+    Then the bot doesn't reply
+
+  Scenario: Unlinked accounts can't send commands anymore
+    Given the Matrix account "@kiki:example.org" is linked
+    When I submit the form "" to "/me/settings/matrix-bot/unlink"
+    Then the response redirects to "/me/settings"
+    When "@kiki:example.org" sends "!panic" to the bot
+    Then the bot replies "noch nicht verknüpft"
@@ -0,0 +1,33 @@
+Feature: End-to-end encrypted Matrix alerts
+  Matrix alerts are sent from the user's own account into encrypted direct
+  chats only. Unencrypted rooms are used only when the user allows them.
+
+  Background:
+    Given a fresh application state
+    And a registered user "cutie" with email "cutie@example.com" and password "supersecret1"
+    And the registered user is logged in
+    And Matrix alerts go through the user's homeserver
+
+  Scenario: Without an access token the alert fails right away
+    Given the user's contacts are "@friend:matrix.org"
+    When I send "POST" to "/api/v1/panic"
+    Then the response status is 201
+    And the JSON at "/notified_contacts" is []
+    And 1 queued notification is "failed"
+    And the latest queued notification failed with "no Matrix homeserver and access token"
+
+  Scenario: An unreachable homeserver is retried
+    Given the user's Matrix account is on "http://127.0.0.1:9" with a stored access token
+    And the user's contacts are "@friend:matrix.org"
+    When I send "POST" to "/api/v1/panic"
+    Then the response status is 201
+    And 1 queued notification is "pending"
+
+  Scenario: Unencrypted direct chats are opt-in
+    When I send "GET" to "/api/v1/settings"
+    Then the JSON at "/matrix_allow_unencrypted" is false
+    When I submit the form "display_name=Cutie&homeserver_url=&matrix_user_id=&timezone=Europe%2FBerlin&auto_notify_threshold=1&matrix_allow_unencrypted=on" to "/me/settings"
+    And I send "GET" to "/api/v1/settings"
+    Then the JSON at "/matrix_allow_unencrypted" is true
+    When I send "PATCH" to "/api/v1/settings" with the JSON '{"matrix_allow_unencrypted": false}'
+    Then the JSON at "/matrix_allow_unencrypted" is false
@@ -0,0 +1,90 @@
+Feature: Notification outbox
+  Every alert is queued per contact before it is sent. Failed sends are
+  retried in the background, and users see how many contacts were reached.
+
+  Background:
+    Given a fresh application state
+    And a registered user "cutie" with email "cutie@example.com" and password "supersecret1"
+    And the registered user is logged in
+
+  Scenario: A failed panic alert is retried until it is delivered
+    Given a local webhook receiver answering 503
+    And the user's contacts are "@friend:matrix.org, webhook:{receiver}"
+    When I submit the form "" to "/me/panic/trigger"
+    And I follow the redirect
+    Then the response contains "An 1 von 2 Kontakten zugestellt"
+    And 1 queued notification is "delivered"
+    And 1 queued notification is "pending"
+    When the notification outbox runs
+    Then the webhook receiver got 1 request
+    When the webhook receiver answers 200
+    And the notification retries are due
+    And the notification outbox runs
+    Then the webhook receiver got 2 requests
+    And 2 queued notifications are "delivered"
+    When I request the page of the latest panic event
+    Then the response contains "An 2 von 2 Kontakten zugestellt"
+
+  Scenario: Nobody reached yet
+    Given a local webhook receiver answering 500
+    And the user's contacts are "webhook:{receiver}"
+    When I submit the form "" to "/me/panic/trigger"
+    And I follow the redirect
+    Then the response contains "An 0 von 1 Kontakt zugestellt"
+    And the response contains "wir versuchen es weiter"
+
+  Scenario: The check-in detail shows who was alerted about a low mood
+    Given a local webhook receiver answering 200
+    And the user's contacts are "@friend:matrix.org, email:friend@example.com"
+    When I send "POST" to "/api/v1/checkins" with the JSON '{"mood": -4}'
+    Then 2 queued notifications are "pending"
+    When the notification outbox runs
+    And I request the detail page of the latest check-in
+    Then the response contains "An 1 von 2 Kontakten zugestellt"
+    And the response contains "email:friend@example.com"
+    And 1 queued notification is "failed"
+
+  Scenario: Concurrent runs send a retry once
+    Given a local webhook receiver answering 500
+    And the user's contacts are "webhook:{receiver}"
+    When I submit the form "" to "/me/panic/trigger"
+    And the webhook receiver answers 200
+    And the notification retries are due
+    And two notification outbox runs happen at once
+    Then the webhook receiver got 2 requests
+    And 1 queued notification is "delivered"
+
+  Scenario: An unreadable entry fails without holding up the others
+    Given a local webhook receiver answering 500
+    And the user's contacts are "webhook:{receiver}"
+    When I submit the form "" to "/me/panic/trigger"
+    And I submit the form "" to "/me/panic/trigger"
+    And the oldest queued notification can't be opened
+    And the webhook receiver answers 200
+    And the notification retries are due
+    And the notification outbox runs
+    Then the webhook receiver got 3 requests
+    And 1 queued notification is "failed"
+    And 1 queued notification is "delivered"
+
+  Scenario: Unreadable entries are left out of the delivery status
+    Given a local webhook receiver answering 200
+    And the user's contacts are "@friend:matrix.org, webhook:{receiver}"
+    When I submit the form "" to "/me/panic/trigger"
+    And the oldest queued notification can't be opened
+    And I follow the redirect
+    Then the response status is 200
+    And the response contains "An 1 von 1 Kontakt zugestellt"
+
+  Scenario: Retries wait for a locked data key without using up attempts
+    Given a local webhook receiver answering 500
+    And the user's contacts are "webhook:{receiver}"
+    When I submit the form "" to "/me/panic/trigger"
+    And the user's data key is locked
+    And the notification retries are due
+    And the notification outbox runs
+    And the notification retries are due
+    And the notification outbox runs
+    Then the webhook receiver got 1 request
+    And 1 queued notification is "pending"
+    And the queued notifications used 1 attempt
@@ -0,0 +1,53 @@
+Feature: Notification cooldowns and quiet hours
+  Low-mood alerts pause for a while after one went out, and contacts can
+  set quiet hours in which only panic alerts reach them right away.
+
+  Background:
+    Given a fresh application state
+    And a registered user "cutie" with email "cutie@example.com" and password "supersecret1"
+    And the registered user is logged in
+    And a local webhook receiver answering 200
+
+  Scenario: Several low check-ins in a row alert contacts once
+    Given the user's contacts are "webhook:{receiver}"
+    When I send "POST" to "/api/v1/checkins" with the JSON '{"mood": -3}'
+    And I send "POST" to "/api/v1/checkins" with the JSON '{"mood": -4}'
+    And I send "POST" to "/api/v1/checkins" with the JSON '{"mood": -5}'
+    And the notification outbox runs
+    Then the webhook receiver got 1 request
+    And 1 queued notification is "delivered"
+
+  Scenario: Without a cooldown every low check-in alerts
+    Given the user's contacts are "webhook:{receiver}"
+    And the user's low-mood cooldown is 0 hours
+    When I send "POST" to "/api/v1/checkins" with the JSON '{"mood": -3}'
+    And I send "POST" to "/api/v1/checkins" with the JSON '{"mood": -4}'
+    And the notification outbox runs
+    Then the webhook receiver got 2 requests
+
+  Scenario: Panic alerts ignore the cooldown
+    Given the user's contacts are "webhook:{receiver}"
+    When I send "POST" to "/api/v1/checkins" with the JSON '{"mood": -3}'
+    And the notification outbox runs
+    And I send "POST" to "/api/v1/panic"
+    And I send "POST" to "/api/v1/panic"
+    Then the webhook receiver got 3 requests
+
+  Scenario: Quiet hours hold back low-mood alerts but not panic
+    Given the user's contacts are "webhook:{receiver} {quiet_now}"
+    When I send "POST" to "/api/v1/checkins" with the JSON '{"mood": -4}'
+    And the notification outbox runs
+    Then the webhook receiver got 0 requests
+    And 1 queued notification is "pending"
+    When the notification retries are due
+    And the notification outbox runs
+    Then the webhook receiver got 0 requests
+    When I send "POST" to "/api/v1/panic"
+    Then the webhook receiver got 1 request
+    And request 1 has true at "/urgent"
+
+  Scenario: Quiet hours are written after the contact
+    Then the contact "email:friend@example.com 22:00-07:00" has quiet hours "22:00-07:00"
+    And the contact "@friend:matrix.org 23:30-06:00" uses the "matrix" channel
+    And the contact "email:friend@example.com 25:00-07:00" is rejected
+    And the contact "email:friend@example.com 22:00-22:00" is rejected
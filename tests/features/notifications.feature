@@ -0,0 +1,45 @@
+Feature: Notification channels
+  Contacts name their channel (Matrix, email, ntfy/Gotify push or a webhook),
+  and alerts fan out to each contact on its own channel.
+
+  Background:
+    Given a fresh application state
+    And a registered user "cutie" with email "cutie@example.com" and password "supersecret1"
+    And the registered user is logged in
+
+  Scenario: A panic reaches every contact on its channel
+    Given a local webhook receiver answering 200
+    And the user's contacts are "@friend:matrix.org, push:{receiver}, webhook:{receiver}"
+    When I send "POST" to "/api/v1/panic"
+    Then the response status is 201
+    And the JSON at "/notified_contacts/0" is "@friend:matrix.org"
+    And the webhook receiver got 2 requests
+    And request 1 asks for "urgent" priority
+    And request 2 has true at "/urgent"
+    And request 2 has "cutie" at "/from"
+
+  Scenario: Unreachable contacts don't stop the others
+    Given a local webhook receiver answering 500
+    And the user's contacts are "email:friend@example.com, webhook:{receiver}, @friend:matrix.org"
+    When I send "POST" to "/api/v1/panic"
+    Then the response status is 201
+    And the JSON at "/notified_contacts" is ["@friend:matrix.org"]
+    And the webhook receiver got 1 request
+
+  Scenario: Contacts are recognised by their shape
+    Then the contact "@alex:matrix.org" uses the "matrix" channel
+    And the contact "alex@example.com" uses the "email" channel
+    And the contact "https://ntfy.sh/alex" uses the "push" channel
+    And the contact "webhook:https://example.org/hook" uses the "webhook" channel
+
+  Scenario: Contacts saved before channels existed still load
+    Then a stored config with the primary contact "@friend:matrix.org" loads it as "@friend:matrix.org"
+
+  Scenario: Invalid contacts are rejected in the settings form
+    When I submit the form "display_name=Cutie&homeserver_url=&matrix_user_id=&timezone=Europe%2FBerlin&auto_notify_threshold=1&primary_contact=push%3Anot-a-url" to "/me/settings"
+    Then the response status is 400
+    And the form field "primary_contact" has the value "push:not-a-url"
+
+  Scenario: Invalid contacts are rejected by the API
+    When I send "PATCH" to "/api/v1/settings" with the JSON '{"emergency_contacts": ["email:"]}'
+    Then the response status is 400
@@ -0,0 +1,64 @@
+Feature: Check-in reminders
+  Users are reminded to check in daily at a set time or every few hours
+  during a trip. Reminders are left out after a recent check-in, and the
+  schedule is kept in the database, so it survives restarts.
+
+  Background:
+    Given a fresh application state
+    And a registered user "cutie" with email "cutie@example.com" and password "supersecret1"
+    And the registered user is logged in
+    And a local webhook receiver answering 200
+
+  Scenario: A daily reminder goes out once per slot
+    When I add a daily reminder at "20:00" to "webhook:{receiver}"
+    Then the response redirects to "/me/settings"
+    And the reminder is scheduled in the future
+    When I request "/me/settings"
+    Then the response contains "Täglich um 20:00"
+    When the reminder was due 1 minute ago
+    And the reminders run
+    And the reminders run
+    Then the webhook receiver got 1 request
+    And request 1 has "cutie" at "/from"
+    And the reminder was "sent"
+    And the reminder is scheduled in the future
+
+  Scenario: A recent check-in makes the reminder unnecessary
+    When I add a daily reminder at "20:00" to "webhook:{receiver}"
+    And I send "POST" to "/api/v1/checkins" with the JSON '{"mood": 3}'
+    And the reminder was due 1 minute ago
+    And the reminders run
+    Then the webhook receiver got 0 requests
+    And the reminder was "skipped"
+
+  Scenario: Slots missed while the app was down are skipped
+    When I add a daily reminder at "20:00" to "webhook:{receiver}"
+    And the reminder was due 180 minutes ago
+    And the reminders run
+    Then the webhook receiver got 0 requests
+    And the reminder was "skipped"
+    And the reminder is scheduled in the future
+
+  Scenario: Trip reminders run while a trip does
+    When I add a trip reminder every 3 hours to "webhook:{receiver}"
+    Then the reminder isn't scheduled
+    When I send "POST" to "/api/v1/trips" with the JSON '{"title": "Festival"}'
+    Then the reminder is scheduled in about 3 hours
+    When the reminder was due 1 minute ago
+    And the reminders run
+    Then the webhook receiver got 1 request
+    And the reminder was "sent"
+    When I send "POST" to "/api/v1/trips/{id}/end"
+    Then the reminder isn't scheduled
+
+  Scenario: Intervals are limited to a day
+    When I add a trip reminder every 30 hours to "webhook:{receiver}"
+    Then the response status is 400
+    And the response contains "1 bis 24 Stunden"
+
+  Scenario: Changing the timezone through the API moves daily reminders
+    When I add a daily reminder at "20:00" to "webhook:{receiver}"
+    Then the reminder is scheduled at "20:00" in "Europe/Berlin"
+    When I send "PATCH" to "/api/v1/settings" with the JSON '{"timezone": "America/New_York"}'
+    Then the response status is 200
+    And the reminder is scheduled at "20:00" in "America/New_York"
@@ -0,0 +1,31 @@
+Feature: Secrets
+  Access tokens live encrypted in the secrets table and never in the
+  committed `ai/` tree. Tokens committed by older versions can be scrubbed
+  from the history. Without `SECRETS_KEY` a random key is generated once.
+
+  Background:
+    Given a fresh application state
+    And a registered user "cutie" with email "cutie@example.com" and password "supersecret1"
+
+  Scenario: A stored access token is encrypted and reads back
+    When the user stores the Matrix access token "syt_kept_secret"
+    Then the secrets table does not contain "syt_kept_secret"
+    And the user's Matrix access token is "syt_kept_secret"
+    And a secrets store with another key can't read the user's Matrix access token
+
+  Scenario: Committed access tokens are scrubbed from the history
+    When a user config with the Matrix access token "syt_plain_old" is committed unsealed
+    And a user config with the Matrix access token "syt_sealed_old" is committed sealed
+    Then a committed version of the user config contains "syt_plain_old"
+    And a committed version of the user config contains "syt_sealed_old"
+    When committed tokens are scrubbed
+    Then 2 commits were rewritten
+    And no committed version of the user config contains "syt_"
+    And the user's Matrix access token is "syt_sealed_old"
+    And the secrets table does not contain "syt_sealed_old"
+
+  Scenario: A generated secrets key is kept across restarts
+    Then the secrets key in "secrets.key" is generated once and kept
+
+  Scenario: The secrets key can't be kept in the committed data
+    Then a secrets key in "repo/ai/secrets.key" is refused
@@ -0,0 +1,55 @@
+Feature: Weekly summaries via Matrix
+  Opted-in users get a summary of the last full week, and each delivery is
+  recorded so a restart never sends it twice.
+
+  Background:
+    Given a fresh application state
+    And a registered user "cutie" with email "cutie@example.com" and password "supersecret1"
+
+  Scenario: Nothing is sent without opting in
+    Given a check-in with mood 1 logged on day 2 of last week
+    When the summary job runs
+    Then 0 summaries were sent
+
+  Scenario: The weekly summary is delivered once
+    Given the user opted into weekly summaries for "@cutie:example.org"
+    And a check-in with mood -2 logged on day 1 of last week
+    And a check-in with mood 3 logged on day 4 of last week
+    When the summary job runs
+    Then 1 summaries were sent
+    And 1 summary deliveries are recorded
+    When the summary job runs
+    Then 0 summaries were sent
+
+  Scenario: The therapist gets a copy when shared
+    Given the user opted into weekly summaries for "@cutie:example.org"
+    And the user shares summaries with the therapist "@therapist:example.org"
+    And a check-in with mood 0 logged on day 7 of last week
+    When the summary job runs
+    Then 2 summaries were sent
+    And 2 summary deliveries are recorded
+
+  Scenario: A failing recipient doesn't hold up the others
+    Given the user opted into weekly summaries for "@cutie:unreachable.example"
+    And the user shares summaries with the therapist "@therapist:example.org"
+    And a check-in with mood 0 logged on day 3 of last week
+    When the summary job runs
+    Then 1 summaries were sent
+    And 1 summary deliveries are recorded
+    When the summary job runs
+    Then 0 summaries were sent
+    And 1 summary deliveries are recorded
+
+  Scenario: An empty week is skipped
+    Given the user opted into weekly summaries for "@cutie:example.org"
+    When the summary job runs
+    Then 0 summaries were sent
+
+  Scenario: Logged substances are counted
+    Given the registered user is logged in
+    And the user opted into weekly summaries for "@cutie:example.org"
+    When I log a check-in with the substances "MDMA, Ketamin" on day 2 of last week
+    And I log a check-in with the substances "mdma" on day 5 of last week
+    And the summary job runs
+    Then 1 summaries were sent
+    And the summary sent to "@cutie:example.org" contains "MDMA ×2, Ketamin ×1"
@@ -0,0 +1,12 @@
+Feature: Per-user timezone
+  Timestamps are shown in the user's IANA timezone, including across DST.
+
+  Scenario: Summer and winter time in Europe/Berlin
+    Given a user config with timezone "Europe/Berlin"
+    Then "2026-07-01T12:00:00Z" is shown as "01.07.2026 14:00"
+    And "2026-10-24T22:30:00Z" is shown as "25.10.2026 00:30"
+    And "2026-12-01T12:00:00Z" is shown as "01.12.2026 13:00"
+
+  Scenario: Unknown timezones fall back to UTC
+    Given a user config with timezone "Mars/Olympus_Mons"
+    Then "2026-07-01T12:00:00Z" is shown as "01.07.2026 12:00"
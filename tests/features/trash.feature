@@ -0,0 +1,27 @@
+Feature: Check-in trash
+  Deleted check-ins are kept as tombstones until the trash is purged.
+
+  Scenario: Deleting and purging a check-in
+    Given a fresh application state
+    And a registered user "cutie" with email "cutie@example.com" and password "supersecret1"
+    When I submit a check-in with mood 1 and high 0 and notes "oops"
+    And I delete the latest check-in
+    Then the user has 1 stored check-ins
+    And the user has 1 check-in in the trash
+    When I empty the trash
+    Then the user has 0 stored check-ins
+
+  Scenario: A daily job purges check-ins trashed past the retention period
+    Given a fresh application state
+    And a registered user "cutie" with email "cutie@example.com" and password "supersecret1"
+    When I submit a check-in with mood 1 and high 0 and notes "long gone"
+    And I delete the latest check-in
+    And the trashed check-ins were deleted 31 days ago
+    And I submit a check-in with mood 2 and high 0 and notes "still here"
+    And I delete the latest check-in
+    Then the user has 2 check-ins in the trash
+    When the built-in jobs are registered
+    And the job "trash-purge" was due 1 minute ago
+    And the job runner runs due jobs
+    Then the user has 1 check-in in the trash
+    And the job "trash-purge" is scheduled in the future
@@ -0,0 +1,30 @@
+Feature: Mood trend early warning
+  The last 3 days are compared with the personal baseline of the 4 weeks before.
+
+  Background:
+    Given a fresh application state
+    And a registered user "cutie" with email "cutie@example.com" and password "supersecret1"
+    And a check-in with mood 2 logged 120 hours ago
+    And a check-in with mood 2 logged 144 hours ago
+    And a check-in with mood 2 logged 168 hours ago
+    And a check-in with mood 2 logged 192 hours ago
+    And a check-in with mood 2 logged 216 hours ago
+
+  Scenario: A clear drop of the recent average
+    Given a check-in with mood 0 logged 30 hours ago
+    And a check-in with mood -1 logged 5 hours ago
+    Then a downward trend is detected at sensitivity "low"
+    And no downward trend is detected at sensitivity "off"
+
+  Scenario: Consecutive declines only count at high sensitivity
+    Given a check-in with mood 3 logged 60 hours ago
+    And a check-in with mood 3 logged 50 hours ago
+    And a check-in with mood 1 logged 40 hours ago
+    And a check-in with mood 0 logged 20 hours ago
+    And a check-in with mood -1 logged 2 hours ago
+    Then a declining downward trend is detected at sensitivity "high"
+    And no downward trend is detected at sensitivity "medium"
+
+  Scenario: Too few recent check-ins
+    Given a check-in with mood -5 logged 1 hours ago
+    Then no downward trend is detected at sensitivity "high"
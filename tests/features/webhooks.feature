@@ -0,0 +1,60 @@
+Feature: Outgoing webhooks
+  Users can register URLs that get signed JSON payloads for new check-ins,
+  low-mood check-ins and panic events. Deliveries are queued, retried and
+  logged.
+
+  Background:
+    Given a fresh application state
+    And a registered user "cutie" with email "cutie@example.com" and password "supersecret1"
+    And the registered user is logged in
+
+  Scenario: A new check-in is delivered with a valid signature
+    Given a local webhook receiver answering 200
+    And the user has a webhook for "checkin.created"
+    When I send "POST" to "/api/v1/checkins" with the JSON '{"mood": 3, "notes": "secret diary"}'
+    And the webhook queue runs
+    Then the webhook receiver got 1 request
+    And request 1 is a signed "checkin.created" webhook
+    And request 1 has 3 at "/data/mood"
+    And request 1 has "cutie" at "/user"
+    And request 1 has nothing at "/data/notes"
+    And 1 webhook delivery is "delivered"
+
+  Scenario: Only subscribed events are sent
+    Given a local webhook receiver answering 204
+    And the user has a webhook for "checkin.low_mood panic.triggered"
+    When I send "POST" to "/api/v1/checkins" with the JSON '{"mood": 3}'
+    And I send "POST" to "/api/v1/checkins" with the JSON '{"mood": -4}'
+    And I send "POST" to "/api/v1/panic"
+    And the webhook queue runs
+    Then the webhook receiver got 2 requests
+    And request 1 is a signed "checkin.low_mood" webhook
+    And request 1 has -4 at "/data/mood"
+    And request 2 is a signed "panic.triggered" webhook
+    And request 2 has -4 at "/data/mood_at_panic"
+
+  Scenario: Failed deliveries are retried
+    Given a local webhook receiver answering 500
+    And the user has a webhook for "panic.triggered"
+    When I send "POST" to "/api/v1/panic"
+    And the webhook queue runs
+    Then the webhook receiver got 1 request
+    And 1 webhook delivery is "pending"
+    When the webhook queue runs
+    Then the webhook receiver got 1 request
+    When the webhook receiver answers 200
+    And the webhook retries are due
+    And the webhook queue runs
+    Then the webhook receiver got 2 requests
+    And 1 webhook delivery is "delivered"
+
+  Scenario: Webhooks are managed in the settings
+    When I submit the form "url=ftp%3A%2F%2Fexample.com&event=panic.triggered" to "/me/settings/webhooks"
+    Then the response status is 400
+    When I submit the form "url=https%3A%2F%2Fexample.com%2Fhook" to "/me/settings/webhooks"
+    Then the response status is 400
+    When I submit the form "url=https%3A%2F%2Fexample.com%2Fhook&event=panic.triggered" to "/me/settings/webhooks"
+    Then the response status is 200
+    And the response contains "whsec_"
+    When I request "/me/settings"
+    Then the response contains "https://example.com/hook"